@@ -0,0 +1,48 @@
+#!/bin/sh
+# Downloads the bundled dictionaries from the LibreOffice dictionaries repository, at a pinned
+# release tag, and checks them against SHA256SUMS.
+# en_US is SCOWL-derived; its licence (README_en_US.txt) allows redistribution as long as
+# the notice ships with it, so the bundle includes that file too.
+#
+#   fetch.sh           download missing files and verify every file
+#   fetch.sh --update  download all files again at $ref and rewrite SHA256SUMS (after bumping $ref)
+set -eu
+
+dir="$(cd "$(dirname "$0")" && pwd)"
+ref="libreoffice-24.2.0.1"
+base="https://raw.githubusercontent.com/LibreOffice/dictionaries/$ref/en"
+files="en_US.aff en_US.dic README_en_US.txt"
+
+sha256() {
+    if command -v sha256sum >/dev/null 2>&1; then
+        sha256sum "$@"
+    else
+        shasum -a 256 "$@"
+    fi
+}
+
+cd "$dir"
+update=false
+if [ "${1:-}" = "--update" ]; then
+    update=true
+elif [ ! -s SHA256SUMS ]; then
+    echo "dictionaries/SHA256SUMS is missing; run fetch.sh --update once with network access and commit it" >&2
+    exit 1
+fi
+
+for file in $files; do
+    if $update || [ ! -s "$file" ]; then
+        curl -fsSL "$base/$file" -o "$file.part"
+        mv "$file.part" "$file"
+    fi
+done
+
+if $update; then
+    # shellcheck disable=SC2086
+    sha256 $files > SHA256SUMS
+else
+    sha256 -c SHA256SUMS >/dev/null || {
+        echo "dictionaries do not match SHA256SUMS; delete them and run fetch.sh again" >&2
+        exit 1
+    }
+fi
@@ -0,0 +1,643 @@
+// Opt-in localhost API for scripts and editor plugins: JSON-RPC 2.0 over HTTP, plus a
+// WebSocket feed of project changes. It works on the saved project file, the same one the
+// app's Save writes, and calls the same engine functions as the Tauri commands.
+
+use std::fs;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::{broadcast, oneshot};
+
+use crate::audit::LogLevel;
+use crate::engine::{self, AppConfig};
+use crate::provenance::GenerationRequest;
+use crate::retrieval::RetrievalOptions;
+use crate::search::SearchFilters;
+use crate::stats::{self, StatsOptions};
+use crate::{outline, ApiError, ProjectData, Scene, ScenePlan, Star, StarTags};
+
+pub const DEFAULT_PORT: u16 = 7357;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ApiSettings {
+    pub enabled: bool,
+    pub port: u16,
+    pub token: String, // sent as `Authorization: Bearer <token>`, or `?token=` for WebSockets
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        Self { enabled: false, port: DEFAULT_PORT, token: new_token() }
+    }
+}
+
+impl ApiSettings {
+    // A missing or unreadable file gives the defaults, with a fresh token
+    pub fn load(path: &Path) -> Self {
+        let mut settings: ApiSettings = fs::read_to_string(path).ok().and_then(|s| serde_json::from_str(&s).ok()).unwrap_or_default();
+        if settings.token.is_empty() {
+            settings.token = new_token();
+        }
+        settings
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self).unwrap())
+    }
+}
+
+pub fn new_token() -> String {
+    format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())
+}
+
+// Pushed to WebSocket clients, and to the app window when the change came from the API
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProjectEvent {
+    pub kind: String,   // "project_saved" | "scene_created" | "scene_updated" | "scene_deleted" | "star_*" | "draft_added" | "vault_synced"
+    pub source: String, // "app" | "api" | "scripts" (a save script changed what the app saved) | "vault" | "external" (another program wrote the file)
+    pub id: Option<String>,
+    pub at: u64,
+}
+
+impl ProjectEvent {
+    pub fn new(kind: &str, source: &str, id: Option<String>) -> Self {
+        Self { kind: kind.to_string(), source: source.to_string(), id, at: now_ms() }
+    }
+}
+
+// Next event for a subscriber; a subscriber that fell behind skips what it missed
+pub async fn next_event(events: &mut broadcast::Receiver<ProjectEvent>) -> Option<ProjectEvent> {
+    loop {
+        match events.recv().await {
+            Ok(event) => return Some(event),
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+pub(crate) const PARSE_ERROR: i64 = -32700;
+pub(crate) const INVALID_REQUEST: i64 = -32600;
+pub(crate) const METHOD_NOT_FOUND: i64 = -32601;
+pub(crate) const INVALID_PARAMS: i64 = -32602;
+pub(crate) const APP_ERROR: i64 = -32000; // `data.code` carries the ApiError code
+
+impl From<ApiError> for RpcError {
+    fn from(e: ApiError) -> Self {
+        RpcError { code: APP_ERROR, message: e.message, data: e.code.map(|code| json!({ "code": code })) }
+    }
+}
+
+fn invalid_params(e: serde_json::Error) -> RpcError {
+    RpcError { code: INVALID_PARAMS, message: format!("Invalid params: {}", e), data: None }
+}
+
+fn not_found(kind: &str, id: &str) -> RpcError {
+    ApiError {
+        error: true,
+        message: format!("{} not found: {}", kind, id),
+        code: Some("NOT_FOUND".to_string()),
+    }
+    .into()
+}
+
+fn params<T: for<'de> Deserialize<'de>>(value: Value) -> Result<T, RpcError> {
+    // Methods without arguments accept a missing `params`
+    let value = if value.is_null() { json!({}) } else { value };
+    serde_json::from_value(value).map_err(invalid_params)
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, RpcError> {
+    Ok(serde_json::to_value(value).unwrap())
+}
+
+#[derive(Deserialize)]
+struct IdParams {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    query: String,
+    #[serde(default)]
+    filters: SearchFilters,
+}
+
+#[derive(Deserialize)]
+struct RetrieveParams {
+    user_input: String,
+    scene_id: Option<String>,
+    #[serde(default)]
+    options: RetrievalOptions,
+}
+
+#[derive(Deserialize)]
+struct StatsParams {
+    #[serde(default)]
+    options: StatsOptions,
+}
+
+#[derive(Deserialize)]
+struct SceneFields {
+    name: Option<String>,
+    setting: Option<String>,
+    backstory: Option<String>,
+    plan: Option<String>, // raw plan text; parse it in the app to get steps
+}
+
+#[derive(Deserialize)]
+struct SceneUpdate {
+    id: String,
+    #[serde(flatten)]
+    fields: SceneFields,
+}
+
+#[derive(Deserialize)]
+struct StarFields {
+    title: Option<String>,
+    body: Option<String>,
+    tags: Option<StarTags>,
+    priority: Option<f64>,
+    is_checked: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct StarUpdate {
+    id: String,
+    #[serde(flatten)]
+    fields: StarFields,
+}
+
+#[derive(Deserialize)]
+struct GenerateParams {
+    #[serde(flatten)]
+    request: GenerationRequest,
+    #[serde(default)]
+    write: bool, // scene timelines go to the workbench; descriptions need `tab_id`
+    tab_id: Option<String>,
+    event_id: Option<String>,
+}
+
+// Run one method against the saved project; used by the HTTP handler and callable directly
+pub async fn call(config: &AppConfig, method: &str, params_value: Value) -> Result<Value, RpcError> {
+    match method {
+        "project.get" => to_value(load(config)?),
+        "project.search" => {
+            let p: SearchParams = params(params_value)?;
+            let project = load(config)?;
+            let mut index = config.search_index.lock().unwrap_or_else(|e| e.into_inner());
+            index.sync(&project);
+            to_value(index.search(&p.query, &p.filters))
+        }
+        "project.retrieve" => {
+            let p: RetrieveParams = params(params_value)?;
+            let project = load(config)?;
+            to_value(engine::query_index(config, &project, p.scene_id.as_deref(), &p.user_input, &p.options))
+        }
+        "project.stats" => {
+            let p: StatsParams = params(params_value)?;
+            to_value(stats::compute(&load(config)?, &p.options))
+        }
+        "project.manuscript" => {
+            let (text, _) = engine::script_host(config).on_export("markdown", outline::manuscript_markdown(&load(config)?));
+            to_value(text)
+        }
+
+        "scene.list" => {
+            let project = load(config)?;
+            to_value(outline::ordered_scenes(&project))
+        }
+        "scene.get" => {
+            let p: IdParams = params(params_value)?;
+            let project = load(config)?;
+            let scene = project.scenes.get(&p.id).ok_or_else(|| not_found("Scene", &p.id))?;
+            let tabs: Vec<_> = scene.draft_tab_ids.iter().filter_map(|id| project.draft_tabs.get(id)).collect();
+            to_value(json!({ "scene": scene, "draft_tabs": tabs }))
+        }
+        "scene.create" => {
+            let p: SceneFields = params(params_value)?;
+            mutate(config, |project| {
+                let now = now_ms();
+                let scene = Scene {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    name: p.name.unwrap_or_else(|| "New Scene".to_string()),
+                    setting: p.setting,
+                    backstory: p.backstory,
+                    plan: ScenePlan { raw_text: p.plan.unwrap_or_default(), parsed_steps: Vec::new() },
+                    draft_tab_ids: Vec::new(),
+                    created_at: now,
+                    updated_at: now,
+                };
+                let id = scene.id.clone();
+                project.scenes.insert(id.clone(), scene);
+                outline::normalize(project); // files it at the end of the last chapter
+                Ok((project.scenes[&id].clone(), ProjectEvent::new("scene_created", "api", Some(id))))
+            })
+        }
+        "scene.update" => {
+            let p: SceneUpdate = params(params_value)?;
+            mutate(config, |project| {
+                let scene = project.scenes.get_mut(&p.id).ok_or_else(|| not_found("Scene", &p.id))?;
+                let SceneFields { name, setting, backstory, plan } = p.fields;
+                if let Some(name) = name {
+                    scene.name = name;
+                }
+                if setting.is_some() {
+                    scene.setting = setting;
+                }
+                if backstory.is_some() {
+                    scene.backstory = backstory;
+                }
+                if let Some(plan) = plan {
+                    scene.plan.raw_text = plan;
+                }
+                scene.updated_at = now_ms();
+                Ok((scene.clone(), ProjectEvent::new("scene_updated", "api", Some(p.id.clone()))))
+            })
+        }
+        "scene.delete" => {
+            let p: IdParams = params(params_value)?;
+            mutate(config, |project| {
+                let scene = project.scenes.remove(&p.id).ok_or_else(|| not_found("Scene", &p.id))?;
+                // Its drafts are kept in the workbench
+                for tab_id in &scene.draft_tab_ids {
+                    if let Some(tab) = project.draft_tabs.get_mut(tab_id) {
+                        tab.scene_id = None;
+                        project.workbench.unassigned_draft_tab_ids.push(tab_id.clone());
+                    }
+                }
+                if project.active_scene_id.as_deref() == Some(p.id.as_str()) {
+                    project.active_scene_id = outline::ordered_scenes(project).first().map(|s| s.id.clone());
+                }
+                outline::normalize(project);
+                Ok((true, ProjectEvent::new("scene_deleted", "api", Some(p.id.clone()))))
+            })
+        }
+
+        "star.list" => {
+            let project = load(config)?;
+            let mut stars: Vec<&Star> = project.stars.values().collect();
+            stars.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+            to_value(stars)
+        }
+        "star.get" => {
+            let p: IdParams = params(params_value)?;
+            let project = load(config)?;
+            to_value(project.stars.get(&p.id).ok_or_else(|| not_found("Star", &p.id))?)
+        }
+        "star.create" => {
+            let p: StarFields = params(params_value)?;
+            mutate(config, |project| {
+                let mut star = Star::new(p.title.unwrap_or_default(), p.body.unwrap_or_default(), now_ms());
+                if let Some(tags) = p.tags {
+                    star.tags = tags;
+                }
+                star.priority = p.priority.unwrap_or(star.priority).clamp(0.0, 1.0);
+                star.is_checked = p.is_checked.unwrap_or(star.is_checked);
+                let id = star.id.clone();
+                project.stars.insert(id.clone(), star.clone());
+                Ok((star, ProjectEvent::new("star_created", "api", Some(id))))
+            })
+        }
+        "star.update" => {
+            let p: StarUpdate = params(params_value)?;
+            mutate(config, |project| {
+                let star = project.stars.get_mut(&p.id).ok_or_else(|| not_found("Star", &p.id))?;
+                let StarFields { title, body, tags, priority, is_checked } = p.fields;
+                if let Some(title) = title {
+                    star.title = title;
+                }
+                if let Some(body) = body {
+                    star.body = body;
+                }
+                if let Some(tags) = tags {
+                    star.tags = tags;
+                }
+                if let Some(priority) = priority {
+                    star.priority = priority.clamp(0.0, 1.0);
+                }
+                if let Some(is_checked) = is_checked {
+                    star.is_checked = is_checked;
+                }
+                Ok((star.clone(), ProjectEvent::new("star_updated", "api", Some(p.id.clone()))))
+            })
+        }
+        "star.delete" => {
+            let p: IdParams = params(params_value)?;
+            mutate(config, |project| {
+                engine::remove_star(project, &p.id).ok_or_else(|| not_found("Star", &p.id))?;
+                Ok((true, ProjectEvent::new("star_deleted", "api", Some(p.id.clone()))))
+            })
+        }
+
+        "generate" => {
+            let p: GenerateParams = params(params_value)?;
+            let project = load(config)?;
+            let generated = engine::run_generation(config, &engine::script_host(config), &project, p.request).await?;
+            if !p.write {
+                return to_value(generated);
+            }
+            // The project may have changed during the LLM call, so re-read it before adding the result
+            let result = to_value(&generated)?;
+            if generated.description.is_some() && p.tab_id.is_none() {
+                return Err(RpcError { code: INVALID_PARAMS, message: "Writing a description needs tab_id".to_string(), data: None });
+            }
+            mutate(config, move |project| {
+                let ids = engine::write_generation(project, generated, None, p.tab_id.as_deref(), p.event_id.as_deref(), now_ms())?;
+                // Descriptions announce the tab they were added to
+                let id = p.tab_id.unwrap_or_else(|| ids.join(","));
+                Ok((result, ProjectEvent::new("draft_added", "api", Some(id))))
+            })
+        }
+
+        _ => Err(RpcError { code: METHOD_NOT_FOUND, message: format!("Unknown method: {}", method), data: None }),
+    }
+}
+
+fn load(config: &AppConfig) -> Result<ProjectData, RpcError> {
+    let path = config.get_project_path();
+    if !path.exists() {
+        return Err(ApiError {
+            error: true,
+            message: "No saved project yet; save in the app first".to_string(),
+            code: Some("NO_PROJECT".to_string()),
+        }
+        .into());
+    }
+    Ok(engine::read_project(&path)?)
+}
+
+// Read-modify-write of the saved project under the project lock, then announce the change
+fn mutate<T: Serialize>(
+    config: &AppConfig,
+    change: impl FnOnce(&mut ProjectData) -> Result<(T, ProjectEvent), RpcError>,
+) -> Result<Value, RpcError> {
+    config.require_saved_project_open()?;
+    let _guard = config.project_lock.lock().unwrap_or_else(|e| e.into_inner());
+    let mut project = load(config)?;
+    let (result, event) = change(&mut project)?;
+    project.metadata.updated_at = now_ms();
+    let (project, _) = engine::script_host(config).before_save(project);
+    config.write_saved_project(&serde_json::to_string(&project).unwrap())?;
+    config.search_index.lock().unwrap_or_else(|e| e.into_inner()).sync(&project);
+    // No receivers is fine
+    let _ = config.events.send(event);
+    to_value(result)
+}
+
+fn now_ms() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+// HTTP layer
+
+struct ServerState {
+    config: Arc<AppConfig>,
+    token: String,
+}
+
+pub struct ApiServer {
+    pub addr: SocketAddr,
+    shutdown: oneshot::Sender<()>,
+}
+
+impl ApiServer {
+    // Binds 127.0.0.1 only; port 0 picks a free port
+    pub async fn start(config: Arc<AppConfig>, settings: &ApiSettings) -> io::Result<Self> {
+        let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, settings.port)).await?;
+        let addr = listener.local_addr()?;
+        config.audit.add_secret(&settings.token);
+        let state = Arc::new(ServerState { config, token: settings.token.clone() });
+        let app = Router::new()
+            .route("/health", get(health))
+            .route("/rpc", post(rpc))
+            .route("/events", get(events))
+            .with_state(state);
+
+        let (shutdown, stopped) = oneshot::channel();
+        tokio::spawn(async move {
+            let server = axum::serve(listener, app).with_graceful_shutdown(async {
+                let _ = stopped.await;
+            });
+            if let Err(e) = server.await {
+                eprintln!("API server stopped: {}", e);
+            }
+        });
+        Ok(Self { addr, shutdown })
+    }
+
+    pub fn stop(self) {
+        let _ = self.shutdown.send(());
+    }
+}
+
+async fn health() -> Json<Value> {
+    Json(json!({ "ok": true, "version": env!("CARGO_PKG_VERSION") }))
+}
+
+// Constant-time so the token can't be guessed byte by byte from response times
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len() && given.bytes().zip(expected.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+fn authorized(headers: &HeaderMap, query_token: Option<&str>, token: &str) -> bool {
+    let bearer = headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()).and_then(|v| v.strip_prefix("Bearer "));
+    bearer.or(query_token).map_or(false, |given| token_matches(given, token))
+}
+
+fn unauthorized() -> Response {
+    (StatusCode::UNAUTHORIZED, Json(json!({ "error": "Missing or invalid token" }))).into_response()
+}
+
+async fn rpc(State(state): State<Arc<ServerState>>, headers: HeaderMap, body: String) -> Response {
+    if !authorized(&headers, None, &state.token) {
+        return unauthorized();
+    }
+    Json(handle_rpc(&state.config, &body).await).into_response()
+}
+
+// One JSON-RPC request in, one response out; notifications (no id) still get a reply
+pub async fn handle_rpc(config: &AppConfig, body: &str) -> Value {
+    let request: Value = match serde_json::from_str(body) {
+        Ok(request) => request,
+        Err(e) => return rpc_response(Value::Null, Err(RpcError { code: PARSE_ERROR, message: e.to_string(), data: None })),
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let method = match request.get("method").and_then(Value::as_str) {
+        Some(method) => method.to_string(),
+        None => {
+            return rpc_response(id, Err(RpcError { code: INVALID_REQUEST, message: "Missing method".to_string(), data: None }))
+        }
+    };
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    config.audit.log(LogLevel::Info, "api_call", &method, Value::Null, params.clone());
+    rpc_response(id, call(config, &method, params).await)
+}
+
+fn rpc_response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+    }
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    token: Option<String>,
+}
+
+async fn events(
+    State(state): State<Arc<ServerState>>,
+    headers: HeaderMap,
+    Query(query): Query<EventsQuery>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    if !authorized(&headers, query.token.as_deref(), &state.token) {
+        return unauthorized();
+    }
+    let receiver = state.config.events.subscribe();
+    upgrade.on_upgrade(move |socket| forward_events(socket, receiver))
+}
+
+async fn forward_events(socket: WebSocket, mut receiver: broadcast::Receiver<ProjectEvent>) {
+    let (mut sender, mut incoming) = socket.split();
+    let forward = tokio::spawn(async move {
+        while let Some(event) = next_event(&mut receiver).await {
+            if sender.send(Message::Text(serde_json::to_string(&event).unwrap())).await.is_err() {
+                break;
+            }
+        }
+    });
+    // Clients don't send anything; reading notices when they go away
+    while let Some(Ok(message)) = incoming.next().await {
+        if let Message::Close(_) = message {
+            break;
+        }
+    }
+    forward.abort();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openai_client::Transport;
+    use crate::testing::{client, temp_dir};
+
+    fn config_in(dir: &Path) -> AppConfig {
+        let config = AppConfig::with_client(dir.to_path_buf(), client("http://127.0.0.1:9", Transport::Mock));
+        let mut project = ProjectData::new("Test", 0);
+        let scene = Scene {
+            id: "s1".to_string(),
+            name: "Opening".to_string(),
+            setting: Some("A harbour at night".to_string()),
+            backstory: None,
+            plan: ScenePlan { raw_text: String::new(), parsed_steps: Vec::new() },
+            draft_tab_ids: Vec::new(),
+            created_at: 0,
+            updated_at: 0,
+        };
+        project.scenes.insert("s1".to_string(), scene);
+        engine::write_project(&config.get_project_path(), &project).unwrap();
+        config
+    }
+
+    #[tokio::test]
+    async fn crud_writes_the_saved_project_and_announces_changes() {
+        let dir = temp_dir("api-crud");
+        let config = config_in(&dir);
+        let mut events = config.events.subscribe();
+
+        let star = call(&config, "star.create", json!({ "title": "Mira fears water", "priority": 0.9 })).await.unwrap();
+        let id = star["id"].as_str().unwrap().to_string();
+        assert_eq!(next_event(&mut events).await.unwrap().kind, "star_created");
+        call(&config, "star.update", json!({ "id": id, "body": "Since the wreck" })).await.unwrap();
+        assert_eq!(engine::read_project(&config.get_project_path()).unwrap().stars[&id].body, "Since the wreck");
+
+        let scene = call(&config, "scene.create", json!({ "name": "Crossing" })).await.unwrap();
+        let scenes = call(&config, "scene.list", Value::Null).await.unwrap();
+        assert_eq!(scenes.as_array().unwrap().last().unwrap()["id"], scene["id"]);
+
+        call(&config, "star.delete", json!({ "id": id })).await.unwrap();
+        let missing = call(&config, "star.get", json!({ "id": id })).await.unwrap_err();
+        assert_eq!(missing.data, Some(json!({ "code": "NOT_FOUND" })));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn generate_with_write_adds_a_workbench_draft() {
+        let dir = temp_dir("api-generate");
+        let config = config_in(&dir);
+        let request = json!({ "scene_id": "s1", "prompt_type": "SCENE_TIMELINE", "user_input": "Mira boards", "write": true });
+        let generated = call(&config, "generate", request).await.unwrap();
+        assert!(!generated["tabs"][0]["timeline"].as_array().unwrap().is_empty());
+        let project = engine::read_project(&config.get_project_path()).unwrap();
+        assert_eq!(project.workbench.unassigned_draft_tab_ids.len(), 1);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn writes_are_refused_while_the_app_has_another_project_open() {
+        let dir = temp_dir("api-detached");
+        let config = config_in(&dir);
+        config.saved_project_open.store(false, std::sync::atomic::Ordering::SeqCst);
+
+        let refused = call(&config, "star.create", json!({ "title": "Mira fears water" })).await.unwrap_err();
+        assert_eq!(refused.data, Some(json!({ "code": "PROJECT_NOT_OPEN" })));
+        assert!(engine::read_project(&config.get_project_path()).unwrap().stars.is_empty());
+        assert!(call(&config, "scene.list", Value::Null).await.is_ok());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn http_requires_the_token() {
+        let dir = temp_dir("api-http");
+        let config = Arc::new(config_in(&dir));
+        let settings = ApiSettings { enabled: true, port: 0, token: "secret-token".to_string() };
+        let server = ApiServer::start(config, &settings).await.unwrap();
+        let url = format!("http://{}/rpc", server.addr);
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": "scene.get", "params": { "id": "s1" } });
+        let http = reqwest::Client::new();
+
+        let denied = http.post(&url).json(&body).send().await.unwrap();
+        assert_eq!(denied.status(), StatusCode::UNAUTHORIZED);
+        let reply: Value = http.post(&url).bearer_auth("secret-token").json(&body).send().await.unwrap().json().await.unwrap();
+        assert_eq!(reply["id"], 1);
+        assert_eq!(reply["result"]["scene"]["name"], "Opening");
+        let unknown: Value = http
+            .post(&url)
+            .bearer_auth("secret-token")
+            .json(&json!({ "jsonrpc": "2.0", "id": 2, "method": "nope" }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+        server.stop();
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,294 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::summaries::content_hash;
+
+const LOG_FILE: &str = "spica.log";
+const CONFIG_FILE: &str = "log_config.json";
+// Longer strings in logged content are cut; project JSON arrives with almost every command
+const MAX_CONTENT_CHARS: usize = 20_000;
+const REDACTED: &str = "[REDACTED]";
+
+// Each level includes the ones before it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Off,
+    Error, // failed LLM calls
+    Info,  // every command name, and every LLM call with its timing and token usage
+    Debug, // plus command arguments and full prompts and responses
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct LogConfig {
+    pub level: LogLevel,
+    pub redact_content: bool, // log length and hash instead of prompts, responses and manuscript text
+    pub max_file_bytes: u64,
+    pub max_files: usize, // rotated files kept besides the current one
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self { level: LogLevel::Info, redact_content: false, max_file_bytes: 5 * 1024 * 1024, max_files: 5 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LogEntry {
+    pub ts: String, // RFC 3339, UTC
+    pub level: LogLevel,
+    pub kind: String, // "startup" | "command" | "llm_request" | "llm_response" | "llm_error"
+    pub name: String, // command name or model
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub meta: Value,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub content: Value, // Debug level only
+}
+
+// JSON-lines log under <app dir>/logs, rotated by size
+pub struct AuditLog {
+    dir: PathBuf,
+    config: Mutex<LogConfig>,
+    secrets: Mutex<Vec<String>>,
+    key_pattern: Regex,
+    write_lock: Mutex<()>,
+}
+
+fn rotated(dir: &Path, n: usize) -> PathBuf {
+    if n == 0 {
+        dir.join(LOG_FILE)
+    } else {
+        dir.join(format!("spica.{}.log", n))
+    }
+}
+
+// Strings become "[N chars, hash H]"; structure is kept so the entry still shows what was sent
+fn redact_value(value: &Value) -> Value {
+    match value {
+        Value::String(s) => Value::String(format!("[{} chars, hash {}]", s.chars().count(), content_hash(s))),
+        Value::Array(items) => Value::Array(items.iter().map(redact_value).collect()),
+        Value::Object(map) => Value::Object(map.iter().map(|(k, v)| (k.clone(), redact_value(v))).collect::<Map<_, _>>()),
+        other => other.clone(),
+    }
+}
+
+fn truncate_value(value: &Value) -> Value {
+    match value {
+        Value::String(s) if s.chars().count() > MAX_CONTENT_CHARS => {
+            let kept: String = s.chars().take(MAX_CONTENT_CHARS).collect();
+            Value::String(format!("{}… [+{} chars]", kept, s.chars().count() - MAX_CONTENT_CHARS))
+        }
+        Value::Array(items) => Value::Array(items.iter().map(truncate_value).collect()),
+        Value::Object(map) => Value::Object(map.iter().map(|(k, v)| (k.clone(), truncate_value(v))).collect::<Map<_, _>>()),
+        other => other.clone(),
+    }
+}
+
+impl AuditLog {
+    pub fn new(dir: PathBuf) -> Self {
+        let config = fs::read_to_string(dir.join(CONFIG_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            dir,
+            config: Mutex::new(config),
+            secrets: Mutex::new(Vec::new()),
+            // OpenAI-style keys and bearer tokens, wherever they turn up
+            key_pattern: Regex::new(r"sk-[A-Za-z0-9_\-]{16,}|(?i:bearer)\s+[A-Za-z0-9._\-]{16,}").expect("valid key pattern"),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    pub fn config(&self) -> LogConfig {
+        self.config.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn set_config(&self, config: LogConfig) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join(CONFIG_FILE), serde_json::to_string_pretty(&config)?)?;
+        *self.config.lock().unwrap_or_else(|e| e.into_inner()) = config;
+        Ok(())
+    }
+
+    // Values that must never reach the log, e.g. the API key
+    pub fn add_secret(&self, secret: &str) {
+        if !secret.is_empty() {
+            self.secrets.lock().unwrap_or_else(|e| e.into_inner()).push(secret.to_string());
+        }
+    }
+
+    pub fn enabled(&self, level: LogLevel) -> bool {
+        level != LogLevel::Off && level <= self.config().level
+    }
+
+    pub fn log(&self, level: LogLevel, kind: &str, name: &str, meta: Value, content: Value) {
+        let config = self.config();
+        if level == LogLevel::Off || level > config.level {
+            return;
+        }
+        let content = match (config.level, content) {
+            (LogLevel::Debug, Value::Null) => Value::Null,
+            (LogLevel::Debug, content) if config.redact_content => redact_value(&content),
+            (LogLevel::Debug, content) => truncate_value(&content),
+            _ => Value::Null,
+        };
+        let entry = LogEntry {
+            ts: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+            level,
+            kind: kind.to_string(),
+            name: name.to_string(),
+            meta,
+            content,
+        };
+        let line = match serde_json::to_string(&entry) {
+            Ok(line) => self.redact_secrets(&line),
+            Err(_) => return,
+        };
+        // Logging must never break a command
+        if let Err(e) = self.append(&line, &config) {
+            eprintln!("Failed to write log: {}", e);
+        }
+    }
+
+    // Written as the command is invoked. Tauri's invoke handler never sees the result, so
+    // command entries carry no timing or outcome; failed LLM calls are logged separately.
+    pub fn command(&self, name: &str, payload: &Value) {
+        // Skip copying the arguments unless they will be written
+        let content = if self.enabled(LogLevel::Debug) { payload.clone() } else { Value::Null };
+        self.log(LogLevel::Info, "command", name, Value::Null, content);
+    }
+
+    fn redact_secrets(&self, line: &str) -> String {
+        let mut line = self.key_pattern.replace_all(line, REDACTED).into_owned();
+        for secret in self.secrets.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+            line = line.replace(secret.as_str(), REDACTED);
+        }
+        line
+    }
+
+    fn append(&self, line: &str, config: &LogConfig) -> io::Result<()> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        fs::create_dir_all(&self.dir)?;
+        let current = rotated(&self.dir, 0);
+        let size = fs::metadata(&current).map(|m| m.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 + 1 > config.max_file_bytes {
+            self.rotate(config.max_files)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&current)?;
+        writeln!(file, "{}", line)
+    }
+
+    // spica.log -> spica.1.log -> ... -> spica.<max_files>.log, dropping the oldest
+    fn rotate(&self, max_files: usize) -> io::Result<()> {
+        if max_files == 0 {
+            return fs::remove_file(rotated(&self.dir, 0));
+        }
+        let oldest = rotated(&self.dir, max_files);
+        if oldest.exists() {
+            fs::remove_file(oldest)?;
+        }
+        for n in (0..max_files).rev() {
+            let from = rotated(&self.dir, n);
+            if from.exists() {
+                fs::rename(from, rotated(&self.dir, n + 1))?;
+            }
+        }
+        Ok(())
+    }
+
+    // The newest `limit` entries as JSON lines, oldest first, for attaching to bug reports
+    pub fn export(&self, limit: usize) -> io::Result<String> {
+        let _guard = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let max_files = self.config().max_files;
+        let mut lines: Vec<String> = Vec::new();
+        for n in 0..=max_files {
+            if lines.len() >= limit {
+                break;
+            }
+            let path = rotated(&self.dir, n);
+            if !path.exists() {
+                continue;
+            }
+            let content = fs::read_to_string(path)?;
+            let mut file_lines: Vec<String> = content.lines().filter(|l| !l.trim().is_empty()).map(str::to_string).collect();
+            file_lines.append(&mut lines);
+            lines = file_lines;
+        }
+        let skip = lines.len().saturating_sub(limit);
+        let mut out = lines.split_off(skip).join("\n");
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+    use serde_json::json;
+
+    fn names(export: &str) -> Vec<String> {
+        export.lines().map(|line| serde_json::from_str::<LogEntry>(line).unwrap().name).collect()
+    }
+
+    #[test]
+    fn levels_filter_entries_and_the_config_is_kept() {
+        let dir = temp_dir("audit-levels");
+        let log = AuditLog::new(dir.clone());
+        log.log(LogLevel::Debug, "llm_request", "hidden", Value::Null, Value::Null);
+        log.log(LogLevel::Error, "llm_error", "shown", Value::Null, json!("prompt"));
+        let export = log.export(10).unwrap();
+        assert_eq!(names(&export), vec!["shown"]);
+        // Content is only written at Debug level
+        assert!(!export.contains("prompt"));
+
+        log.set_config(LogConfig { level: LogLevel::Off, ..LogConfig::default() }).unwrap();
+        let reopened = AuditLog::new(dir);
+        assert!(!reopened.enabled(LogLevel::Error));
+        reopened.command("save_project", &json!({}));
+        assert_eq!(names(&reopened.export(10).unwrap()), vec!["shown"]);
+    }
+
+    #[test]
+    fn secrets_and_keys_never_reach_the_file() {
+        let log = AuditLog::new(temp_dir("audit-secrets"));
+        log.set_config(LogConfig { level: LogLevel::Debug, ..LogConfig::default() }).unwrap();
+        log.add_secret("hunter2-project-key");
+        log.command("set_api_key", &json!({ "key": "sk-abcdefghijklmnopqrstuvwx", "note": "hunter2-project-key", "auth": "Bearer abcdefghijklmnopqrstu" }));
+        let export = log.export(10).unwrap();
+        assert_eq!(export.matches(REDACTED).count(), 3);
+        assert!(!export.contains("hunter2") && !export.contains("sk-abc") && !export.contains("abcdefghijklmnopqrstu"));
+    }
+
+    #[test]
+    fn debug_content_is_truncated_or_redacted() {
+        let long = "x".repeat(MAX_CONTENT_CHARS + 5);
+        assert_eq!(truncate_value(&json!([long]))[0].as_str().unwrap().chars().count(), MAX_CONTENT_CHARS + "… [+5 chars]".chars().count());
+        let redacted = redact_value(&json!({ "text": "Mira", "count": 2 }));
+        assert_eq!(redacted, json!({ "text": format!("[4 chars, hash {}]", content_hash("Mira")), "count": 2 }));
+    }
+
+    #[test]
+    fn files_rotate_by_size_and_export_reads_across_them() {
+        let dir = temp_dir("audit-rotate");
+        let log = AuditLog::new(dir.clone());
+        // Each entry is under 100 bytes, so two fit in a file
+        log.set_config(LogConfig { level: LogLevel::Info, redact_content: false, max_file_bytes: 200, max_files: 2 }).unwrap();
+        for n in 0..7 {
+            log.command(&format!("c{}", n), &Value::Null);
+        }
+        assert!(rotated(&dir, 2).exists() && !rotated(&dir, 3).exists());
+        assert_eq!(names(&log.export(100).unwrap()), vec!["c2", "c3", "c4", "c5", "c6"]);
+        assert_eq!(names(&log.export(3).unwrap()), vec!["c4", "c5", "c6"]);
+    }
+}
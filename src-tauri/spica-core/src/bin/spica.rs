@@ -0,0 +1,508 @@
+// Headless access to Spica project files for scripts, batch jobs and CI
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use spica_core::context_engine::{PromptType, SectionBudgets};
+use spica_core::engine::{self, AppConfig};
+use spica_core::mcp::McpServer;
+use spica_core::openai_client::{ClientError, GenerationParams, OpenAIClient};
+use spica_core::provenance::GenerationRequest;
+use spica_core::scripting::{self, ScriptHost, ScriptMessage};
+use spica_core::stats::{self, StatsOptions};
+use spica_core::vault::{self, Keep};
+use spica_core::{outline, templates, ApiError, ProjectData};
+
+#[derive(Parser)]
+#[command(name = "spica", version, about = "Work with Spica Writer project files from the command line")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a scene timeline draft from a prompt
+    Generate {
+        project: PathBuf,
+        #[arg(long)]
+        scene: String,
+        #[arg(long)]
+        prompt: String,
+        #[command(flatten)]
+        llm: LlmArgs,
+        /// Add the draft to the end of the scene instead of only printing it
+        #[arg(long)]
+        write: bool,
+    },
+    /// Generate a description for a draft tab or one of its events
+    Describe {
+        project: PathBuf,
+        #[arg(long)]
+        tab: String,
+        #[arg(long)]
+        event: Option<String>,
+        /// Needed when the tab is not in a scene
+        #[arg(long)]
+        scene: Option<String>,
+        #[arg(long, default_value = "")]
+        prompt: String,
+        #[command(flatten)]
+        llm: LlmArgs,
+        /// Attach the description to the tab instead of only printing it
+        #[arg(long)]
+        write: bool,
+    },
+    /// Export the manuscript, the project or its statistics
+    Export {
+        project: PathBuf,
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: ExportFormat,
+        /// Write to a file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Report dangling references and invalid project templates; exits with 1 when any are found
+    Validate { project: PathBuf },
+    /// Word counts per scene and character
+    Stats {
+        project: PathBuf,
+        /// Count unchecked events too
+        #[arg(long)]
+        all_events: bool,
+        #[arg(long)]
+        no_descriptions: bool,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Serve the project to chat assistants as a Model Context Protocol server over stdio
+    Mcp {
+        /// Defaults to the project the app saves to
+        project: Option<PathBuf>,
+    },
+    /// Sync characters and stars with a folder of Markdown notes, such as an Obsidian vault; exits with 1 on conflicts
+    Vault {
+        project: PathBuf,
+        folder: PathBuf,
+        /// Settle the conflict for this character or star id with the project's version
+        #[arg(long, value_name = "ID")]
+        keep_project: Vec<String>,
+        /// Settle the conflict for this character or star id with the note's version
+        #[arg(long, value_name = "ID")]
+        keep_vault: Vec<String>,
+    },
+    /// Rewrite a project file in the current format, keeping a .bak copy of the original
+    Migrate {
+        project: PathBuf,
+        #[arg(long)]
+        dry_run: bool,
+        #[arg(long)]
+        no_backup: bool,
+    },
+}
+
+#[derive(Args)]
+struct LlmArgs {
+    #[arg(long)]
+    model: Option<String>,
+    #[arg(long)]
+    temperature: Option<f32>,
+    /// Template to use instead of the one the project selected for the prompt type
+    #[arg(long)]
+    template: Option<String>,
+    /// Template version to use instead of the project's active one
+    #[arg(long)]
+    template_version: Option<u32>,
+    /// Print the full result, including provenance, as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    Markdown,
+    Json,
+    ScenesCsv,
+    CharactersCsv,
+    HistoryCsv,
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv::dotenv().ok();
+    // 1 means the command ran and found problems (validate), 2 that it failed
+    let code = match run(Cli::parse().command, OpenAIClient::new).await {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            match e.code {
+                Some(code) => eprintln!("error [{}]: {}", code, e.message),
+                None => eprintln!("error: {}", e.message),
+            }
+            2
+        }
+    };
+    process::exit(code);
+}
+
+// `new_client` is only called by commands that generate
+async fn run(command: Command, new_client: fn() -> Result<OpenAIClient, ClientError>) -> Result<bool, ApiError> {
+    match command {
+        Command::Generate { project: path, scene, prompt, llm, write } => {
+            let mut project = engine::read_project(&path)?;
+            let scripts = scripts_for(&path);
+            let request = generation_request(scene.clone(), PromptType::SceneTimeline, prompt, None, &llm);
+            let generated = engine::run_generation(&load_config(&path, new_client)?, &scripts, &project, request).await?;
+            if llm.json {
+                println!("{}", serde_json::to_string_pretty(&generated).unwrap());
+            } else {
+                for tab in &generated.tabs {
+                    for event in &tab.timeline {
+                        match &event.dialogue {
+                            Some(dialogue) => println!("- {} \"{}\"", event.text, dialogue),
+                            None => println!("- {}", event.text),
+                        }
+                    }
+                }
+            }
+            if write {
+                let ids = engine::write_generation(&mut project, generated, Some(&scene), None, None, now_ms())?;
+                save(&path, &scripts, project)?;
+                eprintln!("Added draft tab(s) {} to scene {}", ids.join(", "), scene);
+            }
+            Ok(true)
+        }
+
+        Command::Describe { project: path, tab, event, scene, prompt, llm, write } => {
+            let mut project = engine::read_project(&path)?;
+            let draft = project.draft_tabs.get(&tab).ok_or_else(|| not_found("Draft tab", &tab))?;
+            let target_event = match &event {
+                Some(event_id) => Some(
+                    draft
+                        .timeline
+                        .iter()
+                        .find(|e| e.id.as_deref() == Some(event_id.as_str()))
+                        .ok_or_else(|| not_found("Event", event_id))?
+                        .text
+                        .clone(),
+                ),
+                None => None,
+            };
+            let scene = scene.or_else(|| draft.scene_id.clone()).ok_or(ApiError {
+                error: true,
+                message: format!("Draft tab {} is not in a scene; pass --scene", tab),
+                code: Some("SCENE_REQUIRED".to_string()),
+            })?;
+
+            let scripts = scripts_for(&path);
+            let request = generation_request(scene, PromptType::EventDescription, prompt, target_event, &llm);
+            let generated = engine::run_generation(&load_config(&path, new_client)?, &scripts, &project, request).await?;
+            if llm.json {
+                println!("{}", serde_json::to_string_pretty(&generated).unwrap());
+            } else {
+                println!("{}", generated.description.as_deref().unwrap_or_default());
+            }
+            if write {
+                let ids = engine::write_generation(&mut project, generated, None, Some(&tab), event.as_deref(), now_ms())?;
+                save(&path, &scripts, project)?;
+                eprintln!("Added description {} to draft tab {}", ids.join(", "), tab);
+            }
+            Ok(true)
+        }
+
+        Command::Export { project: path, format, output } => {
+            let project = engine::read_project(&path)?;
+            let options = StatsOptions::default();
+            let (name, text) = match format {
+                ExportFormat::Markdown => ("markdown", outline::manuscript_markdown(&project)),
+                ExportFormat::Json => ("json", serde_json::to_string_pretty(&project).unwrap()),
+                ExportFormat::ScenesCsv => ("scenes_csv", stats::scenes_csv(&stats::compute(&project, &options))),
+                ExportFormat::CharactersCsv => ("characters_csv", stats::characters_csv(&stats::compute(&project, &options))),
+                ExportFormat::HistoryCsv => ("history_csv", stats::history_csv(&project.progress)),
+            };
+            let (text, messages) = scripts_for(&path).on_export(name, text);
+            report(&messages);
+            match output {
+                Some(output) => fs::write(&output, text).map_err(|e| ApiError {
+                    error: true,
+                    message: format!("Failed to write {}: {}", output.display(), e),
+                    code: Some("EXPORT_ERROR".to_string()),
+                })?,
+                None => print!("{}", text),
+            }
+            Ok(true)
+        }
+
+        Command::Validate { project: path } => {
+            let project = engine::read_project(&path)?;
+            let mut issues = engine::dangling_references(&project);
+            for template in &project.prompt_templates.templates {
+                for issue in templates::validate(template) {
+                    issues.push(format!("Template {} v{} {}: {}", template.name, template.version, issue.field, issue.message));
+                }
+            }
+            for issue in &issues {
+                println!("{}", issue);
+            }
+            if issues.is_empty() {
+                eprintln!("{}: no problems found", path.display());
+                Ok(true)
+            } else {
+                eprintln!("{}: {} problem(s) found", path.display(), issues.len());
+                Ok(false)
+            }
+        }
+
+        Command::Stats { project: path, all_events, no_descriptions, json } => {
+            let project = engine::read_project(&path)?;
+            let options = StatsOptions { checked_only: !all_events, include_descriptions: !no_descriptions };
+            let stats = stats::compute(&project, &options);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&stats).unwrap());
+                return Ok(true);
+            }
+            println!("{}", project.metadata.title);
+            println!(
+                "{} words ({} narration, {} dialogue, {} description), {:.0}% dialogue",
+                stats.words.total,
+                stats.words.narration,
+                stats.words.dialogue,
+                stats.words.description,
+                stats.dialogue_ratio * 100.0
+            );
+            if stats.workbench_words > 0 {
+                println!("{} words in the workbench (not counted)", stats.workbench_words);
+            }
+            println!();
+            for scene in &stats.scenes {
+                println!("{:>8}  {} ({} tabs, {} events)", scene.words.total, scene.name, scene.draft_tabs, scene.events);
+            }
+            if !stats.characters.is_empty() {
+                println!();
+                for character in &stats.characters {
+                    println!("{:>8}  {} ({} lines, {} mentions)", character.dialogue_words, character.name, character.lines, character.mentions);
+                }
+            }
+            Ok(true)
+        }
+
+        Command::Mcp { project } => {
+            let path = match project {
+                Some(path) => path,
+                None => engine::default_project_dir().ok_or(ApiError {
+                    error: true,
+                    message: "Could not find home directory; pass the project file".to_string(),
+                    code: Some("HOME_DIR_ERROR".to_string()),
+                })?
+                .join(engine::PROJECT_FILE),
+            };
+            // Fail now rather than on the client's first request
+            engine::read_project(&path)?;
+            let stdin = io::stdin();
+            McpServer::new(path).serve(stdin.lock(), io::stdout()).map_err(|e| ApiError {
+                error: true,
+                message: format!("MCP connection failed: {}", e),
+                code: Some("IO_ERROR".to_string()),
+            })?;
+            Ok(true)
+        }
+
+        Command::Vault { project: path, folder, keep_project, keep_vault } => {
+            let mut project = engine::read_project(&path)?;
+            let mut resolutions = HashMap::new();
+            resolutions.extend(keep_project.into_iter().map(|id| (id, Keep::Project)));
+            resolutions.extend(keep_vault.into_iter().map(|id| (id, Keep::Vault)));
+            let report = fs::create_dir_all(&folder)
+                .and_then(|_| vault::sync(&mut project, &folder, &resolutions))
+                .map_err(|e| ApiError {
+                    error: true,
+                    message: format!("Failed to sync with {}: {}", folder.display(), e),
+                    code: Some("VAULT_ERROR".to_string()),
+                })?;
+            if report.project_changed() {
+                save(&path, &scripts_for(&path), project)?;
+            }
+
+            for note in &report.written {
+                println!("wrote {}", note);
+            }
+            for note in &report.removed {
+                println!("removed {}", note);
+            }
+            for id in &report.imported {
+                println!("imported {}", id);
+            }
+            for id in &report.deleted {
+                println!("deleted {}", id);
+            }
+            for warning in &report.warnings {
+                eprintln!("warning: {}", warning);
+            }
+            for conflict in &report.conflicts {
+                println!("conflict {} ({}): {}", conflict.path, conflict.id, conflict.reason);
+            }
+            Ok(report.conflicts.is_empty())
+        }
+
+        Command::Migrate { project: path, dry_run, no_backup } => {
+            let original = fs::read_to_string(&path).map_err(|e| ApiError {
+                error: true,
+                message: format!("Failed to load project: {}", e),
+                code: Some("LOAD_ERROR".to_string()),
+            })?;
+            let project = engine::parse_project(&original)?;
+            let before: serde_json::Value = serde_json::from_str(&original).unwrap_or_default();
+            if before == serde_json::to_value(&project).unwrap() {
+                eprintln!("{} is already up to date", path.display());
+                return Ok(true);
+            }
+            if dry_run {
+                eprintln!("{} would be rewritten in the current format", path.display());
+                return Ok(true);
+            }
+            if !no_backup {
+                let backup = backup_path(&path);
+                fs::copy(&path, &backup).map_err(|e| ApiError {
+                    error: true,
+                    message: format!("Failed to back up project: {}", e),
+                    code: Some("SAVE_ERROR".to_string()),
+                })?;
+                eprintln!("Original saved to {}", backup.display());
+            }
+            engine::write_project(&path, &project)?;
+            eprintln!("Migrated {}", path.display());
+            Ok(true)
+        }
+    }
+}
+
+// Templates and logs come from beside the project file, as the app's do, so the CLI never
+// creates the app's own folder
+fn load_config(project_path: &Path, new_client: fn() -> Result<OpenAIClient, ClientError>) -> Result<AppConfig, ApiError> {
+    let dir = project_path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+    let client = new_client().map_err(|e| ApiError {
+        error: true,
+        message: format!("Failed to initialize: {}", e),
+        code: Some("CONFIG_ERROR".to_string()),
+    })?;
+    Ok(AppConfig::with_client(dir.to_path_buf(), client))
+}
+
+fn generation_request(scene_id: String, prompt_type: PromptType, user_input: String, target_event: Option<String>, llm: &LlmArgs) -> GenerationRequest {
+    GenerationRequest {
+        scene_id,
+        prompt_type,
+        user_input,
+        target_event,
+        budgets: SectionBudgets::default(),
+        params: GenerationParams { model: llm.model.clone(), temperature: llm.temperature },
+        template_name: llm.template.clone(),
+        template_version: llm.template_version,
+    }
+}
+
+fn scripts_for(project_path: &Path) -> ScriptHost {
+    ScriptHost::load_dir(&scripting::script_dir(project_path))
+}
+
+fn report(messages: &[ScriptMessage]) {
+    for message in messages {
+        eprintln!("script {}: {}", message.script, message.message);
+    }
+}
+
+fn save(path: &Path, scripts: &ScriptHost, mut project: ProjectData) -> Result<(), ApiError> {
+    project.metadata.updated_at = now_ms();
+    let (project, messages) = scripts.before_save(project);
+    report(&messages);
+    engine::write_project(path, &project)
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+fn not_found(kind: &str, id: &str) -> ApiError {
+    ApiError {
+        error: true,
+        message: format!("{} not found: {}", kind, id),
+        code: Some("NOT_FOUND".to_string()),
+    }
+}
+
+fn now_ms() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spica_core::model::{Scene, ScenePlan};
+    use spica_core::openai_client::{ClientConfig, Transport};
+
+    fn mock_client() -> Result<OpenAIClient, ClientError> {
+        Ok(OpenAIClient::with_config(ClientConfig {
+            api_key: String::new(),
+            base_url: "http://127.0.0.1:9/v1".to_string(),
+            model: "gpt-4.1".to_string(),
+            transport: Transport::Mock,
+        }))
+    }
+
+    // A project file with one empty scene, "s1", in a fresh directory
+    fn project_file(label: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("spica-cli-{}-{}", label, uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut project = ProjectData::new("Test", 0);
+        project.scenes.insert(
+            "s1".to_string(),
+            Scene {
+                id: "s1".to_string(),
+                name: "Harbour".to_string(),
+                setting: None,
+                backstory: None,
+                plan: ScenePlan { raw_text: String::new(), parsed_steps: Vec::new() },
+                draft_tab_ids: Vec::new(),
+                created_at: 0,
+                updated_at: 0,
+            },
+        );
+        let path = dir.join("project.json");
+        engine::write_project(&path, &project).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn generate_with_write_adds_the_draft_to_the_scene() {
+        let path = project_file("generate");
+        let llm = LlmArgs { model: None, temperature: None, template: None, template_version: None, json: false };
+        let command = Command::Generate { project: path.clone(), scene: "s1".to_string(), prompt: "Mira runs".to_string(), llm, write: true };
+        assert!(run(command, mock_client).await.unwrap_or_else(|e| panic!("{}", e.message)));
+
+        let project = engine::read_project(&path).unwrap();
+        let ids = &project.scenes["s1"].draft_tab_ids;
+        assert_eq!(ids.len(), 1);
+        assert_eq!(project.draft_tabs[&ids[0]].scene_id.as_deref(), Some("s1"));
+        assert!(!project.draft_tabs[&ids[0]].timeline.is_empty());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[tokio::test]
+    async fn validate_reports_dangling_references() {
+        let path = project_file("validate");
+        assert!(run(Command::Validate { project: path.clone() }, mock_client).await.unwrap());
+
+        let mut project = engine::read_project(&path).unwrap();
+        project.scenes.get_mut("s1").unwrap().draft_tab_ids.push("missing".to_string());
+        engine::write_project(&path, &project).unwrap();
+        assert!(!run(Command::Validate { project: path.clone() }, mock_client).await.unwrap());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,517 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::continuity::{self, Attribute};
+use crate::openai_client::extract_json_object;
+use crate::prompts;
+use crate::speaker::{character_mentions, parse_explicit_speaker};
+use crate::{Character, DraftTab, ProjectData};
+
+// Capitalised words that are never names on their own
+const NOT_NAMES: &[&str] = &[
+    "I", "The", "A", "An", "He", "She", "They", "It", "We", "You", "His", "Her", "Their", "Its", "Then", "When",
+    "As", "After", "Before", "Suddenly", "Meanwhile", "Later", "Finally", "But", "And", "Or", "If", "While",
+    "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday", "January", "February",
+    "March", "April", "May", "June", "July", "August", "September", "October", "November", "December",
+];
+
+// "<Name> lives in Harrow" -> home = Harrow
+const LOCATION_PATTERNS: &[(&str, &str)] = &[
+    (" lives in ", "home"),
+    (" was born in ", "birthplace"),
+    (", from ", "origin"),
+    (" from ", "origin"),
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Citation {
+    pub tab_id: String,
+    pub event_index: Option<usize>,
+    pub description_id: Option<String>,
+    pub excerpt: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalStatus {
+    New,      // no field covers this yet
+    Conflict, // disagrees with an existing field value
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FieldProposal {
+    pub character_id: String,
+    pub character_name: String,
+    pub category: String, // "appearance" | "mannerism" | "relationship" | "location"
+    pub field: String,
+    pub value: String,
+    pub status: ProposalStatus,
+    pub existing_field: Option<String>,
+    pub existing_value: Option<String>,
+    pub citations: Vec<Citation>,
+    pub method: String, // "rule" | "llm"
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NewCharacterProposal {
+    pub name: String,
+    pub occurrences: usize,
+    pub citations: Vec<Citation>,
+    pub character: Character, // ready to insert if the writer accepts it
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ProfileReport {
+    pub field_proposals: Vec<FieldProposal>,
+    pub new_characters: Vec<NewCharacterProposal>,
+}
+
+type NamedCitation = (String, Citation); // unknown character name, where it appeared
+
+// One attribute value seen in the prose, before grouping
+struct Finding {
+    character_id: String,
+    category: String,
+    field: String,
+    value: String,
+    citation: Citation,
+    method: &'static str,
+}
+
+fn event_text(tab: &DraftTab, index: usize) -> String {
+    let event = &tab.timeline[index];
+    match &event.dialogue {
+        Some(dialogue) => format!("{} \"{}\"", event.text, dialogue),
+        None => event.text.clone(),
+    }
+}
+
+// (event index, description id, text) for every piece of prose in the draft
+fn texts(tab: &DraftTab) -> Vec<(Option<usize>, Option<String>, String)> {
+    let mut texts: Vec<_> = (0..tab.timeline.len()).map(|i| (Some(i), None, event_text(tab, i))).collect();
+    texts.extend(tab.descriptions.iter().map(|d| (None, Some(d.id.clone()), d.text.clone())));
+    texts
+}
+
+fn capitalised_run(text: &str) -> String {
+    text.split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()))
+        .take_while(|w| w.chars().next().map_or(false, char::is_uppercase))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn rule_findings(tab: &DraftTab, characters: &[Character]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for (event_index, description_id, observation) in continuity::observe_tab(tab, characters) {
+        let (category, field) = match observation.attribute {
+            Attribute::Eyes => ("appearance", "eyes".to_string()),
+            Attribute::Hair => ("appearance", "hair".to_string()),
+            Attribute::Age => ("appearance", "age".to_string()),
+            Attribute::Relation => ("relationship", observation.relation.clone().unwrap_or_default()),
+        };
+        findings.push(Finding {
+            character_id: observation.character_id,
+            category: category.to_string(),
+            field,
+            value: observation.value,
+            citation: Citation { tab_id: tab.id.clone(), event_index, description_id, excerpt: observation.excerpt },
+            method: "rule",
+        });
+    }
+
+    for (event_index, description_id, text) in texts(tab) {
+        for (_, end, character) in character_mentions(&text, characters) {
+            let rest = &text[end..];
+            for (pattern, field) in LOCATION_PATTERNS {
+                if let Some(place) = rest.strip_prefix(pattern).map(capitalised_run) {
+                    if !place.is_empty() {
+                        findings.push(Finding {
+                            character_id: character.id.clone(),
+                            category: "location".to_string(),
+                            field: field.to_string(),
+                            value: place,
+                            citation: Citation {
+                                tab_id: tab.id.clone(),
+                                event_index,
+                                description_id: description_id.clone(),
+                                excerpt: text.clone(),
+                            },
+                            method: "rule",
+                        });
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    findings
+}
+
+#[derive(Deserialize)]
+struct LlmObservation {
+    character: String,
+    category: String,
+    field: String,
+    value: String,
+    event_index: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct LlmUnknown {
+    name: String,
+    event_index: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct LlmProfile {
+    #[serde(default)]
+    observations: Vec<LlmObservation>,
+    #[serde(default)]
+    unknown_characters: Vec<LlmUnknown>,
+}
+
+pub fn build_llm_prompt(project: &ProjectData, tab: &DraftTab) -> (String, String) {
+    let mut names: Vec<&str> = project.characters.values().map(|c| c.name.as_str()).collect();
+    names.sort();
+
+    let mut user_prompt = format!("### KNOWN CHARACTERS\n{}\n\n### TIMELINE\n", names.join(", "));
+    for index in 0..tab.timeline.len() {
+        user_prompt.push_str(&format!("{}. {}\n", index, event_text(tab, index)));
+    }
+    if !tab.descriptions.is_empty() {
+        user_prompt.push_str("\n### DESCRIPTIONS\n");
+        for description in &tab.descriptions {
+            user_prompt.push_str(&format!("- {}\n", description.text));
+        }
+    }
+    user_prompt.push('\n');
+    user_prompt.push_str(prompts::PROFILE_EXTRACTION_INSTRUCTIONS);
+
+    (prompts::PROFILE_EXTRACTION_SYSTEM.to_string(), user_prompt)
+}
+
+// Parse the model's reply into findings plus unknown names with where they appeared
+fn parse_llm_reply(
+    response: &str,
+    tab: &DraftTab,
+    characters: &[Character],
+) -> Result<(Vec<Finding>, Vec<NamedCitation>), String> {
+    let json = extract_json_object(response).ok_or("Profile reply contained no JSON object")?;
+    let parsed: LlmProfile = serde_json::from_str(json).map_err(|e| format!("Invalid profile JSON: {}", e))?;
+
+    let citation = |event_index: Option<usize>| {
+        let event_index = event_index.filter(|i| *i < tab.timeline.len());
+        Citation {
+            tab_id: tab.id.clone(),
+            event_index,
+            description_id: None,
+            excerpt: event_index.map(|i| event_text(tab, i)).unwrap_or_default(),
+        }
+    };
+
+    let findings = parsed
+        .observations
+        .into_iter()
+        .filter(|o| !o.field.trim().is_empty() && !o.value.trim().is_empty())
+        .filter_map(|o| {
+            let (_, _, character) = character_mentions(&o.character, characters).into_iter().next()?;
+            Some(Finding {
+                character_id: character.id.clone(),
+                category: o.category,
+                field: o.field.trim().to_lowercase(),
+                value: o.value.trim().to_string(),
+                citation: citation(o.event_index),
+                method: "llm",
+            })
+        })
+        .collect();
+
+    let unknown = parsed
+        .unknown_characters
+        .into_iter()
+        .filter(|u| !u.name.trim().is_empty() && character_mentions(&u.name, characters).is_empty())
+        .map(|u| (u.name.trim().to_string(), citation(u.event_index)))
+        .collect();
+
+    Ok((findings, unknown))
+}
+
+// Name-like capitalised runs that match no known character, with explicit speakers always included.
+// Sentence openers are skipped, and so are places: runs after a location pattern, wherever they recur.
+fn unknown_names(tabs: &[&DraftTab], characters: &[Character], known_terms: &HashSet<String>) -> Vec<(String, Citation, bool)> {
+    let mut found = Vec::new();
+    let mut places = HashSet::new();
+
+    for tab in tabs {
+        for (event_index, description_id, text) in texts(tab) {
+            let citation = Citation {
+                tab_id: tab.id.clone(),
+                event_index,
+                description_id: description_id.clone(),
+                excerpt: text.clone(),
+            };
+
+            if let Some(index) = event_index {
+                let event = &tab.timeline[index];
+                if event.dialogue.is_some() {
+                    if let Some(name) = parse_explicit_speaker(&event.text) {
+                        if character_mentions(&name, characters).is_empty() {
+                            found.push((name, citation.clone(), true));
+                        }
+                    }
+                }
+            }
+
+            let mentioned: Vec<(usize, usize)> =
+                character_mentions(&text, characters).into_iter().map(|(s, e, _)| (s, e)).collect();
+            let mut run: Vec<&str> = Vec::new();
+            let mut offset = 0;
+            let words: Vec<(usize, &str)> = text
+                .split_whitespace()
+                .map(|w| {
+                    let position = text[offset..].find(w).map(|p| p + offset).unwrap_or(offset);
+                    offset = position + w.len();
+                    (position, w)
+                })
+                .collect();
+
+            let mut run_is_place = false;
+            for (i, (position, raw)) in words.iter().enumerate() {
+                let word = raw.trim_matches(|c: char| !c.is_alphanumeric());
+                // Capitalised because a sentence or a quotation starts here, not because it is a name
+                let sentence_start = i == 0
+                    || raw.starts_with(['"', '\'', '\u{201c}', '\u{2018}'])
+                    || words[i - 1].1.trim_end_matches(['"', '\'', ')', '\u{201d}', '\u{2019}']).ends_with(['.', '!', '?', ':']);
+                let is_candidate = word.chars().count() > 1
+                    && word.chars().next().map_or(false, char::is_uppercase)
+                    && word.chars().skip(1).all(|c| c.is_lowercase() || c == '\'')
+                    && !NOT_NAMES.contains(&word)
+                    && !known_terms.contains(&word.to_lowercase())
+                    && !mentioned.iter().any(|(s, e)| position >= s && position < e);
+                if is_candidate && !(run.is_empty() && sentence_start) {
+                    if run.is_empty() {
+                        run_is_place = LOCATION_PATTERNS.iter().any(|(pattern, _)| text[..*position].ends_with(pattern));
+                    }
+                    run.push(word);
+                }
+                // A run ends at a non-candidate word or at punctuation
+                let ends_here = !is_candidate || raw.ends_with(|c: char| !c.is_alphanumeric()) || i + 1 == words.len();
+                if ends_here && !run.is_empty() {
+                    if run_is_place {
+                        places.insert(run.join(" "));
+                    } else {
+                        found.push((run.join(" "), citation.clone(), false));
+                    }
+                    run.clear();
+                }
+            }
+        }
+    }
+
+    found.retain(|(name, _, explicit)| *explicit || !places.contains(name));
+    found
+}
+
+fn existing_field<'a>(character: &'a Character, field: &str) -> Option<(&'a String, &'a String)> {
+    let wanted = continuity::attribute_for_field(field);
+    character.fields.iter().find(|(key, _)| {
+        key.trim().eq_ignore_ascii_case(field) || (wanted.is_some() && continuity::attribute_for_field(key) == wanted)
+    })
+}
+
+fn field_agrees(field: &str, expected: &str, observed: &str) -> bool {
+    match continuity::attribute_for_field(field) {
+        Some((attribute, _)) => continuity::agrees(attribute, expected, observed),
+        None => expected.to_lowercase().contains(&observed.to_lowercase()),
+    }
+}
+
+pub fn build_report(
+    project: &ProjectData,
+    tabs: &[&DraftTab],
+    llm_replies: &[(String, String)], // (tab id, model reply)
+) -> Result<ProfileReport, String> {
+    let characters: Vec<Character> = project.characters.values().cloned().collect();
+
+    let mut findings: Vec<Finding> = tabs.iter().flat_map(|tab| rule_findings(tab, &characters)).collect();
+    let mut llm_unknown = Vec::new();
+    for (tab_id, reply) in llm_replies {
+        if let Some(tab) = tabs.iter().find(|t| &t.id == tab_id) {
+            let (llm_findings, unknown) = parse_llm_reply(reply, tab, &characters)?;
+            findings.extend(llm_findings);
+            llm_unknown.extend(unknown);
+        }
+    }
+
+    // Group identical findings so each proposal carries all of its citations
+    let mut grouped: BTreeMap<(String, String, String), Vec<Finding>> = BTreeMap::new();
+    for finding in findings {
+        let key = (finding.character_id.clone(), finding.field.clone(), finding.value.to_lowercase());
+        grouped.entry(key).or_default().push(finding);
+    }
+
+    let mut field_proposals = Vec::new();
+    for ((character_id, field, _), group) in grouped {
+        let character = match project.characters.get(&character_id) {
+            Some(character) => character,
+            None => continue,
+        };
+        let first = &group[0];
+        let (status, existing_field, existing_value) = match existing_field(character, &field) {
+            Some((_, value)) if field_agrees(&field, value, &first.value) => continue,
+            Some((key, value)) => (ProposalStatus::Conflict, Some(key.clone()), Some(value.clone())),
+            None => (ProposalStatus::New, None, None),
+        };
+        field_proposals.push(FieldProposal {
+            character_id,
+            character_name: character.name.clone(),
+            category: first.category.clone(),
+            field,
+            value: first.value.clone(),
+            status,
+            existing_field,
+            existing_value,
+            method: if group.iter().any(|f| f.method == "rule") { "rule" } else { "llm" }.to_string(),
+            citations: group.into_iter().map(|f| f.citation).collect(),
+        });
+    }
+
+    // Words that appear lowercased somewhere are ordinary vocabulary, not names
+    let mut known_terms: HashSet<String> = HashSet::new();
+    for tab in tabs {
+        for (_, _, text) in texts(tab) {
+            for word in text.split(|c: char| !c.is_alphanumeric()) {
+                if word.chars().next().map_or(false, char::is_lowercase) {
+                    known_terms.insert(word.to_string());
+                }
+            }
+        }
+    }
+    for star in project.stars.values() {
+        for word in star.title.split_whitespace() {
+            known_terms.insert(word.to_lowercase());
+        }
+    }
+
+    let mut candidates: HashMap<String, (Vec<Citation>, bool)> = HashMap::new();
+    let mut add_candidate = |name: String, citation: Citation, explicit: bool| {
+        let entry = candidates.entry(name).or_default();
+        let repeated = entry.0.iter().any(|c| {
+            c.tab_id == citation.tab_id && c.event_index == citation.event_index && c.description_id == citation.description_id
+        });
+        if !repeated {
+            entry.0.push(citation);
+        }
+        entry.1 |= explicit;
+    };
+    for (name, citation, explicit) in unknown_names(tabs, &characters, &known_terms) {
+        add_candidate(name, citation, explicit);
+    }
+    for (name, citation) in llm_unknown {
+        add_candidate(name, citation, true);
+    }
+
+    let mut new_characters: Vec<NewCharacterProposal> = candidates
+        .into_iter()
+        // A single passing capitalised word is too weak; explicit speakers and LLM finds are enough
+        .filter(|(_, (citations, explicit))| *explicit || citations.len() >= 2)
+        .map(|(name, (citations, _))| NewCharacterProposal {
+            occurrences: citations.len(),
+            citations,
+            character: Character {
+                id: uuid::Uuid::new_v4().to_string(),
+                name: name.clone(),
+                fields: HashMap::new(),
+                is_checked: true,
+                last_used_in_prompt: None,
+                usage_count: 0,
+            },
+            name,
+        })
+        .collect();
+    new_characters.sort_by(|a, b| b.occurrences.cmp(&a.occurrences).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(ProfileReport { field_proposals, new_characters })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openai_client::TimelineEvent;
+    use crate::testing::{add_scene, character, event};
+
+    fn project() -> ProjectData {
+        let mut project = ProjectData::new("Test", 0);
+        let mut mira = character("c1", "Mira");
+        mira.fields.insert("Eye colour".to_string(), "green".to_string());
+        project.characters.insert("c1".to_string(), mira);
+        project.characters.insert("c2".to_string(), character("c2", "Oren"));
+        project
+    }
+
+    fn said(text: &str, dialogue: &str) -> TimelineEvent {
+        TimelineEvent { dialogue: Some(dialogue.to_string()), ..event(text) }
+    }
+
+    #[test]
+    fn rules_find_appearance_relations_and_places() {
+        let mut project = project();
+        add_scene(&mut project, "s1", "Harbour", &[&["Mira's blue eyes narrowed.", "Mira lives in Port Harrow now.", "Mira's sister Ana waves."]]);
+        let characters: Vec<Character> = project.characters.values().cloned().collect();
+        let mut found: Vec<_> = rule_findings(&project.draft_tabs["s1-t0"], &characters)
+            .into_iter()
+            .map(|f| (f.citation.event_index, f.category, f.field, f.value))
+            .collect();
+        found.sort();
+        let expected = [(0, "appearance", "eyes", "blue"), (1, "location", "home", "Port Harrow"), (2, "relationship", "sister", "Ana")];
+        assert_eq!(found, expected.map(|(i, c, f, v)| (Some(i), c.to_string(), f.to_string(), v.to_string())));
+    }
+
+    #[test]
+    fn findings_are_grouped_and_compared_with_existing_fields() {
+        let mut project = project();
+        add_scene(&mut project, "s1", "Harbour", &[&["Mira's blue eyes narrowed."], &["Mira's blue eyes shine.", "Mira has green eyes.", "Oren lives in Harrow."]]);
+        let tabs: Vec<&DraftTab> = vec![&project.draft_tabs["s1-t0"], &project.draft_tabs["s1-t1"]];
+        let reply = r#"{"observations": [{"character": "Mira", "category": "appearance", "field": "Eyes", "value": "Blue", "event_index": 0}],
+            "unknown_characters": [{"name": "Tomas", "event_index": 9}, {"name": "Oren", "event_index": 0}]}"#;
+        let report = build_report(&project, &tabs, &[("s1-t0".to_string(), reply.to_string())]).unwrap();
+
+        let proposals: Vec<_> = report.field_proposals.iter().map(|p| (p.character_name.as_str(), p.field.as_str(), p.value.as_str(), p.status)).collect();
+        assert_eq!(proposals, vec![("Mira", "eyes", "blue", ProposalStatus::Conflict), ("Oren", "home", "Harrow", ProposalStatus::New)]);
+        let eyes = &report.field_proposals[0];
+        assert_eq!((eyes.existing_field.as_deref(), eyes.citations.len(), eyes.method.as_str()), (Some("Eye colour"), 3, "rule"));
+
+        // The LLM's unknown names count on their own; known characters are not proposed again
+        let names: Vec<_> = report.new_characters.iter().map(|c| (c.name.as_str(), c.citations[0].event_index)).collect();
+        assert_eq!(names, vec![("Tomas", None)]);
+        assert!(build_report(&project, &tabs, &[("s1-t0".to_string(), "no json".to_string())]).is_err());
+    }
+
+    #[test]
+    fn sentence_openers_and_places_are_not_new_characters() {
+        let mut project = project();
+        add_scene(&mut project, "s1", "Harbour", &[&[
+            "Suddenly the door opens. Harbour lights flicker.",
+            "Mira lives in Port Harrow.",
+            "Oren sails to Port Harrow with Tomas.",
+            "The boat carries Tomas home.",
+        ]]);
+        let tab = project.draft_tabs.get_mut("s1-t0").unwrap();
+        tab.timeline.push(said("Oren shrugs", "Where is she?"));
+        tab.timeline.push(said("Ferris says", "Go."));
+        let tabs: Vec<&DraftTab> = vec![&project.draft_tabs["s1-t0"]];
+        let characters: Vec<Character> = project.characters.values().cloned().collect();
+
+        let found: Vec<_> = unknown_names(&tabs, &characters, &HashSet::new()).into_iter().map(|(name, _, explicit)| (name, explicit)).collect();
+        let expected = [("Tomas", false), ("Tomas", false), ("Ferris", true)];
+        assert_eq!(found, expected.map(|(name, explicit)| (name.to_string(), explicit)));
+
+        let report = build_report(&project, &tabs, &[]).unwrap();
+        let names: Vec<_> = report.new_characters.iter().map(|c| (c.name.as_str(), c.occurrences)).collect();
+        assert_eq!(names, vec![("Tomas", 2), ("Ferris", 1)]);
+    }
+}
@@ -0,0 +1,688 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::outline;
+use crate::summaries;
+use crate::templates::{self, PromptTemplate, TemplateValues};
+use crate::{Character, DraftTab, ProjectData, Scene, Star};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PromptType {
+    SceneTimeline,
+    EventDescription,
+}
+
+// Per-section limits, in estimated tokens unless noted otherwise
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SectionBudgets {
+    pub story_so_far: usize,
+    pub scene: usize,
+    pub characters: usize,
+    pub plan: usize,
+    pub recent_events: usize,
+    pub constraints: usize,
+    pub key_facts: usize,
+    pub max_key_facts: usize,    // item count
+    pub full_recent_tabs: usize, // tabs shown event by event; older tabs use their summary
+}
+
+impl Default for SectionBudgets {
+    fn default() -> Self {
+        Self {
+            story_so_far: 800,
+            scene: 400,
+            characters: 1200,
+            plan: 600,
+            recent_events: 1500,
+            constraints: 600,
+            key_facts: 800,
+            max_key_facts: 10,
+            full_recent_tabs: 3,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TraceEntry {
+    pub section: String,
+    pub kind: String, // "story_summary" | "scene_summary" | "setting" | "backstory" | "character" | "plan" | "plan_step" | "summary" | "event" | "constraint" | "star"
+    pub id: Option<String>,
+    pub included: bool,
+    pub reason: String,
+    pub tokens: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AssembledPrompt {
+    pub template_name: String,
+    pub template_version: u32,
+    pub system_prompt: String,
+    pub user_prompt: String,
+    pub context: String,
+    pub trace: Vec<TraceEntry>,
+    pub included_star_ids: Vec<String>, // recorded on the stars when the generation is written
+    pub included_character_ids: Vec<String>,
+}
+
+pub struct ContextRequest<'a> {
+    pub scene_id: &'a str,
+    pub template: &'a PromptTemplate,
+    pub user_input: &'a str,
+    pub target_event: Option<&'a str>,
+    pub budgets: SectionBudgets,
+    pub star_scores: Option<HashMap<String, f64>>, // retrieval scores; key facts fall back to priority without them
+}
+
+// Rough estimate (~4 characters per token); good enough for budgeting
+pub fn estimate_tokens(text: &str) -> usize {
+    (text.chars().count() + 3) / 4
+}
+
+// A context section that accepts lines until its budget runs out, recording every decision
+struct Section<'t> {
+    name: &'static str,
+    budget: usize,
+    used: usize,
+    body: String,
+    trace: &'t mut Vec<TraceEntry>,
+}
+
+impl<'t> Section<'t> {
+    fn new(name: &'static str, budget: usize, trace: &'t mut Vec<TraceEntry>) -> Self {
+        Self { name, budget, used: 0, body: String::new(), trace }
+    }
+
+    fn fits(&self, text: &str) -> bool {
+        self.used + estimate_tokens(text) <= self.budget
+    }
+
+    fn record(&mut self, kind: &str, id: Option<&str>, included: bool, reason: &str, tokens: usize) {
+        self.trace.push(TraceEntry {
+            section: self.name.to_string(),
+            kind: kind.to_string(),
+            id: id.map(|s| s.to_string()),
+            included,
+            reason: reason.to_string(),
+            tokens,
+        });
+    }
+
+    // Append `text` if it fits the remaining budget
+    fn push(&mut self, kind: &str, id: Option<&str>, text: &str, reason: &str) -> bool {
+        let tokens = estimate_tokens(text);
+        if !self.fits(text) {
+            self.record(kind, id, false, "over section budget", tokens);
+            return false;
+        }
+        self.used += tokens;
+        self.body.push_str(text);
+        self.record(kind, id, true, reason, tokens);
+        true
+    }
+
+    fn skip(&mut self, kind: &str, id: Option<&str>, reason: &str) {
+        self.record(kind, id, false, reason, 0);
+    }
+}
+
+// Summaries of the scenes before this one, labelled with their chapter; a rollup of those same
+// scenes stands in for the ones that do not fit
+fn story_so_far_section(project: &ProjectData, scene: &Scene, budget: usize, trace: &mut Vec<TraceEntry>) -> String {
+    let earlier: Vec<&Scene> = outline::ordered_scenes(project)
+        .into_iter()
+        .take_while(|s| s.id != scene.id)
+        .collect();
+    if earlier.is_empty() {
+        return String::new();
+    }
+
+    let current_chapter = outline::position(project, &scene.id).map(|p| p.chapter.id.clone());
+    let previous_chapter = earlier
+        .iter()
+        .rev()
+        .filter_map(|s| outline::position(project, &s.id))
+        .map(|p| p.chapter.id.clone())
+        .find(|id| Some(id) != current_chapter.as_ref());
+
+    let mut section = Section::new("STORY SO FAR", budget, trace);
+    let mut lines: Vec<(&Scene, String, String)> = Vec::new();
+    for earlier_scene in &earlier {
+        let cached = match project.summaries.scenes.get(&earlier_scene.id) {
+            Some(cached) => cached,
+            None => {
+                section.skip("scene_summary", Some(&earlier_scene.id), "earlier scene has no summary");
+                continue;
+            }
+        };
+        let position = outline::position(project, &earlier_scene.id);
+        let chapter_id = position.as_ref().map(|p| p.chapter.id.clone());
+        let mut reason = if chapter_id.is_some() && chapter_id == current_chapter {
+            "earlier scene in this chapter".to_string()
+        } else if chapter_id.is_some() && chapter_id == previous_chapter {
+            "scene in the previous chapter".to_string()
+        } else {
+            "earlier scene summary".to_string()
+        };
+        if cached.hash != summaries::scene_hash(project, earlier_scene) {
+            reason.push_str(" (stale, drafts changed since)");
+        }
+        let label = match &position {
+            Some(position) => format!("{} / {}", position.chapter.title, earlier_scene.name),
+            None => earlier_scene.name.clone(),
+        };
+        lines.push((earlier_scene, format!("- {}: {}\n", label, cached.text), reason));
+    }
+
+    // Newest scenes claim the budget first; the rollup covers the rest if it fits
+    let mut selected = vec![false; lines.len()];
+    let mut reserved = 0;
+    let all_fit = lines.iter().map(|(_, line, _)| estimate_tokens(line)).sum::<usize>() <= budget;
+    let story = if all_fit { None } else { summaries::rollup(project, &earlier) };
+    let story_line = story.map(|text| format!("{}\n", text));
+    if let Some(line) = &story_line {
+        if section.fits(line) {
+            reserved = estimate_tokens(line);
+            section.used += reserved;
+        }
+    }
+    for (i, (earlier_scene, line, reason)) in lines.iter().enumerate().rev() {
+        if section.fits(line) {
+            section.used += estimate_tokens(line);
+            section.record("scene_summary", Some(&earlier_scene.id), true, reason, estimate_tokens(line));
+            selected[i] = true;
+        } else {
+            section.record("scene_summary", Some(&earlier_scene.id), false, "over section budget", estimate_tokens(line));
+        }
+    }
+
+    let mut body = String::new();
+    if let Some(line) = &story_line {
+        if reserved > 0 {
+            section.record("story_summary", None, true, "earlier scenes exceed budget", reserved);
+            body.push_str(line);
+        } else {
+            section.record("story_summary", None, false, "over section budget", estimate_tokens(line));
+        }
+    }
+    for ((_, line, _), keep) in lines.iter().zip(&selected) {
+        if *keep {
+            body.push_str(line);
+        }
+    }
+
+    if body.is_empty() {
+        return String::new();
+    }
+    format!("### STORY SO FAR\n{}\n", body)
+}
+
+fn scene_section(scene: &Scene, budget: usize, trace: &mut Vec<TraceEntry>) -> String {
+    let mut section = Section::new("SCENE", budget, trace);
+    let mut out = format!("### SCENE: {}\n", scene.name);
+
+    if let Some(setting) = scene.setting.as_deref().filter(|s| !s.trim().is_empty()) {
+        section.push("setting", Some(&scene.id), &format!("Setting: {}\n", setting), "scene setting");
+    }
+    if let Some(backstory) = scene.backstory.as_deref().filter(|s| !s.trim().is_empty()) {
+        section.push("backstory", Some(&scene.id), &format!("Backstory: {}\n", backstory), "scene backstory");
+    }
+
+    out.push_str(&section.body);
+    out.push('\n');
+    out
+}
+
+fn characters_section(characters: &[&Character], budget: usize, trace: &mut Vec<TraceEntry>) -> String {
+    if characters.is_empty() {
+        return String::new();
+    }
+
+    let mut section = Section::new("CHARACTERS", budget, trace);
+    for character in characters {
+        let mut block = format!("**{}**\n", character.name);
+        let fields: BTreeMap<_, _> = character.fields.iter().collect();
+        for (key, value) in fields {
+            block.push_str(&format!("- {}: {}\n", key, value));
+        }
+        block.push('\n');
+        section.push("character", Some(&character.id), &block, "checked character");
+    }
+
+    if section.body.is_empty() {
+        return String::new();
+    }
+    format!("### CHARACTERS\n{}", section.body)
+}
+
+fn plan_section(scene: &Scene, budget: usize, trace: &mut Vec<TraceEntry>) -> String {
+    if scene.plan.raw_text.trim().is_empty() {
+        return String::new();
+    }
+
+    let mut section = Section::new("SCENE PLAN", budget, trace);
+    let raw = format!("{}\n", scene.plan.raw_text);
+    if section.fits(&raw) {
+        section.push("plan", Some(&scene.id), &raw, "full scene plan");
+    } else {
+        // Too long to include verbatim: fall back to parsed steps in order
+        section.skip("plan", Some(&scene.id), "raw plan over section budget, using parsed steps");
+        for step in &scene.plan.parsed_steps {
+            section.push("plan_step", Some(&step.id), &format!("- {}\n", step.text), "parsed plan step");
+        }
+    }
+
+    if section.body.is_empty() {
+        return String::new();
+    }
+    format!("### SCENE PLAN\n{}\n", section.body)
+}
+
+fn recent_events_section(tabs: &[&DraftTab], budgets: &SectionBudgets, trace: &mut Vec<TraceEntry>) -> String {
+    if tabs.is_empty() {
+        return String::new();
+    }
+
+    // Candidate lines in chronological order: summaries for older tabs, checked events for the latest ones
+    let split = tabs.len().saturating_sub(budgets.full_recent_tabs);
+    let mut candidates: Vec<(&str, String, String, &str)> = Vec::new();
+    let mut section = Section::new("RECENT EVENTS", budgets.recent_events, trace);
+
+    for tab in &tabs[..split] {
+        match tab.summary.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+            Some(summary) => candidates.push(("summary", tab.id.clone(), format!("- {}\n", summary), "summary of older tab")),
+            None => section.skip("summary", Some(&tab.id), "older tab has no summary"),
+        }
+    }
+    for tab in &tabs[split..] {
+        for (index, event) in tab.timeline.iter().enumerate() {
+            let id = format!("{}:{}", tab.id, index);
+            if !event.checked {
+                section.skip("event", Some(&id), "event unchecked");
+                continue;
+            }
+            let dialogue = event.dialogue.as_ref().map(|d| format!(" -> \"{}\"", d)).unwrap_or_default();
+            candidates.push(("event", id, format!("- {}{}\n", event.text, dialogue), "checked event in recent tab"));
+        }
+    }
+
+    // Spend the budget on the newest material first, then restore chronological order
+    let mut selected = vec![false; candidates.len()];
+    for (i, (kind, id, line, reason)) in candidates.iter().enumerate().rev() {
+        if section.fits(line) {
+            section.used += estimate_tokens(line);
+            section.record(kind, Some(id), true, reason, estimate_tokens(line));
+            selected[i] = true;
+        } else {
+            section.record(kind, Some(id), false, "over section budget", estimate_tokens(line));
+        }
+    }
+
+    let body: String = candidates
+        .iter()
+        .zip(&selected)
+        .filter(|(_, keep)| **keep)
+        .map(|((_, _, line, _), _)| line.as_str())
+        .collect();
+
+    if body.is_empty() {
+        return String::new();
+    }
+    format!("### RECENT EVENTS\n{}\n", body)
+}
+
+fn constraints_section(
+    constraints: &[&Star],
+    characters: &[Character],
+    budget: usize,
+    trace: &mut Vec<TraceEntry>,
+) -> String {
+    if constraints.is_empty() {
+        return String::new();
+    }
+
+    let mut section = Section::new("CHARACTER BEHAVIORAL CONSTRAINTS", budget, trace);
+    let mut by_character: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    // Highest priority constraints claim the budget first
+    for star in constraints {
+        let character_id = star.applies_to_character.as_deref().unwrap_or_default();
+        let character_name = characters
+            .iter()
+            .find(|c| c.id == character_id)
+            .map(|c| c.name.clone())
+            .unwrap_or_else(|| "Unknown Character".to_string());
+        let type_label = star
+            .constraint_type
+            .as_deref()
+            .unwrap_or_default()
+            .replacen("character_", "", 1)
+            .replacen('_', " ", 1);
+        let situation = star.situation_context.as_ref().map(|s| format!(" ({})", s)).unwrap_or_default();
+        let line = format!("- {}{}: {}\n", type_label, situation, star.body);
+
+        if section.push("constraint", Some(&star.id), &line, "checked character constraint") {
+            by_character.entry(character_name).or_default().push(line);
+        }
+    }
+
+    if by_character.is_empty() {
+        return String::new();
+    }
+
+    let mut out = String::from("### CHARACTER BEHAVIORAL CONSTRAINTS\n");
+    for (character_name, lines) in by_character {
+        out.push_str(&format!("**{}**\n", character_name));
+        for line in lines {
+            out.push_str(&line);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn key_facts_section(
+    stars: &[&Star],
+    star_scores: Option<&HashMap<String, f64>>,
+    budgets: &SectionBudgets,
+    trace: &mut Vec<TraceEntry>,
+) -> String {
+    if stars.is_empty() {
+        return String::new();
+    }
+
+    let mut stars = stars.to_vec();
+    if let Some(scores) = star_scores {
+        let score = |star: &Star| scores.get(&star.id).copied().unwrap_or(0.0);
+        stars.sort_by(|a, b| {
+            score(b)
+                .partial_cmp(&score(a))
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| by_priority(a, b))
+        });
+    }
+
+    let mut section = Section::new("KEY FACTS", budgets.key_facts, trace);
+    let mut included = 0;
+    for star in stars {
+        if included >= budgets.max_key_facts {
+            section.skip("star", Some(&star.id), "beyond top stars by rank");
+            continue;
+        }
+        let line = format!("- {}: {}\n", star.title, star.body);
+        let reason = match star_scores.and_then(|scores| scores.get(&star.id)) {
+            Some(score) => format!("checked star, ranked by relevance (score {:.3})", score),
+            None => "checked star, ranked by priority".to_string(),
+        };
+        if section.push("star", Some(&star.id), &line, &reason) {
+            included += 1;
+        }
+    }
+
+    if section.body.is_empty() {
+        return String::new();
+    }
+    format!("### KEY FACTS\n{}\n", section.body)
+}
+
+fn by_priority(a: &&Star, b: &&Star) -> std::cmp::Ordering {
+    b.priority
+        .partial_cmp(&a.priority)
+        .unwrap_or(std::cmp::Ordering::Equal)
+        .then_with(|| a.title.cmp(&b.title))
+}
+
+// Build the context block for a scene, mirroring the section layout the frontend used to produce
+pub fn build_context(
+    project: &ProjectData,
+    scene_id: &str,
+    budgets: &SectionBudgets,
+    star_scores: Option<&HashMap<String, f64>>,
+    trace: &mut Vec<TraceEntry>,
+) -> Result<String, String> {
+    let scene = project
+        .scenes
+        .get(scene_id)
+        .ok_or_else(|| format!("Scene not found: {}", scene_id))?;
+
+    let mut characters: Vec<&Character> = project.characters.values().filter(|c| c.is_checked).collect();
+    characters.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut tabs: Vec<&DraftTab> = scene
+        .draft_tab_ids
+        .iter()
+        .filter_map(|id| project.draft_tabs.get(id))
+        .collect();
+    tabs.sort_by_key(|tab| tab.index);
+
+    let mut checked_stars: Vec<&Star> = project.stars.values().filter(|s| s.is_checked).collect();
+    checked_stars.sort_by(by_priority);
+    let (constraints, facts): (Vec<&Star>, Vec<&Star>) = checked_stars
+        .into_iter()
+        .partition(|s| s.constraint_type.is_some() && s.applies_to_character.is_some());
+
+    let all_characters: Vec<Character> = project.characters.values().cloned().collect();
+
+    let mut context = String::new();
+    context.push_str(&story_so_far_section(project, scene, budgets.story_so_far, trace));
+    context.push_str(&scene_section(scene, budgets.scene, trace));
+    context.push_str(&characters_section(&characters, budgets.characters, trace));
+    context.push_str(&plan_section(scene, budgets.plan, trace));
+    context.push_str(&recent_events_section(&tabs, budgets, trace));
+    context.push_str(&constraints_section(&constraints, &all_characters, budgets.constraints, trace));
+    context.push_str(&key_facts_section(&facts, star_scores, budgets, trace));
+
+    Ok(context)
+}
+
+// Assemble the full system and user prompts for a scene, with a trace of what went into the context
+pub fn assemble_prompt(project: &ProjectData, request: &ContextRequest) -> Result<AssembledPrompt, String> {
+    let mut trace = Vec::new();
+    let context = build_context(
+        project,
+        request.scene_id,
+        &request.budgets,
+        request.star_scores.as_ref(),
+        &mut trace,
+    )?;
+
+    let values = TemplateValues {
+        context: context.clone(),
+        user_input: request.user_input.to_string(),
+        target_event: request.target_event.map(str::to_string),
+    };
+    let rendered = templates::render(request.template, &values).map_err(|issues| {
+        let messages: Vec<String> = issues.into_iter().map(|issue| issue.message).collect();
+        format!("Template {} v{} is invalid: {}", request.template.name, request.template.version, messages.join("; "))
+    })?;
+
+    let included_ids = |kinds: &[&str]| -> Vec<String> {
+        trace
+            .iter()
+            .filter(|entry| entry.included && kinds.contains(&entry.kind.as_str()))
+            .filter_map(|entry| entry.id.clone())
+            .collect()
+    };
+    let included_star_ids = included_ids(&["constraint", "star"]);
+    let included_character_ids = included_ids(&["character"]);
+
+    Ok(AssembledPrompt {
+        template_name: rendered.name,
+        template_version: rendered.version,
+        system_prompt: rendered.system_prompt,
+        user_prompt: rendered.user_prompt,
+        context,
+        trace,
+        included_star_ids,
+        included_character_ids,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add_scene, character};
+
+    fn star(title: &str, priority: f64) -> Star {
+        let mut star = Star::new(title.to_string(), format!("{} body", title), 0);
+        star.priority = priority;
+        star
+    }
+
+    fn build(project: &ProjectData, budgets: &SectionBudgets) -> (String, Vec<TraceEntry>) {
+        let mut trace = Vec::new();
+        let context = build_context(project, "s1", budgets, None, &mut trace).unwrap();
+        (context, trace)
+    }
+
+    #[test]
+    fn sections_follow_the_fixed_order() {
+        let mut project = ProjectData::new("Test", 0);
+        add_scene(&mut project, "s1", "Harbour", &[&["Mira reaches the gate."]]);
+        project.scenes.get_mut("s1").unwrap().plan.raw_text = "Mira boards".to_string();
+        project.characters.insert("c1".to_string(), character("c1", "Mira"));
+        let mut constraint = star("Calm", 0.9);
+        constraint.constraint_type = Some("character_emotion".to_string());
+        constraint.applies_to_character = Some("c1".to_string());
+        project.stars.insert(constraint.id.clone(), constraint);
+        let fact = star("Ferry", 0.5);
+        project.stars.insert(fact.id.clone(), fact);
+
+        let (context, _) = build(&project, &SectionBudgets::default());
+        let headings: Vec<&str> = context.lines().filter(|line| line.starts_with("### ")).collect();
+        assert_eq!(
+            headings,
+            vec!["### SCENE: Harbour", "### CHARACTERS", "### SCENE PLAN", "### RECENT EVENTS", "### CHARACTER BEHAVIORAL CONSTRAINTS", "### KEY FACTS"]
+        );
+    }
+
+    #[test]
+    fn recent_events_keep_the_newest_within_budget_in_story_order() {
+        let mut project = ProjectData::new("Test", 0);
+        add_scene(&mut project, "s1", "Harbour", &[&["First event here.", "Second event here.", "Third event here."]]);
+        project.draft_tabs.get_mut("s1-t0").unwrap().timeline[1].checked = false;
+        // Each line is "- <text>\n", about 5 tokens
+        let budgets = SectionBudgets { recent_events: 5, ..SectionBudgets::default() };
+
+        let (context, trace) = build(&project, &budgets);
+        assert!(context.contains("Third event here."));
+        assert!(!context.contains("First event here."));
+        let skipped: Vec<&str> = trace.iter().filter(|e| !e.included).map(|e| e.reason.as_str()).collect();
+        assert_eq!(skipped, vec!["event unchecked", "over section budget"]);
+
+        let (context, _) = build(&project, &SectionBudgets::default());
+        assert!(context.find("First event").unwrap() < context.find("Third event").unwrap());
+    }
+
+    #[test]
+    fn older_tabs_contribute_their_summary() {
+        let mut project = ProjectData::new("Test", 0);
+        add_scene(&mut project, "s1", "Harbour", &[&["Old event."], &["New event."]]);
+        project.draft_tabs.get_mut("s1-t0").unwrap().summary = Some("The old tab in brief".to_string());
+        let budgets = SectionBudgets { full_recent_tabs: 1, ..SectionBudgets::default() };
+
+        let (context, _) = build(&project, &budgets);
+        assert!(context.contains("- The old tab in brief\n- New event.\n"));
+        assert!(!context.contains("Old event."));
+    }
+
+    #[test]
+    fn key_facts_are_ranked_by_priority_and_capped() {
+        let mut project = ProjectData::new("Test", 0);
+        add_scene(&mut project, "s1", "Harbour", &[]);
+        for (title, priority) in [("Low", 0.1), ("High", 0.9), ("Mid", 0.5)] {
+            let star = star(title, priority);
+            project.stars.insert(star.id.clone(), star);
+        }
+        let budgets = SectionBudgets { max_key_facts: 2, ..SectionBudgets::default() };
+
+        let (context, trace) = build(&project, &budgets);
+        assert!(context.find("- High:").unwrap() < context.find("- Mid:").unwrap());
+        assert!(!context.contains("- Low:"));
+        assert!(trace.iter().any(|e| e.kind == "star" && e.reason == "beyond top stars by rank"));
+    }
+
+    #[test]
+    fn retrieval_scores_outrank_priority() {
+        let mut project = ProjectData::new("Test", 0);
+        add_scene(&mut project, "s1", "Harbour", &[]);
+        let (high, low) = (star("High", 0.9), star("Low", 0.1));
+        let scores: HashMap<String, f64> = [(low.id.clone(), 0.8), (high.id.clone(), 0.2)].into_iter().collect();
+        project.stars.insert(high.id.clone(), high);
+        project.stars.insert(low.id.clone(), low);
+
+        let mut trace = Vec::new();
+        let context = build_context(&project, "s1", &SectionBudgets::default(), Some(&scores), &mut trace).unwrap();
+        assert!(context.find("- Low:").unwrap() < context.find("- High:").unwrap());
+    }
+
+    #[test]
+    fn long_plans_fall_back_to_parsed_steps() {
+        let mut project = ProjectData::new("Test", 0);
+        add_scene(&mut project, "s1", "Harbour", &[]);
+        let scene = project.scenes.get_mut("s1").unwrap();
+        scene.plan.raw_text = "Mira boards the ferry. ".repeat(20);
+        scene.plan.parsed_steps = vec![crate::PlanStep {
+            id: "p1".to_string(),
+            text: "Mira boards".to_string(),
+            fulfilled_by: Vec::new(),
+            linked_stars: Vec::new(),
+            parent_id: None,
+        }];
+        let budgets = SectionBudgets { plan: 20, ..SectionBudgets::default() };
+
+        let (context, _) = build(&project, &budgets);
+        assert!(context.contains("### SCENE PLAN\n- Mira boards\n"));
+    }
+
+    #[test]
+    fn only_included_stars_and_characters_are_reported() {
+        let mut project = ProjectData::new("Test", 0);
+        add_scene(&mut project, "s1", "Harbour", &[]);
+        project.characters.insert("c1".to_string(), character("c1", "Mira"));
+        let mut unchecked = character("c2", "Tobias");
+        unchecked.is_checked = false;
+        project.characters.insert("c2".to_string(), unchecked);
+        let (kept, dropped) = (star("Kept", 0.9), star("Dropped", 0.1));
+        let kept_id = kept.id.clone();
+        project.stars.insert(kept.id.clone(), kept);
+        project.stars.insert(dropped.id.clone(), dropped);
+        let template = templates::builtin().remove(0);
+        let request = ContextRequest {
+            scene_id: "s1",
+            template: &template,
+            user_input: "Go",
+            target_event: None,
+            budgets: SectionBudgets { max_key_facts: 1, ..SectionBudgets::default() },
+            star_scores: None,
+        };
+
+        let assembled = assemble_prompt(&project, &request).unwrap();
+        assert_eq!(assembled.included_star_ids, vec![kept_id]);
+        assert_eq!(assembled.included_character_ids, vec!["c1".to_string()]);
+        assert!(assembled.user_prompt.contains(&assembled.context));
+    }
+
+    #[test]
+    fn story_so_far_never_mentions_later_scenes() {
+        let mut project = ProjectData::new("Test", 0);
+        for (i, id) in ["a", "b", "s1", "later"].iter().enumerate() {
+            add_scene(&mut project, id, &format!("Scene {}", id), &[]);
+            project.scenes.get_mut(*id).unwrap().created_at = i as u64;
+            let text = format!("Summary of scene {} with enough words to fill a line", id);
+            project.summaries.scenes.insert(id.to_string(), summaries::CachedSummary { hash: String::new(), text, updated_at: 0 });
+        }
+        // Cached from every scene, including ones after s1
+        project.summaries.story = Some(summaries::CachedSummary { hash: String::new(), text: "The ending is revealed".to_string(), updated_at: 0 });
+        let budgets = SectionBudgets { story_so_far: 40, ..SectionBudgets::default() };
+
+        let (context, trace) = build(&project, &budgets);
+        assert!(context.contains("### STORY SO FAR"));
+        assert!(context.contains("Summary of scene b"));
+        assert!(!context.contains("The ending is revealed"));
+        assert!(!context.contains("scene later"));
+        assert!(!trace.iter().any(|e| e.id.as_deref() == Some("later")));
+    }
+}
@@ -0,0 +1,580 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::openai_client::extract_json_object;
+use crate::outline;
+use crate::retrieval::tokenize;
+use crate::speaker::character_mentions;
+use crate::{Character, DraftTab, ProjectData, Scene, Star};
+
+const COLOURS: &[&str] = &[
+    "amber", "auburn", "black", "blonde", "blue", "brown", "copper", "ginger", "golden", "green", "grey",
+    "hazel", "red", "silver", "violet", "white",
+];
+
+const RELATIONS: &[&str] = &[
+    "mother", "father", "sister", "brother", "wife", "husband", "daughter", "son", "aunt", "uncle",
+    "cousin", "mentor", "partner",
+];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Attribute {
+    Eyes,
+    Hair,
+    Age,
+    Relation,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FactSource {
+    CharacterField { character_id: String, field: String },
+    Star { star_id: String },
+    EarlierScene { scene_id: String, tab_id: String, event_index: Option<usize> },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ContinuityIssue {
+    pub tab_id: String,
+    pub event_index: Option<usize>,
+    pub description_id: Option<String>,
+    pub excerpt: String,
+    pub conflicting_fact: String,
+    pub source: Option<FactSource>, // None when the LLM could not name a source
+    pub method: String,             // "rule" | "llm"
+    pub explanation: String,
+}
+
+// A piece of draft text the checker looks at
+struct TextUnit<'a> {
+    tab: &'a DraftTab,
+    event_index: Option<usize>,
+    description_id: Option<&'a str>,
+    text: String,
+}
+
+// An attribute value stated in prose, e.g. Mira's eyes are "blue"
+#[derive(Clone)]
+pub struct Observation {
+    pub character_id: String,
+    pub attribute: Attribute,
+    pub relation: Option<String>,
+    pub value: String,
+    pub excerpt: String,
+}
+
+type ObservationKey = (String, Attribute, Option<String>); // character ID, attribute, relation
+
+// First statement of an attribute value in an earlier scene
+struct Established {
+    observation: Observation,
+    scene_id: String,
+    tab_id: String,
+    event_index: Option<usize>,
+}
+
+fn normalise_colour(word: &str) -> &str {
+    match word {
+        "gray" => "grey",
+        "blond" => "blonde",
+        "gold" => "golden",
+        other => other,
+    }
+}
+
+pub fn attribute_for_field(key: &str) -> Option<(Attribute, Option<String>)> {
+    let key = key.trim().to_lowercase();
+    if key.contains("eye") {
+        Some((Attribute::Eyes, None))
+    } else if key.contains("hair") {
+        Some((Attribute::Hair, None))
+    } else if key == "age" {
+        Some((Attribute::Age, None))
+    } else if RELATIONS.contains(&key.as_str()) {
+        Some((Attribute::Relation, Some(key)))
+    } else {
+        None
+    }
+}
+
+fn units_for_tab(tab: &DraftTab) -> Vec<TextUnit<'_>> {
+    let mut units: Vec<TextUnit> = tab
+        .timeline
+        .iter()
+        .enumerate()
+        .map(|(index, event)| TextUnit {
+            tab,
+            event_index: Some(index),
+            description_id: None,
+            text: match &event.dialogue {
+                Some(dialogue) => format!("{} \"{}\"", event.text, dialogue),
+                None => event.text.clone(),
+            },
+        })
+        .collect();
+    units.extend(tab.descriptions.iter().map(|description| TextUnit {
+        tab,
+        event_index: None,
+        description_id: Some(&description.id),
+        text: description.text.clone(),
+    }));
+    units
+}
+
+fn sentences(text: &str) -> Vec<&str> {
+    text.split_inclusive(['.', '!', '?', '\n'])
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+fn parse_age(words: &[String], i: usize) -> Option<String> {
+    // "32-year-old" tokenizes to ["32", "year", "old"]; also "32 years old" and "aged 32"
+    let word = &words[i];
+    if word.chars().all(|c| c.is_ascii_digit()) {
+        let next = words.get(i + 1).map(String::as_str);
+        let after = words.get(i + 2).map(String::as_str);
+        if matches!(next, Some("year") | Some("years")) && after == Some("old") {
+            return Some(word.clone());
+        }
+        if i > 0 && words[i - 1] == "aged" {
+            return Some(word.clone());
+        }
+    }
+    None
+}
+
+// Attribute statements in one sentence, tied to the nearest preceding character mention
+fn observe_sentence(sentence: &str, characters: &[Character]) -> Vec<Observation> {
+    let mentions = character_mentions(sentence, characters);
+    if mentions.is_empty() {
+        return Vec::new();
+    }
+
+    let mut observations = Vec::new();
+    let lower = sentence.to_lowercase();
+
+    // Character nearest before `position`, or the first one if none precedes it
+    let owner_at = |position: usize| -> &Character {
+        mentions
+            .iter()
+            .rev()
+            .find(|(start, _, _)| *start <= position)
+            .or_else(|| mentions.first())
+            .map(|(_, _, c)| *c)
+            .unwrap()
+    };
+
+    let words = tokenize(sentence);
+    let mut search_from = 0;
+    for (i, word) in words.iter().enumerate() {
+        let position = lower[search_from..].find(word.as_str()).map(|p| p + search_from).unwrap_or(search_from);
+        search_from = position;
+
+        let attribute = match word.as_str() {
+            "eye" | "eyes" => Some(Attribute::Eyes),
+            "hair" => Some(Attribute::Hair),
+            _ => None,
+        };
+        if let Some(attribute) = attribute {
+            // Look back a few words for a colour: "her green eyes", "blue, tired eyes"
+            let colour = words[i.saturating_sub(3)..i]
+                .iter()
+                .rev()
+                .map(|w| normalise_colour(w))
+                .find(|w| COLOURS.contains(w));
+            if let Some(colour) = colour {
+                observations.push(Observation {
+                    character_id: owner_at(position).id.clone(),
+                    attribute,
+                    relation: None,
+                    value: colour.to_string(),
+                    excerpt: sentence.to_string(),
+                });
+            }
+        }
+
+        if let Some(age) = parse_age(&words, i) {
+            observations.push(Observation {
+                character_id: owner_at(position).id.clone(),
+                attribute: Attribute::Age,
+                relation: None,
+                value: age,
+                excerpt: sentence.to_string(),
+            });
+        }
+    }
+
+    // "Mira's sister, Lena" / "Mira's sister Lena"
+    for (_, end, character) in &mentions {
+        let rest = &sentence[*end..];
+        let rest = match rest.strip_prefix("'s ").or_else(|| rest.strip_prefix("’s ")) {
+            Some(rest) => rest,
+            None => continue,
+        };
+        let mut parts = rest.split_whitespace();
+        let relation = parts.next().map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase());
+        let name = parts.next().map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()));
+        if let (Some(relation), Some(name)) = (relation, name) {
+            if RELATIONS.contains(&relation.as_str()) && name.chars().next().map_or(false, char::is_uppercase) {
+                observations.push(Observation {
+                    character_id: character.id.clone(),
+                    attribute: Attribute::Relation,
+                    relation: Some(relation),
+                    value: name.to_string(),
+                    excerpt: sentence.to_string(),
+                });
+            }
+        }
+    }
+
+    observations
+}
+
+fn observe(unit: &TextUnit, characters: &[Character]) -> Vec<Observation> {
+    sentences(&unit.text)
+        .into_iter()
+        .flat_map(|sentence| observe_sentence(sentence, characters))
+        .collect()
+}
+
+// Does the observed value agree with a field value such as "green, flecked with gold"?
+pub fn agrees(attribute: Attribute, expected: &str, observed: &str) -> bool {
+    match attribute {
+        Attribute::Eyes | Attribute::Hair => {
+            let expected_colours: Vec<&str> = expected
+                .split(|c: char| !c.is_alphanumeric())
+                .map(|w| w.to_lowercase())
+                .filter_map(|w| COLOURS.iter().find(|c| **c == normalise_colour(&w)).copied())
+                .collect();
+            // Free-text values with no recognisable colour can't be checked
+            expected_colours.is_empty() || expected_colours.contains(&observed)
+        }
+        Attribute::Age => {
+            let expected_age: String = expected.chars().filter(|c| c.is_ascii_digit()).collect();
+            expected_age.is_empty() || expected_age == observed
+        }
+        Attribute::Relation => expected
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|w| w.eq_ignore_ascii_case(observed)),
+    }
+}
+
+// Observations in a draft together with the event index or description id they came from
+pub fn observe_tab(tab: &DraftTab, characters: &[Character]) -> Vec<(Option<usize>, Option<String>, Observation)> {
+    units_for_tab(tab)
+        .iter()
+        .flat_map(|unit| {
+            observe(unit, characters)
+                .into_iter()
+                .map(move |observation| (unit.event_index, unit.description_id.map(str::to_string), observation))
+        })
+        .collect()
+}
+
+fn describe(character: &Character, observation: &Observation) -> String {
+    match observation.attribute {
+        Attribute::Eyes => format!("{} has {} eyes", character.name, observation.value),
+        Attribute::Hair => format!("{} has {} hair", character.name, observation.value),
+        Attribute::Age => format!("{} is {} years old", character.name, observation.value),
+        Attribute::Relation => format!(
+            "{}'s {} is {}",
+            character.name,
+            observation.relation.as_deref().unwrap_or("relation"),
+            observation.value
+        ),
+    }
+}
+
+fn issue(unit: &TextUnit, excerpt: &str, fact: String, source: FactSource, explanation: String) -> ContinuityIssue {
+    ContinuityIssue {
+        tab_id: unit.tab.id.clone(),
+        event_index: unit.event_index,
+        description_id: unit.description_id.map(str::to_string),
+        excerpt: excerpt.to_string(),
+        conflicting_fact: fact,
+        source: Some(source),
+        method: "rule".to_string(),
+        explanation,
+    }
+}
+
+// Scenes before `scene` in outline order
+fn earlier_scenes<'a>(project: &'a ProjectData, scene: Option<&Scene>) -> Vec<&'a Scene> {
+    let scene = match scene {
+        Some(scene) => scene,
+        None => return Vec::new(),
+    };
+    outline::ordered_scenes(project).into_iter().take_while(|s| s.id != scene.id).collect()
+}
+
+// Rule-based pass over the given tabs: character fields, future-plot stars and earlier scenes
+pub fn check_rules(project: &ProjectData, tabs: &[&DraftTab], scene: Option<&Scene>) -> Vec<ContinuityIssue> {
+    let characters: Vec<Character> = project.characters.values().cloned().collect();
+    let mut issues = Vec::new();
+
+    // Attribute values established in earlier scenes, first statement wins
+    let mut established: HashMap<ObservationKey, Established> = HashMap::new();
+    for earlier in earlier_scenes(project, scene) {
+        for tab in earlier.draft_tab_ids.iter().filter_map(|id| project.draft_tabs.get(id)) {
+            for unit in units_for_tab(tab) {
+                for observation in observe(&unit, &characters) {
+                    let key = (observation.character_id.clone(), observation.attribute, observation.relation.clone());
+                    established.entry(key).or_insert(Established {
+                        observation,
+                        scene_id: earlier.id.clone(),
+                        tab_id: tab.id.clone(),
+                        event_index: unit.event_index,
+                    });
+                }
+            }
+        }
+    }
+
+    let future_stars: Vec<&Star> = project
+        .stars
+        .values()
+        .filter(|star| star.tags.scope == "FuturePlot" && star.tags.status != "Resolved")
+        .collect();
+
+    for tab in tabs {
+        for unit in units_for_tab(tab) {
+            for observation in observe(&unit, &characters) {
+                let character = match project.characters.get(&observation.character_id) {
+                    Some(character) => character,
+                    None => continue,
+                };
+
+                // 1. Explicit character fields
+                let mut checked_against_field = false;
+                for (key, value) in &character.fields {
+                    let (attribute, relation) = match attribute_for_field(key) {
+                        Some(found) => found,
+                        None => continue,
+                    };
+                    if attribute != observation.attribute || relation != observation.relation {
+                        continue;
+                    }
+                    checked_against_field = true;
+                    if !agrees(attribute, value, &observation.value) {
+                        issues.push(issue(
+                            &unit,
+                            &observation.excerpt,
+                            format!("{}: {} = {}", character.name, key, value),
+                            FactSource::CharacterField { character_id: character.id.clone(), field: key.clone() },
+                            format!("Text suggests {}", describe(character, &observation)),
+                        ));
+                    }
+                }
+
+                // 2. Values stated in earlier scenes, when no field covers them
+                if checked_against_field {
+                    continue;
+                }
+                let key = (observation.character_id.clone(), observation.attribute, observation.relation.clone());
+                if let Some(prior) = established.get(&key) {
+                    let earlier = &prior.observation;
+                    if !agrees(observation.attribute, &earlier.value, &observation.value) {
+                        issues.push(issue(
+                            &unit,
+                            &observation.excerpt,
+                            describe(character, earlier),
+                            FactSource::EarlierScene {
+                                scene_id: prior.scene_id.clone(),
+                                tab_id: prior.tab_id.clone(),
+                                event_index: prior.event_index,
+                            },
+                            format!("Earlier scene said \"{}\"; this text suggests {}", earlier.excerpt, describe(character, &observation)),
+                        ));
+                    }
+                }
+            }
+
+            // 3. Future plot points that surface before their time
+            for star in &future_stars {
+                let title = star.title.trim();
+                if !title.is_empty() && unit.text.to_lowercase().contains(&title.to_lowercase()) {
+                    issues.push(issue(
+                        &unit,
+                        &unit.text,
+                        format!("{}: {}", star.title, star.body),
+                        FactSource::Star { star_id: star.id.clone() },
+                        "Mentions a star scoped to future plot that is not resolved yet".to_string(),
+                    ));
+                }
+            }
+        }
+    }
+
+    issues
+}
+
+#[derive(Deserialize)]
+struct LlmIssue {
+    event_index: Option<usize>,
+    description_id: Option<String>,
+    fact: String,
+    star_id: Option<String>,
+    explanation: String,
+}
+
+#[derive(Deserialize)]
+struct LlmIssues {
+    issues: Vec<LlmIssue>,
+}
+
+pub fn build_llm_prompt(project: &ProjectData, tab: &DraftTab) -> (String, String) {
+    let mut user_prompt = String::from("### FACTS\n");
+
+    let mut stars: Vec<&Star> = project.stars.values().filter(|s| s.is_checked).collect();
+    stars.sort_by(|a, b| a.title.cmp(&b.title));
+    for star in stars {
+        user_prompt.push_str(&format!("- [star:{}] {}: {}\n", star.id, star.title, star.body));
+    }
+    let mut characters: Vec<&Character> = project.characters.values().collect();
+    characters.sort_by(|a, b| a.name.cmp(&b.name));
+    for character in characters {
+        for (key, value) in &character.fields {
+            user_prompt.push_str(&format!("- {} {}: {}\n", character.name, key, value));
+        }
+    }
+
+    user_prompt.push_str("\n### TIMELINE\n");
+    for (index, event) in tab.timeline.iter().enumerate() {
+        let dialogue = event.dialogue.as_ref().map(|d| format!(" -> \"{}\"", d)).unwrap_or_default();
+        user_prompt.push_str(&format!("{}. {}{}\n", index, event.text, dialogue));
+    }
+    if !tab.descriptions.is_empty() {
+        user_prompt.push_str("\n### DESCRIPTIONS\n");
+        for description in &tab.descriptions {
+            user_prompt.push_str(&format!("[{}] {}\n", description.id, description.text));
+        }
+    }
+    user_prompt.push_str(concat!(
+        "\nList every statement in TIMELINE or DESCRIPTIONS that contradicts a FACT. ",
+        "Respond with JSON only: {\"issues\":[{\"event_index\":<number or null>,\"description_id\":<string or null>,",
+        "\"fact\":\"<the contradicted fact>\",\"star_id\":<string or null>,\"explanation\":\"<one sentence>\"}]}",
+    ));
+
+    (
+        "You are a meticulous continuity editor. Report contradictions only, never style issues.".to_string(),
+        user_prompt,
+    )
+}
+
+pub fn parse_llm_issues(response: &str, tab: &DraftTab) -> Result<Vec<ContinuityIssue>, String> {
+    let json = extract_json_object(response).ok_or("Continuity reply contained no JSON object")?;
+    let parsed: LlmIssues = serde_json::from_str(json).map_err(|e| format!("Invalid continuity JSON: {}", e))?;
+
+    Ok(parsed
+        .issues
+        .into_iter()
+        // Drop references to events or descriptions that do not exist
+        .filter(|i| i.event_index.map_or(true, |index| index < tab.timeline.len()))
+        .filter(|i| i.description_id.as_ref().map_or(true, |id| tab.descriptions.iter().any(|d| &d.id == id)))
+        .map(|i| {
+            let excerpt = match (i.event_index, &i.description_id) {
+                (Some(index), _) => tab.timeline[index].text.clone(),
+                (None, Some(id)) => tab.descriptions.iter().find(|d| &d.id == id).map(|d| d.text.clone()).unwrap_or_default(),
+                (None, None) => String::new(),
+            };
+            ContinuityIssue {
+                tab_id: tab.id.clone(),
+                event_index: i.event_index,
+                description_id: i.description_id,
+                excerpt,
+                conflicting_fact: i.fact,
+                source: i.star_id.map(|star_id| FactSource::Star { star_id }),
+                method: "llm".to_string(),
+                explanation: i.explanation,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add_scene, character};
+
+    fn project() -> ProjectData {
+        let mut project = ProjectData::new("Test", 0);
+        let mut mira = character("c1", "Mira");
+        for (key, value) in [("Eye colour", "green, flecked with gold"), ("Age", "32"), ("Sister", "Lena")] {
+            mira.fields.insert(key.to_string(), value.to_string());
+        }
+        project.characters.insert("c1".to_string(), mira);
+        project.characters.insert("c2".to_string(), character("c2", "Oren"));
+        project
+    }
+
+    fn found(issues: &[ContinuityIssue]) -> Vec<(&str, Option<usize>, &str)> {
+        issues.iter().map(|i| (i.tab_id.as_str(), i.event_index, i.conflicting_fact.as_str())).collect()
+    }
+
+    #[test]
+    fn prose_is_checked_against_character_fields() {
+        let mut project = project();
+        add_scene(&mut project, "s1", "Harbour", &[&[
+            "Mira's blue eyes narrowed.",
+            "Mira has green eyes.",
+            "Mira, a 40-year-old smuggler, waits. Oren is aged 32.",
+            "Mira's sister Ana waves.",
+            "Mira's sister Lena waves.",
+        ]]);
+        let tab = &project.draft_tabs["s1-t0"];
+        let issues = check_rules(&project, &[tab], project.scenes.get("s1"));
+        assert_eq!(
+            found(&issues),
+            vec![
+                ("s1-t0", Some(0), "Mira: Eye colour = green, flecked with gold"),
+                ("s1-t0", Some(2), "Mira: Age = 32"),
+                ("s1-t0", Some(3), "Mira: Sister = Lena"),
+            ]
+        );
+        assert!(matches!(&issues[0].source, Some(FactSource::CharacterField { field, .. }) if field == "Eye colour"));
+        assert_eq!(issues[0].explanation, "Text suggests Mira has blue eyes");
+    }
+
+    #[test]
+    fn earlier_scenes_and_future_stars_are_facts_too() {
+        let mut project = project();
+        add_scene(&mut project, "s1", "Harbour", &[&["Oren's grey hair is wet."]]);
+        add_scene(&mut project, "s2", "Ferry", &[&["Oren pushes back his gray hair.", "Oren's brown hair drips. The lighthouse falls."]]);
+        let mut star = Star::new("The lighthouse falls".to_string(), "In the last act".to_string(), 0);
+        star.id = "star1".to_string();
+        star.tags.scope = "FuturePlot".to_string();
+        project.stars.insert(star.id.clone(), star);
+
+        let tab = &project.draft_tabs["s2-t0"];
+        let issues = check_rules(&project, &[tab], project.scenes.get("s2"));
+        assert_eq!(found(&issues), vec![("s2-t0", Some(1), "Oren has grey hair"), ("s2-t0", Some(1), "The lighthouse falls: In the last act")]);
+        assert!(matches!(&issues[0].source, Some(FactSource::EarlierScene { scene_id, event_index: Some(0), .. }) if scene_id == "s1"));
+
+        // Nothing earlier than the first scene, and resolved stars no longer count
+        project.stars.get_mut("star1").unwrap().tags.status = "Resolved".to_string();
+        let tab = &project.draft_tabs["s2-t0"];
+        assert!(check_rules(&project, &[tab], project.scenes.get("s1")).is_empty());
+    }
+
+    #[test]
+    fn llm_issues_must_point_at_real_events() {
+        let mut project = project();
+        add_scene(&mut project, "s1", "Harbour", &[&["Mira swims ashore."]]);
+        let tab = &project.draft_tabs["s1-t0"];
+        let (_, prompt) = build_llm_prompt(&project, tab);
+        assert!(prompt.contains("- Mira Sister: Lena") && prompt.contains("0. Mira swims ashore."));
+
+        let reply = r#"{"issues": [
+            {"event_index": 0, "fact": "Mira cannot swim", "star_id": "star1", "explanation": "She swims"},
+            {"event_index": 5, "fact": "Out of range", "explanation": "-"},
+            {"description_id": "gone", "fact": "No such description", "explanation": "-"}
+        ]}"#;
+        let issues = parse_llm_issues(reply, tab).unwrap();
+        assert_eq!(found(&issues), vec![("s1-t0", Some(0), "Mira cannot swim")]);
+        assert_eq!((issues[0].excerpt.as_str(), issues[0].method.as_str()), ("Mira swims ashore.", "llm"));
+        assert!(parse_llm_issues("{", tab).is_err());
+    }
+}
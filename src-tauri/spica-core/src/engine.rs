@@ -0,0 +1,1037 @@
+// Application state and the operations shared by the desktop app and the CLI
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::api_server::ProjectEvent;
+use crate::audit::{AuditLog, LogLevel};
+use crate::context_engine::{self, AssembledPrompt, ContextRequest, PromptType};
+use crate::fulfilment::{self, StepCandidate, StepVerdict};
+use crate::openai_client::{self, LLMTab, OpenAIClient, TimelineEvent};
+use crate::outline;
+use crate::provenance::{GenerationRequest, Provenance};
+use crate::retrieval::{self, RetrievalHit, RetrievalIndex, RetrievalOptions};
+use crate::scripting::{self, Hook, PromptPayload, ScriptHost};
+use crate::search::SearchIndex;
+use crate::speaker;
+use crate::spellcheck::{self, Dictionary};
+use crate::templates;
+use crate::usage::{self, PromptUsage};
+use crate::summaries;
+use crate::vault::{self, Keep, SyncReport, VaultWatcher};
+use crate::{ApiError, Character, Description, DraftTab, PlanStep, ProjectData, Star};
+
+// The project the app saves to, inside the project directory
+pub const PROJECT_FILE: &str = "last_project.json";
+
+pub fn default_project_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join("Documents").join("SpicaWriter"))
+}
+
+// Application state
+pub struct AppConfig {
+    pub project_dir: PathBuf,
+    pub openai_client: OpenAIClient,
+    pub audit: Arc<AuditLog>,
+    pub retrieval_index: Mutex<RetrievalIndex>,
+    pub search_index: Mutex<SearchIndex>,
+    pub dictionaries: Mutex<std::collections::HashMap<String, Arc<Dictionary>>>, // by language, loaded on first use
+    pub project_lock: Mutex<()>, // held while the saved project file is read and rewritten
+    pub saved_project_open: AtomicBool, // the window shows the saved project file, so the API and vault may write it
+    pub saved_project_hash: Mutex<String>, // content hash of the saved project as this process last read or wrote it
+    pub events: broadcast::Sender<ProjectEvent>,
+}
+
+impl AppConfig {
+    pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
+        // Create project storage directory
+        let project_dir = default_project_dir().ok_or("Could not find home directory")?;
+        
+        if !project_dir.exists() {
+            fs::create_dir_all(&project_dir)?;
+        }
+
+        // Initialize OpenAI client
+        let openai_client = OpenAIClient::new()?;
+        Ok(Self::with_client(project_dir, openai_client))
+    }
+
+    pub fn with_client(project_dir: PathBuf, openai_client: OpenAIClient) -> Self {
+        let audit = Arc::new(AuditLog::new(project_dir.join("logs")));
+        let (events, _) = broadcast::channel(64);
+
+        Self {
+            project_dir,
+            openai_client: openai_client.with_audit(audit.clone()),
+            audit,
+            retrieval_index: Mutex::new(RetrievalIndex::new()),
+            search_index: Mutex::new(SearchIndex::new()),
+            dictionaries: Mutex::new(std::collections::HashMap::new()),
+            project_lock: Mutex::new(()),
+            saved_project_open: AtomicBool::new(true),
+            saved_project_hash: Mutex::new(String::new()),
+            events,
+        }
+    }
+
+    pub fn write_saved_project(&self, json: &str) -> Result<(), ApiError> {
+        write_project_json(&self.get_project_path(), json)?;
+        self.note_saved_project(json);
+        Ok(())
+    }
+
+    // Remember what the saved project holds, so the watcher only reports other processes' writes
+    pub fn note_saved_project(&self, json: &str) {
+        *self.saved_project_hash.lock().unwrap_or_else(|e| e.into_inner()) = summaries::content_hash(json);
+    }
+
+    // Writes behind the window's back are only safe while it shows the saved file; otherwise its
+    // next save would overwrite them, or reloading would swap the project it has open
+    pub fn require_saved_project_open(&self) -> Result<(), ApiError> {
+        if self.saved_project_open.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        Err(ApiError {
+            error: true,
+            message: "The app has a different project open; save it or reopen the saved project first".to_string(),
+            code: Some("PROJECT_NOT_OPEN".to_string()),
+        })
+    }
+
+    pub fn get_project_path(&self) -> PathBuf {
+        self.project_dir.join(PROJECT_FILE)
+    }
+
+    pub fn get_snapshot_dir(&self) -> PathBuf {
+        self.project_dir.join("snapshots")
+    }
+
+    pub fn get_template_dir(&self) -> PathBuf {
+        self.project_dir.join("templates")
+    }
+
+    pub fn get_script_dir(&self) -> PathBuf {
+        scripting::script_dir(&self.get_project_path())
+    }
+
+    pub fn get_api_settings_path(&self) -> PathBuf {
+        self.project_dir.join("api_settings.json")
+    }
+
+    pub fn get_vault_settings_path(&self) -> PathBuf {
+        self.project_dir.join("vault_settings.json")
+    }
+
+    // User-installed dictionaries take precedence over the bundled ones
+    pub fn get_dictionary_dirs(&self, bundled: Option<PathBuf>) -> Vec<PathBuf> {
+        let mut dirs = vec![self.project_dir.join("dictionaries")];
+        dirs.extend(bundled);
+        dirs
+    }
+}
+
+// Parse LLM response into timeline events and extract summary and atmosphere
+pub fn parse_response(response: &str) -> (Vec<TimelineEvent>, Option<String>, Option<String>) {
+    let mut timeline = Vec::new();
+    let mut summary: Option<String> = None;
+    let mut atmosphere: Option<String> = None;
+    
+    // Extract summary and atmosphere from between pipes (|Summary|Atmosphere|)
+    if let Some(first_pipe) = response.rfind('|') {
+        if let Some(third_pipe) = response[..first_pipe].rfind('|') {
+            if let Some(second_pipe) = response[..third_pipe].rfind('|') {
+                let summary_text = response[second_pipe + 1..third_pipe].trim();
+                let atmosphere_text = response[third_pipe + 1..first_pipe].trim();
+                
+                if !summary_text.is_empty() {
+                    summary = Some(summary_text.to_string());
+                }
+                if !atmosphere_text.is_empty() {
+                    atmosphere = Some(atmosphere_text.to_string());
+                }
+                
+                // Process only the content before the summary
+                let content_without_summary = &response[..second_pipe];
+            
+                for line in content_without_summary.lines() {
+                    let line = line.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    
+                    // Check if line contains dialogue (indicated by quotes)
+                    if let Some(dialogue_start) = line.find('"') {
+                        if let Some(dialogue_end) = line.rfind('"') {
+                            if dialogue_start < dialogue_end {
+                                let text_part = line[..dialogue_start].trim();
+                                let dialogue_part = line[dialogue_start + 1..dialogue_end].trim();
+                                
+                                timeline.push(TimelineEvent {
+                                    text: text_part.to_string(),
+                                    dialogue: if dialogue_part.is_empty() { None } else { Some(dialogue_part.to_string()) },
+                                    checked: true,
+                                    ..Default::default()
+                                });
+                                continue;
+                            }
+                        }
+                    }
+                    
+                    // Regular text event
+                    timeline.push(TimelineEvent {
+                        text: line.to_string(),
+                        dialogue: None,
+                        checked: true,
+                        ..Default::default()
+                    });
+                }
+            } else {
+                // Fallback: only one pipe found, try to extract as summary only
+                let summary_text = response[..first_pipe].trim();
+                if !summary_text.is_empty() {
+                    summary = Some(summary_text.to_string());
+                }
+                
+                // Process only the content before the summary
+                let content_without_summary = &response[..first_pipe];
+                
+                for line in content_without_summary.lines() {
+                    let line = line.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    
+                    // Check if line contains dialogue (indicated by quotes)
+                    if let Some(dialogue_start) = line.find('"') {
+                        if let Some(dialogue_end) = line.rfind('"') {
+                            if dialogue_start < dialogue_end {
+                                let text_part = line[..dialogue_start].trim();
+                                let dialogue_part = line[dialogue_start + 1..dialogue_end].trim();
+                                
+                                timeline.push(TimelineEvent {
+                                    text: text_part.to_string(),
+                                    dialogue: if dialogue_part.is_empty() { None } else { Some(dialogue_part.to_string()) },
+                                    checked: true,
+                                    ..Default::default()
+                                });
+                                continue;
+                            }
+                        }
+                    }
+                    
+                    // Regular text event
+                    timeline.push(TimelineEvent {
+                        text: line.to_string(),
+                        dialogue: None,
+                        checked: true,
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+    } else {
+        // Fallback: no pipes found, process entire response as timeline
+        for line in response.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            
+            // Check if line contains dialogue (indicated by quotes)
+            if let Some(dialogue_start) = line.find('"') {
+                if let Some(dialogue_end) = line.rfind('"') {
+                    if dialogue_start < dialogue_end {
+                        let text_part = line[..dialogue_start].trim();
+                        let dialogue_part = line[dialogue_start + 1..dialogue_end].trim();
+                        
+                        timeline.push(TimelineEvent {
+                            text: text_part.to_string(),
+                            dialogue: if dialogue_part.is_empty() { None } else { Some(dialogue_part.to_string()) },
+                            checked: true,
+                            ..Default::default()
+                        });
+                        continue;
+                    }
+                }
+            }
+            
+            // Regular text event
+            timeline.push(TimelineEvent {
+                text: line.to_string(),
+                dialogue: None,
+                checked: true,
+                ..Default::default()
+            });
+        }
+    }
+    
+    if timeline.is_empty() {
+        // Fallback: create a single event with the entire response
+        timeline.push(TimelineEvent {
+            text: response.trim().to_string(),
+            dialogue: None,
+            checked: true,
+            ..Default::default()
+        });
+    }
+    
+    (timeline, summary, atmosphere)
+}
+
+// Parsed projects always come with a consistent outline, so flat projects migrate on first use
+pub fn parse_project(state_json: &str) -> Result<ProjectData, ApiError> {
+    let mut project: ProjectData = serde_json::from_str(state_json).map_err(|e| ApiError {
+        error: true,
+        message: format!("Failed to parse project: {}", e),
+        code: Some("PARSE_ERROR".to_string()),
+    })?;
+    outline::normalize(&mut project);
+    Ok(project)
+}
+
+// Loaded on every use, so edits to the scripts apply without a restart
+pub fn script_host(config: &AppConfig) -> ScriptHost {
+    ScriptHost::load_dir(&config.get_script_dir()).with_audit(config.audit.clone())
+}
+
+// Sync the retrieval index with the project (only edited items are re-indexed) and query it
+pub fn query_index(
+    config: &AppConfig,
+    project: &ProjectData,
+    scene_id: Option<&str>,
+    user_input: &str,
+    options: &RetrievalOptions,
+) -> Vec<RetrievalHit> {
+    let mut index = config.retrieval_index.lock().unwrap_or_else(|e| e.into_inner());
+    index.sync(project, options.use_embeddings);
+    let scene_text = retrieval::scene_query_text(project, scene_id);
+    index.query(project, user_input, &scene_text, options, chrono::Utc::now().timestamp_millis() as u64)
+}
+
+pub fn template_registry(config: &AppConfig, project: Option<&ProjectData>) -> (templates::Registry, Vec<String>) {
+    let (app_templates, load_errors) = templates::load_dir(&config.get_template_dir());
+    let project_templates = project.map(|p| p.prompt_templates.clone()).unwrap_or_default();
+    (templates::Registry::new(app_templates, &project_templates), load_errors)
+}
+
+pub fn template_not_found(name: &str, version: Option<u32>) -> ApiError {
+    let message = match version {
+        Some(version) => format!("Template not found: {} v{}", name, version),
+        None => format!("Template not found: {}", name),
+    };
+    ApiError { error: true, message, code: Some("NOT_FOUND".to_string()) }
+}
+
+// Assemble with the request's template, or else the one the project selected for the prompt type;
+// at the exact version the request names, or else the project's pinned or newest one
+pub fn assemble_for(config: &AppConfig, project: &ProjectData, request: &GenerationRequest) -> Result<AssembledPrompt, ApiError> {
+    let (registry, _) = template_registry(config, Some(project));
+    let name = request.template_name.as_deref().unwrap_or_else(|| project.prompt_templates.template_name(request.prompt_type));
+    let template = &match request.template_version {
+        Some(version) => registry.get(name, version),
+        None => registry.active(name, &project.prompt_templates.pinned),
+    }
+    .ok_or_else(|| template_not_found(name, request.template_version))?
+    .template;
+    if template.prompt_type != request.prompt_type {
+        return Err(ApiError {
+            error: true,
+            message: format!("Template {} is for {:?} prompts, not {:?}", name, template.prompt_type, request.prompt_type),
+            code: Some("TEMPLATE_MISMATCH".to_string()),
+        });
+    }
+
+    // Rank checked stars for the request so relevant low-priority facts can make the cut
+    let star_scores = if request.user_input.trim().is_empty() {
+        None
+    } else {
+        let options = RetrievalOptions {
+            limit: usize::MAX,
+            checked_only: true,
+            include_characters: false,
+            include_unmatched: true,
+            ..RetrievalOptions::default()
+        };
+        let hits = query_index(config, project, Some(&request.scene_id), &request.user_input, &options);
+        Some(hits.into_iter().map(|hit| (hit.key.id, hit.score)).collect())
+    };
+
+    let context_request = ContextRequest {
+        scene_id: &request.scene_id,
+        template,
+        user_input: &request.user_input,
+        target_event: request.target_event.as_deref(),
+        budgets: request.budgets.clone(),
+        star_scores,
+    };
+
+    context_engine::assemble_prompt(project, &context_request).map_err(|message| ApiError {
+        error: true,
+        message,
+        code: Some("CONTEXT_ERROR".to_string()),
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GeneratedDraft {
+    pub tabs: Vec<openai_client::LLMTab>, // scene timeline generations; each carries the provenance
+    pub description: Option<String>, // event description generations
+    pub provenance: Provenance, // store on the DraftTab or Description
+    pub prompt_changed: Option<bool>, // regenerations only: whether the assembled prompt differs from the original
+}
+
+pub async fn run_generation(config: &AppConfig, scripts: &ScriptHost, project: &ProjectData, request: GenerationRequest) -> Result<GeneratedDraft, ApiError> {
+    let assembled = assemble_for(config, project, &request)?;
+    let prompt = PromptPayload { system_prompt: assembled.system_prompt, user_prompt: assembled.user_prompt };
+    let (prompt, _) = scripts.run(Hook::BeforePromptSend, prompt);
+    let completion = config
+        .openai_client
+        .complete(&prompt.system_prompt, &prompt.user_prompt, &request.params)
+        .await
+        .map_err(|e| ApiError {
+            error: true,
+            message: format!("Failed to process prompt: {}", e),
+            code: Some("LLM_ERROR".to_string()),
+        })?;
+
+    let now = chrono::Utc::now().timestamp_millis() as u64;
+    let provider = config.openai_client.provider();
+    let mut provenance = Provenance::new(provider, &completion, &request.params, &prompt.system_prompt, &prompt.user_prompt, now);
+    provenance.template_name = Some(assembled.template_name);
+    provenance.template_version = Some(assembled.template_version);
+    provenance.included_star_ids = assembled.included_star_ids;
+    provenance.included_character_ids = assembled.included_character_ids;
+    provenance.request = Some(request.clone());
+
+    match request.prompt_type {
+        PromptType::SceneTimeline => {
+            let (mut timeline, summary, atmosphere) = parse_response(&completion.text);
+            let characters: Vec<Character> = project.characters.values().cloned().collect();
+            let unresolved_speakers = speaker::resolve_speakers(&mut timeline, &characters);
+            let tab = openai_client::LLMTab {
+                title: "Generated Scene Segment".to_string(),
+                timeline,
+                summary,
+                atmosphere,
+                unresolved_speakers,
+                provenance: Some(provenance.clone()),
+                plan_verdicts: Vec::new(),
+            };
+            let (mut tabs, _) = scripts.run(Hook::AfterResponseParse, vec![tab]);
+            // Judged now and linked when the tabs are written; a failed judge call falls back to the lexical verdicts
+            if let Some(scene) = project.scenes.get(&request.scene_id) {
+                for tab in &mut tabs {
+                    tab.plan_verdicts = match judge_plan_steps(&config.openai_client, &scene.plan.parsed_steps, &tab.timeline, true).await {
+                        Ok((_, verdicts)) => verdicts,
+                        Err(e) => {
+                            config.audit.log(LogLevel::Error, "plan_judge", "lexical_fallback", serde_json::json!({ "message": e.message }), serde_json::Value::Null);
+                            fulfilment::lexical_verdicts(&fulfilment::lexical_candidates(&scene.plan.parsed_steps, &tab.timeline))
+                        }
+                    };
+                }
+            }
+            Ok(GeneratedDraft { tabs, description: None, provenance, prompt_changed: None })
+        }
+        PromptType::EventDescription => {
+            Ok(GeneratedDraft { tabs: Vec::new(), description: Some(completion.text), provenance, prompt_changed: None })
+        }
+    }
+}
+
+// Which plan steps a timeline covers: steps sharing enough vocabulary with it go to the LLM judge,
+// or are scored by that overlap alone without one
+pub async fn judge_plan_steps(
+    client: &OpenAIClient,
+    steps: &[PlanStep],
+    timeline: &[TimelineEvent],
+    use_llm: bool,
+) -> Result<(Vec<StepCandidate>, Vec<StepVerdict>), ApiError> {
+    let candidates = fulfilment::lexical_candidates(steps, timeline);
+    if candidates.is_empty() {
+        return Ok((candidates, Vec::new()));
+    }
+    if !use_llm {
+        let verdicts = fulfilment::lexical_verdicts(&candidates);
+        return Ok((candidates, verdicts));
+    }
+    let (system_prompt, user_prompt) = fulfilment::build_judge_prompt(&candidates, timeline);
+    let response = client.send_prompt(&system_prompt, &user_prompt).await.map_err(|e| ApiError {
+        error: true,
+        message: format!("Failed to judge plan fulfilment: {}", e),
+        code: Some("LLM_ERROR".to_string()),
+    })?;
+    let verdicts = fulfilment::parse_verdicts(&response, &candidates).map_err(|message| ApiError {
+        error: true,
+        message,
+        code: Some("JUDGE_PARSE_ERROR".to_string()),
+    })?;
+    Ok((candidates, verdicts))
+}
+
+pub fn load_dictionary(language: &str, dirs: &[PathBuf], config: &AppConfig) -> Result<Arc<Dictionary>, ApiError> {
+    if !spellcheck::is_language_name(language) {
+        return Err(ApiError {
+            error: true,
+            message: format!("Invalid language name: {}", language),
+            code: Some("INVALID_LANGUAGE".to_string()),
+        });
+    }
+    if let Some(dictionary) = config.dictionaries.lock().unwrap_or_else(|e| e.into_inner()).get(language) {
+        return Ok(dictionary.clone());
+    }
+    let (aff, dic) = spellcheck::find_dictionary(dirs, language).ok_or(ApiError {
+        error: true,
+        message: format!("No {} dictionary installed", language),
+        code: Some("DICTIONARY_MISSING".to_string()),
+    })?;
+    let dictionary = Arc::new(Dictionary::load(&aff, &dic).map_err(|e| ApiError {
+        error: true,
+        message: format!("Failed to read dictionary {}: {}", language, e),
+        code: Some("DICTIONARY_ERROR".to_string()),
+    })?);
+    config.dictionaries.lock().unwrap_or_else(|e| e.into_inner()).insert(language.to_string(), dictionary.clone());
+    Ok(dictionary)
+}
+
+
+pub fn read_project(path: &Path) -> Result<ProjectData, ApiError> {
+    let content = fs::read_to_string(path).map_err(|e| ApiError {
+        error: true,
+        message: format!("Failed to load project: {}", e),
+        code: Some("LOAD_ERROR".to_string()),
+    })?;
+    parse_project(&content)
+}
+
+pub fn write_project(path: &Path, project: &ProjectData) -> Result<(), ApiError> {
+    write_project_json(path, &serde_json::to_string(project).unwrap())
+}
+
+// Written beside the project and renamed over it, so other processes never read half a file
+pub fn write_project_json(path: &Path, json: &str) -> Result<(), ApiError> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    fs::write(&temp, json).and_then(|_| fs::rename(&temp, path)).map_err(|e| ApiError {
+        error: true,
+        message: format!("Failed to save project: {}", e),
+        code: Some("SAVE_ERROR".to_string()),
+    })
+}
+
+// Changes when another process rewrites the file
+pub fn file_stamp(path: &Path) -> Option<(std::time::SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+// Announces writes to the saved project by other processes, such as the MCP server or the CLI,
+// while the window shows it; the app's own writes are recognised by their content
+pub fn watch_saved_project(config: Arc<AppConfig>) -> notify::Result<VaultWatcher> {
+    let path = config.get_project_path();
+    let watched = path.clone();
+    vault::watch_files(&config.project_dir.clone(), false, move |changed| changed == watched.as_path(), move || {
+        if !config.saved_project_open.load(Ordering::SeqCst) {
+            return;
+        }
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => return,
+        };
+        let hash = summaries::content_hash(&content);
+        let mut known = config.saved_project_hash.lock().unwrap_or_else(|e| e.into_inner());
+        if *known != hash {
+            *known = hash;
+            let _ = config.events.send(ProjectEvent::new("project_saved", "external", None));
+        }
+    })
+}
+
+// Syncs the saved project with a notes folder; the window reloads when notes changed the project
+pub fn sync_vault(config: &AppConfig, folder: &Path, resolutions: &std::collections::HashMap<String, Keep>) -> Result<SyncReport, ApiError> {
+    config.require_saved_project_open()?;
+    let _guard = config.project_lock.lock().unwrap_or_else(|e| e.into_inner());
+    let path = config.get_project_path();
+    let mut project = read_project(&path)?;
+    let report = vault::sync(&mut project, folder, resolutions).map_err(|e| ApiError {
+        error: true,
+        message: format!("Failed to sync with {}: {}", folder.display(), e),
+        code: Some("VAULT_ERROR".to_string()),
+    })?;
+    if report.project_changed() {
+        project.metadata.updated_at = chrono::Utc::now().timestamp_millis() as u64;
+        let (project, _) = script_host(config).before_save(project);
+        config.write_saved_project(&serde_json::to_string(&project).unwrap())?;
+        config.search_index.lock().unwrap_or_else(|e| e.into_inner()).sync(&project);
+        let _ = config.events.send(ProjectEvent::new("vault_synced", "vault", None));
+    }
+    Ok(report)
+}
+
+// Stamps the stars and characters a generation's prompt included, linking the drafts it produced
+fn record_generation(project: &mut ProjectData, provenance: &Provenance, draft_tab_ids: Vec<String>, now_ms: u64) {
+    let prompt_usage = PromptUsage {
+        star_ids: provenance.included_star_ids.clone(),
+        character_ids: provenance.included_character_ids.clone(),
+        draft_tab_ids,
+    };
+    usage::record_usage(project, &prompt_usage, now_ms);
+}
+
+// Generated tabs land in the workbench, as they do in the app; returns the new tab ids.
+// The tabs are expected to come from one generation, which is recorded once.
+// New tabs go to the end of `scene_id` when given and it exists, otherwise to the workbench
+pub fn add_generated_tabs(project: &mut ProjectData, tabs: Vec<LLMTab>, scene_id: Option<&str>, now_ms: u64) -> Vec<String> {
+    let target = scene_id.filter(|id| project.scenes.contains_key(*id)).map(str::to_string);
+    let provenance = tabs.iter().find_map(|tab| tab.provenance.clone());
+    let judged_scene = provenance.as_ref().and_then(|p| p.request.as_ref()).map(|request| request.scene_id.clone());
+    let mut ids = Vec::new();
+    for tab in tabs {
+        let id = uuid::Uuid::new_v4().to_string();
+        let verdicts = tab.plan_verdicts;
+        let index = target.as_ref().map_or(0, |scene_id| project.scenes[scene_id].draft_tab_ids.len() as u32);
+        let timeline = tab
+            .timeline
+            .into_iter()
+            .map(|event| TimelineEvent { id: Some(uuid::Uuid::new_v4().to_string()), checked: true, ..event })
+            .collect();
+        project.draft_tabs.insert(
+            id.clone(),
+            DraftTab {
+                id: id.clone(),
+                scene_id: target.clone(),
+                index,
+                timeline,
+                descriptions: Vec::new(),
+                summary: tab.summary,
+                atmosphere: tab.atmosphere,
+                fulfilled_plan_steps: Vec::new(),
+                suggested_plan_steps: Vec::new(),
+                created_at: now_ms,
+                updated_at: now_ms,
+                provenance: tab.provenance,
+            },
+        );
+        match target.as_ref().and_then(|scene_id| project.scenes.get_mut(scene_id)) {
+            Some(scene) => scene.draft_tab_ids.push(id.clone()),
+            None => project.workbench.unassigned_draft_tab_ids.push(id.clone()),
+        }
+        if let Some(scene_id) = &judged_scene {
+            fulfilment::apply_verdicts(project, scene_id, &id, &verdicts);
+        }
+        ids.push(id);
+    }
+    if let Some(provenance) = provenance {
+        record_generation(project, &provenance, ids.clone(), now_ms);
+    }
+    ids
+}
+
+pub fn add_description(
+    project: &mut ProjectData,
+    tab_id: &str,
+    event_id: Option<&str>,
+    text: String,
+    provenance: Option<Provenance>,
+    now_ms: u64,
+) -> Result<String, ApiError> {
+    let tab = project.draft_tabs.get_mut(tab_id).ok_or_else(|| ApiError {
+        error: true,
+        message: format!("Draft tab not found: {}", tab_id),
+        code: Some("NOT_FOUND".to_string()),
+    })?;
+    let id = uuid::Uuid::new_v4().to_string();
+    tab.descriptions.push(Description {
+        id: id.clone(),
+        text,
+        is_important: false,
+        origin_star_id: None,
+        target_event_id: event_id.map(str::to_string),
+        scope: if event_id.is_some() { "event" } else { "tab" }.to_string(),
+        provenance: provenance.clone(),
+    });
+    tab.updated_at = now_ms;
+    if let Some(provenance) = provenance {
+        record_generation(project, &provenance, vec![tab_id.to_string()], now_ms);
+    }
+    Ok(id)
+}
+
+// Add an event to the end of a tab, attributing its dialogue like a generated one
+pub fn append_timeline_event(
+    project: &mut ProjectData,
+    tab_id: &str,
+    text: String,
+    dialogue: Option<String>,
+    now_ms: u64,
+) -> Result<TimelineEvent, ApiError> {
+    let characters: Vec<Character> = project.characters.values().cloned().collect();
+    let tab = project.draft_tabs.get_mut(tab_id).ok_or_else(|| ApiError {
+        error: true,
+        message: format!("Draft tab not found: {}", tab_id),
+        code: Some("NOT_FOUND".to_string()),
+    })?;
+    let mut event = TimelineEvent {
+        id: Some(uuid::Uuid::new_v4().to_string()),
+        text,
+        dialogue,
+        checked: true,
+        associated_stars: Vec::new(),
+        speaker: None,
+    };
+    speaker::resolve_speakers(std::slice::from_mut(&mut event), &characters);
+    tab.timeline.push(event.clone());
+    tab.updated_at = now_ms;
+    Ok(event)
+}
+
+// Adds a generation to the project the same way for the app, the API and the CLI: timelines become
+// draft tabs, at the end of `scene_id` or else in no scene, and a description goes on `tab_id`.
+// Returns the new tab ids, or the new description's id.
+pub fn write_generation(
+    project: &mut ProjectData,
+    generated: GeneratedDraft,
+    scene_id: Option<&str>,
+    tab_id: Option<&str>,
+    event_id: Option<&str>,
+    now_ms: u64,
+) -> Result<Vec<String>, ApiError> {
+    match (generated.description, tab_id) {
+        (Some(text), Some(tab_id)) => Ok(vec![add_description(project, tab_id, event_id, text, Some(generated.provenance), now_ms)?]),
+        (Some(_), None) => Err(ApiError {
+            error: true,
+            message: "Writing a description needs a draft tab".to_string(),
+            code: Some("TAB_REQUIRED".to_string()),
+        }),
+        (None, _) => Ok(add_generated_tabs(project, generated.tabs, scene_id, now_ms)),
+    }
+}
+
+// Removes a star and every link to it, as deleting in the app does
+pub fn remove_star(project: &mut ProjectData, star_id: &str) -> Option<Star> {
+    let star = project.stars.remove(star_id)?;
+    for event in project.draft_tabs.values_mut().flat_map(|tab| tab.timeline.iter_mut()) {
+        event.associated_stars.retain(|id| id != star_id);
+    }
+    for step in project.plan_steps.values_mut() {
+        step.linked_stars.retain(|id| id != star_id);
+    }
+    Some(star)
+}
+
+// Removes a character and its tags and constraints on stars
+pub fn remove_character(project: &mut ProjectData, character_id: &str) -> Option<Character> {
+    let character = project.characters.remove(character_id)?;
+    for star in project.stars.values_mut() {
+        star.tags.characters.retain(|id| id != character_id);
+        if star.applies_to_character.as_deref() == Some(character_id) {
+            star.applies_to_character = None;
+        }
+    }
+    Some(character)
+}
+
+// References to scenes, tabs, stars and steps that no longer exist
+pub fn dangling_references(project: &ProjectData) -> Vec<String> {
+    let mut issues = Vec::new();
+    let mut check_tab = |owner: &str, tab_id: &str| {
+        if !project.draft_tabs.contains_key(tab_id) {
+            issues.push(format!("{} lists missing draft tab {}", owner, tab_id));
+        }
+    };
+    for scene in project.scenes.values() {
+        for tab_id in &scene.draft_tab_ids {
+            check_tab(&format!("Scene \"{}\"", scene.name), tab_id);
+        }
+    }
+    for tab_id in &project.workbench.unassigned_draft_tab_ids {
+        check_tab("Workbench", tab_id);
+    }
+    for tab_id in &project.idea_bank.stored_draft_tab_ids {
+        check_tab("Idea bank", tab_id);
+    }
+
+    for tab in project.draft_tabs.values() {
+        if let Some(scene_id) = &tab.scene_id {
+            if !project.scenes.contains_key(scene_id) {
+                issues.push(format!("Draft tab {} belongs to missing scene {}", tab.id, scene_id));
+            }
+        }
+        for star_id in tab.timeline.iter().flat_map(|event| &event.associated_stars) {
+            if !project.stars.contains_key(star_id) {
+                issues.push(format!("Draft tab {} links missing star {}", tab.id, star_id));
+            }
+        }
+        // Suggested steps are free text, not plan step ids
+        for step_id in &tab.fulfilled_plan_steps {
+            if !project.plan_steps.contains_key(step_id) {
+                issues.push(format!("Draft tab {} links missing plan step {}", tab.id, step_id));
+            }
+        }
+        for description in &tab.descriptions {
+            if let Some(event_id) = &description.target_event_id {
+                if !tab.timeline.iter().any(|event| event.id.as_deref() == Some(event_id.as_str())) {
+                    issues.push(format!("Description {} in draft tab {} targets missing event {}", description.id, tab.id, event_id));
+                }
+            }
+        }
+    }
+
+    for star in project.stars.values() {
+        if let Some(tab_id) = &star.origin_draft_tab_id {
+            if !project.draft_tabs.contains_key(tab_id) {
+                issues.push(format!("Star \"{}\" comes from missing draft tab {}", star.title, tab_id));
+            }
+        }
+    }
+    for step in project.plan_steps.values() {
+        for star_id in &step.linked_stars {
+            if !project.stars.contains_key(star_id) {
+                issues.push(format!("Plan step {} links missing star {}", step.id, star_id));
+            }
+        }
+    }
+    if let Some(scene_id) = &project.active_scene_id {
+        if !project.scenes.contains_key(scene_id) {
+            issues.push(format!("Active scene {} does not exist", scene_id));
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openai_client::{GenerationParams, Transport};
+    use crate::testing::{client, completion_body, temp_dir, MockServer};
+    use crate::{Scene, ScenePlan};
+
+    fn character(id: &str, name: &str) -> Character {
+        Character {
+            id: id.to_string(),
+            name: name.to_string(),
+            fields: std::collections::HashMap::new(),
+            is_checked: true,
+            last_used_in_prompt: None,
+            usage_count: 0,
+        }
+    }
+
+    fn scene(id: &str) -> Scene {
+        Scene {
+            id: id.to_string(),
+            name: "Harbour".to_string(),
+            setting: Some("A harbour at night".to_string()),
+            backstory: None,
+            plan: ScenePlan { raw_text: String::new(), parsed_steps: Vec::new() },
+            draft_tab_ids: Vec::new(),
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    fn timeline_request(scene_id: &str) -> GenerationRequest {
+        GenerationRequest {
+            scene_id: scene_id.to_string(),
+            prompt_type: PromptType::SceneTimeline,
+            user_input: "Mira boards the ferry".to_string(),
+            target_event: None,
+            budgets: Default::default(),
+            params: GenerationParams::default(),
+            template_name: None,
+            template_version: None,
+        }
+    }
+
+    const TIMELINE_REPLY: &str = "Mira reaches the harbour gate.\nMira says \"No ticket, no passage.\"\nThe ferryman steps aside.\n|Mira talks her way aboard.|A cold harbour at night.|";
+
+    #[test]
+    fn parse_response_splits_events_dialogue_summary_and_atmosphere() {
+        let (timeline, summary, atmosphere) = parse_response(TIMELINE_REPLY);
+        assert_eq!(timeline.len(), 3);
+        assert_eq!(timeline[1].text, "Mira says");
+        assert_eq!(timeline[1].dialogue.as_deref(), Some("No ticket, no passage."));
+        assert!(timeline[0].dialogue.is_none());
+        assert!(timeline.iter().all(|e| e.checked));
+        assert_eq!(summary.as_deref(), Some("Mira talks her way aboard."));
+        assert_eq!(atmosphere.as_deref(), Some("A cold harbour at night."));
+    }
+
+    #[test]
+    fn parse_response_without_pipes_keeps_every_line() {
+        let (timeline, summary, atmosphere) = parse_response("One.\nTwo.");
+        assert_eq!(timeline.len(), 2);
+        assert!(summary.is_none() && atmosphere.is_none());
+    }
+
+    #[test]
+    fn generation_uses_the_selected_or_requested_template() {
+        let dir = temp_dir("engine-templates");
+        let config = AppConfig::with_client(dir.clone(), client("http://127.0.0.1:9/v1", Transport::Mock));
+        let mut project = ProjectData::new("Test", 0);
+        project.scenes.insert("s1".to_string(), scene("s1"));
+        let mut noir = templates::builtin().remove(0);
+        noir.name = "noir".to_string();
+        noir.system = "Write it dark.".to_string();
+        project.prompt_templates.templates.push(noir);
+
+        assert_eq!(assemble_for(&config, &project, &timeline_request("s1")).unwrap().template_name, "scene_timeline");
+        project.prompt_templates.selected.insert(PromptType::SceneTimeline, "noir".to_string());
+        let assembled = assemble_for(&config, &project, &timeline_request("s1")).unwrap();
+        assert_eq!((assembled.template_name.as_str(), assembled.system_prompt.as_str()), ("noir", "Write it dark."));
+
+        let request = GenerationRequest { template_name: Some("scene_timeline".to_string()), ..timeline_request("s1") };
+        assert_eq!(assemble_for(&config, &project, &request).unwrap().template_name, "scene_timeline");
+        let request = GenerationRequest { template_name: Some("event_description".to_string()), ..timeline_request("s1") };
+        assert_eq!(assemble_for(&config, &project, &request).unwrap_err().code.as_deref(), Some("TEMPLATE_MISMATCH"));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn generation_end_to_end_against_mock_server() {
+        let server = MockServer::start(vec![(200, completion_body(TIMELINE_REPLY))]).await;
+        let dir = temp_dir("engine-e2e");
+        let config = AppConfig::with_client(dir.clone(), client(&server.base_url, Transport::Live));
+        let mut project = ProjectData::new("Test", 0);
+        project.scenes.insert("s1".to_string(), scene("s1"));
+        project.characters.insert("c1".to_string(), character("c1", "Mira"));
+
+        let generated = run_generation(&config, &ScriptHost::default(), &project, timeline_request("s1")).await.unwrap_or_else(|e| panic!("{}", e.message));
+        assert_eq!(generated.tabs.len(), 1);
+        let tab = &generated.tabs[0];
+        assert_eq!(tab.timeline.len(), 3);
+        assert_eq!(tab.summary.as_deref(), Some("Mira talks her way aboard."));
+        let speaker = tab.timeline[1].speaker.as_ref().expect("dialogue is attributed");
+        assert_eq!(speaker.character_id.as_deref(), Some("c1"));
+
+        let provenance = tab.provenance.as_ref().unwrap();
+        assert_eq!(provenance.provider, "openai");
+        assert_eq!(provenance.model, "gpt-4.1-2025-04-14");
+        assert!(provenance.template_name.is_some() && provenance.request.is_some());
+        assert_eq!(provenance.usage.as_ref().unwrap().total_tokens, 46);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn generation_errors_become_api_errors() {
+        let server = MockServer::start(vec![(503, "overloaded".to_string())]).await;
+        let dir = temp_dir("engine-error");
+        let config = AppConfig::with_client(dir.clone(), client(&server.base_url, Transport::Live));
+        let mut project = ProjectData::new("Test", 0);
+        project.scenes.insert("s1".to_string(), scene("s1"));
+        match run_generation(&config, &ScriptHost::default(), &project, timeline_request("s1")).await {
+            Err(e) => assert_eq!(e.code.as_deref(), Some("LLM_ERROR")),
+            Ok(_) => panic!("expected an error"),
+        }
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn generation_with_mock_provider_needs_no_network() {
+        let dir = temp_dir("engine-mock");
+        let config = AppConfig::with_client(dir.clone(), client("http://127.0.0.1:9/v1", Transport::Mock));
+        let mut project = ProjectData::new("Test", 0);
+        project.scenes.insert("s1".to_string(), scene("s1"));
+        let generated = run_generation(&config, &ScriptHost::default(), &project, timeline_request("s1")).await.unwrap_or_else(|e| panic!("{}", e.message));
+        let tab = &generated.tabs[0];
+        assert!(!tab.timeline.is_empty());
+        assert!(tab.summary.is_some() && tab.atmosphere.is_some());
+        assert_eq!(tab.provenance.as_ref().unwrap().provider, "mock");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn generated_tabs_land_in_the_workbench_without_dangling_references() {
+        let mut project = ProjectData::new("Test", 0);
+        let (timeline, summary, atmosphere) = parse_response(TIMELINE_REPLY);
+        let tab = LLMTab { title: "Draft".to_string(), timeline, summary, atmosphere, unresolved_speakers: Vec::new(), provenance: None, plan_verdicts: Vec::new() };
+
+        let ids = add_generated_tabs(&mut project, vec![tab], None, 1);
+        assert_eq!(project.workbench.unassigned_draft_tab_ids, ids);
+        let event_id = project.draft_tabs[&ids[0]].timeline[0].id.clone().unwrap();
+        add_description(&mut project, &ids[0], Some(&event_id), "Cold.".to_string(), None, 2).unwrap_or_else(|e| panic!("{}", e.message));
+        assert!(dangling_references(&project).is_empty());
+
+        project.workbench.unassigned_draft_tab_ids.push("gone".to_string());
+        project.draft_tabs.get_mut(&ids[0]).unwrap().timeline.remove(0);
+        assert_eq!(dangling_references(&project).len(), 2);
+    }
+
+    #[test]
+    fn generated_tabs_can_go_straight_to_a_scene() {
+        let mut project = ProjectData::new("Test", 0);
+        project.scenes.insert("s1".to_string(), scene("s1"));
+        let tab = || LLMTab { title: "Draft".to_string(), timeline: parse_response(TIMELINE_REPLY).0, summary: None, atmosphere: None, unresolved_speakers: Vec::new(), provenance: None, plan_verdicts: Vec::new() };
+
+        let ids = add_generated_tabs(&mut project, vec![tab(), tab()], Some("s1"), 1);
+        assert_eq!(project.scenes["s1"].draft_tab_ids, ids);
+        assert!(project.workbench.unassigned_draft_tab_ids.is_empty());
+        assert_eq!((project.draft_tabs[&ids[0]].index, project.draft_tabs[&ids[1]].index), (0, 1));
+        assert_eq!(project.draft_tabs[&ids[1]].scene_id.as_deref(), Some("s1"));
+
+        // Suggested plan steps hold step text, so they are never dangling ids
+        project.draft_tabs.get_mut(&ids[0]).unwrap().suggested_plan_steps.push("Reveal the letter".to_string());
+        assert!(dangling_references(&project).is_empty());
+
+        let ids = add_generated_tabs(&mut project, vec![tab()], Some("missing"), 2);
+        assert_eq!(project.workbench.unassigned_draft_tab_ids, ids);
+    }
+
+    #[tokio::test]
+    async fn generated_timelines_are_judged_against_the_plan_and_linked_on_write() {
+        let judge_reply = r#"{"verdicts": [{"step_id": "p1", "status": "fulfilled", "reason": "Mira reaches the gate"}]}"#;
+        let server = MockServer::start(vec![(200, completion_body(TIMELINE_REPLY)), (200, completion_body(judge_reply))]).await;
+        let dir = temp_dir("engine-judge");
+        let config = AppConfig::with_client(dir.clone(), client(&server.base_url, Transport::Live));
+        let mut project = ProjectData::new("Test", 0);
+        let mut harbour = scene("s1");
+        let step = PlanStep {
+            id: "p1".to_string(),
+            text: "Mira reaches the harbour gate".to_string(),
+            fulfilled_by: Vec::new(),
+            linked_stars: Vec::new(),
+            parent_id: None,
+        };
+        harbour.plan = ScenePlan { raw_text: step.text.clone(), parsed_steps: vec![step.clone()] };
+        project.scenes.insert("s1".to_string(), harbour);
+        project.plan_steps.insert("p1".to_string(), step);
+
+        let generated = run_generation(&config, &ScriptHost::default(), &project, timeline_request("s1")).await.unwrap_or_else(|e| panic!("{}", e.message));
+        assert_eq!(generated.tabs[0].plan_verdicts.len(), 1);
+        let ids = write_generation(&mut project, generated, None, None, None, 5).unwrap_or_else(|e| panic!("{}", e.message));
+
+        assert_eq!(project.draft_tabs[&ids[0]].fulfilled_plan_steps, vec!["p1".to_string()]);
+        assert_eq!(project.plan_steps["p1"].fulfilled_by, ids);
+        assert_eq!(project.scenes["s1"].plan.parsed_steps[0].fulfilled_by, ids);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn written_generations_record_prompt_usage() {
+        let client = client("http://127.0.0.1:9/v1", Transport::Mock);
+        let dir = temp_dir("engine-usage");
+        let config = AppConfig::with_client(dir.clone(), client);
+        let mut project = ProjectData::new("Test", 0);
+        let scene_id = "s1".to_string();
+        project.scenes.insert(scene_id.clone(), scene(&scene_id));
+        project.characters.insert("c1".to_string(), character("c1", "Mira"));
+        let mut star = Star::new("Mira fears water".to_string(), "Since the wreck".to_string(), 0);
+        star.is_checked = true;
+        let star_id = star.id.clone();
+        project.stars.insert(star_id.clone(), star);
+
+        let generated = run_generation(&config, &ScriptHost::default(), &project, timeline_request(&scene_id)).await.unwrap_or_else(|e| panic!("{}", e.message));
+        let ids = write_generation(&mut project, generated, None, None, None, 5).unwrap_or_else(|e| panic!("{}", e.message));
+
+        let star = &project.stars[&star_id];
+        assert_eq!((star.usage_count, star.last_used_in_prompt), (1, Some(5)));
+        assert_eq!(star.influenced_draft_tab_ids, ids);
+        assert_eq!(project.characters["c1"].usage_count, 1);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::openai_client::extract_json_object;
+use crate::prompts;
+use crate::retrieval::tokenize;
+use crate::openai_client::TimelineEvent;
+use crate::{PlanStep, ProjectData};
+
+// Share of a step's terms that must appear in the draft before it is worth judging
+const CANDIDATE_RECALL: f64 = 0.25;
+// Without an LLM judge, a step counts as fulfilled only at this much overlap
+const LEXICAL_FULFILMENT_RECALL: f64 = 0.6;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VerdictStatus {
+    Fulfilled,
+    Partial,
+    NotFulfilled,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StepVerdict {
+    pub step_id: String,
+    pub status: VerdictStatus,
+    pub reason: String,
+}
+
+#[derive(Deserialize)]
+struct JudgeResponse {
+    verdicts: Vec<StepVerdict>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StepCandidate {
+    pub step_id: String,
+    pub text: String,
+    pub recall: f64, // share of the step's terms found in the draft
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StepProgress {
+    pub step_id: String,
+    pub text: String,
+    pub parent_id: Option<String>,
+    pub fulfilled_by: Vec<String>, // DraftTab IDs
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlanProgress {
+    pub scene_id: String,
+    pub total_steps: usize,
+    pub fulfilled_steps: usize,
+    pub percent_complete: f64,
+    pub steps: Vec<StepProgress>,
+}
+
+fn timeline_text(timeline: &[TimelineEvent]) -> String {
+    timeline
+        .iter()
+        .map(|event| match &event.dialogue {
+            Some(dialogue) => format!("{} \"{}\"", event.text, dialogue),
+            None => event.text.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Cheap pre-filter: steps sharing enough vocabulary with the draft to be worth judging
+pub fn lexical_candidates(steps: &[PlanStep], timeline: &[TimelineEvent]) -> Vec<StepCandidate> {
+    let draft_terms: HashSet<String> = tokenize(&timeline_text(timeline)).into_iter().collect();
+
+    steps
+        .iter()
+        .filter_map(|step| {
+            let step_terms: HashSet<String> = tokenize(&step.text).into_iter().collect();
+            if step_terms.is_empty() {
+                return None;
+            }
+            let recall = step_terms.iter().filter(|t| draft_terms.contains(*t)).count() as f64 / step_terms.len() as f64;
+            if recall < CANDIDATE_RECALL {
+                return None;
+            }
+            Some(StepCandidate { step_id: step.id.clone(), text: step.text.clone(), recall })
+        })
+        .collect()
+}
+
+pub fn build_judge_prompt(candidates: &[StepCandidate], timeline: &[TimelineEvent]) -> (String, String) {
+    let mut user_prompt = String::from("### PLAN STEPS\n");
+    for candidate in candidates {
+        user_prompt.push_str(&format!("- [{}] {}\n", candidate.step_id, candidate.text));
+    }
+    user_prompt.push_str("\n### TIMELINE\n");
+    for event in timeline.iter().filter(|e| e.checked) {
+        let dialogue = event.dialogue.as_ref().map(|d| format!(" -> \"{}\"", d)).unwrap_or_default();
+        user_prompt.push_str(&format!("- {}{}\n", event.text, dialogue));
+    }
+    user_prompt.push('\n');
+    user_prompt.push_str(prompts::PLAN_JUDGE_INSTRUCTIONS);
+
+    (prompts::PLAN_JUDGE_SYSTEM.to_string(), user_prompt)
+}
+
+// Strict parse of the judge reply: valid JSON, known step ids only, one verdict per step
+pub fn parse_verdicts(response: &str, candidates: &[StepCandidate]) -> Result<Vec<StepVerdict>, String> {
+    let json = extract_json_object(response).ok_or("Judge reply contained no JSON object")?;
+    let parsed: JudgeResponse = serde_json::from_str(json).map_err(|e| format!("Invalid judge JSON: {}", e))?;
+
+    let known: HashSet<&str> = candidates.iter().map(|c| c.step_id.as_str()).collect();
+    let mut seen = HashSet::new();
+    let mut verdicts = Vec::new();
+    for verdict in parsed.verdicts {
+        if !known.contains(verdict.step_id.as_str()) {
+            return Err(format!("Judge returned unknown step id: {}", verdict.step_id));
+        }
+        if seen.insert(verdict.step_id.clone()) {
+            verdicts.push(verdict);
+        }
+    }
+    Ok(verdicts)
+}
+
+// Fallback verdicts when no judge call is made
+pub fn lexical_verdicts(candidates: &[StepCandidate]) -> Vec<StepVerdict> {
+    candidates
+        .iter()
+        .map(|candidate| StepVerdict {
+            step_id: candidate.step_id.clone(),
+            status: if candidate.recall >= LEXICAL_FULFILMENT_RECALL {
+                VerdictStatus::Fulfilled
+            } else {
+                VerdictStatus::Partial
+            },
+            reason: format!("{:.0}% of the step's terms appear in the draft", candidate.recall * 100.0),
+        })
+        .collect()
+}
+
+fn link_step(step: &mut PlanStep, tab_id: &str, fulfilled: bool) {
+    step.fulfilled_by.retain(|id| id != tab_id);
+    if fulfilled {
+        step.fulfilled_by.push(tab_id.to_string());
+    }
+}
+
+// Write verdicts to both sides of the link: DraftTab.fulfilled_plan_steps and PlanStep.fulfilled_by
+// (in the scene plan and the project-wide step map). Partial steps become suggestions on the tab.
+// The verdicts replace the tab's earlier ones: a step without a verdict counts as not fulfilled.
+pub fn apply_verdicts(project: &mut ProjectData, scene_id: &str, tab_id: &str, verdicts: &[StepVerdict]) {
+    let step_texts: Vec<(String, String)> = project
+        .scenes
+        .get(scene_id)
+        .map(|scene| scene.plan.parsed_steps.iter().map(|s| (s.id.clone(), s.text.clone())).collect())
+        .unwrap_or_default();
+
+    if let Some(scene) = project.scenes.get_mut(scene_id) {
+        for step in &mut scene.plan.parsed_steps {
+            link_step(step, tab_id, false);
+        }
+    }
+    for (step_id, text) in &step_texts {
+        if let Some(step) = project.plan_steps.get_mut(step_id) {
+            link_step(step, tab_id, false);
+        }
+        if let Some(tab) = project.draft_tabs.get_mut(tab_id) {
+            tab.fulfilled_plan_steps.retain(|id| id != step_id);
+            tab.suggested_plan_steps.retain(|t| t != text);
+        }
+    }
+
+    for verdict in verdicts {
+        let fulfilled = verdict.status == VerdictStatus::Fulfilled;
+        if let Some(scene) = project.scenes.get_mut(scene_id) {
+            if let Some(step) = scene.plan.parsed_steps.iter_mut().find(|s| s.id == verdict.step_id) {
+                link_step(step, tab_id, fulfilled);
+            }
+        }
+        if let Some(step) = project.plan_steps.get_mut(&verdict.step_id) {
+            link_step(step, tab_id, fulfilled);
+        }
+
+        if let Some(tab) = project.draft_tabs.get_mut(tab_id) {
+            tab.fulfilled_plan_steps.retain(|id| id != &verdict.step_id);
+            if fulfilled {
+                tab.fulfilled_plan_steps.push(verdict.step_id.clone());
+            }
+
+            if let Some((_, text)) = step_texts.iter().find(|(id, _)| id == &verdict.step_id) {
+                tab.suggested_plan_steps.retain(|t| t != text);
+                if verdict.status == VerdictStatus::Partial {
+                    tab.suggested_plan_steps.push(text.clone());
+                }
+            }
+        }
+    }
+}
+
+pub fn plan_progress(project: &ProjectData, scene_id: &str) -> Option<PlanProgress> {
+    let scene = project.scenes.get(scene_id)?;
+    let steps: Vec<StepProgress> = scene
+        .plan
+        .parsed_steps
+        .iter()
+        .map(|step| StepProgress {
+            step_id: step.id.clone(),
+            text: step.text.clone(),
+            parent_id: step.parent_id.clone(),
+            // Ignore links to drafts that no longer exist
+            fulfilled_by: step
+                .fulfilled_by
+                .iter()
+                .filter(|id| project.draft_tabs.contains_key(*id))
+                .cloned()
+                .collect(),
+        })
+        .collect();
+
+    let total_steps = steps.len();
+    let fulfilled_steps = steps.iter().filter(|s| !s.fulfilled_by.is_empty()).count();
+    let percent_complete = if total_steps == 0 { 0.0 } else { fulfilled_steps as f64 * 100.0 / total_steps as f64 };
+
+    Some(PlanProgress {
+        scene_id: scene_id.to_string(),
+        total_steps,
+        fulfilled_steps,
+        percent_complete,
+        steps,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add_scene, event};
+
+    fn step(id: &str, text: &str) -> PlanStep {
+        PlanStep { id: id.to_string(), text: text.to_string(), fulfilled_by: Vec::new(), linked_stars: Vec::new(), parent_id: None }
+    }
+
+    fn steps() -> Vec<PlanStep> {
+        vec![step("p1", "Mira finds the letter"), step("p2", "The ferry leaves at dawn"), step("p3", "Hale burns the ship")]
+    }
+
+    fn timeline() -> Vec<TimelineEvent> {
+        vec![event("Mira finds a letter under the floorboards"), TimelineEvent { dialogue: Some("The ferry is late.".to_string()), ..event("Oren shrugs") }]
+    }
+
+    #[test]
+    fn candidates_share_vocabulary_and_fall_back_to_lexical_verdicts() {
+        let candidates = lexical_candidates(&steps(), &timeline());
+        let recalls: Vec<(&str, f64)> = candidates.iter().map(|c| (c.step_id.as_str(), c.recall)).collect();
+        assert_eq!(recalls, vec![("p1", 1.0), ("p2", 1.0 / 3.0)]);
+
+        let verdicts = lexical_verdicts(&candidates);
+        assert_eq!(verdicts[0].status, VerdictStatus::Fulfilled);
+        assert_eq!(verdicts[1].status, VerdictStatus::Partial);
+        assert_eq!(verdicts[1].reason, "33% of the step's terms appear in the draft");
+
+        let (_, prompt) = build_judge_prompt(&candidates, &timeline());
+        assert!(prompt.contains("- [p2] The ferry leaves at dawn") && prompt.contains("- Oren shrugs -> \"The ferry is late.\""));
+    }
+
+    #[test]
+    fn judge_replies_are_parsed_strictly() {
+        let candidates = lexical_candidates(&steps(), &timeline());
+        let reply = r#"Sure: {"verdicts": [
+            {"step_id": "p1", "status": "fulfilled", "reason": "found"},
+            {"step_id": "p1", "status": "partial", "reason": "again"},
+            {"step_id": "p2", "status": "not_fulfilled", "reason": "only mentioned"}
+        ]}"#;
+        let verdicts = parse_verdicts(reply, &candidates).unwrap();
+        assert_eq!(verdicts.iter().map(|v| (v.step_id.as_str(), v.status)).collect::<Vec<_>>(), vec![("p1", VerdictStatus::Fulfilled), ("p2", VerdictStatus::NotFulfilled)]);
+
+        let unknown = r#"{"verdicts": [{"step_id": "p3", "status": "fulfilled", "reason": ""}]}"#;
+        assert!(parse_verdicts(unknown, &candidates).unwrap_err().contains("unknown step id: p3"));
+        assert!(parse_verdicts("no idea", &candidates).is_err());
+    }
+
+    #[test]
+    fn verdicts_link_both_sides_and_count_towards_progress() {
+        let mut project = ProjectData::new("Test", 0);
+        add_scene(&mut project, "s1", "Harbour", &[&["Mira finds the letter"], &["Hale burns the ship"]]);
+        project.scenes.get_mut("s1").unwrap().plan.parsed_steps = steps();
+        for step in steps() {
+            project.plan_steps.insert(step.id.clone(), step);
+        }
+        let verdict = |step_id: &str, status| StepVerdict { step_id: step_id.to_string(), status, reason: String::new() };
+
+        apply_verdicts(&mut project, "s1", "s1-t0", &[verdict("p1", VerdictStatus::Fulfilled), verdict("p2", VerdictStatus::Partial)]);
+        apply_verdicts(&mut project, "s1", "s1-t1", &[verdict("p3", VerdictStatus::Fulfilled)]);
+        let tab = &project.draft_tabs["s1-t0"];
+        assert_eq!(tab.fulfilled_plan_steps, vec!["p1".to_string()]);
+        assert_eq!(tab.suggested_plan_steps, vec!["The ferry leaves at dawn".to_string()]);
+        assert_eq!(project.plan_steps["p1"].fulfilled_by, vec!["s1-t0".to_string()]);
+        assert_eq!(project.scenes["s1"].plan.parsed_steps[0].fulfilled_by, vec!["s1-t0".to_string()]);
+
+        // A later verdict replaces the earlier one, and deleted drafts no longer count
+        apply_verdicts(&mut project, "s1", "s1-t0", &[verdict("p1", VerdictStatus::Fulfilled), verdict("p2", VerdictStatus::Fulfilled)]);
+        assert!(project.draft_tabs["s1-t0"].suggested_plan_steps.is_empty());
+        project.draft_tabs.remove("s1-t1");
+        let progress = plan_progress(&project, "s1").unwrap();
+        assert_eq!((progress.total_steps, progress.fulfilled_steps), (3, 2));
+        assert!(progress.steps[2].fulfilled_by.is_empty());
+        assert!(plan_progress(&project, "nope").is_none());
+    }
+
+    #[test]
+    fn judging_a_tab_again_drops_links_to_steps_it_no_longer_covers() {
+        let mut project = ProjectData::new("Test", 0);
+        add_scene(&mut project, "s1", "Harbour", &[&["Mira finds a letter under the floorboards"]]);
+        project.scenes.get_mut("s1").unwrap().plan.parsed_steps = steps();
+        for step in steps() {
+            project.plan_steps.insert(step.id.clone(), step);
+        }
+        let judge = |project: &mut ProjectData| {
+            let timeline = project.draft_tabs["s1-t0"].timeline.clone();
+            let verdicts = lexical_verdicts(&lexical_candidates(&project.scenes["s1"].plan.parsed_steps, &timeline));
+            apply_verdicts(project, "s1", "s1-t0", &verdicts);
+        };
+
+        judge(&mut project);
+        assert_eq!(project.draft_tabs["s1-t0"].fulfilled_plan_steps, vec!["p1".to_string()]);
+
+        // Rewritten so that no step is a candidate any more
+        project.draft_tabs.get_mut("s1-t0").unwrap().timeline = vec![event("Oren mends a net")];
+        judge(&mut project);
+        assert!(project.draft_tabs["s1-t0"].fulfilled_plan_steps.is_empty());
+        assert!(project.plan_steps["p1"].fulfilled_by.is_empty());
+        assert!(project.scenes["s1"].plan.parsed_steps[0].fulfilled_by.is_empty());
+        assert_eq!(plan_progress(&project, "s1").unwrap().fulfilled_steps, 0);
+    }
+}
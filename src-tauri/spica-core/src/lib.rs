@@ -0,0 +1,35 @@
+// Project model, LLM client and generation pipeline shared by the desktop app and the `spica` CLI
+
+pub mod api_server;
+pub mod audit;
+pub mod character_profiles;
+pub mod context_engine;
+pub mod continuity;
+pub mod engine;
+pub mod fulfilment;
+pub mod llm_fixtures;
+pub mod mcp;
+pub mod model;
+pub mod openai_client;
+pub mod outline;
+pub mod plan_parser;
+pub mod prompts;
+pub mod provenance;
+pub mod replace;
+pub mod retrieval;
+pub mod scripting;
+pub mod search;
+pub mod snapshots;
+pub mod speaker;
+pub mod spellcheck;
+pub mod star_extraction;
+pub mod stats;
+pub mod style;
+pub mod summaries;
+pub mod templates;
+#[cfg(test)]
+mod testing;
+pub mod usage;
+pub mod vault;
+
+pub use model::*;
@@ -0,0 +1,477 @@
+// Model Context Protocol server over stdio, so chat assistants can read the story bible and
+// make small edits. Messages are newline-delimited JSON-RPC 2.0; stdout carries nothing else.
+
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::api_server::{RpcError, INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND, PARSE_ERROR};
+use crate::engine;
+use crate::scripting::{self, ScriptHost};
+use crate::search::{SearchFilters, SearchIndex, SearchKind};
+use crate::{ApiError, ProjectData, Star, STAR_SCOPES};
+
+// Newest first; an unknown client version gets the newest
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+const URI_PREFIX: &str = "spica://";
+
+pub struct McpServer {
+    path: PathBuf, // the project file; re-read on every request so edits made elsewhere show up
+    search_index: SearchIndex,
+}
+
+#[derive(Deserialize)]
+struct SearchArgs {
+    query: String,
+    kinds: Option<Vec<SearchKind>>,
+    scene_id: Option<String>,
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct AddStarArgs {
+    title: String,
+    body: String,
+    #[serde(default)]
+    characters: Vec<String>, // character ids
+    scope: Option<String>,
+    priority: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct AppendEventArgs {
+    tab_id: String,
+    text: String,
+    dialogue: Option<String>,
+}
+
+impl McpServer {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, search_index: SearchIndex::new() }
+    }
+
+    // Serve until the input closes
+    pub fn serve(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            for message in self.handle_line(&line) {
+                writeln!(output, "{}", message)?;
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    // Replies and notifications to send for one incoming message
+    pub fn handle_line(&mut self, line: &str) -> Vec<Value> {
+        let message: Value = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(e) => return vec![error_response(Value::Null, RpcError { code: PARSE_ERROR, message: e.to_string(), data: None })],
+        };
+        let method = message.get("method").and_then(Value::as_str).unwrap_or_default().to_string();
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        let id = match message.get("id") {
+            Some(id) => id.clone(),
+            // Notifications (initialized, cancelled, ...) need no reply
+            None => return Vec::new(),
+        };
+        if method.is_empty() {
+            return vec![error_response(id, RpcError { code: INVALID_REQUEST, message: "Missing method".to_string(), data: None })];
+        }
+
+        let mut out = Vec::new();
+        match self.dispatch(&method, params) {
+            Ok((result, changed)) => {
+                out.push(json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+                if changed {
+                    out.push(json!({ "jsonrpc": "2.0", "method": "notifications/resources/list_changed" }));
+                }
+            }
+            Err(e) => out.push(error_response(id, e)),
+        }
+        out
+    }
+
+    // The result, and whether the project's resources changed
+    fn dispatch(&mut self, method: &str, params: Value) -> Result<(Value, bool), RpcError> {
+        match method {
+            "initialize" => {
+                let requested = params.get("protocolVersion").and_then(Value::as_str).unwrap_or_default();
+                let version = PROTOCOL_VERSIONS.iter().find(|v| **v == requested).unwrap_or(&PROTOCOL_VERSIONS[0]);
+                let result = json!({
+                    "protocolVersion": version,
+                    "capabilities": { "resources": { "listChanged": true }, "tools": {} },
+                    "serverInfo": { "name": "spica", "version": env!("CARGO_PKG_VERSION") },
+                    "instructions": "The Spica story bible: characters, stars (story notes), scenes and draft tabs. \
+                                     Read resources for detail, search to find passages, and use the tools to add notes or events.",
+                });
+                Ok((result, false))
+            }
+            "ping" => Ok((json!({}), false)),
+            "resources/list" => Ok((json!({ "resources": resources(&self.load()?) }), false)),
+            "resources/templates/list" => {
+                let templates: Vec<Value> = [("characters", "Character"), ("stars", "Star"), ("scenes", "Scene"), ("draft-tabs", "Draft tab")]
+                    .iter()
+                    .map(|(kind, name)| {
+                        json!({ "uriTemplate": format!("{}{}/{{id}}", URI_PREFIX, kind), "name": name, "mimeType": "application/json" })
+                    })
+                    .collect();
+                Ok((json!({ "resourceTemplates": templates }), false))
+            }
+            "resources/read" => {
+                let uri = params.get("uri").and_then(Value::as_str).ok_or_else(|| invalid_params("uri is required"))?;
+                let text = read_resource(&self.load()?, uri)?;
+                Ok((json!({ "contents": [{ "uri": uri, "mimeType": "application/json", "text": text }] }), false))
+            }
+            "tools/list" => Ok((json!({ "tools": tools() }), false)),
+            "tools/call" => {
+                let name = params.get("name").and_then(Value::as_str).ok_or_else(|| invalid_params("name is required"))?.to_string();
+                let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+                // Failures inside a tool are reported to the model rather than as protocol errors
+                match self.call_tool(&name, args)? {
+                    Ok((text, changed)) => Ok((json!({ "content": [{ "type": "text", "text": text }], "isError": false }), changed)),
+                    Err(message) => Ok((json!({ "content": [{ "type": "text", "text": message }], "isError": true }), false)),
+                }
+            }
+            _ => Err(RpcError { code: METHOD_NOT_FOUND, message: format!("Unknown method: {}", method), data: None }),
+        }
+    }
+
+    fn call_tool(&mut self, name: &str, args: Value) -> Result<Result<(String, bool), String>, RpcError> {
+        let result = match name {
+            "search" => {
+                let args: SearchArgs = tool_args(args)?;
+                let project = self.load()?;
+                self.search_index.sync(&project);
+                let filters = SearchFilters {
+                    kinds: args.kinds,
+                    scene_id: args.scene_id,
+                    limit: args.limit.unwrap_or(20),
+                    ..SearchFilters::default()
+                };
+                let hits: Vec<Value> = self
+                    .search_index
+                    .search(&args.query, &filters)
+                    .into_iter()
+                    .map(|hit| json!({ "key": hit.key, "title": hit.title, "scene_id": hit.scene_id, "snippet": hit.snippet }))
+                    .collect();
+                Ok((serde_json::to_string_pretty(&hits).unwrap(), false))
+            }
+            "add_star" => {
+                let args: AddStarArgs = tool_args(args)?;
+                self.edit(|project| {
+                    if let Some(missing) = args.characters.iter().find(|id| !project.characters.contains_key(*id)) {
+                        return Err(format!("Character not found: {}", missing));
+                    }
+                    if let Some(scope) = args.scope.as_deref().filter(|scope| !STAR_SCOPES.contains(scope)) {
+                        return Err(format!("Unknown scope {}, expected one of {}", scope, STAR_SCOPES.join(", ")));
+                    }
+                    let mut star = Star::new(args.title, args.body, now_ms());
+                    star.tags.characters = args.characters;
+                    if let Some(scope) = args.scope {
+                        star.tags.scope = scope;
+                    }
+                    star.priority = args.priority.unwrap_or(star.priority).clamp(0.0, 1.0);
+                    let text = format!("Added star {} ({}{})", star.title, URI_PREFIX, star_uri_path(&star.id));
+                    project.stars.insert(star.id.clone(), star);
+                    Ok(text)
+                })
+            }
+            "append_timeline_event" => {
+                let args: AppendEventArgs = tool_args(args)?;
+                self.edit(|project| {
+                    let event = engine::append_timeline_event(project, &args.tab_id, args.text, args.dialogue, now_ms()).map_err(|e| e.message)?;
+                    Ok(serde_json::to_string_pretty(&event).unwrap())
+                })
+            }
+            _ => return Err(invalid_params(&format!("Unknown tool: {}", name))),
+        };
+        Ok(result)
+    }
+
+    fn load(&self) -> Result<ProjectData, RpcError> {
+        Ok(engine::read_project(&self.path)?)
+    }
+
+    // The app may save the same file at any time: if it did while this edit was made, nothing is
+    // written. The app notices the rewrite and reloads or merges it.
+    fn edit(&mut self, change: impl FnOnce(&mut ProjectData) -> Result<String, String>) -> Result<(String, bool), String> {
+        let stamp = engine::file_stamp(&self.path);
+        let mut project = engine::read_project(&self.path).map_err(|e| e.message)?;
+        let text = change(&mut project)?;
+        project.metadata.updated_at = now_ms();
+        // stdout carries the protocol, so script problems go to the client's log on stderr
+        let (project, messages) = ScriptHost::load_dir(&scripting::script_dir(&self.path)).before_save(project);
+        for message in &messages {
+            eprintln!("script {}: {}", message.script, message.message);
+        }
+        if engine::file_stamp(&self.path) != stamp {
+            return Err("The project was saved by another program during this edit; nothing was changed, try again".to_string());
+        }
+        engine::write_project(&self.path, &project).map_err(|e| e.message)?;
+        Ok((text, true))
+    }
+}
+
+fn resources(project: &ProjectData) -> Vec<Value> {
+    let mut resources = vec![json!({
+        "uri": format!("{}project", URI_PREFIX),
+        "name": project.metadata.title,
+        "description": "Project overview: title, scene order and counts",
+        "mimeType": "application/json",
+    })];
+    let mut push = |path: String, name: &str, description: String| {
+        resources.push(json!({ "uri": format!("{}{}", URI_PREFIX, path), "name": name, "description": description, "mimeType": "application/json" }));
+    };
+
+    let mut characters: Vec<_> = project.characters.values().collect();
+    characters.sort_by(|a, b| a.name.cmp(&b.name));
+    for character in characters {
+        push(format!("characters/{}", character.id), &character.name, "Character".to_string());
+    }
+    let mut stars: Vec<_> = project.stars.values().collect();
+    stars.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+    for star in stars {
+        push(star_uri_path(&star.id), &star.title, format!("Star ({}, {})", star.tags.scope, star.tags.status));
+    }
+    for scene in crate::outline::ordered_scenes(project) {
+        push(format!("scenes/{}", scene.id), &scene.name, "Scene".to_string());
+        for (n, tab_id) in scene.draft_tab_ids.iter().enumerate() {
+            if let Some(tab) = project.draft_tabs.get(tab_id) {
+                push(format!("draft-tabs/{}", tab.id), &format!("{} draft {}", scene.name, n + 1), tab.summary.clone().unwrap_or_default());
+            }
+        }
+    }
+    for tab_id in &project.workbench.unassigned_draft_tab_ids {
+        if let Some(tab) = project.draft_tabs.get(tab_id) {
+            push(format!("draft-tabs/{}", tab.id), "Workbench draft", tab.summary.clone().unwrap_or_default());
+        }
+    }
+    resources
+}
+
+fn read_resource(project: &ProjectData, uri: &str) -> Result<String, RpcError> {
+    let path = uri.strip_prefix(URI_PREFIX).ok_or_else(|| invalid_params(&format!("Not a Spica resource: {}", uri)))?;
+    let missing = || -> RpcError {
+        ApiError {
+            error: true,
+            message: format!("Resource not found: {}", uri),
+            code: Some("NOT_FOUND".to_string()),
+        }
+        .into()
+    };
+    let value = match path.split_once('/') {
+        None if path == "project" => {
+            let scenes: Vec<Value> = crate::outline::ordered_scenes(project)
+                .into_iter()
+                .map(|scene| json!({ "id": scene.id, "name": scene.name, "draft_tabs": scene.draft_tab_ids.len() }))
+                .collect();
+            json!({
+                "title": project.metadata.title,
+                "author": project.metadata.author,
+                "scenes": scenes,
+                "characters": project.characters.len(),
+                "stars": project.stars.len(),
+                "workbench_drafts": project.workbench.unassigned_draft_tab_ids.len(),
+            })
+        }
+        Some(("characters", id)) => json!(project.characters.get(id).ok_or_else(missing)?),
+        Some(("stars", id)) => json!(project.stars.get(id).ok_or_else(missing)?),
+        Some(("scenes", id)) => json!(project.scenes.get(id).ok_or_else(missing)?),
+        Some(("draft-tabs", id)) => json!(project.draft_tabs.get(id).ok_or_else(missing)?),
+        _ => return Err(missing()),
+    };
+    Ok(serde_json::to_string_pretty(&value).unwrap())
+}
+
+fn star_uri_path(id: &str) -> String {
+    format!("stars/{}", id)
+}
+
+fn tools() -> Value {
+    json!([
+        {
+            "name": "search",
+            "description": "Full-text search over events, descriptions, stars, character fields and scenes",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string" },
+                    "kinds": { "type": "array", "items": { "enum": ["event", "description", "star", "character_field", "scene"] } },
+                    "scene_id": { "type": "string" },
+                    "limit": { "type": "integer", "minimum": 1 }
+                },
+                "required": ["query"]
+            }
+        },
+        {
+            "name": "add_star",
+            "description": "Add a story note (star) that future generations will take into account",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string" },
+                    "body": { "type": "string" },
+                    "characters": { "type": "array", "items": { "type": "string" }, "description": "Character ids the note is about" },
+                    "scope": { "enum": STAR_SCOPES },
+                    "priority": { "type": "number", "minimum": 0, "maximum": 1 }
+                },
+                "required": ["title", "body"]
+            }
+        },
+        {
+            "name": "append_timeline_event",
+            "description": "Append an event, with optional dialogue, to the end of a draft tab's timeline",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "tab_id": { "type": "string" },
+                    "text": { "type": "string" },
+                    "dialogue": { "type": "string" }
+                },
+                "required": ["tab_id", "text"]
+            }
+        }
+    ])
+}
+
+fn tool_args<T: for<'de> Deserialize<'de>>(args: Value) -> Result<T, RpcError> {
+    serde_json::from_value(args).map_err(|e| invalid_params(&format!("Invalid arguments: {}", e)))
+}
+
+fn invalid_params(message: &str) -> RpcError {
+    RpcError { code: INVALID_PARAMS, message: message.to_string(), data: None }
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}
+
+fn now_ms() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+    use crate::{Character, DraftTab};
+
+    fn server_with_project(label: &str) -> (McpServer, PathBuf) {
+        let dir = temp_dir(label);
+        let path = dir.join("project.json");
+        let mut project = ProjectData::new("Test", 0);
+        project.characters.insert(
+            "c1".to_string(),
+            Character {
+                id: "c1".to_string(),
+                name: "Mira".to_string(),
+                fields: std::collections::HashMap::new(),
+                is_checked: true,
+                last_used_in_prompt: None,
+                usage_count: 0,
+            },
+        );
+        project.draft_tabs.insert(
+            "t1".to_string(),
+            DraftTab {
+                id: "t1".to_string(),
+                scene_id: None,
+                index: 0,
+                timeline: Vec::new(),
+                descriptions: Vec::new(),
+                summary: None,
+                atmosphere: None,
+                fulfilled_plan_steps: Vec::new(),
+                suggested_plan_steps: Vec::new(),
+                created_at: 0,
+                updated_at: 0,
+                provenance: None,
+            },
+        );
+        project.workbench.unassigned_draft_tab_ids.push("t1".to_string());
+        engine::write_project(&path, &project).unwrap();
+        (McpServer::new(path), dir)
+    }
+
+    fn request(server: &mut McpServer, id: u64, method: &str, params: Value) -> Vec<Value> {
+        server.handle_line(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }).to_string())
+    }
+
+    #[test]
+    fn session_over_stdio_lists_reads_and_edits() {
+        let (mut server, dir) = server_with_project("mcp-session");
+        let input = [
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "2024-11-05", "capabilities": {} } }),
+            json!({ "jsonrpc": "2.0", "method": "notifications/initialized" }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "resources/list" }),
+        ]
+        .iter()
+        .map(|m| m.to_string() + "\n")
+        .collect::<String>();
+        let mut output = Vec::new();
+        server.serve(input.as_bytes(), &mut output).unwrap();
+        let replies: Vec<Value> = String::from_utf8(output).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(replies.len(), 2, "the notification gets no reply");
+        assert_eq!(replies[0]["result"]["protocolVersion"], "2024-11-05");
+        let uris: Vec<&str> = replies[1]["result"]["resources"].as_array().unwrap().iter().map(|r| r["uri"].as_str().unwrap()).collect();
+        assert!(uris.contains(&"spica://characters/c1") && uris.contains(&"spica://draft-tabs/t1"));
+
+        let read = request(&mut server, 3, "resources/read", json!({ "uri": "spica://characters/c1" }));
+        assert!(read[0]["result"]["contents"][0]["text"].as_str().unwrap().contains("Mira"));
+
+        let added = request(&mut server, 4, "tools/call", json!({ "name": "add_star", "arguments": { "title": "Mira fears water", "body": "Since the wreck", "characters": ["c1"] } }));
+        assert_eq!(added[0]["result"]["isError"], false);
+        assert_eq!(added[1]["method"], "notifications/resources/list_changed");
+        let appended = request(&mut server, 5, "tools/call", json!({ "name": "append_timeline_event", "arguments": { "tab_id": "t1", "text": "Mira says", "dialogue": "Not the ferry." } }));
+        assert_eq!(appended[0]["result"]["isError"], false);
+
+        let project = engine::read_project(&server.path).unwrap();
+        assert_eq!(project.stars.values().next().unwrap().tags.characters, vec!["c1".to_string()]);
+        let event = &project.draft_tabs["t1"].timeline[0];
+        assert_eq!(event.speaker.as_ref().and_then(|s| s.character_id.as_deref()), Some("c1"));
+
+        let found = request(&mut server, 6, "tools/call", json!({ "name": "search", "arguments": { "query": "wreck" } }));
+        assert!(found[0]["result"]["content"][0]["text"].as_str().unwrap().contains("Mira fears water"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn bad_tool_input_is_reported_not_fatal() {
+        let (mut server, dir) = server_with_project("mcp-errors");
+        let missing_tab = request(&mut server, 1, "tools/call", json!({ "name": "append_timeline_event", "arguments": { "tab_id": "nope", "text": "x" } }));
+        assert_eq!(missing_tab[0]["result"]["isError"], true);
+        let unknown_tool = request(&mut server, 2, "tools/call", json!({ "name": "delete_everything", "arguments": {} }));
+        assert_eq!(unknown_tool[0]["error"]["code"], INVALID_PARAMS);
+        let unknown_resource = request(&mut server, 3, "resources/read", json!({ "uri": "spica://stars/nope" }));
+        assert_eq!(unknown_resource[0]["error"]["data"]["code"], "NOT_FOUND");
+        let bad_scope = request(&mut server, 4, "tools/call", json!({ "name": "add_star", "arguments": { "title": "t", "body": "b", "scope": "Anything" } }));
+        assert_eq!(bad_scope[0]["result"]["isError"], true);
+        assert!(bad_scope[0]["result"]["content"][0]["text"].as_str().unwrap().starts_with("Unknown scope Anything"));
+        assert!(engine::read_project(&server.path).unwrap().stars.is_empty());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn edit_is_dropped_when_the_file_is_saved_meanwhile() {
+        let (mut server, dir) = server_with_project("mcp-conflict");
+        let path = server.path.clone();
+        let result = server.edit(|project| {
+            let mut saved = project.clone();
+            saved.metadata.title = "Saved by the app".to_string();
+            engine::write_project(&path, &saved).unwrap();
+            project.metadata.title = "Edited over MCP".to_string();
+            Ok(String::new())
+        });
+        assert!(result.unwrap_err().contains("nothing was changed"));
+        assert_eq!(engine::read_project(&path).unwrap().metadata.title, "Saved by the app");
+        assert!(!dir.join("project.json.tmp").exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,242 @@
+// Data structures matching the frontend types
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::openai_client::TimelineEvent;
+use crate::outline::Outline;
+use crate::provenance::Provenance;
+use crate::spellcheck::CustomDictionary;
+use crate::stats::WritingProgress;
+use crate::summaries::SummaryCache;
+use crate::templates::ProjectTemplates;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Description {
+    pub id: String,
+    pub text: String,
+    pub is_important: bool,
+    pub origin_star_id: Option<String>,
+    pub target_event_id: Option<String>,
+    #[serde(default = "default_description_scope")]
+    pub scope: String, // "event" | "tab"
+    pub provenance: Option<Provenance>,
+}
+
+fn default_description_scope() -> String {
+    "tab".to_string()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DraftTab {
+    pub id: String,
+    pub scene_id: Option<String>, // None means the tab sits in the workbench
+    pub index: u32,
+    pub timeline: Vec<TimelineEvent>,
+    pub descriptions: Vec<Description>,
+    pub summary: Option<String>,
+    pub atmosphere: Option<String>,
+    pub fulfilled_plan_steps: Vec<String>,
+    pub suggested_plan_steps: Vec<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub provenance: Option<Provenance>, // set when the tab came from a generation
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Character {
+    pub id: String,
+    pub name: String,
+    pub fields: HashMap<String, String>,
+    #[serde(default = "default_true")]
+    pub is_checked: bool,
+    pub last_used_in_prompt: Option<u64>,
+    #[serde(default)]
+    pub usage_count: u32,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// Every valid StarTags.scope
+pub const STAR_SCOPES: [&str; 4] = ["CurrentScene", "FuturePlot", "Backstory", "Worldbuilding"];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StarTags {
+    pub characters: Vec<String>,
+    pub scope: String, // "CurrentScene" | "FuturePlot" | "Backstory" | "Worldbuilding"
+    pub status: String, // "Active" | "Resolved" | "Deferred"
+    pub custom: Vec<String>,
+    pub constraint_context: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StarSourceEvent {
+    pub tab_id: String,
+    pub event_id: String,
+    pub event_text: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Star {
+    pub id: String,
+    pub title: String,
+    pub body: String,
+    pub tags: StarTags,
+    pub priority: f64,
+    pub is_checked: bool,
+    pub origin_draft_tab_id: Option<String>,
+    pub created_at: u64,
+    pub last_used_in_prompt: Option<u64>,
+    // Character constraint fields
+    pub constraint_type: Option<String>, // "character_behavior" | "character_dialogue" | ...
+    pub applies_to_character: Option<String>,
+    pub situation_context: Option<String>,
+    pub source_event: Option<StarSourceEvent>,
+    // Prompt usage analytics
+    #[serde(default)]
+    pub usage_count: u32,
+    #[serde(default)]
+    pub influenced_draft_tab_ids: Vec<String>,
+}
+
+impl Star {
+    // An active, checked note for the current scene, as the app creates them
+    pub fn new(title: String, body: String, now_ms: u64) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            title,
+            body,
+            tags: StarTags {
+                characters: Vec::new(),
+                scope: "CurrentScene".to_string(),
+                status: "Active".to_string(),
+                custom: Vec::new(),
+                constraint_context: None,
+            },
+            priority: 0.5,
+            is_checked: true,
+            origin_draft_tab_id: None,
+            created_at: now_ms,
+            last_used_in_prompt: None,
+            constraint_type: None,
+            applies_to_character: None,
+            situation_context: None,
+            source_event: None,
+            usage_count: 0,
+            influenced_draft_tab_ids: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PlanStep {
+    pub id: String,
+    pub text: String,
+    pub fulfilled_by: Vec<String>,
+    pub linked_stars: Vec<String>,
+    pub parent_id: Option<String>, // set for indented sub-steps
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ScenePlan {
+    pub raw_text: String,
+    pub parsed_steps: Vec<PlanStep>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Scene {
+    pub id: String,
+    pub name: String,
+    pub setting: Option<String>,
+    pub backstory: Option<String>,
+    pub plan: ScenePlan,
+    pub draft_tab_ids: Vec<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Workbench {
+    pub unassigned_draft_tab_ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IdeaBank {
+    pub stored_draft_tab_ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProjectMetadata {
+    // Stable across renames; older projects have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub title: String,
+    pub author: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProjectData {
+    pub version: String,
+    pub metadata: ProjectMetadata,
+    pub scenes: HashMap<String, Scene>,
+    pub draft_tabs: HashMap<String, DraftTab>,
+    #[serde(default)]
+    pub workbench: Workbench,
+    pub stars: HashMap<String, Star>,
+    pub characters: HashMap<String, Character>,
+    pub plan_steps: HashMap<String, PlanStep>,
+    pub idea_bank: IdeaBank,
+    pub active_scene_id: Option<String>,
+    #[serde(default)]
+    pub outline: Outline,
+    #[serde(default)]
+    pub summaries: SummaryCache,
+    #[serde(default)]
+    pub progress: WritingProgress,
+    #[serde(default)]
+    pub custom_dictionary: CustomDictionary,
+    #[serde(default)]
+    pub prompt_templates: ProjectTemplates,
+}
+
+impl ProjectData {
+    pub fn new(title: &str, now_ms: u64) -> Self {
+        Self {
+            version: "1.0".to_string(),
+            metadata: ProjectMetadata {
+                id: Some(uuid::Uuid::new_v4().to_string()),
+                title: title.to_string(),
+                author: None,
+                created_at: now_ms,
+                updated_at: now_ms,
+            },
+            scenes: HashMap::new(),
+            draft_tabs: HashMap::new(),
+            workbench: Workbench::default(),
+            stars: HashMap::new(),
+            characters: HashMap::new(),
+            plan_steps: HashMap::new(),
+            idea_bank: IdeaBank {
+                stored_draft_tab_ids: Vec::new(),
+            },
+            active_scene_id: None,
+            outline: Outline::default(),
+            summaries: SummaryCache::default(),
+            progress: WritingProgress::default(),
+            custom_dictionary: CustomDictionary::default(),
+            prompt_templates: ProjectTemplates::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ApiError {
+    pub error: bool,
+    pub message: String,
+    pub code: Option<String>,
+}
@@ -0,0 +1,473 @@
+use reqwest;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+
+use serde_json::json;
+
+use crate::audit::{AuditLog, LogLevel};
+use crate::fulfilment::StepVerdict;
+use crate::llm_fixtures;
+use crate::provenance::Provenance;
+use crate::speaker::{SpeakerAttribution, UnresolvedSpeaker};
+
+#[derive(Debug)]
+pub enum ClientError {
+    NetworkError(reqwest::Error),
+    ApiError(String),
+    ConfigError(String),
+    OpenAIError(String),
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientError::NetworkError(e) => write!(f, "Network error: {}", e),
+            ClientError::ApiError(msg) => write!(f, "API error: {}", msg),
+            ClientError::ConfigError(msg) => write!(f, "Config error: {}", msg),
+            ClientError::OpenAIError(msg) => write!(f, "OpenAI error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(error: reqwest::Error) -> Self {
+        ClientError::NetworkError(error)
+    }
+}
+
+impl From<String> for ClientError {
+    fn from(error: String) -> Self {
+        ClientError::ApiError(error)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Message {
+    role: String,
+    content: String,
+}
+
+#[derive(Serialize)]
+struct OpenAIPrompt {
+    model: String,
+    messages: Vec<Message>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct OpenAIResponse {
+    #[serde(default)]
+    model: Option<String>,
+    choices: Vec<Choice>,
+    #[serde(default)]
+    usage: Option<TokenUsage>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+// Per-request overrides; unset fields use the client's defaults
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct GenerationParams {
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+}
+
+pub struct Completion {
+    pub text: String,
+    pub model: String, // as reported by the API, e.g. with a date suffix
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: Message,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAIError {
+    error: ErrorDetails,
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorDetails {
+    message: String,
+    #[serde(rename = "type")]
+    #[allow(dead_code)]
+    error_type: String,
+    code: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TimelineEvent {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub text: String,
+    pub dialogue: Option<String>,
+    pub checked: bool,
+    #[serde(default)]
+    pub associated_stars: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<SpeakerAttribution>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LLMTab {
+    pub title: String,
+    pub timeline: Vec<TimelineEvent>,
+    pub summary: Option<String>,
+    pub atmosphere: Option<String>,
+    #[serde(default)]
+    pub unresolved_speakers: Vec<UnresolvedSpeaker>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+    #[serde(default)]
+    pub plan_verdicts: Vec<StepVerdict>, // the scene's plan steps judged against this timeline; applied when the tab is written
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LLMResponse {
+    pub tabs: Vec<LLMTab>,
+}
+
+const DEFAULT_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_MODEL: &str = "gpt-4.1";
+
+// Where requests go; chosen at startup with SPICA_LLM_MODE
+#[derive(Clone, Debug, PartialEq)]
+pub enum Transport {
+    Live,
+    Record(PathBuf), // live, saving every exchange as a fixture file
+    Replay(PathBuf), // recorded fixtures only; an unrecorded request is an error
+    Mock,            // canned responses; needs neither network nor API key
+}
+
+pub struct ClientConfig {
+    pub api_key: String,
+    pub base_url: String,
+    pub model: String,
+    pub transport: Transport,
+}
+
+impl ClientConfig {
+    // SPICA_LLM_MODE is live (default), record, replay or mock; record and replay use SPICA_LLM_FIXTURES
+    pub fn from_env() -> Result<Self, ClientError> {
+        let fixtures = || {
+            env::var("SPICA_LLM_FIXTURES")
+                .map(PathBuf::from)
+                .map_err(|_| ClientError::ConfigError("SPICA_LLM_FIXTURES must name the fixture directory".to_string()))
+        };
+        let transport = match env::var("SPICA_LLM_MODE").unwrap_or_default().as_str() {
+            "" | "live" => Transport::Live,
+            "record" => Transport::Record(fixtures()?),
+            "replay" => Transport::Replay(fixtures()?),
+            "mock" => Transport::Mock,
+            other => return Err(ClientError::ConfigError(format!("Unknown SPICA_LLM_MODE: {}", other))),
+        };
+
+        let api_key = match env::var("OPENAI_API_KEY") {
+            Ok(key) => key,
+            Err(_) if matches!(transport, Transport::Replay(_) | Transport::Mock) => String::new(),
+            Err(_) => return Err(ClientError::ConfigError("OPENAI_API_KEY not found in environment".to_string())),
+        };
+
+        Ok(Self {
+            api_key,
+            base_url: env::var("OPENAI_BASE_URL").unwrap_or_else(|_| DEFAULT_BASE_URL.to_string()),
+            // model: "gpt-4o-mini".to_string(),
+            model: DEFAULT_MODEL.to_string(),
+            transport,
+        })
+    }
+}
+
+pub struct OpenAIClient {
+    client: reqwest::Client,
+    api_key: String,
+    base_url: String,
+    model: String,
+    transport: Transport,
+    audit: Option<Arc<AuditLog>>,
+}
+
+impl OpenAIClient {
+    pub fn new() -> Result<Self, ClientError> {
+        Ok(Self::with_config(ClientConfig::from_env()?))
+    }
+
+    pub fn with_config(config: ClientConfig) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_key: config.api_key,
+            base_url: config.base_url,
+            model: config.model,
+            transport: config.transport,
+            audit: None,
+        }
+    }
+
+    // Log every request and response; the API key is registered so it is always redacted
+    pub fn with_audit(mut self, audit: Arc<AuditLog>) -> Self {
+        audit.add_secret(&self.api_key);
+        self.audit = Some(audit);
+        self
+    }
+
+    fn log(&self, level: LogLevel, kind: &str, model: &str, meta: serde_json::Value, content: serde_json::Value) {
+        if let Some(audit) = &self.audit {
+            audit.log(level, kind, model, meta, content);
+        }
+    }
+
+    pub fn provider(&self) -> &str {
+        match self.transport {
+            Transport::Mock => "mock",
+            _ => "openai",
+        }
+    }
+
+    pub async fn send_prompt(&self, system_prompt: &str, user_prompt: &str) -> Result<String, ClientError> {
+        Ok(self.complete(system_prompt, user_prompt, &GenerationParams::default()).await?.text)
+    }
+
+    pub async fn complete(&self, system_prompt: &str, user_prompt: &str, params: &GenerationParams) -> Result<Completion, ClientError> {
+        let model = params.model.clone().unwrap_or_else(|| self.model.clone());
+        let started = Instant::now();
+        self.log(
+            LogLevel::Info,
+            "llm_request",
+            &model,
+            json!({ "provider": self.provider(), "temperature": params.temperature, "system_chars": system_prompt.len(), "user_chars": user_prompt.len() }),
+            json!({ "system_prompt": system_prompt, "user_prompt": user_prompt }),
+        );
+        let result = self.send_request(&model, system_prompt, user_prompt, params).await;
+        let duration_ms = started.elapsed().as_millis() as u64;
+        match &result {
+            Ok(completion) => self.log(
+                LogLevel::Info,
+                "llm_response",
+                &completion.model,
+                json!({ "duration_ms": duration_ms, "usage": completion.usage }),
+                json!({ "text": completion.text }),
+            ),
+            Err(e) => self.log(
+                LogLevel::Error,
+                "llm_error",
+                &model,
+                json!({ "duration_ms": duration_ms, "error": e.to_string() }),
+                serde_json::Value::Null,
+            ),
+        }
+        result
+    }
+
+    async fn send_request(&self, model: &str, system_prompt: &str, user_prompt: &str, params: &GenerationParams) -> Result<Completion, ClientError> {
+        let model = model.to_string();
+        let request_body = OpenAIPrompt {
+            model: model.clone(),
+            messages: vec![
+                Message {
+                    role: "system".to_string(),
+                    content: system_prompt.to_string(),
+                },
+                Message {
+                    role: "user".to_string(),
+                    content: user_prompt.to_string(),
+                }
+            ],
+            temperature: params.temperature,
+        };
+
+        let body = serde_json::to_value(&request_body).map_err(|e| ClientError::ApiError(e.to_string()))?;
+        let (status, response_text) = match &self.transport {
+            Transport::Live => self.post(&body).await?,
+            Transport::Record(dir) => {
+                let (status, text) = self.post(&body).await?;
+                llm_fixtures::save(dir, &body, status, &text)
+                    .map_err(|e| ClientError::ConfigError(format!("Failed to record fixture: {}", e)))?;
+                (status, text)
+            }
+            Transport::Replay(dir) => {
+                let fixture = llm_fixtures::load(dir, &body).map_err(|e| ClientError::ConfigError(e.to_string()))?;
+                (fixture.status, fixture.response)
+            }
+            Transport::Mock => (200, llm_fixtures::mock_response(&body)),
+        };
+
+        if !(200..300).contains(&status) {
+            let status = reqwest::StatusCode::from_u16(status).map_or_else(|_| status.to_string(), |s| s.to_string());
+            let error_text = response_text;
+            
+            // Try to parse as structured OpenAI error
+            if let Ok(error_response) = serde_json::from_str::<OpenAIError>(&error_text) {
+                return Err(ClientError::OpenAIError(format!("OpenAI API Error ({}): {} ({})", 
+                                  status,
+                                  error_response.error.message, 
+                                  error_response.error.code)));
+            } else {
+                // Fallback for non-JSON errors (server errors, etc.)
+                return Err(ClientError::ApiError(format!("HTTP Error {}: {}", status, error_text)));
+            }
+        }
+
+        let success_response: OpenAIResponse = serde_json::from_str(&response_text)
+            .map_err(|e| ClientError::ApiError(format!("Unexpected response body: {}", e)))?;
+        let choice = success_response.choices.first().ok_or_else(|| ClientError::ApiError("Response had no choices".to_string()))?;
+        Ok(Completion {
+            text: choice.message.content.clone(),
+            model: success_response.model.unwrap_or(model),
+            usage: success_response.usage,
+        })
+    }
+
+    async fn post(&self, body: &serde_json::Value) -> Result<(u16, String), ClientError> {
+        let response = self.client
+            .post(format!("{}/chat/completions", self.base_url))
+            .bearer_auth(&self.api_key)
+            .header("Content-Type", "application/json")
+            .json(body)
+            .send()
+            .await?;
+        let status = response.status().as_u16();
+        Ok((status, response.text().await?))
+    }
+}
+
+// Pull the JSON object out of a model reply, tolerating code fences or stray prose around it
+pub fn extract_json_object(response: &str) -> Option<&str> {
+    let start = response.find('{')?;
+    let end = response.rfind('}')?;
+    if start < end {
+        Some(&response[start..=end])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{client, completion_body, temp_dir, MockServer};
+
+    #[tokio::test]
+    async fn live_request_returns_text_model_and_usage() {
+        let server = MockServer::start(vec![(200, completion_body("Mira runs."))]).await;
+        let client = client(&server.base_url, Transport::Live);
+
+        let params = GenerationParams { model: None, temperature: Some(0.2) };
+        let completion = client.complete("system text", "user text", &params).await.unwrap();
+        assert_eq!(completion.text, "Mira runs.");
+        assert_eq!(completion.model, "gpt-4.1-2025-04-14");
+        assert_eq!(completion.usage.unwrap().total_tokens, 46);
+
+        let received = server.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].path, "/v1/chat/completions");
+        assert_eq!(received[0].authorization.as_deref(), Some("Bearer sk-test-key"));
+        assert_eq!(received[0].body["model"], "gpt-4.1");
+        assert_eq!(received[0].body["messages"][0]["content"], "system text");
+        assert_eq!(received[0].body["messages"][1]["content"], "user text");
+        assert!((received[0].body["temperature"].as_f64().unwrap() - 0.2).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn temperature_is_omitted_unless_set() {
+        let server = MockServer::start(vec![(200, completion_body("ok"))]).await;
+        client(&server.base_url, Transport::Live).send_prompt("s", "u").await.unwrap();
+        assert!(server.received()[0].body.get("temperature").is_none());
+    }
+
+    #[tokio::test]
+    async fn api_errors_are_reported() {
+        let structured = r#"{"error":{"message":"Incorrect API key","type":"invalid_request_error","code":"invalid_api_key"}}"#;
+        let server = MockServer::start(vec![(401, structured.to_string()), (502, "bad gateway".to_string())]).await;
+        let client = client(&server.base_url, Transport::Live);
+
+        match client.send_prompt("s", "u").await {
+            Err(ClientError::OpenAIError(message)) => {
+                assert!(message.contains("401"));
+                assert!(message.contains("Incorrect API key (invalid_api_key)"));
+            }
+            other => panic!("expected an OpenAI error, got {:?}", other.map(|_| ())),
+        }
+        match client.send_prompt("s", "u").await {
+            Err(ClientError::ApiError(message)) => assert!(message.contains("502") && message.contains("bad gateway")),
+            other => panic!("expected an HTTP error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn malformed_body_is_an_error_not_a_panic() {
+        let server = MockServer::start(vec![(200, r#"{"choices":[]}"#.to_string())]).await;
+        let result = client(&server.base_url, Transport::Live).send_prompt("s", "u").await;
+        assert!(matches!(result, Err(ClientError::ApiError(_))));
+    }
+
+    #[tokio::test]
+    async fn recorded_exchanges_replay_without_a_server() {
+        let dir = temp_dir("fixtures");
+        let server = MockServer::start(vec![(200, completion_body("First take."))]).await;
+        let recorder = client(&server.base_url, Transport::Record(dir.clone()));
+        let recorded = recorder.send_prompt("system", "user").await.unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        // Nothing listens here; replay must not touch the network
+        let replayer = client("http://127.0.0.1:9/v1", Transport::Replay(dir.clone()));
+        for _ in 0..2 {
+            assert_eq!(replayer.send_prompt("system", "user").await.unwrap(), recorded);
+        }
+        assert_eq!(server.received().len(), 1);
+
+        let unrecorded = replayer.send_prompt("system", "a different request").await;
+        assert!(matches!(unrecorded, Err(ClientError::ConfigError(message)) if message.contains("No recorded response")));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn recorded_errors_replay_as_errors() {
+        let dir = temp_dir("fixtures");
+        let server = MockServer::start(vec![(500, "upstream failure".to_string())]).await;
+        assert!(client(&server.base_url, Transport::Record(dir.clone())).send_prompt("s", "u").await.is_err());
+
+        let replayed = client("http://127.0.0.1:9/v1", Transport::Replay(dir.clone())).send_prompt("s", "u").await;
+        assert!(matches!(replayed, Err(ClientError::ApiError(message)) if message.contains("upstream failure")));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn mock_transport_answers_in_the_shape_each_prompt_asks_for() {
+        let client = client("http://127.0.0.1:9/v1", Transport::Mock);
+        assert_eq!(client.provider(), "mock");
+
+        let timeline = client.send_prompt("planner", "### USER REQUEST\nGo\n\nEnclose both in pipes: |TheSummary|TheAtmosphere|").await.unwrap();
+        assert!(timeline.trim_end().ends_with('|'));
+
+        let judged = client
+            .send_prompt(crate::prompts::PLAN_JUDGE_SYSTEM, crate::prompts::PLAN_JUDGE_INSTRUCTIONS)
+            .await
+            .unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(extract_json_object(&judged).unwrap()).unwrap();
+        assert!(parsed["verdicts"].is_array());
+
+        let completion = client.complete("s", "u", &GenerationParams::default()).await.unwrap();
+        assert_eq!(completion.model, "gpt-4.1");
+        assert!(completion.usage.unwrap().total_tokens > 0);
+    }
+
+    #[test]
+    fn json_object_is_extracted_from_fenced_replies() {
+        assert_eq!(extract_json_object("```json\n{\"a\":1}\n```"), Some("{\"a\":1}"));
+        assert_eq!(extract_json_object("no json here"), None);
+    }
+}
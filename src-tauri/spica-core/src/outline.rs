@@ -0,0 +1,396 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{DraftTab, ProjectData, Scene};
+
+// The book is the project itself; parts hold chapters, chapters hold scenes, each in explicit order
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Outline {
+    #[serde(default)]
+    pub part_ids: Vec<String>,
+    #[serde(default)]
+    pub parts: HashMap<String, Part>,
+    #[serde(default)]
+    pub chapters: HashMap<String, Chapter>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Part {
+    pub id: String,
+    pub title: String,
+    pub chapter_ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Chapter {
+    pub id: String,
+    pub title: String,
+    pub scene_ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    Part,
+    Chapter,
+    Scene,
+}
+
+// A scene's place in the book
+#[derive(Clone, Debug)]
+pub struct ScenePosition<'a> {
+    pub part: &'a Part,
+    pub chapter: &'a Chapter,
+}
+
+fn new_part(title: &str) -> Part {
+    Part { id: uuid::Uuid::new_v4().to_string(), title: title.to_string(), chapter_ids: Vec::new() }
+}
+
+fn new_chapter(title: &str) -> Chapter {
+    Chapter { id: uuid::Uuid::new_v4().to_string(), title: title.to_string(), scene_ids: Vec::new() }
+}
+
+fn insert_at(ids: &mut Vec<String>, id: String, index: Option<usize>) {
+    let index = index.unwrap_or(ids.len()).min(ids.len());
+    ids.insert(index, id);
+}
+
+// Make the outline agree with the project: drop dangling ids and file unplaced scenes.
+// Flat projects from before the outline existed end up in a single default chapter.
+pub fn normalize(project: &mut ProjectData) {
+    let ProjectData { outline, scenes, .. } = project;
+
+    outline.part_ids.retain(|id| outline.parts.contains_key(id));
+    let listed: HashSet<String> = outline.part_ids.iter().cloned().collect();
+    let mut stray_parts: Vec<String> = outline.parts.keys().filter(|id| !listed.contains(*id)).cloned().collect();
+    stray_parts.sort();
+    outline.part_ids.extend(stray_parts);
+
+    let mut placed_chapters = HashSet::new();
+    let mut chapter_order = Vec::new();
+    for part_id in &outline.part_ids {
+        if let Some(part) = outline.parts.get_mut(part_id) {
+            part.chapter_ids.retain(|id| outline.chapters.contains_key(id) && placed_chapters.insert(id.clone()));
+            chapter_order.extend(part.chapter_ids.iter().cloned());
+        }
+    }
+    let mut unplaced_chapters: Vec<String> =
+        outline.chapters.keys().filter(|id| !placed_chapters.contains(*id)).cloned().collect();
+    unplaced_chapters.sort();
+
+    // Chapters in reading order, so a scene listed twice stays where it is read first
+    let mut placed_scenes = HashSet::new();
+    for chapter_id in chapter_order.iter().chain(&unplaced_chapters) {
+        if let Some(chapter) = outline.chapters.get_mut(chapter_id) {
+            chapter.scene_ids.retain(|id| scenes.contains_key(id) && placed_scenes.insert(id.clone()));
+        }
+    }
+
+    let mut unplaced_scenes: Vec<&Scene> = scenes.values().filter(|s| !placed_scenes.contains(&s.id)).collect();
+    unplaced_scenes.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+
+    if unplaced_chapters.is_empty() && unplaced_scenes.is_empty() {
+        return;
+    }
+
+    if outline.part_ids.is_empty() {
+        let part = new_part("Part 1");
+        outline.part_ids.push(part.id.clone());
+        outline.parts.insert(part.id.clone(), part);
+    }
+    let last_part_id = outline.part_ids.last().cloned().unwrap_or_default();
+    if let Some(part) = outline.parts.get_mut(&last_part_id) {
+        part.chapter_ids.extend(unplaced_chapters);
+    }
+
+    if unplaced_scenes.is_empty() {
+        return;
+    }
+    let last_chapter_id = match outline.parts.get(&last_part_id).and_then(|p| p.chapter_ids.last()) {
+        Some(id) => id.clone(),
+        None => {
+            let chapter = new_chapter("Chapter 1");
+            let id = chapter.id.clone();
+            outline.chapters.insert(id.clone(), chapter);
+            if let Some(part) = outline.parts.get_mut(&last_part_id) {
+                part.chapter_ids.push(id.clone());
+            }
+            id
+        }
+    };
+    if let Some(chapter) = outline.chapters.get_mut(&last_chapter_id) {
+        chapter.scene_ids.extend(unplaced_scenes.iter().map(|s| s.id.clone()));
+    }
+}
+
+// Scenes in reading order; scenes missing from the outline follow, oldest first
+pub fn ordered_scenes(project: &ProjectData) -> Vec<&Scene> {
+    let mut ordered: Vec<&Scene> = Vec::new();
+    let mut seen = HashSet::new();
+    for part_id in &project.outline.part_ids {
+        let chapter_ids = project.outline.parts.get(part_id).map(|p| p.chapter_ids.as_slice()).unwrap_or_default();
+        for chapter_id in chapter_ids {
+            let scene_ids = project.outline.chapters.get(chapter_id).map(|c| c.scene_ids.as_slice()).unwrap_or_default();
+            for scene_id in scene_ids {
+                if let Some(scene) = project.scenes.get(scene_id) {
+                    if seen.insert(scene_id) {
+                        ordered.push(scene);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut rest: Vec<&Scene> = project.scenes.values().filter(|s| !seen.contains(&s.id)).collect();
+    rest.sort_by(|a, b| a.created_at.cmp(&b.created_at).then_with(|| a.id.cmp(&b.id)));
+    ordered.extend(rest);
+    ordered
+}
+
+pub fn position<'a>(project: &'a ProjectData, scene_id: &str) -> Option<ScenePosition<'a>> {
+    let outline = &project.outline;
+    let chapter = outline.chapters.values().find(|c| c.scene_ids.iter().any(|id| id == scene_id))?;
+    let part = outline.parts.values().find(|p| p.chapter_ids.contains(&chapter.id))?;
+    Some(ScenePosition { part, chapter })
+}
+
+fn kind_of(project: &ProjectData, id: &str) -> Option<NodeKind> {
+    if project.outline.parts.contains_key(id) {
+        Some(NodeKind::Part)
+    } else if project.outline.chapters.contains_key(id) {
+        Some(NodeKind::Chapter)
+    } else if project.scenes.contains_key(id) {
+        Some(NodeKind::Scene)
+    } else {
+        None
+    }
+}
+
+// Children of `parent_id`, or the top-level parts when it is None
+fn children_mut<'a>(project: &'a mut ProjectData, parent_id: Option<&str>) -> Result<&'a mut Vec<String>, String> {
+    let outline = &mut project.outline;
+    match parent_id {
+        None => Ok(&mut outline.part_ids),
+        Some(id) => {
+            if let Some(part) = outline.parts.get_mut(id) {
+                Ok(&mut part.chapter_ids)
+            } else if let Some(chapter) = outline.chapters.get_mut(id) {
+                Ok(&mut chapter.scene_ids)
+            } else {
+                Err(format!("Outline node not found: {}", id))
+            }
+        }
+    }
+}
+
+// Create a part (at the top level) or a chapter (inside `parent_id`); returns the new node's ID
+pub fn create_node(
+    project: &mut ProjectData,
+    kind: NodeKind,
+    title: &str,
+    parent_id: Option<&str>,
+    index: Option<usize>,
+) -> Result<String, String> {
+    match kind {
+        NodeKind::Part => {
+            if parent_id.is_some() {
+                return Err("Parts sit at the top level and take no parent".to_string());
+            }
+            let part = new_part(title);
+            let id = part.id.clone();
+            project.outline.parts.insert(id.clone(), part);
+            insert_at(&mut project.outline.part_ids, id.clone(), index);
+            Ok(id)
+        }
+        NodeKind::Chapter => {
+            let parent_id = parent_id.ok_or("A chapter needs a parent part")?;
+            if kind_of(project, parent_id) != Some(NodeKind::Part) {
+                return Err(format!("Chapter parent must be a part: {}", parent_id));
+            }
+            let chapter = new_chapter(title);
+            let id = chapter.id.clone();
+            project.outline.chapters.insert(id.clone(), chapter);
+            insert_at(children_mut(project, Some(parent_id))?, id.clone(), index);
+            Ok(id)
+        }
+        NodeKind::Scene => Err("Scenes are created by the editor and placed with move_outline_node".to_string()),
+    }
+}
+
+// Move a chapter to another part, or a scene to another chapter, at `index` (end if None)
+pub fn move_node(project: &mut ProjectData, node_id: &str, new_parent_id: &str, index: Option<usize>) -> Result<(), String> {
+    let kind = kind_of(project, node_id).ok_or_else(|| format!("Outline node not found: {}", node_id))?;
+    let (expected_parent, rule) = match kind {
+        NodeKind::Part => return Err("Parts have no parent; use reorder_outline_children to reorder them".to_string()),
+        NodeKind::Chapter => (NodeKind::Part, "A chapter must move into a part"),
+        NodeKind::Scene => (NodeKind::Chapter, "A scene must move into a chapter"),
+    };
+    if kind_of(project, new_parent_id) != Some(expected_parent) {
+        return Err(format!("{}: {}", rule, new_parent_id));
+    }
+
+    let outline = &mut project.outline;
+    match kind {
+        NodeKind::Chapter => outline.parts.values_mut().for_each(|p| p.chapter_ids.retain(|id| id != node_id)),
+        _ => outline.chapters.values_mut().for_each(|c| c.scene_ids.retain(|id| id != node_id)),
+    }
+    insert_at(children_mut(project, Some(new_parent_id))?, node_id.to_string(), index);
+    Ok(())
+}
+
+// Replace the order of a node's children; `ordered_ids` must be a permutation of the current children
+pub fn reorder_children(project: &mut ProjectData, parent_id: Option<&str>, ordered_ids: Vec<String>) -> Result<(), String> {
+    let children = children_mut(project, parent_id)?;
+    let current: HashSet<&String> = children.iter().collect();
+    let requested: HashSet<&String> = ordered_ids.iter().collect();
+    if current != requested || ordered_ids.len() != children.len() {
+        return Err("Reorder must list exactly the node's current children".to_string());
+    }
+    *children = ordered_ids;
+    Ok(())
+}
+
+fn tab_prose(tab: &DraftTab) -> String {
+    let mut out = String::new();
+    for event in tab.timeline.iter().filter(|e| e.checked) {
+        match &event.dialogue {
+            Some(dialogue) => out.push_str(&format!("{} \"{}\"\n\n", event.text, dialogue)),
+            None => out.push_str(&format!("{}\n\n", event.text)),
+        }
+    }
+    for description in &tab.descriptions {
+        out.push_str(&format!("{}\n\n", description.text));
+    }
+    out
+}
+
+// Markdown manuscript in outline order: # book, ## part, ### chapter, #### scene
+pub fn manuscript_markdown(project: &ProjectData) -> String {
+    let mut out = format!("# {}\n\n", project.metadata.title);
+    if let Some(author) = project.metadata.author.as_deref().filter(|a| !a.trim().is_empty()) {
+        out.push_str(&format!("*{}*\n\n", author));
+    }
+
+    let mut current_part = None;
+    let mut current_chapter = None;
+    for scene in ordered_scenes(project) {
+        if let Some(position) = position(project, &scene.id) {
+            if current_part != Some(&position.part.id) {
+                out.push_str(&format!("## {}\n\n", position.part.title));
+                current_part = Some(&position.part.id);
+            }
+            if current_chapter != Some(&position.chapter.id) {
+                out.push_str(&format!("### {}\n\n", position.chapter.title));
+                current_chapter = Some(&position.chapter.id);
+            }
+        }
+        out.push_str(&format!("#### {}\n\n", scene.name));
+
+        let mut tabs: Vec<&DraftTab> = scene.draft_tab_ids.iter().filter_map(|id| project.draft_tabs.get(id)).collect();
+        tabs.sort_by_key(|tab| tab.index);
+        for tab in tabs {
+            out.push_str(&tab_prose(tab));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::add_scene;
+
+    fn chapter(id: &str, scene_ids: &[&str]) -> Chapter {
+        Chapter { id: id.to_string(), title: id.to_uppercase(), scene_ids: scene_ids.iter().map(|s| s.to_string()).collect() }
+    }
+
+    // One part "p1" holding chapters "c1" (scene s1) and "c2" (scene s2)
+    fn project() -> ProjectData {
+        let mut project = ProjectData::new("Test", 0);
+        add_scene(&mut project, "s1", "Harbour", &[&["Mira finds the letter"]]);
+        add_scene(&mut project, "s2", "Ferry", &[&["The ferry leaves"]]);
+        let outline = &mut project.outline;
+        outline.part_ids.push("p1".to_string());
+        outline.parts.insert("p1".to_string(), Part { id: "p1".to_string(), title: "One".to_string(), chapter_ids: vec!["c1".to_string(), "c2".to_string()] });
+        outline.chapters.insert("c1".to_string(), chapter("c1", &["s1"]));
+        outline.chapters.insert("c2".to_string(), chapter("c2", &["s2"]));
+        project
+    }
+
+    #[test]
+    fn flat_projects_get_one_part_and_chapter() {
+        let mut project = ProjectData::new("Test", 0);
+        add_scene(&mut project, "s2", "Ferry", &[]);
+        add_scene(&mut project, "s1", "Harbour", &[]);
+        normalize(&mut project);
+        normalize(&mut project);
+
+        let outline = &project.outline;
+        assert_eq!((outline.part_ids.len(), outline.chapters.len()), (1, 1));
+        let part = &outline.parts[&outline.part_ids[0]];
+        assert_eq!(part.title, "Part 1");
+        let chapter = &outline.chapters[&part.chapter_ids[0]];
+        assert_eq!((chapter.title.as_str(), chapter.scene_ids.clone()), ("Chapter 1", vec!["s1".to_string(), "s2".to_string()]));
+    }
+
+    #[test]
+    fn a_scene_listed_twice_stays_in_the_chapter_read_first() {
+        let mut project = project();
+        let outline = &mut project.outline;
+        // "c2" sorts after "c1" but is read first; the stray chapter and dangling ids go too
+        outline.parts.get_mut("p1").unwrap().chapter_ids = vec!["c2".to_string(), "c1".to_string(), "gone".to_string()];
+        outline.chapters.get_mut("c1").unwrap().scene_ids = vec!["s2".to_string(), "s1".to_string()];
+        outline.chapters.get_mut("c2").unwrap().scene_ids = vec!["s2".to_string(), "gone".to_string()];
+        outline.chapters.insert("c0".to_string(), chapter("c0", &["s1"]));
+        normalize(&mut project);
+
+        let outline = &project.outline;
+        assert_eq!(outline.parts["p1"].chapter_ids, vec!["c2", "c1", "c0"]);
+        assert_eq!(outline.chapters["c2"].scene_ids, vec!["s2"]);
+        assert_eq!(outline.chapters["c1"].scene_ids, vec!["s1"]);
+        assert!(outline.chapters["c0"].scene_ids.is_empty());
+    }
+
+    #[test]
+    fn nodes_are_created_moved_and_reordered_within_the_rules() {
+        let mut project = project();
+        assert!(create_node(&mut project, NodeKind::Part, "Two", Some("p1"), None).unwrap_err().contains("top level"));
+        assert_eq!(create_node(&mut project, NodeKind::Chapter, "C", None, None).unwrap_err(), "A chapter needs a parent part");
+        assert_eq!(create_node(&mut project, NodeKind::Chapter, "C", Some("c1"), None).unwrap_err(), "Chapter parent must be a part: c1");
+        assert!(create_node(&mut project, NodeKind::Scene, "S", Some("c1"), None).is_err());
+        let c0 = create_node(&mut project, NodeKind::Chapter, "Zero", Some("p1"), Some(0)).unwrap();
+        assert_eq!(project.outline.parts["p1"].chapter_ids[0], c0);
+
+        assert!(move_node(&mut project, "p1", "p1", None).unwrap_err().contains("Parts have no parent"));
+        assert_eq!(move_node(&mut project, "s1", "p1", None).unwrap_err(), "A scene must move into a chapter: p1");
+        assert_eq!(move_node(&mut project, "nope", "c1", None).unwrap_err(), "Outline node not found: nope");
+        move_node(&mut project, "s2", "c1", Some(0)).unwrap();
+        assert_eq!(project.outline.chapters["c1"].scene_ids, vec!["s2", "s1"]);
+        assert!(project.outline.chapters["c2"].scene_ids.is_empty());
+
+        let order = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        assert!(reorder_children(&mut project, Some("c1"), order(&["s1"])).is_err());
+        assert!(reorder_children(&mut project, Some("c1"), order(&["s1", "s1"])).is_err());
+        assert_eq!(reorder_children(&mut project, Some("nope"), Vec::new()).unwrap_err(), "Outline node not found: nope");
+        reorder_children(&mut project, Some("c1"), order(&["s1", "s2"])).unwrap();
+        assert_eq!(project.outline.chapters["c1"].scene_ids, vec!["s1", "s2"]);
+    }
+
+    #[test]
+    fn manuscript_follows_the_outline() {
+        let mut project = project();
+        project.metadata.author = Some("A. Writer".to_string());
+        let tab = project.draft_tabs.get_mut("s1-t0").unwrap();
+        tab.timeline[0].dialogue = Some("Here it is.".to_string());
+        tab.timeline.push(crate::testing::event("Cut line"));
+        tab.timeline[1].checked = false;
+        project.outline.parts.get_mut("p1").unwrap().chapter_ids.reverse();
+
+        assert_eq!(
+            manuscript_markdown(&project),
+            "# Test\n\n*A. Writer*\n\n## One\n\n### C2\n\n#### Ferry\n\nThe ferry leaves\n\n### C1\n\n#### Harbour\n\nMira finds the letter \"Here it is.\"\n\n"
+        );
+    }
+}
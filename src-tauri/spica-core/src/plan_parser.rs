@@ -0,0 +1,252 @@
+use serde::{Deserialize, Serialize};
+
+use crate::retrieval::similarity;
+use crate::{PlanStep, Star};
+
+// Minimum token overlap for an edited line to keep the id of the step it replaced
+const REWORD_SIMILARITY: f64 = 0.5;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RewordedStep {
+    pub id: String,
+    pub old_text: String,
+    pub new_text: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PlanDiff {
+    pub added: Vec<String>,   // PlanStep IDs
+    pub removed: Vec<String>, // PlanStep IDs
+    pub reworded: Vec<RewordedStep>,
+    pub unchanged: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ParsedPlan {
+    pub steps: Vec<PlanStep>,
+    pub diff: PlanDiff,
+}
+
+struct RawStep {
+    text: String,
+    indent: usize,
+    parent: Option<usize>, // index into the raw step list
+}
+
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+// Strip a list marker ("1.", "2)", "a.", "iv)", "-", "*", "•", "+") and return the remaining text
+fn strip_marker(line: &str) -> Option<&str> {
+    for bullet in ["- ", "* ", "• ", "+ "] {
+        if let Some(rest) = line.strip_prefix(bullet) {
+            return Some(rest.trim());
+        }
+    }
+
+    let marker_end = line.find(['.', ')'])?;
+    let marker = &line[..marker_end];
+    let is_number = !marker.is_empty() && marker.chars().all(|c| c.is_ascii_digit());
+    let is_letter = marker.len() == 1 && marker.chars().all(|c| c.is_ascii_alphabetic());
+    let is_roman = !marker.is_empty() && marker.len() <= 4 && marker.chars().all(|c| "ivxIVX".contains(c));
+    let rest = &line[marker_end + 1..];
+    if (is_number || is_letter || is_roman) && (rest.is_empty() || rest.starts_with(char::is_whitespace)) {
+        return Some(rest.trim());
+    }
+    None
+}
+
+fn parse_lines(raw_text: &str) -> Vec<RawStep> {
+    let mut steps: Vec<RawStep> = Vec::new();
+
+    for line in raw_text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let indent = indent_width(line);
+
+        match strip_marker(trimmed) {
+            Some(text) => {
+                if text.is_empty() {
+                    continue;
+                }
+                // Parent is the closest earlier step indented less than this one
+                let parent = steps.iter().rposition(|s| s.indent < indent);
+                steps.push(RawStep { text: text.to_string(), indent, parent });
+            }
+            None => match steps.last_mut() {
+                // Unmarked, indented lines continue the previous step
+                Some(last) if indent > last.indent => {
+                    last.text.push(' ');
+                    last.text.push_str(trimmed);
+                }
+                _ => steps.push(RawStep { text: trimmed.to_string(), indent, parent: None }),
+            },
+        }
+    }
+
+    steps
+}
+
+fn mentions_title(text: &str, title: &str) -> bool {
+    let title = title.trim().to_lowercase();
+    if title.is_empty() {
+        return false;
+    }
+    let text = text.to_lowercase();
+    text.match_indices(&title).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + title.len()..].chars().next();
+        !before.map_or(false, char::is_alphanumeric) && !after.map_or(false, char::is_alphanumeric)
+    })
+}
+
+// Parse plan text into steps, reusing ids of unchanged or lightly reworded steps from `previous`
+pub fn parse_plan(raw_text: &str, previous: &[PlanStep], stars: &[&Star]) -> ParsedPlan {
+    let raw_steps = parse_lines(raw_text);
+    let mut diff = PlanDiff::default();
+    let mut claimed = vec![false; previous.len()];
+    let mut matches: Vec<Option<usize>> = vec![None; raw_steps.len()];
+
+    // Pass 1: identical text keeps its id
+    for (i, raw) in raw_steps.iter().enumerate() {
+        if let Some(j) = (0..previous.len()).find(|&j| !claimed[j] && previous[j].text.trim() == raw.text) {
+            claimed[j] = true;
+            matches[i] = Some(j);
+            diff.unchanged += 1;
+        }
+    }
+
+    // Pass 2: reworded steps match the most similar leftover, preferring the same position
+    for (i, raw) in raw_steps.iter().enumerate() {
+        if matches[i].is_some() {
+            continue;
+        }
+        let best = (0..previous.len())
+            .filter(|&j| !claimed[j])
+            .map(|j| (j, similarity(&previous[j].text, &raw.text)))
+            .filter(|(_, score)| *score >= REWORD_SIMILARITY)
+            .max_by(|(ja, a), (jb, b)| {
+                a.partial_cmp(b)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| (jb.abs_diff(i)).cmp(&ja.abs_diff(i)))
+            });
+        if let Some((j, _)) = best {
+            claimed[j] = true;
+            matches[i] = Some(j);
+            diff.reworded.push(RewordedStep {
+                id: previous[j].id.clone(),
+                old_text: previous[j].text.clone(),
+                new_text: raw.text.clone(),
+            });
+        }
+    }
+
+    let ids: Vec<String> = matches
+        .iter()
+        .map(|m| match m {
+            Some(j) => previous[*j].id.clone(),
+            None => uuid::Uuid::new_v4().to_string(),
+        })
+        .collect();
+
+    let steps = raw_steps
+        .iter()
+        .enumerate()
+        .map(|(i, raw)| {
+            let prior = matches[i].map(|j| &previous[j]);
+            // A reworded step may no longer be about the stars it was linked to, so only the
+            // ones it still mentions stay linked
+            let mut linked_stars = prior.filter(|p| p.text.trim() == raw.text).map(|p| p.linked_stars.clone()).unwrap_or_default();
+            for star in stars {
+                if mentions_title(&raw.text, &star.title) && !linked_stars.contains(&star.id) {
+                    linked_stars.push(star.id.clone());
+                }
+            }
+            if prior.is_none() {
+                diff.added.push(ids[i].clone());
+            }
+            PlanStep {
+                id: ids[i].clone(),
+                text: raw.text.clone(),
+                fulfilled_by: prior.map(|p| p.fulfilled_by.clone()).unwrap_or_default(),
+                linked_stars,
+                parent_id: raw.parent.map(|p| ids[p].clone()),
+            }
+        })
+        .collect();
+
+    diff.removed = previous
+        .iter()
+        .zip(&claimed)
+        .filter(|(_, claimed)| !**claimed)
+        .map(|(step, _)| step.id.clone())
+        .collect();
+
+    ParsedPlan { steps, diff }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAN: &str = "1. Mira finds the letter\n   a) She hides it\n      from Hale\n   - Hale notices\n2) The ferry leaves\nLoose note\niv. Storm\n3.14 is not a marker\n";
+
+    fn texts(plan: &ParsedPlan) -> Vec<&str> {
+        plan.steps.iter().map(|step| step.text.as_str()).collect()
+    }
+
+    #[test]
+    fn markers_nesting_and_continuation_lines() {
+        let mut ferry = Star::new("Ferry".to_string(), String::new(), 0);
+        ferry.id = "star1".to_string();
+        let mut ferryman = Star::new("Ferryman".to_string(), String::new(), 0);
+        ferryman.id = "star2".to_string();
+        let plan = parse_plan(PLAN, &[], &[&ferry, &ferryman]);
+
+        assert_eq!(
+            texts(&plan),
+            vec!["Mira finds the letter", "She hides it from Hale", "Hale notices", "The ferry leaves", "Loose note", "Storm", "3.14 is not a marker"]
+        );
+        let parents: Vec<Option<&str>> = plan.steps.iter().map(|step| step.parent_id.as_deref()).collect();
+        let first = Some(plan.steps[0].id.as_str());
+        assert_eq!(parents, vec![None, first, first, None, None, None, None]);
+        assert_eq!(plan.steps[3].linked_stars, vec!["star1".to_string()]);
+        assert_eq!(plan.diff.added.len(), 7);
+    }
+
+    #[test]
+    fn reparsing_keeps_ids_of_unchanged_and_reworded_steps() {
+        let mut previous = parse_plan(PLAN, &[], &[]).steps;
+        previous[0].fulfilled_by.push("tab1".to_string());
+        let plan = parse_plan("1. Mira finds the letter\n2. The ferry leaves at dawn\n3. Hale burns the ship", &previous, &[]);
+
+        assert_eq!(plan.steps[0].id, previous[0].id);
+        assert_eq!(plan.steps[0].fulfilled_by, vec!["tab1".to_string()]);
+        assert_eq!(plan.steps[1].id, previous[3].id);
+        assert_eq!(plan.diff.unchanged, 1);
+        assert_eq!(plan.diff.reworded.len(), 1);
+        assert_eq!(plan.diff.reworded[0].old_text, "The ferry leaves");
+        assert_eq!(plan.diff.added, vec![plan.steps[2].id.clone()]);
+        assert_eq!(plan.diff.removed.len(), 5);
+        assert!(!plan.diff.removed.contains(&previous[0].id));
+    }
+
+    #[test]
+    fn reworded_steps_keep_only_the_stars_they_still_mention() {
+        let mut ferry = Star::new("Ferry".to_string(), String::new(), 0);
+        ferry.id = "star1".to_string();
+        let mut previous = parse_plan("1. Mira finds the letter\n2. The ferry leaves at dawn", &[], &[&ferry]).steps;
+        previous[0].linked_stars.push("star2".to_string());
+
+        let plan = parse_plan("1. Mira finds the letter\n2. The boat leaves at dawn", &previous, &[&ferry]);
+        assert_eq!(plan.steps[0].linked_stars, vec!["star2".to_string()]);
+        assert_eq!(plan.steps[1].id, previous[1].id);
+        assert!(plan.steps[1].linked_stars.is_empty());
+    }
+}
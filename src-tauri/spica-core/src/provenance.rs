@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+
+use crate::context_engine::{PromptType, SectionBudgets};
+use crate::openai_client::{Completion, GenerationParams, TokenUsage};
+use crate::summaries::content_hash;
+use crate::ProjectData;
+
+// Everything the backend needs to assemble and send a generation; stored so it can be replayed
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GenerationRequest {
+    pub scene_id: String,
+    pub prompt_type: PromptType,
+    pub user_input: String,
+    #[serde(default)]
+    pub target_event: Option<String>,
+    #[serde(default)]
+    pub budgets: SectionBudgets,
+    #[serde(default)]
+    pub params: GenerationParams,
+    #[serde(default)]
+    pub template_name: Option<String>, // None uses the project's selected template for the prompt type
+    #[serde(default)]
+    pub template_version: Option<u32>, // None uses the project's active version
+}
+
+// How a draft or description was produced
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Provenance {
+    pub provider: String,
+    pub model: String,
+    pub temperature: Option<f32>,
+    pub template_name: Option<String>, // None when the prompt was assembled by the frontend
+    pub template_version: Option<u32>,
+    pub prompt_hash: String, // of the full system and user prompt
+    #[serde(default)]
+    pub included_star_ids: Vec<String>,
+    #[serde(default)]
+    pub included_character_ids: Vec<String>,
+    pub request: Option<GenerationRequest>, // what regenerate replays
+    pub created_at: u64,
+    pub usage: Option<TokenUsage>,
+}
+
+pub fn prompt_hash(system_prompt: &str, user_prompt: &str) -> String {
+    content_hash(&format!("{}\u{0}{}", system_prompt, user_prompt))
+}
+
+impl Provenance {
+    pub fn new(provider: &str, completion: &Completion, params: &GenerationParams, system_prompt: &str, user_prompt: &str, now: u64) -> Self {
+        Self {
+            provider: provider.to_string(),
+            model: completion.model.clone(),
+            temperature: params.temperature,
+            template_name: None,
+            template_version: None,
+            prompt_hash: prompt_hash(system_prompt, user_prompt),
+            included_star_ids: Vec::new(),
+            included_character_ids: Vec::new(),
+            request: None,
+            created_at: now,
+            usage: completion.usage.clone(),
+        }
+    }
+
+    // The request that reproduces this generation: same template, version, model and temperature
+    pub fn replay_request(&self) -> Option<GenerationRequest> {
+        let mut request = self.request.clone()?;
+        request.template_name = self.template_name.clone().or(request.template_name);
+        request.template_version = self.template_version;
+        request.params.model = Some(self.model.clone());
+        request.params.temperature = self.temperature;
+        Some(request)
+    }
+}
+
+// A draft tab's provenance, or one of its descriptions' when `description_id` is given
+pub fn find<'a>(project: &'a ProjectData, tab_id: &str, description_id: Option<&str>) -> Result<&'a Provenance, String> {
+    let tab = project.draft_tabs.get(tab_id).ok_or_else(|| format!("Draft tab not found: {}", tab_id))?;
+    let provenance = match description_id {
+        Some(description_id) => tab
+            .descriptions
+            .iter()
+            .find(|d| d.id == description_id)
+            .ok_or_else(|| format!("Description not found: {}", description_id))?
+            .provenance
+            .as_ref(),
+        None => tab.provenance.as_ref(),
+    };
+    provenance.ok_or_else(|| "No generation record for this draft".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::add_scene;
+
+    fn provenance() -> Provenance {
+        let completion = Completion { text: String::new(), model: "gpt-4.1-2025-04-14".to_string(), usage: None };
+        let params = GenerationParams { model: None, temperature: Some(0.7) };
+        let mut provenance = Provenance::new("openai", &completion, &params, "system", "user", 5);
+        provenance.template_name = Some("noir".to_string());
+        provenance.template_version = Some(3);
+        provenance
+    }
+
+    #[test]
+    fn replay_pins_the_recorded_model_temperature_and_template() {
+        let mut provenance = provenance();
+        assert!(provenance.replay_request().is_none());
+        provenance.request = Some(GenerationRequest {
+            scene_id: "s1".to_string(),
+            prompt_type: PromptType::SceneTimeline,
+            user_input: "Mira reaches the harbour".to_string(),
+            target_event: None,
+            budgets: SectionBudgets::default(),
+            params: GenerationParams { model: Some("gpt-4.1".to_string()), temperature: None },
+            template_name: None,
+            template_version: None,
+        });
+        let request = provenance.replay_request().unwrap();
+        assert_eq!((request.params.model.as_deref(), request.params.temperature, request.template_version), (Some("gpt-4.1-2025-04-14"), Some(0.7), Some(3)));
+        assert_eq!(request.template_name.as_deref(), Some("noir"));
+        assert_eq!(request.user_input, "Mira reaches the harbour");
+    }
+
+    #[test]
+    fn prompt_hash_keeps_system_and_user_apart() {
+        assert_eq!(prompt_hash("system", "user"), provenance().prompt_hash);
+        assert_ne!(prompt_hash("ab", "c"), prompt_hash("a", "bc"));
+    }
+
+    #[test]
+    fn find_reports_what_is_missing() {
+        let mut project = ProjectData::new("Test", 0);
+        add_scene(&mut project, "s1", "Harbour", &[&["Mira finds the letter"]]);
+        assert_eq!(find(&project, "nope", None).unwrap_err(), "Draft tab not found: nope");
+        assert_eq!(find(&project, "s1-t0", None).unwrap_err(), "No generation record for this draft");
+        assert_eq!(find(&project, "s1-t0", Some("d1")).unwrap_err(), "Description not found: d1");
+
+        project.draft_tabs.get_mut("s1-t0").unwrap().provenance = Some(provenance());
+        assert_eq!(find(&project, "s1-t0", None).unwrap().created_at, 5);
+    }
+
+    #[test]
+    fn records_saved_before_star_and_character_lists_still_load() {
+        let json = r#"{"provider":"openai","model":"gpt-4","temperature":null,"template_name":null,"template_version":null,
+            "prompt_hash":"abc","request":null,"created_at":1,"usage":null}"#;
+        let provenance: Provenance = serde_json::from_str(json).unwrap();
+        assert!(provenance.included_star_ids.is_empty() && provenance.included_character_ids.is_empty());
+    }
+}
@@ -0,0 +1,391 @@
+use std::collections::{BTreeMap, HashSet};
+
+use regex::{Captures, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::ProjectData;
+
+// Characters of context shown on each side of a match in the preview
+const PREVIEW_CONTEXT: usize = 40;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    Literal,
+    WholeWord,
+    Regex, // replacement may use $1 / ${name} capture references
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ReplaceOptions {
+    pub mode: MatchMode,
+    pub case_sensitive: bool,
+    pub preserve_case: bool, // "Mira" -> "Lena", "MIRA" -> "LENA", "mira" -> "lena"
+    pub cascade_character_rename: bool, // rewrite a renamed character's old name in the stars linked to it
+}
+
+impl Default for ReplaceOptions {
+    fn default() -> Self {
+        Self { mode: MatchMode::Literal, case_sensitive: false, preserve_case: true, cascade_character_rename: true }
+    }
+}
+
+// Where a piece of text lives, e.g. draft_tab / <id> / timeline[3].dialogue
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TextLocation {
+    pub kind: String, // "project" | "scene" | "plan_step" | "draft_tab" | "star" | "character" | "outline"
+    pub id: String,
+    pub field: String,
+    pub event_index: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ReplacementPreview {
+    pub match_id: String, // stable for an unchanged project; pass back to apply a subset
+    pub location: TextLocation,
+    pub start: usize, // character offsets of the match in the original text
+    pub end: usize,
+    pub original: String,
+    pub replacement: String,
+    pub context_before: String,
+    pub context_after: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CharacterRename {
+    pub character_id: String,
+    pub old_name: String,
+    pub new_name: String,
+    pub star_ids: Vec<String>, // linked stars whose title or body was rewritten
+}
+
+pub struct ReplaceResult {
+    pub project: ProjectData,
+    pub previews: Vec<ReplacementPreview>,
+    pub renames: Vec<CharacterRename>,
+}
+
+fn location(kind: &str, id: &str, field: String, event_index: Option<usize>) -> TextLocation {
+    TextLocation { kind: kind.to_string(), id: id.to_string(), field, event_index }
+}
+
+fn sorted<V>(map: &mut std::collections::HashMap<String, V>) -> BTreeMap<&String, &mut V> {
+    map.iter_mut().collect()
+}
+
+// Every user-visible text in the project, in a stable order
+fn text_fields(project: &mut ProjectData) -> Vec<(TextLocation, &mut String)> {
+    let mut fields = Vec::new();
+    let ProjectData { metadata, scenes, draft_tabs, stars, characters, plan_steps, outline, .. } = project;
+
+    fields.push((location("project", "metadata", "title".to_string(), None), &mut metadata.title));
+
+    for (id, scene) in sorted(scenes) {
+        fields.push((location("scene", id, "name".to_string(), None), &mut scene.name));
+        if let Some(setting) = scene.setting.as_mut() {
+            fields.push((location("scene", id, "setting".to_string(), None), setting));
+        }
+        if let Some(backstory) = scene.backstory.as_mut() {
+            fields.push((location("scene", id, "backstory".to_string(), None), backstory));
+        }
+        fields.push((location("scene", id, "plan.raw_text".to_string(), None), &mut scene.plan.raw_text));
+        for (i, step) in scene.plan.parsed_steps.iter_mut().enumerate() {
+            fields.push((location("scene", id, format!("plan.parsed_steps[{}].text", i), None), &mut step.text));
+        }
+    }
+
+    for (id, step) in sorted(plan_steps) {
+        fields.push((location("plan_step", id, "text".to_string(), None), &mut step.text));
+    }
+
+    for (id, tab) in sorted(draft_tabs) {
+        for (i, event) in tab.timeline.iter_mut().enumerate() {
+            fields.push((location("draft_tab", id, format!("timeline[{}].text", i), Some(i)), &mut event.text));
+            if let Some(dialogue) = event.dialogue.as_mut() {
+                fields.push((location("draft_tab", id, format!("timeline[{}].dialogue", i), Some(i)), dialogue));
+            }
+        }
+        for description in tab.descriptions.iter_mut() {
+            let field = format!("descriptions[{}].text", description.id);
+            fields.push((location("draft_tab", id, field, None), &mut description.text));
+        }
+        if let Some(summary) = tab.summary.as_mut() {
+            fields.push((location("draft_tab", id, "summary".to_string(), None), summary));
+        }
+        if let Some(atmosphere) = tab.atmosphere.as_mut() {
+            fields.push((location("draft_tab", id, "atmosphere".to_string(), None), atmosphere));
+        }
+        for (i, step) in tab.suggested_plan_steps.iter_mut().enumerate() {
+            fields.push((location("draft_tab", id, format!("suggested_plan_steps[{}]", i), None), step));
+        }
+    }
+
+    for (id, star) in sorted(stars) {
+        fields.push((location("star", id, "title".to_string(), None), &mut star.title));
+        fields.push((location("star", id, "body".to_string(), None), &mut star.body));
+        if let Some(situation) = star.situation_context.as_mut() {
+            fields.push((location("star", id, "situation_context".to_string(), None), situation));
+        }
+        for (i, tag) in star.tags.custom.iter_mut().enumerate() {
+            fields.push((location("star", id, format!("tags.custom[{}]", i), None), tag));
+        }
+        if let Some(source) = star.source_event.as_mut() {
+            fields.push((location("star", id, "source_event.event_text".to_string(), None), &mut source.event_text));
+        }
+    }
+
+    for (id, character) in sorted(characters) {
+        fields.push((location("character", id, "name".to_string(), None), &mut character.name));
+        let values: BTreeMap<&String, &mut String> = character.fields.iter_mut().collect();
+        for (key, value) in values {
+            fields.push((location("character", id, format!("fields[{}]", key), None), value));
+        }
+    }
+
+    for (id, part) in sorted(&mut outline.parts) {
+        fields.push((location("outline", id, "title".to_string(), None), &mut part.title));
+    }
+    for (id, chapter) in sorted(&mut outline.chapters) {
+        fields.push((location("outline", id, "title".to_string(), None), &mut chapter.title));
+    }
+
+    fields
+}
+
+fn build_regex(find: &str, options: &ReplaceOptions) -> Result<Regex, String> {
+    let pattern = match options.mode {
+        MatchMode::Literal => regex::escape(find),
+        MatchMode::WholeWord => format!(r"\b{}\b", regex::escape(find)),
+        MatchMode::Regex => find.to_string(),
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+        .map_err(|e| format!("Invalid pattern: {}", e))
+}
+
+// Give the replacement the capitalisation of the text it replaces
+fn match_case(matched: &str, replacement: &str) -> String {
+    let letters: Vec<char> = matched.chars().filter(|c| c.is_alphabetic()).collect();
+    if letters.is_empty() {
+        return replacement.to_string();
+    }
+    if letters.len() > 1 && letters.iter().all(|c| c.is_uppercase()) {
+        return replacement.to_uppercase();
+    }
+    if letters.iter().all(|c| c.is_lowercase()) {
+        return replacement.to_lowercase();
+    }
+    if letters[0].is_uppercase() {
+        let mut chars = replacement.chars();
+        return match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        };
+    }
+    replacement.to_string()
+}
+
+fn replacement_for(caps: &Captures, replace: &str, options: &ReplaceOptions) -> String {
+    let mut replacement = String::new();
+    match options.mode {
+        MatchMode::Regex => caps.expand(replace, &mut replacement),
+        _ => replacement.push_str(replace),
+    }
+    if options.preserve_case {
+        replacement = match_case(&caps[0], &replacement);
+    }
+    replacement
+}
+
+fn char_offset(text: &str, byte: usize) -> usize {
+    text[..byte].chars().count()
+}
+
+fn context_before(text: &str, byte: usize) -> String {
+    let before: Vec<char> = text[..byte].chars().collect();
+    before[before.len().saturating_sub(PREVIEW_CONTEXT)..].iter().collect()
+}
+
+fn context_after(text: &str, byte: usize) -> String {
+    text[byte..].chars().take(PREVIEW_CONTEXT).collect()
+}
+
+// Find every match and build the replaced project. Only matches in `selected` are applied when given;
+// the caller decides whether to keep the result, so applying is all-or-nothing.
+pub fn find_and_replace(
+    project: &ProjectData,
+    find: &str,
+    replace: &str,
+    options: &ReplaceOptions,
+    selected: Option<&HashSet<String>>,
+) -> Result<ReplaceResult, String> {
+    if find.is_empty() {
+        return Err("Search text is empty".to_string());
+    }
+    let regex = build_regex(find, options)?;
+    let mut updated = project.clone();
+    let mut previews = Vec::new();
+    let mut renamed: Vec<(String, String, String)> = Vec::new(); // character ID, old name, new name
+
+    for (location, text) in text_fields(&mut updated) {
+        let mut output = String::with_capacity(text.len());
+        let mut last = 0;
+        let mut changed = false;
+
+        for (n, caps) in regex.captures_iter(text).enumerate() {
+            let whole = caps.get(0).expect("group 0 always exists");
+            if whole.start() == whole.end() {
+                continue; // empty regex matches would insert text everywhere
+            }
+            let replacement = replacement_for(&caps, replace, options);
+            let match_id = format!("{}:{}:{}:{}", location.kind, location.id, location.field, n);
+            let apply = selected.map_or(true, |ids| ids.contains(&match_id));
+
+            previews.push(ReplacementPreview {
+                match_id,
+                location: location.clone(),
+                start: char_offset(text, whole.start()),
+                end: char_offset(text, whole.end()),
+                original: whole.as_str().to_string(),
+                replacement: replacement.clone(),
+                context_before: context_before(text, whole.start()),
+                context_after: context_after(text, whole.end()),
+            });
+
+            if apply && whole.as_str() != replacement {
+                output.push_str(&text[last..whole.start()]);
+                output.push_str(&replacement);
+                last = whole.end();
+                changed = true;
+            }
+        }
+
+        if changed {
+            output.push_str(&text[last..]);
+            if location.kind == "character" && location.field == "name" {
+                renamed.push((location.id.clone(), text.clone(), output.clone()));
+            }
+            *text = output;
+        }
+    }
+
+    let mut renames = Vec::new();
+    for (character_id, old_name, new_name) in renamed {
+        let mut star_ids = Vec::new();
+        if options.cascade_character_rename && !old_name.trim().is_empty() {
+            // Tags hold character IDs, so the link survives the rename; the stars' text still uses the old name
+            let name_options = ReplaceOptions { mode: MatchMode::WholeWord, case_sensitive: false, ..options.clone() };
+            let name = build_regex(&old_name, &name_options)?;
+            for star in updated.stars.values_mut() {
+                let linked = star.tags.characters.contains(&character_id) || star.applies_to_character.as_deref() == Some(character_id.as_str());
+                if !linked {
+                    continue;
+                }
+                let mut touched = false;
+                for text in [&mut star.title, &mut star.body] {
+                    let rewritten = name.replace_all(text, |caps: &Captures| {
+                        if options.preserve_case {
+                            match_case(&caps[0], &new_name)
+                        } else {
+                            new_name.clone()
+                        }
+                    });
+                    if rewritten != text.as_str() {
+                        *text = rewritten.into_owned();
+                        touched = true;
+                    }
+                }
+                if touched {
+                    star_ids.push(star.id.clone());
+                }
+            }
+            star_ids.sort();
+        }
+        renames.push(CharacterRename { character_id, old_name, new_name, star_ids });
+    }
+
+    Ok(ReplaceResult { project: updated, previews, renames })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add_scene, character};
+    use crate::Star;
+
+    fn project() -> ProjectData {
+        let mut project = ProjectData::new("Test", 0);
+        add_scene(&mut project, "s1", "Harbour", &[&["Mira waits. MIRA shouts.", "Admiral Miranda arrives."]]);
+        project.characters.insert("c1".to_string(), character("c1", "Mira"));
+        project
+    }
+
+    fn options(mode: MatchMode) -> ReplaceOptions {
+        ReplaceOptions { mode, ..ReplaceOptions::default() }
+    }
+
+    #[test]
+    fn replacements_keep_the_case_of_each_match() {
+        let result = find_and_replace(&project(), "mira", "lena", &options(MatchMode::WholeWord), None).unwrap();
+        let timeline = &result.project.draft_tabs["s1-t0"].timeline;
+        assert_eq!(timeline[0].text, "Lena waits. LENA shouts.");
+        assert_eq!(timeline[1].text, "Admiral Miranda arrives.");
+        assert_eq!(result.project.characters["c1"].name, "Lena");
+    }
+
+    #[test]
+    fn only_selected_matches_are_applied() {
+        let preview = find_and_replace(&project(), "Mira", "Lena", &options(MatchMode::Literal), None).unwrap();
+        assert_eq!(preview.previews.len(), 5); // literal matches include "Admiral" and "Miranda"
+        let first = preview.previews.iter().find(|p| p.location.kind == "draft_tab").unwrap();
+        assert_eq!((first.start, first.end, first.context_after.as_str()), (0, 4, " waits. MIRA shouts."));
+
+        let selected: HashSet<String> = [first.match_id.clone()].into_iter().collect();
+        let result = find_and_replace(&project(), "Mira", "Lena", &options(MatchMode::Literal), Some(&selected)).unwrap();
+        assert_eq!(result.project.draft_tabs["s1-t0"].timeline[0].text, "Lena waits. MIRA shouts.");
+        assert_eq!(result.project.characters["c1"].name, "Mira");
+        assert!(result.renames.is_empty());
+    }
+
+    #[test]
+    fn regex_replacements_expand_captures() {
+        let options = ReplaceOptions { preserve_case: false, ..options(MatchMode::Regex) };
+        let result = find_and_replace(&project(), r"Admiral (\w+)", "Captain $1", &options, None).unwrap();
+        assert_eq!(result.project.draft_tabs["s1-t0"].timeline[1].text, "Captain Miranda arrives.");
+        assert!(find_and_replace(&project(), "(", "x", &options, None).is_err());
+    }
+
+    #[test]
+    fn renaming_a_character_rewrites_the_stars_linked_to_it() {
+        let mut project = project();
+        let mut tagged = Star::new("Mira fears water".to_string(), "Since the wreck, mira avoids the sea".to_string(), 0);
+        tagged.tags.characters = vec!["c1".to_string()];
+        let mut constraint = Star::new("Calm".to_string(), "Mira never raises her voice".to_string(), 0);
+        constraint.applies_to_character = Some("c1".to_string());
+        let unlinked = Star::new("Mira Bay".to_string(), "A town named after Mira".to_string(), 0);
+        let ids = [tagged.id.clone(), constraint.id.clone(), unlinked.id.clone()];
+        for star in [tagged, constraint, unlinked] {
+            project.stars.insert(star.id.clone(), star);
+        }
+        let name_match = find_and_replace(&project, "Mira", "Lena", &options(MatchMode::WholeWord), None)
+            .unwrap()
+            .previews
+            .into_iter()
+            .find(|p| p.location.kind == "character")
+            .unwrap();
+        let selected: HashSet<String> = [name_match.match_id].into_iter().collect();
+
+        let result = find_and_replace(&project, "Mira", "Lena", &options(MatchMode::WholeWord), Some(&selected)).unwrap();
+        assert_eq!(result.renames.len(), 1);
+        let mut expected = vec![ids[0].clone(), ids[1].clone()];
+        expected.sort();
+        assert_eq!(result.renames[0].star_ids, expected);
+        assert_eq!(result.project.stars[&ids[0]].title, "Lena fears water");
+        assert_eq!(result.project.stars[&ids[0]].body, "Since the wreck, lena avoids the sea");
+        assert_eq!(result.project.stars[&ids[0]].tags.characters, vec!["c1".to_string()]);
+        assert_eq!(result.project.stars[&ids[1]].body, "Lena never raises her voice");
+        assert_eq!(result.project.stars[&ids[2]].title, "Mira Bay");
+    }
+}
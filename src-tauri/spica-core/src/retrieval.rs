@@ -0,0 +1,467 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ProjectData;
+
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+const EMBEDDING_DIMS: usize = 256;
+
+const STOPWORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "from", "has", "have", "he", "her",
+    "his", "in", "is", "it", "its", "of", "on", "or", "she", "that", "the", "their", "them", "they",
+    "this", "to", "was", "were", "will", "with",
+];
+
+// Lowercased alphanumeric terms with stopwords removed
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| t.chars().count() > 1)
+        .map(|t| t.to_lowercase())
+        .filter(|t| !STOPWORDS.contains(&t.as_str()))
+        .collect()
+}
+
+// Jaccard overlap of the two texts' terms; texts with no terms at all count as the same
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a: HashSet<String> = tokenize(a).into_iter().collect();
+    let b: HashSet<String> = tokenize(b).into_iter().collect();
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    a.intersection(&b).count() as f64 / a.union(&b).count() as f64
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DocKind {
+    Star,
+    CharacterField,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DocKey {
+    pub kind: DocKind,
+    pub id: String,            // Star ID or Character ID
+    pub field: Option<String>, // Character.fields key
+}
+
+struct IndexedDoc {
+    fingerprint: u64,
+    term_freqs: HashMap<String, u32>,
+    length: u32,
+    embedding: Option<Vec<f32>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct RetrievalOptions {
+    pub limit: usize,
+    pub relevance_weight: f64,
+    pub priority_weight: f64,
+    pub freshness_weight: f64,
+    pub use_embeddings: bool,
+    pub embedding_weight: f64, // share of relevance taken from the embedding when enabled
+    pub checked_only: bool,
+    pub include_characters: bool,
+    pub include_unmatched: bool, // keep items with zero relevance, ranked by priority and freshness
+}
+
+impl Default for RetrievalOptions {
+    fn default() -> Self {
+        Self {
+            limit: 10,
+            relevance_weight: 0.7,
+            priority_weight: 0.2,
+            freshness_weight: 0.1,
+            use_embeddings: false,
+            embedding_weight: 0.3,
+            checked_only: false,
+            include_characters: true,
+            include_unmatched: false,
+        }
+    }
+}
+
+// Per-item score breakdown so the frontend can explain why something was retrieved
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RetrievalHit {
+    pub key: DocKey,
+    pub title: String,
+    pub bm25: f64,
+    pub bm25_normalized: f64,
+    pub embedding_similarity: Option<f64>,
+    pub priority: f64,
+    pub freshness: f64,
+    pub score: f64,
+    pub matched_terms: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct RetrievalIndex {
+    docs: HashMap<DocKey, IndexedDoc>,
+    doc_freqs: HashMap<String, u32>,
+    total_length: u64,
+    with_embeddings: bool,
+}
+
+fn fingerprint(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Feature-hashed bag of words and character trigrams; a cheap local stand-in for a learned embedding
+pub fn embed(text: &str) -> Vec<f32> {
+    let mut vector = vec![0f32; EMBEDDING_DIMS];
+    let mut add = |feature: &str, weight: f32| {
+        let bucket = (fingerprint(feature) % EMBEDDING_DIMS as u64) as usize;
+        vector[bucket] += weight;
+    };
+
+    for token in tokenize(text) {
+        add(&token, 1.0);
+        let chars: Vec<char> = format!("#{}#", token).chars().collect();
+        for window in chars.windows(3) {
+            add(&window.iter().collect::<String>(), 0.5);
+        }
+    }
+
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+    vector
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f64 {
+    // Both vectors are already normalised
+    a.iter().zip(b).map(|(x, y)| (x * y) as f64).sum::<f64>().max(0.0)
+}
+
+// Text indexed for each star and character field
+fn project_documents(project: &ProjectData) -> Vec<(DocKey, String)> {
+    let mut docs = Vec::new();
+
+    for star in project.stars.values() {
+        let mut text = format!("{} {} {}", star.title, star.title, star.body);
+        text.push(' ');
+        text.push_str(&star.tags.scope);
+        for tag in &star.tags.custom {
+            text.push(' ');
+            text.push_str(tag);
+        }
+        for context in star.tags.constraint_context.iter().flatten() {
+            text.push(' ');
+            text.push_str(context);
+        }
+        docs.push((DocKey { kind: DocKind::Star, id: star.id.clone(), field: None }, text));
+    }
+
+    for character in project.characters.values() {
+        for (key, value) in &character.fields {
+            docs.push((
+                DocKey { kind: DocKind::CharacterField, id: character.id.clone(), field: Some(key.clone()) },
+                format!("{} {} {}", character.name, key, value),
+            ));
+        }
+    }
+
+    docs
+}
+
+impl RetrievalIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn remove(&mut self, key: &DocKey) {
+        if let Some(doc) = self.docs.remove(key) {
+            self.total_length -= doc.length as u64;
+            for term in doc.term_freqs.keys() {
+                if let Some(df) = self.doc_freqs.get_mut(term) {
+                    *df -= 1;
+                    if *df == 0 {
+                        self.doc_freqs.remove(term);
+                    }
+                }
+            }
+        }
+    }
+
+    // Index or re-index a single document; unchanged content is skipped. Returns true if it changed.
+    pub fn upsert(&mut self, key: DocKey, text: &str) -> bool {
+        let fp = fingerprint(text);
+        if let Some(existing) = self.docs.get(&key) {
+            if existing.fingerprint == fp && existing.embedding.is_some() == self.with_embeddings {
+                return false;
+            }
+        }
+        self.remove(&key);
+
+        let terms = tokenize(text);
+        let mut term_freqs: HashMap<String, u32> = HashMap::new();
+        for term in &terms {
+            *term_freqs.entry(term.clone()).or_insert(0) += 1;
+        }
+        for term in term_freqs.keys() {
+            *self.doc_freqs.entry(term.clone()).or_insert(0) += 1;
+        }
+        self.total_length += terms.len() as u64;
+
+        let embedding = if self.with_embeddings { Some(embed(text)) } else { None };
+        self.docs.insert(key, IndexedDoc { fingerprint: fp, term_freqs, length: terms.len() as u32, embedding });
+        true
+    }
+
+    // Bring the index in line with the project: re-index edited items, drop deleted ones.
+    // Returns the number of documents that were (re)indexed.
+    pub fn sync(&mut self, project: &ProjectData, with_embeddings: bool) -> usize {
+        self.with_embeddings = with_embeddings;
+
+        let documents = project_documents(project);
+        let live: HashSet<&DocKey> = documents.iter().map(|(key, _)| key).collect();
+        let stale: Vec<DocKey> = self.docs.keys().filter(|key| !live.contains(key)).cloned().collect();
+        for key in &stale {
+            self.remove(key);
+        }
+
+        documents
+            .into_iter()
+            .filter(|(key, text)| self.upsert(key.clone(), text))
+            .count()
+    }
+
+    fn bm25(&self, doc: &IndexedDoc, query_terms: &HashMap<String, f64>) -> (f64, Vec<String>) {
+        let n = self.docs.len() as f64;
+        let avg_length = if self.docs.is_empty() { 0.0 } else { self.total_length as f64 / n };
+        let mut score = 0.0;
+        let mut matched = Vec::new();
+
+        for (term, weight) in query_terms {
+            let tf = match doc.term_freqs.get(term) {
+                Some(&tf) => tf as f64,
+                None => continue,
+            };
+            let df = *self.doc_freqs.get(term).unwrap_or(&0) as f64;
+            let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+            let norm = if avg_length > 0.0 { doc.length as f64 / avg_length } else { 1.0 };
+            score += weight * idf * (tf * (BM25_K1 + 1.0)) / (tf + BM25_K1 * (1.0 - BM25_B + BM25_B * norm));
+            matched.push(term.clone());
+        }
+
+        matched.sort();
+        (score, matched)
+    }
+
+    // Rank indexed items for the user request (full weight) and scene text (half weight)
+    pub fn query(
+        &self,
+        project: &ProjectData,
+        user_input: &str,
+        scene_text: &str,
+        options: &RetrievalOptions,
+        now_ms: u64,
+    ) -> Vec<RetrievalHit> {
+        let mut query_terms: HashMap<String, f64> = HashMap::new();
+        for term in tokenize(scene_text) {
+            *query_terms.entry(term).or_insert(0.0) += 0.5;
+        }
+        for term in tokenize(user_input) {
+            *query_terms.entry(term).or_insert(0.0) += 1.0;
+        }
+        let query_embedding = if options.use_embeddings && self.with_embeddings {
+            Some(embed(&format!("{} {}", user_input, scene_text)))
+        } else {
+            None
+        };
+
+        let mut hits = Vec::new();
+        for (key, doc) in &self.docs {
+            let (title, priority, last_used, checked) = match key.kind {
+                DocKind::Star => match project.stars.get(&key.id) {
+                    Some(star) => (star.title.clone(), star.priority, star.last_used_in_prompt, star.is_checked),
+                    None => continue,
+                },
+                DocKind::CharacterField => {
+                    if !options.include_characters {
+                        continue;
+                    }
+                    match project.characters.get(&key.id) {
+                        Some(character) => (
+                            format!("{}: {}", character.name, key.field.as_deref().unwrap_or_default()),
+                            0.5,
+                            None,
+                            character.is_checked,
+                        ),
+                        None => continue,
+                    }
+                }
+            };
+            if options.checked_only && !checked {
+                continue;
+            }
+
+            let (bm25, matched_terms) = self.bm25(doc, &query_terms);
+            let embedding_similarity = match (&query_embedding, &doc.embedding) {
+                (Some(q), Some(d)) => Some(cosine(q, d)),
+                _ => None,
+            };
+            if !options.include_unmatched && bm25 <= 0.0 && embedding_similarity.map_or(true, |s| s <= 0.0) {
+                continue;
+            }
+
+            // Items never used, or not used for a while, get a small boost so forgotten facts resurface
+            let freshness = match last_used {
+                None => 1.0,
+                Some(ms) => {
+                    let days = now_ms.saturating_sub(ms) as f64 / 86_400_000.0;
+                    1.0 - (-days / 7.0).exp()
+                }
+            };
+
+            hits.push(RetrievalHit {
+                key: key.clone(),
+                title,
+                bm25,
+                bm25_normalized: 0.0,
+                embedding_similarity,
+                priority: priority.clamp(0.0, 1.0),
+                freshness,
+                score: 0.0,
+                matched_terms,
+            });
+        }
+
+        let max_bm25 = hits.iter().map(|h| h.bm25).fold(0.0, f64::max);
+        for hit in &mut hits {
+            hit.bm25_normalized = if max_bm25 > 0.0 { hit.bm25 / max_bm25 } else { 0.0 };
+            let relevance = match hit.embedding_similarity {
+                Some(similarity) => {
+                    hit.bm25_normalized * (1.0 - options.embedding_weight) + similarity * options.embedding_weight
+                }
+                None => hit.bm25_normalized,
+            };
+            hit.score = options.relevance_weight * relevance
+                + options.priority_weight * hit.priority
+                + options.freshness_weight * hit.freshness;
+        }
+
+        hits.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        hits.truncate(options.limit);
+        hits
+    }
+}
+
+// Query text describing a scene: name, setting and plan
+pub fn scene_query_text(project: &ProjectData, scene_id: Option<&str>) -> String {
+    scene_id
+        .and_then(|id| project.scenes.get(id))
+        .map(|scene| {
+            format!(
+                "{} {} {}",
+                scene.name,
+                scene.setting.as_deref().unwrap_or_default(),
+                scene.plan.raw_text
+            )
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::character;
+    use crate::Star;
+
+    fn project() -> ProjectData {
+        let mut project = ProjectData::new("Test", 0);
+        for (id, title, body) in [("s1", "Mira fears water", "She never learned to swim."), ("s2", "Ferry schedule", "Boats leave at dawn."), ("s3", "Lighthouse", "Abandoned since the storm.")] {
+            let mut star = Star::new(title.to_string(), body.to_string(), 0);
+            star.id = id.to_string();
+            star.is_checked = id != "s3";
+            project.stars.insert(id.to_string(), star);
+        }
+        let mut mira = character("c1", "Mira");
+        mira.fields.insert("Fear".to_string(), "Deep water".to_string());
+        project.characters.insert("c1".to_string(), mira);
+        project
+    }
+
+    fn ids(hits: &[RetrievalHit]) -> Vec<&str> {
+        hits.iter().map(|hit| hit.key.id.as_str()).collect()
+    }
+
+    #[test]
+    fn matching_items_rank_by_relevance_and_the_rest_are_dropped() {
+        let project = project();
+        let mut index = RetrievalIndex::new();
+        index.sync(&project, false);
+
+        let hits = index.query(&project, "Mira and the water", "", &RetrievalOptions::default(), 0);
+        assert_eq!(ids(&hits), vec!["s1", "c1"]);
+        assert_eq!(hits[0].matched_terms, vec!["mira".to_string(), "water".to_string()]);
+        assert_eq!(hits[0].bm25_normalized, 1.0);
+        assert_eq!(hits[1].key.field.as_deref(), Some("Fear"));
+
+        let stars_only = RetrievalOptions { include_characters: false, ..RetrievalOptions::default() };
+        assert_eq!(ids(&index.query(&project, "water", "", &stars_only, 0)), vec!["s1"]);
+
+        // Unmatched items ranked by priority and freshness, checked ones only
+        let browse = RetrievalOptions { include_unmatched: true, checked_only: true, include_characters: false, ..RetrievalOptions::default() };
+        let hits = index.query(&project, "storm", "", &browse, 0);
+        assert_eq!(ids(&hits), vec!["s1", "s2"]);
+        assert!(hits.iter().all(|hit| hit.bm25 == 0.0 && hit.freshness == 1.0));
+    }
+
+    #[test]
+    fn sync_reindexes_only_what_changed() {
+        let mut project = project();
+        let mut index = RetrievalIndex::new();
+        assert_eq!(index.sync(&project, false), 4);
+        assert_eq!(index.sync(&project, false), 0);
+
+        project.stars.get_mut("s2").unwrap().body = "Boats leave at dusk.".to_string();
+        project.stars.remove("s3");
+        assert_eq!(index.sync(&project, false), 1);
+        assert_eq!(ids(&index.query(&project, "dusk", "", &RetrievalOptions::default(), 0)), vec!["s2"]);
+        assert!(index.query(&project, "storm", "", &RetrievalOptions::default(), 0).is_empty());
+
+        // Turning embeddings on re-embeds everything
+        assert_eq!(index.sync(&project, true), 3);
+    }
+
+    #[test]
+    fn embeddings_find_near_spellings_and_recent_use_lowers_freshness() {
+        let mut project = project();
+        let day = 86_400_000;
+        project.stars.get_mut("s1").unwrap().last_used_in_prompt = Some(10 * day);
+        let mut index = RetrievalIndex::new();
+        index.sync(&project, true);
+
+        let options = RetrievalOptions { use_embeddings: true, include_characters: false, ..RetrievalOptions::default() };
+        let hits = index.query(&project, "Lighthouses", "", &options, 10 * day);
+        assert_eq!(hits[0].key.id, "s3");
+        assert!(hits[0].bm25 == 0.0 && hits[0].embedding_similarity.unwrap() > 0.3);
+
+        let hits = index.query(&project, "water", "", &options, 10 * day);
+        assert_eq!(hits[0].key.id, "s1");
+        assert_eq!(hits[0].freshness, 0.0);
+        assert!(embed("").iter().all(|v| *v == 0.0));
+    }
+
+    #[test]
+    fn similarity_compares_terms_not_stopwords() {
+        assert_eq!(similarity("The letter burns", "a letter, burning"), 1.0 / 3.0);
+        assert_eq!(similarity("Mira rows", "mira ROWS!"), 1.0);
+        assert_eq!(similarity("", "the and of"), 1.0, "no terms on either side");
+        assert_eq!(similarity("", "Mira"), 0.0);
+    }
+}
@@ -0,0 +1,401 @@
+// Project-local Rhai scripts that hook into generation, saving and export
+//
+// A script defines any of the hook functions below; each takes one value and returns it, changed
+// or not. Returning nothing (`()`) leaves the value as it was. Scripts run in file-name order, each
+// receiving the previous script's result.
+
+use std::collections::HashSet;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Dynamic, Engine, EvalAltResult, Scope, AST};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::audit::{AuditLog, LogLevel};
+use crate::engine;
+use crate::ProjectData;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Hook {
+    BeforePromptSend,   // PromptPayload
+    AfterResponseParse, // the parsed Vec<LLMTab> of a timeline generation
+    BeforeSave,         // ProjectData
+    OnExport,           // ExportPayload
+}
+
+impl Hook {
+    pub const ALL: [Hook; 4] = [Hook::BeforePromptSend, Hook::AfterResponseParse, Hook::BeforeSave, Hook::OnExport];
+
+    pub fn function_name(self) -> &'static str {
+        match self {
+            Hook::BeforePromptSend => "before_prompt_send",
+            Hook::AfterResponseParse => "after_response_parse",
+            Hook::BeforeSave => "before_save",
+            Hook::OnExport => "on_export",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PromptPayload {
+    pub system_prompt: String,
+    pub user_prompt: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ExportPayload {
+    pub format: String, // "markdown" | "json" | "scenes_csv" | "characters_csv" | "history_csv"
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ScriptMessage {
+    pub script: String, // file name
+    pub hook: Option<Hook>,
+    pub error: bool, // false for the script's own print() output
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ScriptInfo {
+    pub name: String,
+    pub hooks: Vec<Hook>,
+    pub error: Option<String>, // why the script failed to compile; it is skipped
+}
+
+// Rhai has no allocation limit. Each value a script builds is capped at the size of the hook's input
+// plus headroom, counting nested strings and entries, and at most max_variables values are alive at
+// once, so a hook call holds roughly max_variables * (input + headroom) at most
+#[derive(Clone, Debug)]
+pub struct ScriptLimits {
+    pub timeout: Duration, // per hook call
+    pub max_operations: u64,
+    pub string_headroom: usize,     // bytes of text beyond the input's
+    pub collection_headroom: usize, // array and map entries beyond the input's
+    pub max_variables: usize,       // across all nested calls
+    pub max_call_levels: usize,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(2),
+            max_operations: 10_000_000,
+            string_headroom: 1024 * 1024,
+            collection_headroom: 10_000,
+            max_variables: 32,
+            max_call_levels: 32,
+        }
+    }
+}
+
+// Array and map entries, and bytes of text, in a value and everything nested in it
+fn data_size(value: &Dynamic) -> (usize, usize) {
+    if let Ok(text) = value.as_immutable_string_ref() {
+        return (0, text.len());
+    }
+    let nested: Vec<(usize, usize)> = match (value.as_array_ref(), value.as_map_ref()) {
+        (Ok(array), _) => array.iter().map(data_size).collect(),
+        (_, Ok(map)) => map.values().map(data_size).collect(),
+        _ => return (0, 0),
+    };
+    nested.iter().fold((nested.len(), 0), |(entries, bytes), (e, b)| (entries + e, bytes + b))
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+}
+
+#[derive(Default)]
+pub struct ScriptHost {
+    scripts: Vec<Script>,
+    broken: Vec<ScriptInfo>,
+    limits: ScriptLimits,
+    audit: Option<Arc<AuditLog>>,
+}
+
+// Scripts live beside the project file, in <project dir>/scripts/*.rhai
+pub fn script_dir(project_path: &Path) -> PathBuf {
+    project_path.parent().unwrap_or_else(|| Path::new(".")).join("scripts")
+}
+
+impl ScriptHost {
+    pub fn load_dir(dir: &Path) -> Self {
+        let mut host = Self::default();
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return host, // no scripts directory
+        };
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "rhai"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            match fs::read_to_string(&path) {
+                Ok(source) => host.add(&name, &source),
+                Err(e) => host.broken.push(ScriptInfo { name, hooks: Vec::new(), error: Some(e.to_string()) }),
+            }
+        }
+        host
+    }
+
+    // Scripts that fail to compile are listed by info() and never run
+    pub fn add(&mut self, name: &str, source: &str) {
+        match self.sandbox(Arc::new(Mutex::new(Vec::new())), (0, 0)).compile(source) {
+            Ok(ast) => self.scripts.push(Script { name: name.to_string(), ast }),
+            Err(e) => self.broken.push(ScriptInfo { name: name.to_string(), hooks: Vec::new(), error: Some(e.to_string()) }),
+        }
+    }
+
+    pub fn with_limits(mut self, limits: ScriptLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    // Messages are also written to the audit log
+    pub fn with_audit(mut self, audit: Arc<AuditLog>) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scripts.is_empty()
+    }
+
+    pub fn info(&self) -> Vec<ScriptInfo> {
+        let mut info: Vec<ScriptInfo> = self
+            .scripts
+            .iter()
+            .map(|script| ScriptInfo {
+                name: script.name.clone(),
+                hooks: Hook::ALL.iter().copied().filter(|hook| defines(&script.ast, *hook)).collect(),
+                error: None,
+            })
+            .chain(self.broken.iter().cloned())
+            .collect();
+        info.sort_by(|a, b| a.name.cmp(&b.name));
+        info
+    }
+
+    pub fn run<T: Serialize + DeserializeOwned>(&self, hook: Hook, value: T) -> (T, Vec<ScriptMessage>) {
+        self.run_checked(hook, value, |_| Ok(()))
+    }
+
+    // A script's result replaces the value only if it converts back to T and passes the check;
+    // otherwise the script is reported and the value it was given is passed on unchanged
+    pub fn run_checked<T: Serialize + DeserializeOwned>(
+        &self,
+        hook: Hook,
+        mut value: T,
+        check: impl Fn(&T) -> Result<(), String>,
+    ) -> (T, Vec<ScriptMessage>) {
+        let mut messages = Vec::new();
+        for script in self.scripts.iter().filter(|script| defines(&script.ast, hook)) {
+            let printed = Arc::new(Mutex::new(Vec::new()));
+            let result = self.call(script, hook, &value, printed.clone()).and_then(|returned| {
+                if returned.is_unit() {
+                    return Ok(None);
+                }
+                let converted: T = rhai::serde::from_dynamic(&returned)
+                    .map_err(|e| format!("returned a value of the wrong shape: {}", e))?;
+                check(&converted)?;
+                Ok(Some(converted))
+            });
+
+            let mut message = |error: bool, message: String| {
+                messages.push(ScriptMessage { script: script.name.clone(), hook: Some(hook), error, message })
+            };
+            for line in printed.lock().unwrap_or_else(|e| e.into_inner()).drain(..) {
+                message(false, line);
+            }
+            match result {
+                Ok(Some(converted)) => value = converted,
+                Ok(None) => {}
+                Err(e) => message(true, format!("{}; its changes were discarded", e)),
+            }
+        }
+        if let Some(audit) = &self.audit {
+            for message in &messages {
+                let level = if message.error { LogLevel::Error } else { LogLevel::Info };
+                audit.log(level, "script", &message.script, serde_json::to_value(message).unwrap(), serde_json::Value::Null);
+            }
+        }
+        (value, messages)
+    }
+
+    // Rejects results that leave references to tabs, events or scenes that do not exist
+    pub fn before_save(&self, project: ProjectData) -> (ProjectData, Vec<ScriptMessage>) {
+        let existing: HashSet<String> = engine::dangling_references(&project).into_iter().collect();
+        self.run_checked(Hook::BeforeSave, project, |saved| {
+            let added: Vec<String> = engine::dangling_references(saved).into_iter().filter(|r| !existing.contains(r)).collect();
+            match added.first() {
+                Some(first) => Err(format!("left {} dangling reference(s), e.g. {}", added.len(), first)),
+                None => Ok(()),
+            }
+        })
+    }
+
+    pub fn on_export(&self, format: &str, text: String) -> (String, Vec<ScriptMessage>) {
+        let (export, messages) = self.run(Hook::OnExport, ExportPayload { format: format.to_string(), text });
+        (export.text, messages)
+    }
+
+    fn call<T: Serialize>(&self, script: &Script, hook: Hook, value: &T, printed: Arc<Mutex<Vec<String>>>) -> Result<Dynamic, String> {
+        let argument = rhai::serde::to_dynamic(value).map_err(|e| format!("could not be given the {:?} data: {}", hook, e))?;
+        let mut engine = self.sandbox(printed, data_size(&argument));
+        let started = Instant::now();
+        let timeout = self.limits.timeout;
+        engine.on_progress(move |_| if started.elapsed() > timeout { Some(Dynamic::UNIT) } else { None });
+
+        // Rhai reports script errors as values; the guard is for bugs in the interpreter itself
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            engine.call_fn::<Dynamic>(&mut Scope::new(), &script.ast, hook.function_name(), (argument,))
+        }));
+        match outcome {
+            Ok(Ok(returned)) => Ok(returned),
+            Ok(Err(e)) => Err(match *e {
+                EvalAltResult::ErrorTerminated(..) => format!("timed out after {} ms", timeout.as_millis()),
+                e => format!("failed: {}", e),
+            }),
+            Err(_) => Err("crashed the script interpreter".to_string()),
+        }
+    }
+
+    // No file, network or module access; eval is disabled so limits cannot be sidestepped.
+    // Data limits grow with the input's entries and bytes, since scripts return it changed
+    fn sandbox(&self, printed: Arc<Mutex<Vec<String>>>, (entries, bytes): (usize, usize)) -> Engine {
+        let mut engine = Engine::new();
+        // Rhai takes 0 as no limit
+        let collections = (entries + self.limits.collection_headroom).max(1);
+        engine
+            .set_module_resolver(DummyModuleResolver::new())
+            .disable_symbol("eval")
+            .set_max_operations(self.limits.max_operations)
+            .set_max_string_size((bytes + self.limits.string_headroom).max(1))
+            .set_max_array_size(collections)
+            .set_max_map_size(collections)
+            .set_max_variables(self.limits.max_variables.max(1))
+            .set_max_call_levels(self.limits.max_call_levels)
+            .set_max_expr_depths(64, 64);
+        let debug_lines = printed.clone();
+        engine.on_print(move |text| printed.lock().unwrap_or_else(|e| e.into_inner()).push(text.to_string()));
+        engine.on_debug(move |text, _, _| debug_lines.lock().unwrap_or_else(|e| e.into_inner()).push(text.to_string()));
+        engine
+    }
+}
+
+fn defines(ast: &AST, hook: Hook) -> bool {
+    ast.iter_functions().any(|f| f.name == hook.function_name() && f.params.len() == 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openai_client::{LLMTab, TimelineEvent};
+
+    fn tab(events: &[&str]) -> LLMTab {
+        LLMTab {
+            title: "Draft".to_string(),
+            timeline: events
+                .iter()
+                .map(|text| TimelineEvent { text: text.to_string(), checked: true, ..TimelineEvent::default() })
+                .collect(),
+            summary: None,
+            atmosphere: None,
+            unresolved_speakers: Vec::new(),
+            provenance: None,
+            plan_verdicts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn scripts_chain_in_order_and_can_leave_values_alone() {
+        let mut host = ScriptHost::default();
+        host.add("10-words.rhai", r#"
+            fn after_response_parse(tabs) {
+                for t in 0..tabs.len() {
+                    for e in 0..tabs[t].timeline.len() { tabs[t].timeline[e].text.replace("very ", ""); }
+                }
+                tabs
+            }"#);
+        host.add("20-tag.rhai", r#"
+            fn after_response_parse(tabs) { tabs[0].title += " (house style)"; print("tagged"); tabs }
+            fn on_export(data) { }"#);
+
+        let (tabs, messages) = host.run(Hook::AfterResponseParse, vec![tab(&["A very cold night.", "Mira waits."])]);
+        assert_eq!(tabs[0].timeline[0].text, "A cold night.");
+        assert_eq!(tabs[0].title, "Draft (house style)");
+        assert_eq!(messages.len(), 1);
+        assert!(!messages[0].error && messages[0].message == "tagged");
+
+        let export = ExportPayload { format: "markdown".to_string(), text: "# Book".to_string() };
+        let (export, messages) = host.run(Hook::OnExport, export);
+        assert_eq!(export.text, "# Book");
+        assert!(messages.is_empty());
+        assert_eq!(host.info()[1].hooks, vec![Hook::AfterResponseParse, Hook::OnExport]);
+    }
+
+    #[test]
+    fn broken_scripts_never_change_the_project() {
+        let mut project = ProjectData::new("Test", 0);
+        project.workbench.unassigned_draft_tab_ids.push("old".to_string()); // already dangling; not the script's fault
+        let limits = ScriptLimits { timeout: Duration::from_millis(50), ..ScriptLimits::default() };
+        let mut host = ScriptHost::default().with_limits(limits);
+        host.add("a-syntax.rhai", "fn before_save(project) { project.title = ");
+        host.add("b-loop.rhai", "fn before_save(project) { loop { } }");
+        host.add("c-shape.rhai", "fn before_save(project) { project.metadata = 42; project }");
+        host.add("d-dangling.rhai", r#"fn before_save(project) { project.workbench.unassigned_draft_tab_ids.push("gone"); project }"#);
+        host.add("e-throw.rhai", r#"fn before_save(project) { throw "house style violated" }"#);
+        host.add("f-title.rhai", r#"fn before_save(project) { project.metadata.title = "Renamed"; project }"#);
+
+        let (saved, messages) = host.before_save(project.clone());
+        assert_eq!(saved.metadata.title, "Renamed");
+        assert_eq!(saved.workbench.unassigned_draft_tab_ids, vec!["old".to_string()]);
+        let failed: Vec<&str> = messages.iter().filter(|m| m.error).map(|m| m.script.as_str()).collect();
+        assert_eq!(failed, vec!["b-loop.rhai", "c-shape.rhai", "d-dangling.rhai", "e-throw.rhai"]);
+        assert!(messages[0].message.starts_with("timed out"));
+        assert!(host.info()[0].error.is_some());
+    }
+
+    #[test]
+    fn scripts_cannot_reach_outside_the_sandbox() {
+        let mut host = ScriptHost::default();
+        host.add("import.rhai", r#"import "secrets" as s; fn on_export(data) { data }"#);
+        host.add("eval.rhai", r#"fn on_export(data) { eval("1") }"#);
+        let export = ExportPayload { format: "markdown".to_string(), text: "# Book".to_string() };
+        let (export, messages) = host.run(Hook::OnExport, export);
+        assert_eq!(export.text, "# Book");
+        assert!(host.info().iter().any(|info| info.name == "eval.rhai" && info.error.is_some()));
+        assert!(messages.iter().all(|m| m.error));
+    }
+
+    #[test]
+    fn data_limits_follow_the_input_size() {
+        let limits = ScriptLimits { string_headroom: 1000, collection_headroom: 100, ..ScriptLimits::default() };
+        let manuscript = "Mira rows. ".repeat(500);
+
+        let mut host = ScriptHost::default().with_limits(limits.clone());
+        host.add("mark.rhai", r#"fn on_export(data) { data.text += "THE END"; data }"#);
+        let (text, messages) = host.on_export("markdown", manuscript.clone());
+        assert!(messages.is_empty(), "{:?}", messages);
+        assert!(text.ends_with("THE END"));
+
+        let mut host = ScriptHost::default().with_limits(limits);
+        host.add("grow.rhai", r#"fn on_export(data) { let t = data.text; for i in 0..4 { t += t; } data.text = t; data }"#);
+        host.add("hoard.rhai", r#"fn on_export(data) { let list = []; for i in 0..1000 { list.push(i); } data }"#);
+        let (text, messages) = host.on_export("markdown", manuscript.clone());
+        assert_eq!(text, manuscript);
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| m.error), "{:?}", messages);
+    }
+}
@@ -0,0 +1,538 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use crate::speaker::character_mentions;
+use crate::{Character, ProjectData};
+
+// Characters of context kept on each side of the first match
+const SNIPPET_CONTEXT: usize = 60;
+// Score multipliers for how a query term matched
+const EXACT_WEIGHT: f64 = 1.0;
+const FUZZY_WEIGHT: f64 = 0.6;
+const PHRASE_BONUS: f64 = 2.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchKind {
+    Event,
+    Description,
+    Star,
+    CharacterField,
+    Scene,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SearchKey {
+    pub kind: SearchKind,
+    pub entity_id: String,          // DraftTab ID for events, otherwise the entity's own ID
+    pub event_index: Option<usize>, // TimelineEvent index within the tab
+    pub field: Option<String>,      // Character.fields key, or "setting" / "backstory" for scenes
+}
+
+struct Token {
+    term: String,
+    start: usize, // byte offsets into the document text
+    end: usize,
+}
+
+struct SearchDoc {
+    fingerprint: u64,
+    text: String,
+    title: String,
+    scene_id: Option<String>,
+    character_ids: Vec<String>,
+    tokens: Vec<Token>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SearchFilters {
+    pub kinds: Option<Vec<SearchKind>>,
+    pub scene_id: Option<String>,
+    pub character_id: Option<String>,
+    pub fuzzy: bool,
+    pub limit: usize,
+}
+
+impl Default for SearchFilters {
+    fn default() -> Self {
+        Self { kinds: None, scene_id: None, character_id: None, fuzzy: true, limit: 50 }
+    }
+}
+
+// Character offsets within `snippet`, end exclusive
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Highlight {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SearchHit {
+    pub key: SearchKey,
+    pub title: String,
+    pub scene_id: Option<String>,
+    pub snippet: String,
+    pub highlights: Vec<Highlight>,
+    pub score: f64,
+    pub matched_terms: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct SearchIndex {
+    docs: HashMap<SearchKey, SearchDoc>,
+    postings: HashMap<String, HashSet<SearchKey>>,
+}
+
+fn fingerprint(text: &str, scene_id: Option<&str>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    scene_id.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Every lowercased alphanumeric run with its position; stopwords are kept so phrases line up
+fn tokens(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        match (c.is_alphanumeric(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                tokens.push(Token { term: text[s..i].to_lowercase(), start: s, end: i });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+// Optimal string alignment distance: insertions, deletions, substitutions and adjacent swaps
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+// Typos allowed for a query term: none for short words, more for long ones
+fn max_typos(term: &str) -> usize {
+    match term.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+// Quoted phrases and loose terms, e.g. `"silver key" door` -> (["silver key"], ["door"])
+fn parse_query(query: &str) -> (Vec<Vec<String>>, Vec<String>) {
+    let mut phrases = Vec::new();
+    let mut terms = Vec::new();
+    for (i, part) in query.split('"').enumerate() {
+        let words: Vec<String> = tokens(part).into_iter().map(|t| t.term).collect();
+        if i % 2 == 1 && words.len() > 1 {
+            phrases.push(words);
+        } else {
+            terms.extend(words);
+        }
+    }
+    (phrases, terms)
+}
+
+fn mentioned_ids(text: &str, characters: &[Character]) -> Vec<String> {
+    let mut ids: Vec<String> = character_mentions(text, characters).into_iter().map(|(_, _, c)| c.id.clone()).collect();
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+// Everything searchable, with the scene and characters each item belongs to
+fn project_documents(project: &ProjectData) -> Vec<(SearchKey, SearchDoc)> {
+    let characters: Vec<Character> = project.characters.values().cloned().collect();
+    let mut docs = Vec::new();
+    let mut push = |kind,
+                    entity_id: &str,
+                    event_index,
+                    field: Option<&str>,
+                    title: String,
+                    text: String,
+                    scene_id: Option<String>,
+                    mut character_ids: Vec<String>| {
+        if text.trim().is_empty() {
+            return;
+        }
+        character_ids.extend(mentioned_ids(&text, &characters));
+        character_ids.sort();
+        character_ids.dedup();
+        let key = SearchKey { kind, entity_id: entity_id.to_string(), event_index, field: field.map(str::to_string) };
+        docs.push((
+            key,
+            SearchDoc {
+                fingerprint: fingerprint(&text, scene_id.as_deref()),
+                tokens: Vec::new(),
+                text,
+                title,
+                scene_id,
+                character_ids,
+            },
+        ));
+    };
+
+    for tab in project.draft_tabs.values() {
+        let scene_name = tab.scene_id.as_ref().and_then(|id| project.scenes.get(id)).map(|s| s.name.clone());
+        let label = scene_name.unwrap_or_else(|| "Workbench".to_string());
+        for (index, event) in tab.timeline.iter().enumerate() {
+            let text = match &event.dialogue {
+                Some(dialogue) => format!("{} \"{}\"", event.text, dialogue),
+                None => event.text.clone(),
+            };
+            let speaker = event.speaker.as_ref().and_then(|s| s.character_id.clone()).into_iter().collect();
+            let title = format!("{}: event {}", label, index + 1);
+            push(SearchKind::Event, &tab.id, Some(index), None, title, text, tab.scene_id.clone(), speaker);
+        }
+        for description in &tab.descriptions {
+            let title = format!("{}: description", label);
+            push(SearchKind::Description, &description.id, None, None, title, description.text.clone(), tab.scene_id.clone(), Vec::new());
+        }
+    }
+
+    for star in project.stars.values() {
+        let scene_id = star
+            .origin_draft_tab_id
+            .as_ref()
+            .and_then(|id| project.draft_tabs.get(id))
+            .and_then(|tab| tab.scene_id.clone());
+        let text = format!("{}\n{}", star.title, star.body);
+        push(SearchKind::Star, &star.id, None, None, star.title.clone(), text, scene_id, star.tags.characters.clone());
+    }
+
+    for character in project.characters.values() {
+        for (key, value) in &character.fields {
+            let title = format!("{}: {}", character.name, key);
+            push(SearchKind::CharacterField, &character.id, None, Some(key), title, value.clone(), None, vec![character.id.clone()]);
+        }
+    }
+
+    for scene in project.scenes.values() {
+        for (field, value) in [("setting", &scene.setting), ("backstory", &scene.backstory)] {
+            if let Some(value) = value {
+                let title = format!("{}: {}", scene.name, field);
+                push(SearchKind::Scene, &scene.id, None, Some(field), title, value.clone(), Some(scene.id.clone()), Vec::new());
+            }
+        }
+    }
+
+    docs
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn remove(&mut self, key: &SearchKey) {
+        if let Some(doc) = self.docs.remove(key) {
+            for token in &doc.tokens {
+                if let Some(keys) = self.postings.get_mut(&token.term) {
+                    keys.remove(key);
+                    if keys.is_empty() {
+                        self.postings.remove(&token.term);
+                    }
+                }
+            }
+        }
+    }
+
+    // Bring the index in line with the project: re-index edited items, drop deleted ones.
+    // Returns the number of documents that were (re)indexed.
+    pub fn sync(&mut self, project: &ProjectData) -> usize {
+        let documents = project_documents(project);
+        let live: HashSet<&SearchKey> = documents.iter().map(|(key, _)| key).collect();
+        let stale: Vec<SearchKey> = self.docs.keys().filter(|key| !live.contains(key)).cloned().collect();
+        for key in &stale {
+            self.remove(key);
+        }
+
+        let mut changed = 0;
+        for (key, mut doc) in documents {
+            match self.docs.get_mut(&key) {
+                // Titles and character links can change without the text changing
+                Some(existing) if existing.fingerprint == doc.fingerprint => {
+                    existing.title = doc.title;
+                    existing.character_ids = doc.character_ids;
+                    continue;
+                }
+                Some(_) => self.remove(&key),
+                None => {}
+            }
+            doc.tokens = tokens(&doc.text);
+            for token in &doc.tokens {
+                self.postings.entry(token.term.clone()).or_default().insert(key.clone());
+            }
+            self.docs.insert(key, doc);
+            changed += 1;
+        }
+        changed
+    }
+
+    // Indexed terms a query term matches: itself, plus near spellings when fuzzy
+    fn expand(&self, term: &str, fuzzy: bool) -> Vec<(String, f64)> {
+        let mut matches = Vec::new();
+        if self.postings.contains_key(term) {
+            matches.push((term.to_string(), EXACT_WEIGHT));
+        }
+        let typos = max_typos(term);
+        if fuzzy && typos > 0 {
+            let length = term.chars().count();
+            for candidate in self.postings.keys() {
+                if candidate != term
+                    && candidate.chars().count().abs_diff(length) <= typos
+                    && edit_distance(term, candidate) <= typos
+                {
+                    matches.push((candidate.clone(), FUZZY_WEIGHT));
+                }
+            }
+        }
+        matches
+    }
+
+    pub fn search(&self, query: &str, filters: &SearchFilters) -> Vec<SearchHit> {
+        let (phrases, terms) = parse_query(query);
+        if phrases.is_empty() && terms.is_empty() {
+            return Vec::new();
+        }
+
+        // Every loose term must match (exactly or fuzzily); phrases must appear verbatim
+        let expansions: Vec<Vec<(String, f64)>> = terms.iter().map(|t| self.expand(t, filters.fuzzy)).collect();
+        if expansions.iter().any(|e| e.is_empty()) {
+            return Vec::new();
+        }
+        let mut candidates: Option<HashSet<&SearchKey>> = None;
+        let required = expansions
+            .iter()
+            .map(|e| e.iter().map(|(term, _)| term.clone()).collect::<Vec<_>>())
+            .chain(phrases.iter().map(|p| p.clone().into_iter().take(1).collect()));
+        for alternatives in required {
+            let keys: HashSet<&SearchKey> =
+                alternatives.iter().filter_map(|t| self.postings.get(t)).flatten().collect();
+            candidates = Some(match candidates {
+                Some(existing) => existing.intersection(&keys).copied().collect(),
+                None => keys,
+            });
+        }
+
+        let n = self.docs.len().max(1) as f64;
+        let mut hits = Vec::new();
+        for key in candidates.unwrap_or_default() {
+            let doc = &self.docs[key];
+            if filters.kinds.as_ref().map_or(false, |kinds| !kinds.contains(&key.kind))
+                || filters.scene_id.as_ref().map_or(false, |id| doc.scene_id.as_ref() != Some(id))
+                || filters.character_id.as_ref().map_or(false, |id| !doc.character_ids.contains(id))
+            {
+                continue;
+            }
+
+            let mut spans: BTreeSet<(usize, usize)> = BTreeSet::new();
+            let mut matched_terms = BTreeSet::new();
+            let mut score = 0.0;
+
+            let mut phrases_found = true;
+            for phrase in &phrases {
+                let mut found = false;
+                for window in doc.tokens.windows(phrase.len()) {
+                    if window.iter().zip(phrase).all(|(token, word)| &token.term == word) {
+                        spans.insert((window[0].start, window[window.len() - 1].end));
+                        found = true;
+                    }
+                }
+                if !found {
+                    phrases_found = false;
+                    break;
+                }
+                matched_terms.insert(phrase.join(" "));
+                score += PHRASE_BONUS * phrase.len() as f64;
+            }
+            if !phrases_found {
+                continue;
+            }
+
+            for alternatives in &expansions {
+                let mut best = 0.0;
+                for (term, weight) in alternatives {
+                    let occurrences: Vec<&Token> = doc.tokens.iter().filter(|t| &t.term == term).collect();
+                    if occurrences.is_empty() {
+                        continue;
+                    }
+                    let idf = (n / self.postings.get(term).map_or(1, |keys| keys.len()) as f64).ln() + 1.0;
+                    let term_score = weight * idf * (1.0 + (occurrences.len() as f64).ln());
+                    if term_score > best {
+                        best = term_score;
+                    }
+                    spans.extend(occurrences.iter().map(|t| (t.start, t.end)));
+                    matched_terms.insert(term.clone());
+                }
+                score += best;
+            }
+
+            // Short documents where the match is most of the text rank higher
+            score /= 1.0 + (doc.tokens.len() as f64 / 50.0);
+            let (snippet, highlights) = snippet(&doc.text, &spans);
+            hits.push(SearchHit {
+                key: key.clone(),
+                title: doc.title.clone(),
+                scene_id: doc.scene_id.clone(),
+                snippet,
+                highlights,
+                score,
+                matched_terms: matched_terms.into_iter().collect(),
+            });
+        }
+
+        hits.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        hits.truncate(filters.limit);
+        hits
+    }
+}
+
+// Text around the first match, with every match inside the window highlighted
+fn snippet(text: &str, spans: &BTreeSet<(usize, usize)>) -> (String, Vec<Highlight>) {
+    let first = spans.iter().next().map_or(0, |(start, _)| *start);
+    let char_at = |byte: usize| text[..byte].chars().count();
+    let total = text.chars().count();
+    let from = char_at(first).saturating_sub(SNIPPET_CONTEXT);
+    let to = (char_at(first) + SNIPPET_CONTEXT * 2).min(total);
+
+    let mut snippet: String = text.chars().skip(from).take(to - from).collect();
+    let mut offset = 0;
+    if from > 0 {
+        snippet.insert(0, '…');
+        offset = 1;
+    }
+    if to < total {
+        snippet.push('…');
+    }
+
+    let highlights = spans
+        .iter()
+        .map(|(start, end)| (char_at(*start), char_at(*end)))
+        .filter(|(start, end)| *start >= from && *end <= to)
+        .map(|(start, end)| Highlight { start: start - from + offset, end: end - from + offset })
+        .collect();
+    (snippet.replace('\n', " "), highlights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add_scene, character};
+    use crate::Star;
+
+    fn project() -> ProjectData {
+        let mut project = ProjectData::new("Test", 0);
+        add_scene(&mut project, "s1", "Harbour", &[&["Mira finds the silver key", "The key opens no door", "Silver rain over the key"]]);
+        project.scenes.get_mut("s1").unwrap().setting = Some("A grey harbour town".to_string());
+        let mut star = Star::new("Silver key".to_string(), "Opens the lighthouse".to_string(), 0);
+        star.id = "star1".to_string();
+        project.stars.insert(star.id.clone(), star);
+        let mut mira = character("c1", "Mira");
+        mira.fields.insert("Past".to_string(), "Lost a silver ring".to_string());
+        project.characters.insert("c1".to_string(), mira);
+        project
+    }
+
+    fn keys(hits: &[SearchHit]) -> Vec<(SearchKind, Option<usize>)> {
+        let mut keys: Vec<_> = hits.iter().map(|hit| (hit.key.kind, hit.key.event_index)).collect();
+        keys.sort();
+        keys
+    }
+
+    fn index(project: &ProjectData) -> SearchIndex {
+        let mut index = SearchIndex::new();
+        index.sync(project);
+        index
+    }
+
+    #[test]
+    fn phrases_terms_and_typos() {
+        let project = project();
+        let index = index(&project);
+        let all = SearchFilters::default();
+
+        let hits = index.search("\"silver key\"", &all);
+        assert_eq!(keys(&hits), vec![(SearchKind::Event, Some(0)), (SearchKind::Star, None)]);
+        for hit in &hits {
+            let highlighted: String = hit.snippet.chars().skip(hit.highlights[0].start).take(hit.highlights[0].end - hit.highlights[0].start).collect();
+            assert_eq!(highlighted.to_lowercase(), "silver key");
+        }
+
+        assert_eq!(keys(&index.search("key door", &all)), vec![(SearchKind::Event, Some(1))]);
+        assert_eq!(index.search("slver ring", &all)[0].matched_terms, vec!["ring".to_string(), "silver".to_string()]);
+        assert!(index.search("slver", &SearchFilters { fuzzy: false, ..SearchFilters::default() }).is_empty());
+        assert!(index.search("key dragon", &all).is_empty() && index.search("  ", &all).is_empty());
+    }
+
+    #[test]
+    fn filters_narrow_by_kind_scene_and_character() {
+        let project = project();
+        let index = index(&project);
+        let only = |filters: SearchFilters| keys(&index.search("silver harbour", &SearchFilters { fuzzy: false, ..filters }));
+
+        let stars = SearchFilters { kinds: Some(vec![SearchKind::Star]), ..SearchFilters::default() };
+        assert!(only(stars).is_empty());
+        let hits = index.search("grey", &SearchFilters { scene_id: Some("s1".to_string()), ..SearchFilters::default() });
+        assert_eq!(hits[0].title, "Harbour: setting");
+        let mira = SearchFilters { character_id: Some("c1".to_string()), ..SearchFilters::default() };
+        assert_eq!(keys(&index.search("silver", &mira)), vec![(SearchKind::Event, Some(0)), (SearchKind::CharacterField, None)]);
+        let limited = SearchFilters { limit: 1, ..SearchFilters::default() };
+        assert_eq!(index.search("silver", &limited).len(), 1);
+    }
+
+    #[test]
+    fn sync_reindexes_edits_and_drops_deleted_items() {
+        let mut project = project();
+        let mut index = SearchIndex::new();
+        assert_eq!(index.sync(&project), 6);
+        assert_eq!(index.sync(&project), 0);
+
+        project.draft_tabs.get_mut("s1-t0").unwrap().timeline[1].text = "The key opens the gate".to_string();
+        project.stars.clear();
+        assert_eq!(index.sync(&project), 1);
+        assert!(index.search("door", &SearchFilters::default()).is_empty());
+        assert_eq!(keys(&index.search("gate", &SearchFilters::default())), vec![(SearchKind::Event, Some(1))]);
+        assert!(index.search("lighthouse", &SearchFilters::default()).is_empty());
+    }
+
+    #[test]
+    fn snippets_are_cut_around_the_first_match() {
+        let text = format!("{} silver key {}", "a".repeat(100), "b".repeat(200));
+        let spans: BTreeSet<(usize, usize)> = [(101, 107)].into_iter().collect();
+        let (snippet, highlights) = snippet(&text, &spans);
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert_eq!(snippet.chars().count(), SNIPPET_CONTEXT * 3 + 2);
+        let highlighted: String = snippet.chars().skip(highlights[0].start).take(highlights[0].end - highlights[0].start).collect();
+        assert_eq!(highlighted, "silver");
+        assert_eq!(edit_distance("silver", "slivre"), 2);
+    }
+}
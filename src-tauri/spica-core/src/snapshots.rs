@@ -0,0 +1,155 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::ProjectData;
+use crate::summaries::content_hash;
+
+// Oldest snapshots beyond this are deleted when a new one is taken
+const MAX_SNAPSHOTS: usize = 50;
+
+// The whole project as it was before a bulk edit, so the edit can be undone
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Snapshot {
+    pub id: String,
+    pub label: String,
+    pub created_at: u64,
+    pub state_json: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SnapshotInfo {
+    pub id: String,
+    pub label: String,
+    pub created_at: u64,
+}
+
+// Each project keeps its own snapshots, under its id; projects saved before ids existed
+// fall back to their creation time
+pub fn project_dir(root: &Path, project: &ProjectData) -> PathBuf {
+    let key = match &project.metadata.id {
+        Some(id) if !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') => id.clone(),
+        Some(id) => content_hash(id),
+        None => format!("created-{}", project.metadata.created_at),
+    };
+    root.join(key)
+}
+
+fn invalid_data(e: serde_json::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+// A snapshot file is one line of SnapshotInfo followed by the project JSON, so listing
+// reads only the first line
+fn read_info(path: &Path) -> io::Result<SnapshotInfo> {
+    let mut header = String::new();
+    BufReader::new(File::open(path)?).read_line(&mut header)?;
+    serde_json::from_str(header.trim_end()).map_err(invalid_data)
+}
+
+fn read(path: &Path) -> io::Result<Snapshot> {
+    let content = fs::read_to_string(path)?;
+    let (header, state_json) = content
+        .split_once('\n')
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Snapshot has no header"))?;
+    let info: SnapshotInfo = serde_json::from_str(header).map_err(invalid_data)?;
+    Ok(Snapshot { id: info.id, label: info.label, created_at: info.created_at, state_json: state_json.to_string() })
+}
+
+pub fn save(dir: &Path, label: &str, state_json: &str, now_ms: u64) -> io::Result<SnapshotInfo> {
+    fs::create_dir_all(dir)?;
+    // Millisecond prefix keeps file names in creation order
+    let id = format!("{:013}-{}", now_ms, uuid::Uuid::new_v4().simple());
+    let info = SnapshotInfo { id: id.clone(), label: label.to_string(), created_at: now_ms };
+    let header = serde_json::to_string(&info).map_err(invalid_data)?;
+    fs::write(dir.join(format!("{}.json", id)), format!("{}\n{}", header, state_json))?;
+
+    let mut ids: Vec<String> = list(dir)?.into_iter().map(|info| info.id).collect();
+    for old in ids.drain(MAX_SNAPSHOTS.min(ids.len())..) {
+        fs::remove_file(dir.join(format!("{}.json", old)))?;
+    }
+
+    Ok(info)
+}
+
+// Newest first; unreadable files are skipped
+pub fn list(dir: &Path) -> io::Result<Vec<SnapshotInfo>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut infos: Vec<SnapshotInfo> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().map_or(false, |ext| ext == "json"))
+        .filter_map(|entry| read_info(&entry.path()).ok())
+        .collect();
+    infos.sort_by(|a, b| b.id.cmp(&a.id));
+    Ok(infos)
+}
+
+pub fn load(dir: &Path, id: &str) -> io::Result<Snapshot> {
+    // IDs are generated here; anything path-like is not one of ours
+    if id.contains(['/', '\\']) || id.contains("..") {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid snapshot id"));
+    }
+    read(&dir.join(format!("{}.json", id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    #[test]
+    fn saved_snapshots_list_newest_first_and_restore_their_state() {
+        let dir = temp_dir("snapshots-roundtrip");
+        let first = save(&dir, "First", "{\"a\":1}\nsecond line", 1_000).unwrap();
+        let second = save(&dir, "Second", "{\"b\":2}", 2_000).unwrap();
+
+        let listed: Vec<String> = list(&dir).unwrap().into_iter().map(|info| info.label).collect();
+        assert_eq!(listed, vec!["Second", "First"]);
+
+        assert_eq!(load(&dir, &first.id).unwrap().state_json, "{\"a\":1}\nsecond line");
+        assert_eq!(load(&dir, &second.id).unwrap().created_at, 2_000);
+        assert!(load(&dir, "../elsewhere").is_err());
+    }
+
+    #[test]
+    fn listing_reads_only_the_header() {
+        let dir = temp_dir("snapshots-header");
+        let info = save(&dir, "Replace", "not json at all", 1_000).unwrap();
+        let listed = list(&dir).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, info.id);
+    }
+
+    #[test]
+    fn old_snapshots_are_pruned() {
+        let dir = temp_dir("snapshots-prune");
+        for i in 0..(MAX_SNAPSHOTS as u64 + 2) {
+            save(&dir, &format!("Edit {}", i), "{}", 1_000 + i).unwrap();
+        }
+        let listed = list(&dir).unwrap();
+        assert_eq!(listed.len(), MAX_SNAPSHOTS);
+        assert_eq!(listed[0].created_at, 1_000 + MAX_SNAPSHOTS as u64 + 1);
+        assert_eq!(listed.last().unwrap().created_at, 1_002);
+    }
+
+    #[test]
+    fn projects_keep_separate_snapshots() {
+        let root = temp_dir("snapshots-projects");
+        let one = ProjectData::new("One", 0);
+        let two = ProjectData::new("Two", 0);
+        save(&project_dir(&root, &one), "Edit", "{}", 1_000).unwrap();
+
+        assert_eq!(list(&project_dir(&root, &one)).unwrap().len(), 1);
+        assert!(list(&project_dir(&root, &two)).unwrap().is_empty());
+
+        let mut legacy = ProjectData::new("Legacy", 42);
+        legacy.metadata.id = None;
+        assert_eq!(project_dir(&root, &legacy), root.join("created-42"));
+        legacy.metadata.id = Some("../../etc".to_string());
+        assert_eq!(project_dir(&root, &legacy).parent(), Some(root.as_path()));
+    }
+}
@@ -0,0 +1,336 @@
+use serde::{Deserialize, Serialize};
+
+use crate::openai_client::TimelineEvent;
+use crate::Character;
+
+// Verbs that mark "<Name> <verb>" as an explicit speaker attribution
+const SPEECH_VERBS: &[&str] = &[
+    "says", "said", "asks", "asked", "replies", "replied", "answers", "answered",
+    "whispers", "whispered", "shouts", "shouted", "yells", "yelled", "mutters", "muttered",
+    "murmurs", "murmured", "snaps", "snapped", "adds", "added", "continues", "continued",
+    "exclaims", "exclaimed", "calls", "called", "tells", "told",
+];
+
+// Character.fields keys whose values are treated as alternative names
+const ALIAS_FIELD_KEYS: &[&str] = &["alias", "aliases", "nickname", "nicknames", "aka", "also known as"];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum AttributionMethod {
+    Explicit,  // "Name:" label or "Name says" in the event text
+    NameMatch, // character name mentioned in the event text
+    AliasMatch, // alias or first name mentioned in the event text
+    Manual,    // set by the writer, never overwritten
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SpeakerAttribution {
+    pub name: String,
+    pub character_id: Option<String>,
+    pub confidence: f64,
+    pub method: AttributionMethod,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UnresolvedSpeaker {
+    pub event_index: usize,
+    pub reason: String, // "no_candidate" | "ambiguous" | "unknown_character"
+    pub candidates: Vec<String>, // Character IDs
+}
+
+struct NameEntry<'a> {
+    name: String,
+    character: &'a Character,
+    is_alias: bool,
+}
+
+fn build_name_table(characters: &[Character]) -> Vec<NameEntry<'_>> {
+    let mut entries = Vec::new();
+
+    for character in characters {
+        let full_name = character.name.trim();
+        if full_name.is_empty() {
+            continue;
+        }
+        entries.push(NameEntry { name: full_name.to_string(), character, is_alias: false });
+
+        // First name of a multi-word name counts as an alias ("Mira" for "Mira Voss")
+        if let Some(first) = full_name.split_whitespace().next() {
+            if first != full_name {
+                entries.push(NameEntry { name: first.to_string(), character, is_alias: true });
+            }
+        }
+
+        for (key, value) in &character.fields {
+            if !ALIAS_FIELD_KEYS.contains(&key.trim().to_lowercase().as_str()) {
+                continue;
+            }
+            for alias in value.split([',', ';']) {
+                let alias = alias.trim();
+                if !alias.is_empty() {
+                    entries.push(NameEntry { name: alias.to_string(), character, is_alias: true });
+                }
+            }
+        }
+    }
+
+    // Longest names first so "Mira Voss" wins over "Mira"
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.name.len()));
+    entries
+}
+
+// Position of `needle` in `haystack` as a whole word. Case-sensitive on purpose so names
+// like "Will" or "Hope" do not match ordinary words.
+fn find_word(haystack: &str, needle: &str) -> Option<usize> {
+    if needle.is_empty() {
+        return None;
+    }
+
+    let mut start = 0;
+    while let Some(offset) = haystack[start..].find(needle) {
+        let index = start + offset;
+        if is_word_boundary(haystack, index, needle.len()) {
+            return Some(index);
+        }
+        start = index + needle.len();
+    }
+    None
+}
+
+fn is_word_boundary(text: &str, start: usize, len: usize) -> bool {
+    let before = text[..start].chars().next_back();
+    let after = text[start + len..].chars().next();
+    !before.map_or(false, |c| c.is_alphanumeric()) && !after.map_or(false, |c| c.is_alphanumeric())
+}
+
+// Every whole-word mention of a character (name, first name or alias) in `text`, ordered by
+// position. Overlapping matches keep the longest name.
+pub fn character_mentions<'a>(text: &str, characters: &'a [Character]) -> Vec<(usize, usize, &'a Character)> {
+    let table = build_name_table(characters);
+    let mut mentions: Vec<(usize, usize, &Character)> = Vec::new();
+
+    for entry in &table {
+        let mut start = 0;
+        while let Some(offset) = find_word(&text[start..], &entry.name) {
+            let index = start + offset;
+            let end = index + entry.name.len();
+            if !mentions.iter().any(|(s, e, _)| index < *e && end > *s) {
+                mentions.push((index, end, entry.character));
+            }
+            start = end;
+        }
+    }
+
+    mentions.sort_by_key(|(start, _, _)| *start);
+    mentions
+}
+
+// Extract an explicit speaker name from text such as "Mira:" or "Captain Hale says"
+pub fn parse_explicit_speaker(text: &str) -> Option<String> {
+    let text = text.trim().trim_end_matches(',').trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    if let Some(label) = text.strip_suffix(':') {
+        let label = label.trim();
+        let words = label.split_whitespace().count();
+        if words > 0 && words <= 4 {
+            return Some(label.to_string());
+        }
+    }
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let verb_index = words.iter().rposition(|w| {
+        let w = w.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+        SPEECH_VERBS.contains(&w.as_str())
+    })?;
+
+    // Take the run of capitalised words directly before the verb
+    let name_words: Vec<&str> = words[..verb_index]
+        .iter()
+        .rev()
+        .take_while(|w| w.chars().next().map_or(false, |c| c.is_uppercase()))
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()))
+        .collect();
+
+    if name_words.is_empty() {
+        return None;
+    }
+    Some(name_words.into_iter().rev().collect::<Vec<_>>().join(" "))
+}
+
+// Match a parsed name against the table, dropping leading words so "Then Mira" still finds "Mira"
+fn lookup_name<'a>(name: &str, table: &'a [NameEntry<'a>]) -> Option<&'a NameEntry<'a>> {
+    let words: Vec<&str> = name.split_whitespace().collect();
+    (0..words.len()).find_map(|skip| {
+        let candidate = words[skip..].join(" ");
+        table.iter().find(|entry| entry.name.eq_ignore_ascii_case(&candidate))
+    })
+}
+
+// Resolve speakers for every dialogue event; returns the events that could not be attributed
+// with confidence. Manual attributions are left untouched.
+pub fn resolve_speakers(timeline: &mut [TimelineEvent], characters: &[Character]) -> Vec<UnresolvedSpeaker> {
+    let table = build_name_table(characters);
+    let mut unresolved = Vec::new();
+
+    for (event_index, event) in timeline.iter_mut().enumerate() {
+        if event.dialogue.is_none() {
+            continue;
+        }
+        if matches!(&event.speaker, Some(s) if s.method == AttributionMethod::Manual) {
+            continue;
+        }
+
+        // 1. Explicit attribution in the text. A name that is no character's, such as a sentence
+        // opener ("Suddenly shouts") or someone not in the cast yet, leaves the speaker unset.
+        if let Some(name) = parse_explicit_speaker(&event.text) {
+            match lookup_name(&name, &table) {
+                Some(entry) => {
+                    event.speaker = Some(SpeakerAttribution {
+                        name: entry.character.name.clone(),
+                        character_id: Some(entry.character.id.clone()),
+                        confidence: if entry.is_alias { 0.9 } else { 1.0 },
+                        method: AttributionMethod::Explicit,
+                    });
+                }
+                None => {
+                    event.speaker = None;
+                    unresolved.push(UnresolvedSpeaker {
+                        event_index,
+                        reason: "unknown_character".to_string(),
+                        candidates: Vec::new(),
+                    });
+                }
+            }
+            continue;
+        }
+
+        // 2. Fall back to characters mentioned in the event text, earliest mention first
+        let mut mentions: Vec<(usize, &NameEntry)> = Vec::new();
+        for entry in &table {
+            if mentions.iter().any(|(_, m)| m.character.id == entry.character.id) {
+                continue;
+            }
+            if let Some(position) = find_word(&event.text, &entry.name) {
+                mentions.push((position, entry));
+            }
+        }
+        mentions.sort_by_key(|(position, _)| *position);
+
+        match mentions.as_slice() {
+            [] => {
+                event.speaker = None;
+                unresolved.push(UnresolvedSpeaker {
+                    event_index,
+                    reason: "no_candidate".to_string(),
+                    candidates: Vec::new(),
+                });
+            }
+            [(_, entry)] => {
+                event.speaker = Some(SpeakerAttribution {
+                    name: entry.character.name.clone(),
+                    character_id: Some(entry.character.id.clone()),
+                    confidence: if entry.is_alias { 0.6 } else { 0.7 },
+                    method: if entry.is_alias { AttributionMethod::AliasMatch } else { AttributionMethod::NameMatch },
+                });
+            }
+            [(_, first), ..] => {
+                // The subject of the sentence usually comes first
+                event.speaker = Some(SpeakerAttribution {
+                    name: first.character.name.clone(),
+                    character_id: Some(first.character.id.clone()),
+                    confidence: 0.4,
+                    method: if first.is_alias { AttributionMethod::AliasMatch } else { AttributionMethod::NameMatch },
+                });
+                unresolved.push(UnresolvedSpeaker {
+                    event_index,
+                    reason: "ambiguous".to_string(),
+                    candidates: mentions.iter().map(|(_, m)| m.character.id.clone()).collect(),
+                });
+            }
+        }
+    }
+
+    unresolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{character, event};
+
+    fn cast() -> Vec<Character> {
+        let mut hale = character("c2", "Captain Hale");
+        hale.fields.insert("Nicknames".to_string(), "the Old Man; Cap".to_string());
+        vec![character("c1", "Mira Voss"), hale]
+    }
+
+    fn line(text: &str) -> TimelineEvent {
+        TimelineEvent { dialogue: Some("Not the ferry.".to_string()), ..event(text) }
+    }
+
+    fn speaker(event: &TimelineEvent) -> Option<(&str, f64, AttributionMethod)> {
+        event.speaker.as_ref().map(|s| (s.character_id.as_deref().unwrap_or("-"), s.confidence, s.method.clone()))
+    }
+
+    #[test]
+    fn explicit_speakers_come_from_labels_and_speech_verbs() {
+        assert_eq!(parse_explicit_speaker("Mira Voss:").as_deref(), Some("Mira Voss"));
+        assert_eq!(parse_explicit_speaker("Then Captain Hale said,").as_deref(), Some("Then Captain Hale"));
+        assert_eq!(parse_explicit_speaker("the harbourmaster mutters"), None);
+        assert_eq!(parse_explicit_speaker("A long and winding preamble that goes on:"), None);
+    }
+
+    #[test]
+    fn speakers_resolve_by_label_name_and_alias() {
+        let mut timeline = vec![
+            line("Then Captain Hale said"),
+            line("Cap whispers"),
+            line("Jonah shouts"),
+            line("Mira looks at the water"),
+            line("Mira Voss turns to Captain Hale"),
+            line("Rain on the deck"),
+            event("Mira walks away"),
+        ];
+        let unresolved = resolve_speakers(&mut timeline, &cast());
+
+        assert_eq!(speaker(&timeline[0]), Some(("c2", 1.0, AttributionMethod::Explicit)));
+        assert_eq!(speaker(&timeline[1]), Some(("c2", 0.9, AttributionMethod::Explicit)));
+        assert!(timeline[2].speaker.is_none());
+        assert_eq!(speaker(&timeline[3]), Some(("c1", 0.6, AttributionMethod::AliasMatch)));
+        assert_eq!(speaker(&timeline[4]), Some(("c1", 0.4, AttributionMethod::NameMatch)));
+        assert!(timeline[5].speaker.is_none() && timeline[6].speaker.is_none());
+
+        let reasons: Vec<(usize, &str)> = unresolved.iter().map(|u| (u.event_index, u.reason.as_str())).collect();
+        assert_eq!(reasons, vec![(2, "unknown_character"), (4, "ambiguous"), (5, "no_candidate")]);
+        assert_eq!(unresolved[1].candidates, vec!["c1".to_string(), "c2".to_string()]);
+    }
+
+    #[test]
+    fn sentence_openers_and_unknown_names_are_not_speakers() {
+        let mut timeline = vec![line("Suddenly shouts"), line("Then whispers"), line("Suddenly Jonah yells at Mira"), line("Suddenly Mira shouts")];
+        let unresolved = resolve_speakers(&mut timeline, &cast());
+
+        assert!(timeline[..3].iter().all(|event| event.speaker.is_none()));
+        assert_eq!(speaker(&timeline[3]), Some(("c1", 0.9, AttributionMethod::Explicit)));
+        let reasons: Vec<&str> = unresolved.iter().map(|u| u.reason.as_str()).collect();
+        assert_eq!(reasons, vec!["unknown_character"; 3]);
+    }
+
+    #[test]
+    fn manual_speakers_are_kept() {
+        let manual = SpeakerAttribution { name: "Mira Voss".to_string(), character_id: Some("c1".to_string()), confidence: 1.0, method: AttributionMethod::Manual };
+        let mut timeline = vec![TimelineEvent { speaker: Some(manual), ..line("Captain Hale says") }];
+        assert!(resolve_speakers(&mut timeline, &cast()).is_empty());
+        assert_eq!(speaker(&timeline[0]), Some(("c1", 1.0, AttributionMethod::Manual)));
+    }
+
+    #[test]
+    fn mentions_prefer_the_longest_name_and_whole_words() {
+        let cast = cast();
+        let text = "Mira Voss met Mira and Captain Hale near Miramar";
+        let mentions: Vec<(&str, &str)> = character_mentions(text, &cast).into_iter().map(|(s, e, c)| (&text[s..e], c.id.as_str())).collect();
+        assert_eq!(mentions, vec![("Mira Voss", "c1"), ("Mira", "c1"), ("Captain Hale", "c2")]);
+    }
+}
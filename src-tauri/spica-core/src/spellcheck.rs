@@ -0,0 +1,470 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ProjectData;
+
+const MAX_SUGGESTIONS: usize = 5;
+// Words longer than this only get single-edit suggestions; two edits get expensive
+const MAX_TWO_EDIT_LENGTH: usize = 12;
+
+// Words the writer has accepted for this project, on top of names seeded from the project itself
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct CustomDictionary {
+    #[serde(default)]
+    pub words: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Misspelling {
+    pub word: String,
+    pub start: usize, // character offsets, end exclusive
+    pub end: usize,
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FlagMode {
+    Char, // default: one character per flag
+    Long, // FLAG long: two characters per flag
+    Num, // FLAG num: comma-separated numbers
+}
+
+// One character position of an affix condition: `.`, `[abc]`, `[^abc]` or a literal
+enum CondPart {
+    Any,
+    OneOf(Vec<char>),
+    NoneOf(Vec<char>),
+}
+
+impl CondPart {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            CondPart::Any => true,
+            CondPart::OneOf(chars) => chars.contains(&c),
+            CondPart::NoneOf(chars) => !chars.contains(&c),
+        }
+    }
+}
+
+struct AffixRule {
+    strip: String,
+    add: String,
+    condition: Vec<CondPart>,
+}
+
+struct Affix {
+    flag: String,
+    prefix: bool,
+    cross_product: bool,
+    rules: Vec<AffixRule>,
+}
+
+// Hunspell .aff/.dic pair expanded into its full word list
+pub struct Dictionary {
+    words: HashSet<String>,
+    try_chars: Vec<char>,
+    replacements: Vec<(String, String)>,
+}
+
+fn parse_condition(condition: &str) -> Vec<CondPart> {
+    if condition == "." {
+        return Vec::new();
+    }
+    let mut parts = Vec::new();
+    let mut chars = condition.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => parts.push(CondPart::Any),
+            '[' => {
+                let mut set: Vec<char> = Vec::new();
+                let mut negated = false;
+                for c in chars.by_ref() {
+                    match c {
+                        ']' => break,
+                        '^' if set.is_empty() && !negated => negated = true,
+                        c => set.push(c),
+                    }
+                }
+                parts.push(if negated { CondPart::NoneOf(set) } else { CondPart::OneOf(set) });
+            }
+            c => parts.push(CondPart::OneOf(vec![c])),
+        }
+    }
+    parts
+}
+
+fn split_flags(flags: &str, mode: FlagMode) -> Vec<String> {
+    match mode {
+        FlagMode::Char => flags.chars().map(|c| c.to_string()).collect(),
+        FlagMode::Long => {
+            let chars: Vec<char> = flags.chars().collect();
+            chars.chunks(2).map(|pair| pair.iter().collect()).collect()
+        }
+        FlagMode::Num => flags.split(',').map(|f| f.trim().to_string()).filter(|f| !f.is_empty()).collect(),
+    }
+}
+
+impl AffixRule {
+    fn apply(&self, word: &str, prefix: bool) -> Option<String> {
+        let chars: Vec<char> = word.chars().collect();
+        let n = self.condition.len();
+        if chars.len() < n || chars.len() <= self.strip.chars().count() {
+            return None;
+        }
+        let window = if prefix { &chars[..n] } else { &chars[chars.len() - n..] };
+        if !self.condition.iter().zip(window).all(|(part, c)| part.matches(*c)) {
+            return None;
+        }
+        if prefix {
+            let rest = word.strip_prefix(self.strip.as_str())?;
+            Some(format!("{}{}", self.add, rest))
+        } else {
+            let rest = word.strip_suffix(self.strip.as_str())?;
+            Some(format!("{}{}", rest, self.add))
+        }
+    }
+}
+
+impl Dictionary {
+    pub fn load(aff_path: &Path, dic_path: &Path) -> io::Result<Self> {
+        let aff = String::from_utf8_lossy(&fs::read(aff_path)?).into_owned();
+        let dic = String::from_utf8_lossy(&fs::read(dic_path)?).into_owned();
+        Ok(Self::parse(&aff, &dic))
+    }
+
+    pub fn parse(aff: &str, dic: &str) -> Self {
+        let mut mode = FlagMode::Char;
+        let mut try_chars = Vec::new();
+        let mut replacements = Vec::new();
+        let mut affixes: Vec<Affix> = Vec::new();
+
+        for line in aff.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["FLAG", "long", ..] => mode = FlagMode::Long,
+                ["FLAG", "num", ..] => mode = FlagMode::Num,
+                ["TRY", chars, ..] => try_chars = chars.chars().collect(),
+                ["REP", from, to, ..] => replacements.push((from.replace('_', " "), to.replace('_', " "))),
+                [kind @ ("PFX" | "SFX"), flag, cross, count] if count.parse::<usize>().is_ok() => affixes.push(Affix {
+                    flag: flag.to_string(),
+                    prefix: *kind == "PFX",
+                    cross_product: *cross == "Y",
+                    rules: Vec::new(),
+                }),
+                [kind @ ("PFX" | "SFX"), flag, strip, add, rest @ ..] => {
+                    // Continuation flags on the affix ("ing/S") are not expanded further
+                    let add = add.split('/').next().unwrap_or_default();
+                    let rule = AffixRule {
+                        strip: if *strip == "0" { String::new() } else { strip.to_string() },
+                        add: if add == "0" { String::new() } else { add.to_string() },
+                        condition: parse_condition(rest.first().copied().unwrap_or(".")),
+                    };
+                    let prefix = *kind == "PFX";
+                    if let Some(affix) = affixes.iter_mut().rev().find(|a| a.flag == *flag && a.prefix == prefix) {
+                        affix.rules.push(rule);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut words = HashSet::new();
+        // The first line of a .dic file is the approximate word count
+        for line in dic.lines().skip(1) {
+            let entry = match line.split_whitespace().next() {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let (stem, flags) = match entry.split_once('/') {
+                Some((stem, flags)) => (stem, split_flags(flags, mode)),
+                None => (entry, Vec::new()),
+            };
+            words.insert(stem.to_string());
+
+            let applicable: Vec<&Affix> = affixes.iter().filter(|a| flags.contains(&a.flag)).collect();
+            let mut suffixed: Vec<String> = Vec::new();
+            for affix in applicable.iter().filter(|a| !a.prefix) {
+                for rule in &affix.rules {
+                    if let Some(form) = rule.apply(stem, false) {
+                        if affix.cross_product {
+                            suffixed.push(form.clone());
+                        }
+                        words.insert(form);
+                    }
+                }
+            }
+            for affix in applicable.iter().filter(|a| a.prefix) {
+                for rule in &affix.rules {
+                    if let Some(form) = rule.apply(stem, true) {
+                        words.insert(form);
+                    }
+                    if affix.cross_product {
+                        for form in &suffixed {
+                            if let Some(form) = rule.apply(form, true) {
+                                words.insert(form);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if try_chars.is_empty() {
+            try_chars = "esianrtolcdugmphbyfvkwzxjq".chars().collect();
+        }
+        Self { words, try_chars, replacements }
+    }
+
+    // Exact form, or a lowercase dictionary word written Capitalised or in CAPITALS
+    fn knows(&self, word: &str) -> bool {
+        if self.words.contains(word) {
+            return true;
+        }
+        let lower = word.to_lowercase();
+        let mut chars = word.chars();
+        let capitalised = chars.next().map_or(false, char::is_uppercase) && chars.all(|c| !c.is_uppercase());
+        let all_caps = word.chars().all(|c| !c.is_lowercase());
+        if (capitalised || all_caps) && self.words.contains(&lower) {
+            return true;
+        }
+        // "PARIS" for "Paris"
+        if all_caps {
+            let mut title = String::new();
+            for (i, c) in lower.chars().enumerate() {
+                if i == 0 {
+                    title.extend(c.to_uppercase());
+                } else {
+                    title.push(c);
+                }
+            }
+            return self.words.contains(&title);
+        }
+        false
+    }
+
+    fn edits(&self, word: &str) -> Vec<String> {
+        let chars: Vec<char> = word.chars().collect();
+        let mut edits = Vec::new();
+        for i in 0..chars.len() {
+            edits.push(chars[..i].iter().chain(&chars[i + 1..]).collect());
+        }
+        for i in 0..chars.len().saturating_sub(1) {
+            let mut swapped = chars.clone();
+            swapped.swap(i, i + 1);
+            edits.push(swapped.into_iter().collect());
+        }
+        for &c in &self.try_chars {
+            for i in 0..chars.len() {
+                if chars[i] != c {
+                    let mut replaced = chars.clone();
+                    replaced[i] = c;
+                    edits.push(replaced.into_iter().collect());
+                }
+            }
+            for i in 0..=chars.len() {
+                let mut inserted = chars.clone();
+                inserted.insert(i, c);
+                edits.push(inserted.into_iter().collect());
+            }
+        }
+        edits
+    }
+
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let lower = word.to_lowercase();
+        let mut suggestions: Vec<String> = Vec::new();
+        let push = |candidate: String, suggestions: &mut Vec<String>| {
+            if self.knows(&candidate) && !suggestions.contains(&candidate) {
+                suggestions.push(candidate);
+            }
+        };
+
+        // Common misspellings from the REP table first
+        for (from, to) in &self.replacements {
+            for (index, _) in lower.match_indices(from.as_str()) {
+                push(format!("{}{}{}", &lower[..index], to, &lower[index + from.len()..]), &mut suggestions);
+            }
+        }
+        let first_edits = self.edits(&lower);
+        for edit in &first_edits {
+            push(edit.clone(), &mut suggestions);
+        }
+        // Missing space: "thekey" -> "the key"
+        let chars: Vec<char> = lower.chars().collect();
+        for i in 1..chars.len() {
+            let (left, right): (String, String) = (chars[..i].iter().collect(), chars[i..].iter().collect());
+            let split = format!("{} {}", left, right);
+            if self.knows(&left) && self.knows(&right) && !suggestions.contains(&split) {
+                suggestions.push(split);
+            }
+        }
+        if suggestions.is_empty() && chars.len() <= MAX_TWO_EDIT_LENGTH {
+            'outer: for edit in &first_edits {
+                for second in self.edits(edit) {
+                    push(second, &mut suggestions);
+                    if suggestions.len() >= MAX_SUGGESTIONS {
+                        break 'outer;
+                    }
+                }
+            }
+        }
+
+        suggestions.truncate(MAX_SUGGESTIONS);
+        // Match the capitalisation of the misspelling
+        if word.chars().next().map_or(false, char::is_uppercase) {
+            for suggestion in suggestions.iter_mut() {
+                let mut chars = suggestion.chars();
+                if let Some(first) = chars.next() {
+                    *suggestion = first.to_uppercase().chain(chars).collect();
+                }
+            }
+        }
+        suggestions
+    }
+}
+
+// Language codes such as en_US or pt-BR; anything else could name a path outside the dictionary folders
+pub fn is_language_name(language: &str) -> bool {
+    !language.is_empty() && language.chars().all(|c| c.is_ascii_alphabetic() || c == '_' || c == '-')
+}
+
+// <dir>/<language>.aff and .dic in the first directory that has both
+pub fn find_dictionary(dirs: &[PathBuf], language: &str) -> Option<(PathBuf, PathBuf)> {
+    if !is_language_name(language) {
+        return None;
+    }
+    dirs.iter()
+        .map(|dir| (dir.join(format!("{}.aff", language)), dir.join(format!("{}.dic", language))))
+        .find(|(aff, dic)| aff.exists() && dic.exists())
+}
+
+pub fn available_languages(dirs: &[PathBuf]) -> Vec<String> {
+    let mut languages: Vec<String> = dirs
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "dic") && path.with_extension("aff").exists())
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .collect();
+    languages.sort();
+    languages.dedup();
+    languages
+}
+
+// Lowercased words the project itself vouches for: character names, star titles, scene names
+pub fn project_words(project: &ProjectData) -> HashSet<String> {
+    let mut words: HashSet<String> = project.custom_dictionary.words.iter().map(|w| w.to_lowercase()).collect();
+    let names = project
+        .characters
+        .values()
+        .map(|c| &c.name)
+        .chain(project.stars.values().map(|s| &s.title))
+        .chain(project.scenes.values().map(|s| &s.name));
+    for name in names {
+        for word in name.split(|c: char| !c.is_alphanumeric() && c != '\'' && c != '’') {
+            let word = word.trim_matches(['\'', '’']);
+            if !word.is_empty() {
+                words.insert(word.to_lowercase());
+            }
+        }
+    }
+    words
+}
+
+pub fn check(dictionary: &Dictionary, custom: &HashSet<String>, text: &str) -> Vec<Misspelling> {
+    let mut misspellings = Vec::new();
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].is_alphanumeric() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len()
+            && (chars[i].is_alphanumeric()
+                || ((chars[i] == '\'' || chars[i] == '’') && i + 1 < chars.len() && chars[i + 1].is_alphabetic()))
+        {
+            i += 1;
+        }
+        let word: String = chars[start..i].iter().collect();
+        if word.chars().any(|c| c.is_numeric()) || word.chars().count() < 2 {
+            continue;
+        }
+
+        let normalized = word.replace('’', "'");
+        let base = normalized.strip_suffix("'s").unwrap_or(&normalized);
+        let known = dictionary.knows(&normalized)
+            || dictionary.knows(base)
+            || custom.contains(&normalized.to_lowercase())
+            || custom.contains(&base.to_lowercase());
+        if !known {
+            misspellings.push(Misspelling { suggestions: dictionary.suggest(base), word, start, end: i });
+        }
+    }
+    misspellings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{character, temp_dir};
+
+    const AFF: &str = "TRY esiantr\nREP 1\nREP ie ei\nSFX S Y 1\nSFX S 0 s .\nPFX U Y 1\nPFX U 0 un .\n";
+    const DIC: &str = "6\nwalk/S\ntie/US\nthe\nin\nreceive\nParis\n";
+
+    fn words(misspellings: &[Misspelling]) -> Vec<&str> {
+        misspellings.iter().map(|m| m.word.as_str()).collect()
+    }
+
+    #[test]
+    fn affixes_and_capitals_are_accepted() {
+        let dictionary = Dictionary::parse(AFF, DIC);
+        let found = check(&dictionary, &HashSet::new(), "Walks, UNTIES the PARIS walk in Paris; paris walkz");
+        assert_eq!(words(&found), vec!["paris", "walkz"]);
+        assert_eq!((found[1].start, found[1].end), (45, 50), "offsets count characters");
+    }
+
+    #[test]
+    fn suggestions_try_replacements_then_edits_and_keep_capitals() {
+        let dictionary = Dictionary::parse(AFF, DIC);
+        assert_eq!(dictionary.suggest("recieve")[0], "receive");
+        assert_eq!(dictionary.suggest("Teh"), vec!["The"]);
+        assert!(dictionary.suggest("thewalk").contains(&"the walk".to_string()));
+    }
+
+    #[test]
+    fn project_names_and_custom_words_are_accepted() {
+        let dictionary = Dictionary::parse(AFF, DIC);
+        let mut project = ProjectData::new("Test", 0);
+        project.characters.insert("c1".to_string(), character("c1", "Mira Olsen"));
+        project.custom_dictionary.words.push("Skerry".to_string());
+        let found = check(&dictionary, &project_words(&project), "Mira’s walk, the skerry with Olsen and Jonah");
+        assert_eq!(words(&found), vec!["with", "and", "Jonah"]);
+    }
+
+    #[test]
+    fn only_plain_language_names_reach_the_file_system() {
+        let dir = temp_dir("spellcheck-languages");
+        let dictionaries = dir.join("dictionaries");
+        fs::create_dir_all(&dictionaries).unwrap();
+        for name in ["en_US.aff", "en_US.dic", "pt-BR.aff", "pt-BR.dic", "orphan.dic"] {
+            fs::write(dictionaries.join(name), DIC).unwrap();
+        }
+        fs::write(dir.join("secret.aff"), AFF).unwrap();
+        fs::write(dir.join("secret.dic"), DIC).unwrap();
+        let dirs = vec![dictionaries];
+
+        assert!(find_dictionary(&dirs, "en_US").is_some());
+        assert!(find_dictionary(&dirs, "../secret").is_none());
+        assert!(["", "..", "en/US", "en US", "en_US.dic"].iter().all(|name| !is_language_name(name)));
+        assert_eq!(available_languages(&dirs), vec!["en_US".to_string(), "pt-BR".to_string()]);
+        let _ = fs::remove_dir_all(dir);
+    }
+}
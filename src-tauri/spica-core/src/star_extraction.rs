@@ -0,0 +1,310 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::openai_client::extract_json_object;
+use crate::prompts;
+use crate::retrieval::{similarity, tokenize};
+use crate::speaker::character_mentions;
+use crate::{Character, DraftTab, ProjectData, Star, StarSourceEvent, StarTags, STAR_SCOPES};
+
+// Suggestions at least this similar to an existing star are dropped as duplicates
+const DUPLICATE_SIMILARITY: f64 = 0.6;
+// Above this they are kept but point at the similar star
+const RELATED_SIMILARITY: f64 = 0.35;
+
+const PROMISE_MARKERS: &[&str] = &["promise", "promises", "promised", "swear", "swears", "swore", "vow", "vows", "vowed"];
+const FORESHADOWING_MARKERS: &[&str] = &["someday", "one day", "little did", "would later", "soon enough", "before long"];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionKind {
+    Fact,
+    Promise,
+    Foreshadowing,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SimilarStar {
+    pub star_id: String,
+    pub title: String,
+    pub similarity: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StarSuggestion {
+    pub star: Star, // ready to insert if the writer accepts it
+    pub kind: SuggestionKind,
+    pub event_index: Option<usize>,
+    pub similar_star: Option<SimilarStar>,
+}
+
+#[derive(Deserialize)]
+struct ExtractedStar {
+    title: String,
+    body: String,
+    kind: SuggestionKind,
+    scope: Option<String>,
+    #[serde(default)]
+    characters: Vec<String>,
+    priority: Option<f64>,
+    event_index: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct ExtractedStars {
+    stars: Vec<ExtractedStar>,
+}
+
+fn default_scope(kind: SuggestionKind) -> &'static str {
+    match kind {
+        SuggestionKind::Fact => "Worldbuilding",
+        SuggestionKind::Promise | SuggestionKind::Foreshadowing => "FuturePlot",
+    }
+}
+
+// Suggestions from an event point back at it, so the star can show where it came from
+fn source_event(tab: &DraftTab, event_index: Option<usize>) -> Option<StarSourceEvent> {
+    let event = tab.timeline.get(event_index?)?;
+    Some(StarSourceEvent { tab_id: tab.id.clone(), event_id: event.id.clone()?, event_text: event.text.clone() })
+}
+
+fn new_star(tab: &DraftTab, title: String, body: String, scope: String, characters: Vec<String>, priority: f64, now_ms: u64) -> Star {
+    Star {
+        id: uuid::Uuid::new_v4().to_string(),
+        title,
+        body,
+        tags: StarTags {
+            characters,
+            scope,
+            status: "Active".to_string(),
+            custom: Vec::new(),
+            constraint_context: None,
+        },
+        priority: priority.clamp(0.0, 1.0),
+        is_checked: false,
+        origin_draft_tab_id: Some(tab.id.clone()),
+        created_at: now_ms,
+        last_used_in_prompt: None,
+        constraint_type: None,
+        applies_to_character: None,
+        situation_context: None,
+        source_event: None,
+        usage_count: 0,
+        influenced_draft_tab_ids: Vec::new(),
+    }
+}
+
+// Character IDs for names given by the model, plus anyone mentioned in the star text
+fn resolve_characters(names: &[String], text: &str, characters: &[Character]) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::new();
+    let mut push = |id: &str| {
+        if !ids.iter().any(|existing| existing == id) {
+            ids.push(id.to_string());
+        }
+    };
+    for name in names {
+        for (_, _, character) in character_mentions(name, characters) {
+            push(&character.id);
+        }
+    }
+    for (_, _, character) in character_mentions(text, characters) {
+        push(&character.id);
+    }
+    ids
+}
+
+pub fn build_prompt(project: &ProjectData, tab: &DraftTab) -> (String, String) {
+    let mut user_prompt = String::new();
+
+    let mut names: Vec<&str> = project.characters.values().map(|c| c.name.as_str()).collect();
+    names.sort();
+    if !names.is_empty() {
+        user_prompt.push_str(&format!("### CHARACTERS\n{}\n\n", names.join(", ")));
+    }
+
+    user_prompt.push_str("### TIMELINE\n");
+    for (index, event) in tab.timeline.iter().enumerate() {
+        let dialogue = event.dialogue.as_ref().map(|d| format!(" -> \"{}\"", d)).unwrap_or_default();
+        user_prompt.push_str(&format!("{}. {}{}\n", index, event.text, dialogue));
+    }
+    if !tab.descriptions.is_empty() {
+        user_prompt.push_str("\n### DESCRIPTIONS\n");
+        for description in &tab.descriptions {
+            user_prompt.push_str(&format!("- {}\n", description.text));
+        }
+    }
+    user_prompt.push('\n');
+    user_prompt.push_str(prompts::STAR_EXTRACTION_INSTRUCTIONS);
+
+    (prompts::STAR_EXTRACTION_SYSTEM.to_string(), user_prompt)
+}
+
+pub fn parse_llm_suggestions(project: &ProjectData, tab: &DraftTab, response: &str, now_ms: u64) -> Result<Vec<StarSuggestion>, String> {
+    let json = extract_json_object(response).ok_or("Extraction reply contained no JSON object")?;
+    let parsed: ExtractedStars = serde_json::from_str(json).map_err(|e| format!("Invalid extraction JSON: {}", e))?;
+    let characters: Vec<Character> = project.characters.values().cloned().collect();
+
+    Ok(parsed
+        .stars
+        .into_iter()
+        .filter(|s| !s.title.trim().is_empty() && !s.body.trim().is_empty())
+        .map(|s| {
+            let scope = s
+                .scope
+                .filter(|scope| STAR_SCOPES.contains(&scope.as_str()))
+                .unwrap_or_else(|| default_scope(s.kind).to_string());
+            let character_ids = resolve_characters(&s.characters, &format!("{} {}", s.title, s.body), &characters);
+            let event_index = s.event_index.filter(|index| *index < tab.timeline.len());
+            StarSuggestion {
+                star: Star {
+                    source_event: source_event(tab, event_index),
+                    ..new_star(tab, s.title, s.body, scope, character_ids, s.priority.unwrap_or(0.5), now_ms)
+                },
+                kind: s.kind,
+                event_index,
+                similar_star: None,
+            }
+        })
+        .collect())
+}
+
+// Offline pass: promises and foreshadowing by marker words, important descriptions as facts
+pub fn heuristic_suggestions(project: &ProjectData, tab: &DraftTab, now_ms: u64) -> Vec<StarSuggestion> {
+    let characters: Vec<Character> = project.characters.values().cloned().collect();
+    let mut suggestions = Vec::new();
+
+    for (index, event) in tab.timeline.iter().enumerate() {
+        let text = match &event.dialogue {
+            Some(dialogue) => format!("{} \"{}\"", event.text, dialogue),
+            None => event.text.clone(),
+        };
+        let lower = text.to_lowercase();
+        let words: HashSet<String> = tokenize(&text).into_iter().collect();
+
+        let kind = if PROMISE_MARKERS.iter().any(|m| words.contains(*m)) {
+            SuggestionKind::Promise
+        } else if FORESHADOWING_MARKERS.iter().any(|m| lower.contains(m)) {
+            SuggestionKind::Foreshadowing
+        } else {
+            continue;
+        };
+
+        let title: String = event.text.split_whitespace().take(6).collect::<Vec<_>>().join(" ");
+        let character_ids = resolve_characters(&[], &text, &characters);
+        suggestions.push(StarSuggestion {
+            star: Star {
+                source_event: source_event(tab, Some(index)),
+                ..new_star(tab, title, text, default_scope(kind).to_string(), character_ids, 0.6, now_ms)
+            },
+            kind,
+            event_index: Some(index),
+            similar_star: None,
+        });
+    }
+
+    for description in tab.descriptions.iter().filter(|d| d.is_important) {
+        let title: String = description.text.split_whitespace().take(6).collect::<Vec<_>>().join(" ");
+        let character_ids = resolve_characters(&[], &description.text, &characters);
+        suggestions.push(StarSuggestion {
+            star: new_star(tab, title, description.text.clone(), "CurrentScene".to_string(), character_ids, 0.5, now_ms),
+            kind: SuggestionKind::Fact,
+            event_index: None,
+            similar_star: None,
+        });
+    }
+
+    suggestions
+}
+
+// Drop suggestions that repeat an existing star or an earlier suggestion; flag near matches
+pub fn deduplicate(project: &ProjectData, suggestions: Vec<StarSuggestion>) -> Vec<StarSuggestion> {
+    let mut kept: Vec<StarSuggestion> = Vec::new();
+
+    for mut suggestion in suggestions {
+        let text = format!("{} {}", suggestion.star.title, suggestion.star.body);
+
+        let best = project
+            .stars
+            .values()
+            .map(|star| (star, similarity(&text, &format!("{} {}", star.title, star.body))))
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        if let Some((star, score)) = best {
+            if score >= DUPLICATE_SIMILARITY {
+                continue;
+            }
+            if score >= RELATED_SIMILARITY {
+                suggestion.similar_star = Some(SimilarStar {
+                    star_id: star.id.clone(),
+                    title: star.title.clone(),
+                    similarity: score,
+                });
+            }
+        }
+
+        let repeats_earlier = kept
+            .iter()
+            .any(|k| similarity(&text, &format!("{} {}", k.star.title, k.star.body)) >= DUPLICATE_SIMILARITY);
+        if !repeats_earlier {
+            kept.push(suggestion);
+        }
+    }
+
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add_scene, character};
+
+    fn project() -> ProjectData {
+        let mut project = ProjectData::new("Test", 0);
+        project.characters.insert("c1".to_string(), character("c1", "Mira"));
+        add_scene(&mut project, "s1", "Harbour", &[&["Mira swore she would find the letter", "One day the lighthouse would fall"]]);
+        project.draft_tabs.get_mut("s1-t0").unwrap().timeline[0].id = Some("e1".to_string());
+        project
+    }
+
+    #[test]
+    fn suggestions_from_events_point_back_at_them() {
+        let project = project();
+        let tab = &project.draft_tabs["s1-t0"];
+        let suggestions = heuristic_suggestions(&project, tab, 7);
+        assert_eq!(suggestions.iter().map(|s| s.kind).collect::<Vec<_>>(), vec![SuggestionKind::Promise, SuggestionKind::Foreshadowing]);
+        let source = suggestions[0].star.source_event.as_ref().unwrap();
+        assert_eq!((source.tab_id.as_str(), source.event_id.as_str()), ("s1-t0", "e1"));
+        assert_eq!(source.event_text, "Mira swore she would find the letter");
+        assert_eq!(suggestions[0].star.tags.characters, vec!["c1".to_string()]);
+        assert!(suggestions[1].star.source_event.is_none(), "the event has no id to point at");
+
+        let response = r#"{"stars": [
+            {"title": "The letter", "body": "Mira will find it", "kind": "promise", "event_index": 0},
+            {"title": "Lighthouse", "body": "It falls", "kind": "foreshadowing", "event_index": 9}
+        ]}"#;
+        let parsed = parse_llm_suggestions(&project, tab, response, 7).unwrap();
+        assert_eq!(parsed[0].star.source_event.as_ref().map(|s| s.event_id.as_str()), Some("e1"));
+        assert_eq!(parsed[1].event_index, None);
+        assert!(parsed[1].star.source_event.is_none());
+    }
+
+    #[test]
+    fn duplicates_are_dropped_and_near_matches_flagged() {
+        let mut project = project();
+        let mut existing = Star::new("Mira swore".to_string(), "she would find the letter".to_string(), 0);
+        existing.id = "star1".to_string();
+        project.stars.insert(existing.id.clone(), existing);
+        let tab = project.draft_tabs["s1-t0"].clone();
+        let mut suggestions = heuristic_suggestions(&project, &tab, 7);
+        suggestions.push(suggestions[1].clone());
+        let mut related = suggestions[1].clone();
+        related.star.title = "Mira and the lighthouse".to_string();
+        related.star.body = "She swore the letter is in the lighthouse".to_string();
+        suggestions.push(related);
+
+        let kept = deduplicate(&project, suggestions);
+        let titles: Vec<&str> = kept.iter().map(|s| s.star.title.as_str()).collect();
+        assert_eq!(titles, vec!["One day the lighthouse would fall", "Mira and the lighthouse"]);
+        assert_eq!(kept[1].similar_star.as_ref().map(|s| s.star_id.as_str()), Some("star1"));
+    }
+}
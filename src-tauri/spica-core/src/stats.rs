@@ -0,0 +1,465 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use crate::outline::ordered_scenes;
+use crate::speaker::character_mentions;
+use crate::{Character, DraftTab, ProjectData};
+
+// History entries older than this are dropped
+const HISTORY_DAYS: i64 = 730;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct StatsOptions {
+    pub checked_only: bool, // skip unchecked TimelineEvents
+    pub include_descriptions: bool,
+}
+
+impl Default for StatsOptions {
+    fn default() -> Self {
+        Self { checked_only: true, include_descriptions: true }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WordCounts {
+    pub total: usize,
+    pub narration: usize,
+    pub dialogue: usize,
+    pub description: usize,
+}
+
+impl WordCounts {
+    fn add(&mut self, other: &WordCounts) {
+        self.total += other.total;
+        self.narration += other.narration;
+        self.dialogue += other.dialogue;
+        self.description += other.description;
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SceneStats {
+    pub scene_id: String,
+    pub name: String,
+    pub draft_tabs: usize,
+    pub events: usize,
+    pub words: WordCounts,
+    pub dialogue_ratio: f64, // dialogue words / (dialogue + narration)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CharacterStats {
+    pub character_id: String,
+    pub name: String,
+    pub dialogue_words: usize, // from speaker attribution
+    pub lines: usize,
+    pub mentions: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ManuscriptStats {
+    pub words: WordCounts,
+    pub dialogue_ratio: f64,
+    pub scenes: Vec<SceneStats>, // in outline order
+    pub workbench_words: usize, // drafts not in any scene; not part of the totals
+    pub characters: Vec<CharacterStats>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DailyCount {
+    pub date: String, // YYYY-MM-DD, local time
+    pub total_words: usize,
+    pub net_words: i64, // change since the previous recorded day
+}
+
+// Persisted with the project
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WritingProgress {
+    #[serde(default)]
+    pub daily_goal: Option<usize>,
+    #[serde(default)]
+    pub history: Vec<DailyCount>, // oldest first, one entry per day written
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProgressReport {
+    pub today: DailyCount,
+    pub daily_goal: Option<usize>,
+    pub goal_met_today: bool,
+    pub current_streak: usize, // consecutive days meeting the goal (or writing anything without one)
+    pub longest_streak: usize,
+    pub average_last_7_days: f64,
+    pub average_last_30_days: f64,
+}
+
+pub fn count_words(text: &str) -> usize {
+    text.split_whitespace().filter(|w| w.chars().any(char::is_alphanumeric)).count()
+}
+
+fn tab_words(tab: &DraftTab, options: &StatsOptions) -> (WordCounts, usize) {
+    let mut counts = WordCounts::default();
+    let mut events = 0;
+    for event in tab.timeline.iter().filter(|e| e.checked || !options.checked_only) {
+        events += 1;
+        counts.narration += count_words(&event.text);
+        counts.dialogue += event.dialogue.as_deref().map_or(0, count_words);
+    }
+    if options.include_descriptions {
+        counts.description = tab.descriptions.iter().map(|d| count_words(&d.text)).sum();
+    }
+    counts.total = counts.narration + counts.dialogue + counts.description;
+    (counts, events)
+}
+
+fn dialogue_ratio(words: &WordCounts) -> f64 {
+    let spoken_or_told = words.dialogue + words.narration;
+    if spoken_or_told == 0 {
+        0.0
+    } else {
+        words.dialogue as f64 / spoken_or_told as f64
+    }
+}
+
+pub fn compute(project: &ProjectData, options: &StatsOptions) -> ManuscriptStats {
+    let characters: Vec<Character> = project.characters.values().cloned().collect();
+    let mut words = WordCounts::default();
+    let mut scenes = Vec::new();
+    let mut per_character: HashMap<String, CharacterStats> = characters
+        .iter()
+        .map(|c| {
+            let stats = CharacterStats { character_id: c.id.clone(), name: c.name.clone(), dialogue_words: 0, lines: 0, mentions: 0 };
+            (c.id.clone(), stats)
+        })
+        .collect();
+
+    for scene in ordered_scenes(project) {
+        let tabs: Vec<&DraftTab> = scene.draft_tab_ids.iter().filter_map(|id| project.draft_tabs.get(id)).collect();
+        let mut scene_words = WordCounts::default();
+        let mut events = 0;
+
+        for tab in &tabs {
+            let (counts, tab_events) = tab_words(tab, options);
+            scene_words.add(&counts);
+            events += tab_events;
+
+            for event in tab.timeline.iter().filter(|e| e.checked || !options.checked_only) {
+                let speaker_id = event.speaker.as_ref().and_then(|s| s.character_id.as_ref());
+                if let (Some(dialogue), Some(stats)) = (&event.dialogue, speaker_id.and_then(|id| per_character.get_mut(id))) {
+                    stats.dialogue_words += count_words(dialogue);
+                    stats.lines += 1;
+                }
+                let text = match &event.dialogue {
+                    Some(dialogue) => format!("{} {}", event.text, dialogue),
+                    None => event.text.clone(),
+                };
+                for (_, _, character) in character_mentions(&text, &characters) {
+                    if let Some(stats) = per_character.get_mut(&character.id) {
+                        stats.mentions += 1;
+                    }
+                }
+            }
+        }
+
+        words.add(&scene_words);
+        scenes.push(SceneStats {
+            scene_id: scene.id.clone(),
+            name: scene.name.clone(),
+            draft_tabs: tabs.len(),
+            events,
+            dialogue_ratio: dialogue_ratio(&scene_words),
+            words: scene_words,
+        });
+    }
+
+    let workbench_words = project
+        .draft_tabs
+        .values()
+        .filter(|tab| tab.scene_id.is_none())
+        .map(|tab| tab_words(tab, options).0.total)
+        .sum();
+
+    let mut characters: Vec<CharacterStats> = per_character.into_values().collect();
+    characters.sort_by(|a, b| b.dialogue_words.cmp(&a.dialogue_words).then_with(|| a.name.cmp(&b.name)));
+
+    ManuscriptStats { dialogue_ratio: dialogue_ratio(&words), words, scenes, workbench_words, characters }
+}
+
+fn parse_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+fn met_goal(day: &DailyCount, goal: Option<usize>) -> bool {
+    match goal {
+        Some(goal) => day.net_words >= goal as i64,
+        None => day.net_words > 0,
+    }
+}
+
+// Record today's total; the first entry of the day is measured against the last earlier day
+pub fn record_day(progress: &mut WritingProgress, total_words: usize, today: NaiveDate) {
+    let date = today.format("%Y-%m-%d").to_string();
+    progress.history.retain(|d| parse_date(&d.date).map_or(false, |day| (today - day).num_days() <= HISTORY_DAYS));
+
+    let previous_total = progress
+        .history
+        .iter()
+        .rev()
+        .find(|d| d.date < date)
+        .map(|d| d.total_words)
+        .unwrap_or(total_words);
+    let net_words = total_words as i64 - previous_total as i64;
+
+    match progress.history.iter_mut().find(|d| d.date == date) {
+        Some(entry) => {
+            entry.total_words = total_words;
+            entry.net_words = net_words;
+        }
+        None => {
+            progress.history.push(DailyCount { date, total_words, net_words });
+            progress.history.sort_by(|a, b| a.date.cmp(&b.date));
+        }
+    }
+}
+
+pub fn report(progress: &WritingProgress, today: NaiveDate) -> ProgressReport {
+    let date = today.format("%Y-%m-%d").to_string();
+    let by_date: HashMap<NaiveDate, &DailyCount> =
+        progress.history.iter().filter_map(|d| parse_date(&d.date).map(|day| (day, d))).collect();
+    let met = |day: NaiveDate| by_date.get(&day).map_or(false, |d| met_goal(d, progress.daily_goal));
+
+    // A streak still counts if today has not been written yet
+    let mut day = if met(today) { today } else { today - Duration::days(1) };
+    let mut current_streak = 0;
+    while met(day) {
+        current_streak += 1;
+        day -= Duration::days(1);
+    }
+
+    let mut longest_streak = 0;
+    let mut run = 0;
+    let mut last: Option<NaiveDate> = None;
+    for entry in &progress.history {
+        let day = match parse_date(&entry.date) {
+            Some(day) => day,
+            None => continue,
+        };
+        let consecutive = last.map_or(false, |last| day - last == Duration::days(1));
+        run = match (met_goal(entry, progress.daily_goal), consecutive) {
+            (false, _) => 0,
+            (true, true) => run + 1,
+            (true, false) => 1,
+        };
+        longest_streak = longest_streak.max(run);
+        last = Some(day);
+    }
+
+    // Days without an entry count as zero words
+    let average = |days: i64| {
+        let written: i64 = (0..days)
+            .filter_map(|offset| by_date.get(&(today - Duration::days(offset))))
+            .map(|d| d.net_words.max(0))
+            .sum();
+        written as f64 / days as f64
+    };
+
+    let today_entry = progress
+        .history
+        .iter()
+        .find(|d| d.date == date)
+        .cloned()
+        .unwrap_or_else(|| {
+            let total_words = progress.history.last().map_or(0, |d| d.total_words);
+            DailyCount { date, total_words, net_words: 0 }
+        });
+
+    ProgressReport {
+        goal_met_today: met_goal(&today_entry, progress.daily_goal),
+        today: today_entry,
+        daily_goal: progress.daily_goal,
+        current_streak,
+        longest_streak,
+        average_last_7_days: average(7),
+        average_last_30_days: average(30),
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv(header: &[&str], rows: Vec<Vec<String>>) -> String {
+    let mut out = header.join(",");
+    out.push('\n');
+    for row in rows {
+        out.push_str(&row.iter().map(|v| csv_field(v)).collect::<Vec<_>>().join(","));
+        out.push('\n');
+    }
+    out
+}
+
+pub fn scenes_csv(stats: &ManuscriptStats) -> String {
+    let rows = stats
+        .scenes
+        .iter()
+        .map(|s| {
+            vec![
+                s.scene_id.clone(),
+                s.name.clone(),
+                s.draft_tabs.to_string(),
+                s.events.to_string(),
+                s.words.total.to_string(),
+                s.words.narration.to_string(),
+                s.words.dialogue.to_string(),
+                s.words.description.to_string(),
+                format!("{:.3}", s.dialogue_ratio),
+            ]
+        })
+        .collect();
+    csv(
+        &["scene_id", "name", "draft_tabs", "events", "words", "narration", "dialogue", "description", "dialogue_ratio"],
+        rows,
+    )
+}
+
+pub fn characters_csv(stats: &ManuscriptStats) -> String {
+    let rows = stats
+        .characters
+        .iter()
+        .map(|c| {
+            vec![
+                c.character_id.clone(),
+                c.name.clone(),
+                c.dialogue_words.to_string(),
+                c.lines.to_string(),
+                c.mentions.to_string(),
+            ]
+        })
+        .collect();
+    csv(&["character_id", "name", "dialogue_words", "lines", "mentions"], rows)
+}
+
+pub fn history_csv(progress: &WritingProgress) -> String {
+    let rows = progress
+        .history
+        .iter()
+        .map(|d| {
+            let goal_met = met_goal(d, progress.daily_goal);
+            vec![d.date.clone(), d.total_words.to_string(), d.net_words.to_string(), goal_met.to_string()]
+        })
+        .collect();
+    csv(&["date", "total_words", "net_words", "goal_met"], rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::speaker::{AttributionMethod, SpeakerAttribution};
+    use crate::testing::{add_scene, character};
+    use crate::Description;
+
+    fn day(date: &str) -> NaiveDate {
+        parse_date(date).unwrap()
+    }
+
+    fn project() -> ProjectData {
+        let mut project = ProjectData::new("Test", 0);
+        project.characters.insert("c1".to_string(), character("c1", "Mira"));
+        project.characters.insert("c2".to_string(), character("c2", "Hale"));
+        add_scene(&mut project, "s1", "Harbour", &[&["Mira walks in", "Hale waits"]]);
+        add_scene(&mut project, "s2", "Ferry", &[&["The ferry leaves", "Nobody sees"]]);
+
+        let tab = project.draft_tabs.get_mut("s1-t0").unwrap();
+        tab.timeline[1].dialogue = Some("Where is the letter?".to_string());
+        tab.timeline[1].speaker = Some(SpeakerAttribution {
+            name: "Hale".to_string(),
+            character_id: Some("c2".to_string()),
+            confidence: 1.0,
+            method: AttributionMethod::Manual,
+        });
+        tab.descriptions.push(Description {
+            id: "d1".to_string(),
+            text: "Cold salt air".to_string(),
+            is_important: false,
+            origin_star_id: None,
+            target_event_id: None,
+            scope: "tab".to_string(),
+            provenance: None,
+        });
+        project.draft_tabs.get_mut("s2-t0").unwrap().timeline[1].checked = false;
+
+        let mut loose = project.draft_tabs["s2-t0"].clone();
+        loose.id = "w1".to_string();
+        loose.scene_id = None;
+        project.draft_tabs.insert("w1".to_string(), loose);
+        project
+    }
+
+    #[test]
+    fn words_are_split_into_narration_dialogue_and_description() {
+        assert_eq!(count_words("Wait \u{2014} what? ..."), 2);
+
+        let stats = compute(&project(), &StatsOptions::default());
+        let harbour = &stats.scenes[0];
+        assert_eq!((harbour.words.narration, harbour.words.dialogue, harbour.words.description, harbour.words.total), (5, 4, 3, 12));
+        assert!((harbour.dialogue_ratio - 4.0 / 9.0).abs() < 1e-9);
+        assert_eq!((stats.scenes[1].events, stats.scenes[1].words.total), (1, 3));
+        assert_eq!((stats.words.total, stats.workbench_words), (15, 3));
+
+        let options = StatsOptions { checked_only: false, include_descriptions: false };
+        let stats = compute(&project(), &options);
+        assert_eq!((stats.scenes[1].events, stats.words.description, stats.words.total), (2, 0, 14));
+    }
+
+    #[test]
+    fn characters_count_attributed_dialogue_and_mentions() {
+        let stats = compute(&project(), &StatsOptions::default());
+        let rows: Vec<_> = stats.characters.iter().map(|c| (c.name.as_str(), c.dialogue_words, c.lines, c.mentions)).collect();
+        assert_eq!(rows, vec![("Hale", 4, 1, 1), ("Mira", 0, 0, 1)]);
+    }
+
+    #[test]
+    fn daily_history_tracks_net_words_and_streaks() {
+        let mut progress = WritingProgress {
+            daily_goal: None,
+            history: vec![DailyCount { date: "2023-01-01".to_string(), total_words: 10, net_words: 10 }],
+        };
+        record_day(&mut progress, 1000, day("2026-03-01"));
+        record_day(&mut progress, 1300, day("2026-03-02"));
+        record_day(&mut progress, 1500, day("2026-03-03"));
+        // A second save on the same day is still measured against the day before
+        record_day(&mut progress, 1600, day("2026-03-03"));
+        record_day(&mut progress, 1650, day("2026-03-05"));
+        let net: Vec<_> = progress.history.iter().map(|d| (d.date.as_str(), d.net_words)).collect();
+        assert_eq!(net, vec![("2026-03-01", 0), ("2026-03-02", 300), ("2026-03-03", 300), ("2026-03-05", 50)]);
+
+        let report = report(&progress, day("2026-03-05"));
+        assert_eq!((report.current_streak, report.longest_streak), (1, 2));
+        assert!(report.goal_met_today);
+        assert!((report.average_last_7_days - 650.0 / 7.0).abs() < 1e-9);
+
+        // Today not written yet keeps yesterday's streak and total
+        let tomorrow = super::report(&progress, day("2026-03-06"));
+        assert_eq!((tomorrow.current_streak, tomorrow.today.total_words, tomorrow.today.net_words), (1, 1650, 0));
+
+        progress.daily_goal = Some(100);
+        let report = super::report(&progress, day("2026-03-05"));
+        assert_eq!((report.current_streak, report.longest_streak, report.goal_met_today), (0, 2, false));
+    }
+
+    #[test]
+    fn csv_fields_with_separators_are_quoted() {
+        let mut project = project();
+        project.characters.get_mut("c1").unwrap().name = "Voss, \"Mira\"".to_string();
+        let csv = characters_csv(&compute(&project, &StatsOptions::default()));
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], "character_id,name,dialogue_words,lines,mentions");
+        assert_eq!(lines[2], "c1,\"Voss, \"\"Mira\"\"\",0,0,0");
+    }
+}
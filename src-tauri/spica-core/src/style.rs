@@ -0,0 +1,462 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::retrieval::tokenize;
+use crate::{Description, DraftTab};
+
+// -ly words that are not manner adverbs
+const NOT_ADVERBS: &[&str] = &[
+    "only", "early", "family", "likely", "reply", "belly", "fly", "ugly", "holy", "lonely", "lovely", "silly",
+    "friendly", "daily", "jelly", "rely", "supply", "apply", "ally", "bully", "curly", "italy", "july", "lily",
+];
+
+// Words that filter the scene through a character's perception instead of showing it
+const FILTER_WORDS: &[&str] = &[
+    "see", "sees", "saw", "seen", "hear", "hears", "heard", "feel", "feels", "felt", "notice", "notices",
+    "noticed", "seem", "seems", "seemed", "realize", "realizes", "realized", "realise", "realises", "realised",
+    "watch", "watches", "watched", "wonder", "wonders", "wondered", "think", "thinks", "thought", "decide",
+    "decides", "decided", "know", "knows", "knew", "look", "looks", "looked",
+];
+
+const PRONOUNS: &[&str] = &["he", "she", "him", "her", "his", "hers", "himself", "herself", "they", "them", "their", "theirs"];
+
+const PAST_AUXILIARIES: &[&str] = &["was", "were", "had", "did", "hadn't", "wasn't", "weren't", "didn't"];
+const PRESENT_AUXILIARIES: &[&str] = &["is", "are", "am", "has", "does", "isn't", "aren't", "hasn't", "doesn't"];
+
+const IRREGULAR_PAST: &[&str] = &[
+    "went", "came", "saw", "said", "took", "made", "knew", "thought", "felt", "gave", "found", "told", "became",
+    "left", "brought", "began", "kept", "held", "stood", "heard", "let", "meant", "sat", "ran", "met", "paid",
+    "spoke", "wrote", "rose", "drove", "broke", "fell", "grew", "threw", "drew", "wore", "caught", "fought",
+    "taught", "bought", "sought", "slept", "swept", "wept", "crept", "stole", "froze", "chose", "woke", "shook",
+    "struck", "hung", "sang", "rang", "swam", "slid", "bit", "hid", "lay", "lit", "led", "fled", "sent", "spent",
+];
+
+// -ed words that are not past-tense verbs
+const NOT_PAST: &[&str] = &[
+    "need", "feed", "seed", "speed", "bleed", "breed", "greed", "weed", "bed", "red", "shed", "sled",
+    "hundred", "naked", "wicked", "sacred", "rugged", "ragged", "beloved", "aged", "crooked",
+];
+
+// Words that make a following -ed word adjectival or passive rather than past tense
+const LINKING: &[&str] = &["is", "are", "am", "be", "been", "being", "get", "gets", "seems", "looks", "feels", "remains", "stays"];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Tense {
+    Present,
+    Past,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct StyleOptions {
+    pub expected_tense: Option<Tense>,
+    pub description_word_limit: Option<usize>,
+    pub tab_word_limit: Option<usize>,
+    pub long_sentence_words: usize,
+    pub repeat_window: usize, // words; a content word used again within this many is flagged
+}
+
+impl Default for StyleOptions {
+    fn default() -> Self {
+        // Matches the description prompt: present tense, 200 words
+        Self {
+            expected_tense: Some(Tense::Present),
+            description_word_limit: Some(200),
+            tab_word_limit: None,
+            long_sentence_words: 30,
+            repeat_window: 50,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingKind {
+    TenseShift,
+    LongSentence,
+    Adverb,
+    FilterWord,
+    RepeatedWord,
+    Pronoun,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StyleFinding {
+    pub kind: FindingKind,
+    pub event_index: Option<usize>, // for draft tabs; offsets are within that event's text
+    pub start: usize,               // character offsets, end exclusive
+    pub end: usize,
+    pub text: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SentenceStats {
+    pub count: usize,
+    pub mean_words: f64,
+    pub median_words: f64,
+    pub max_words: usize,
+    pub short: usize,     // under 10 words
+    pub medium: usize,    // 10-19
+    pub long: usize,      // 20-29
+    pub very_long: usize, // 30 and over
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct TenseReport {
+    pub present_markers: usize,
+    pub past_markers: usize,
+    pub dominant: Option<Tense>,
+    pub consistent: bool, // all markers agree with the expected (or dominant) tense
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StyleReport {
+    pub kind: String, // "draft_tab" | "description"
+    pub tab_id: String,
+    pub description_id: Option<String>,
+    pub word_count: usize,
+    pub word_limit: Option<usize>,
+    pub over_limit: bool,
+    pub sentences: SentenceStats,
+    pub flesch_reading_ease: f64,
+    pub flesch_kincaid_grade: f64,
+    pub tense: TenseReport,
+    pub adverb_density: f64,      // per 100 words
+    pub filter_word_density: f64, // per 100 words
+    pub pronoun_count: usize,
+    pub findings: Vec<StyleFinding>,
+}
+
+struct Word {
+    lower: String,
+    text: String,
+    start: usize, // character offsets within the unit
+    end: usize,
+}
+
+// A span of prose analysed on its own: one timeline event, or one description
+struct Unit<'a> {
+    event_index: Option<usize>,
+    text: &'a str,
+}
+
+fn words(text: &str) -> Vec<Word> {
+    let mut words = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (i, c) in text.chars().chain(std::iter::once(' ')).enumerate() {
+        // Apostrophes inside words ("didn't") belong to the word
+        if c.is_alphanumeric() || ((c == '\'' || c == '’') && current.is_some()) {
+            current.get_or_insert_with(|| (i, String::new())).1.push(c);
+        } else if let Some((start, word)) = current.take() {
+            let word = word.trim_end_matches(['\'', '’']).to_string();
+            let end = start + word.chars().count();
+            words.push(Word { lower: word.to_lowercase().replace('’', "'"), text: word, start, end });
+        }
+    }
+    words
+}
+
+// Sentences as (start, end) character ranges
+fn sentences(text: &str) -> Vec<(usize, usize)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        if matches!(chars[i], '.' | '!' | '?') {
+            while i + 1 < chars.len() && matches!(chars[i + 1], '.' | '!' | '?' | '"' | '\'' | '”' | '’') {
+                i += 1;
+            }
+            if i + 1 == chars.len() || chars[i + 1].is_whitespace() {
+                ranges.push((start, i + 1));
+                start = i + 1;
+            }
+        }
+        i += 1;
+    }
+    if chars[start..].iter().any(|c| c.is_alphanumeric()) {
+        ranges.push((start, chars.len()));
+    }
+    ranges
+}
+
+fn syllables(word: &str) -> usize {
+    let chars: Vec<char> = word.to_lowercase().chars().filter(|c| c.is_alphabetic()).collect();
+    if chars.is_empty() {
+        return 0;
+    }
+    let is_vowel = |c: char| "aeiouy".contains(c);
+    let mut count = 0;
+    let mut previous_vowel = false;
+    for &c in &chars {
+        let vowel = is_vowel(c);
+        if vowel && !previous_vowel {
+            count += 1;
+        }
+        previous_vowel = vowel;
+    }
+    // Silent trailing e, but not "-le" as in "table"
+    let n = chars.len();
+    if n > 2 && chars[n - 1] == 'e' && chars[n - 2] != 'l' && !is_vowel(chars[n - 2]) && count > 1 {
+        count -= 1;
+    }
+    count.max(1)
+}
+
+fn is_adverb(lower: &str) -> bool {
+    lower.len() > 4 && lower.ends_with("ly") && !NOT_ADVERBS.contains(&lower)
+}
+
+fn past_marker(words: &[Word], i: usize) -> bool {
+    let lower = words[i].lower.as_str();
+    if PAST_AUXILIARIES.contains(&lower) || IRREGULAR_PAST.contains(&lower) {
+        return true;
+    }
+    let previous = i.checked_sub(1).map(|p| words[p].lower.as_str());
+    lower.len() > 4
+        && lower.ends_with("ed")
+        && !NOT_PAST.contains(&lower)
+        && !previous.map_or(false, |p| LINKING.contains(&p) || PAST_AUXILIARIES.contains(&p))
+}
+
+fn present_marker(lower: &str) -> bool {
+    PRESENT_AUXILIARIES.contains(&lower)
+}
+
+fn per_hundred(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 * 100.0 / total as f64
+    }
+}
+
+fn analyse(kind: &str, tab_id: &str, description_id: Option<&str>, units: &[Unit], limit: Option<usize>, options: &StyleOptions) -> StyleReport {
+    let mut findings = Vec::new();
+    let mut finding = |kind, unit: &Unit, start, end, text: &str, message: String| {
+        findings.push(StyleFinding { kind, event_index: unit.event_index, start, end, text: text.to_string(), message });
+    };
+
+    let mut word_count = 0;
+    let mut syllable_count = 0;
+    let mut sentence_lengths = Vec::new();
+    let mut adverbs = 0;
+    let mut filter_words = 0;
+    let mut pronoun_count = 0;
+    let mut tense_markers: Vec<(usize, Tense, usize, usize, String)> = Vec::new(); // unit, tense, start, end, text
+    let mut last_seen: HashMap<String, usize> = HashMap::new(); // content word -> running word position
+
+    for (unit_index, unit) in units.iter().enumerate() {
+        let unit_words = words(unit.text);
+        word_count += unit_words.len();
+        syllable_count += unit_words.iter().map(|w| syllables(&w.text)).sum::<usize>();
+
+        for (start, end) in sentences(unit.text) {
+            let length = unit_words.iter().filter(|w| w.start >= start && w.end <= end).count();
+            if length == 0 {
+                continue;
+            }
+            sentence_lengths.push(length);
+            if length >= options.long_sentence_words {
+                let text: String = unit.text.chars().skip(start).take(end - start).collect();
+                let leading = text.chars().take_while(|c| c.is_whitespace()).count();
+                finding(FindingKind::LongSentence, unit, start + leading, end, text.trim(), format!("Sentence has {} words", length));
+            }
+        }
+
+        for (i, word) in unit_words.iter().enumerate() {
+            let position = word_count - unit_words.len() + i;
+            if is_adverb(&word.lower) {
+                adverbs += 1;
+                finding(FindingKind::Adverb, unit, word.start, word.end, &word.text, "Adverb; a stronger verb may do".to_string());
+            }
+            if FILTER_WORDS.contains(&word.lower.as_str()) {
+                filter_words += 1;
+                finding(FindingKind::FilterWord, unit, word.start, word.end, &word.text, "Filter word; show the perception directly".to_string());
+            }
+            if PRONOUNS.contains(&word.lower.as_str()) {
+                pronoun_count += 1;
+                finding(FindingKind::Pronoun, unit, word.start, word.end, &word.text, "Pronoun; the prompts ask for names".to_string());
+            }
+            if past_marker(&unit_words, i) {
+                tense_markers.push((unit_index, Tense::Past, word.start, word.end, word.text.clone()));
+            } else if present_marker(&word.lower) {
+                tense_markers.push((unit_index, Tense::Present, word.start, word.end, word.text.clone()));
+            }
+
+            // Content words only; tokenize drops stopwords and single letters
+            if tokenize(&word.lower).first() == Some(&word.lower) && word.lower.len() > 3 && !PRONOUNS.contains(&word.lower.as_str()) {
+                if let Some(previous) = last_seen.insert(word.lower.clone(), position) {
+                    let gap = position - previous;
+                    if gap <= options.repeat_window {
+                        finding(FindingKind::RepeatedWord, unit, word.start, word.end, &word.text, format!("\"{}\" repeated {} words after its last use", word.text, gap));
+                    }
+                }
+            }
+        }
+    }
+
+    let present = tense_markers.iter().filter(|m| m.1 == Tense::Present).count();
+    let past = tense_markers.len() - present;
+    let dominant = if tense_markers.is_empty() {
+        None
+    } else if past > present {
+        Some(Tense::Past)
+    } else {
+        Some(Tense::Present)
+    };
+    let expected = options.expected_tense.or(dominant);
+    for (unit_index, tense, start, end, text) in &tense_markers {
+        if Some(*tense) != expected {
+            let message = match tense {
+                Tense::Past => "Past tense in present-tense prose",
+                Tense::Present => "Present tense in past-tense prose",
+            };
+            finding(FindingKind::TenseShift, &units[*unit_index], *start, *end, text, message.to_string());
+        }
+    }
+    let consistent = tense_markers.iter().all(|m| Some(m.1) == expected);
+
+    let mut sorted_lengths = sentence_lengths.clone();
+    sorted_lengths.sort_unstable();
+    let count = sorted_lengths.len();
+    let median_words = match count {
+        0 => 0.0,
+        n if n % 2 == 1 => sorted_lengths[n / 2] as f64,
+        n => (sorted_lengths[n / 2 - 1] + sorted_lengths[n / 2]) as f64 / 2.0,
+    };
+    let sentence_stats = SentenceStats {
+        count,
+        mean_words: if count == 0 { 0.0 } else { sentence_lengths.iter().sum::<usize>() as f64 / count as f64 },
+        median_words,
+        max_words: sorted_lengths.last().copied().unwrap_or(0),
+        short: sentence_lengths.iter().filter(|l| **l < 10).count(),
+        medium: sentence_lengths.iter().filter(|l| (10..20).contains(*l)).count(),
+        long: sentence_lengths.iter().filter(|l| (20..30).contains(*l)).count(),
+        very_long: sentence_lengths.iter().filter(|l| **l >= 30).count(),
+    };
+
+    // Flesch formulas; zero for empty text
+    let (reading_ease, grade) = if word_count == 0 || count == 0 {
+        (0.0, 0.0)
+    } else {
+        let words_per_sentence = word_count as f64 / count as f64;
+        let syllables_per_word = syllable_count as f64 / word_count as f64;
+        (
+            206.835 - 1.015 * words_per_sentence - 84.6 * syllables_per_word,
+            0.39 * words_per_sentence + 11.8 * syllables_per_word - 15.59,
+        )
+    };
+
+    findings.sort_by_key(|f| (f.event_index, f.start));
+
+    StyleReport {
+        kind: kind.to_string(),
+        tab_id: tab_id.to_string(),
+        description_id: description_id.map(str::to_string),
+        word_count,
+        word_limit: limit,
+        over_limit: limit.map_or(false, |limit| word_count > limit),
+        sentences: sentence_stats,
+        flesch_reading_ease: reading_ease,
+        flesch_kincaid_grade: grade,
+        tense: TenseReport { present_markers: present, past_markers: past, dominant, consistent },
+        adverb_density: per_hundred(adverbs, word_count),
+        filter_word_density: per_hundred(filter_words, word_count),
+        pronoun_count,
+        findings,
+    }
+}
+
+// Narration of the tab's timeline; dialogue is the characters' own voice and is left alone
+pub fn analyse_tab(tab: &DraftTab, options: &StyleOptions) -> StyleReport {
+    let units: Vec<Unit> = tab
+        .timeline
+        .iter()
+        .enumerate()
+        .map(|(i, event)| Unit { event_index: Some(i), text: &event.text })
+        .collect();
+    analyse("draft_tab", &tab.id, None, &units, options.tab_word_limit, options)
+}
+
+pub fn analyse_description(tab: &DraftTab, description: &Description, options: &StyleOptions) -> StyleReport {
+    let units = [Unit { event_index: None, text: &description.text }];
+    analyse("description", &tab.id, Some(&description.id), &units, options.description_word_limit, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::add_scene;
+    use crate::ProjectData;
+
+    fn tab(events: &[&str]) -> DraftTab {
+        let mut project = ProjectData::new("Test", 0);
+        add_scene(&mut project, "s1", "Harbour", &[events]);
+        project.draft_tabs.remove("s1-t0").unwrap()
+    }
+
+    fn kinds(report: &StyleReport) -> Vec<(FindingKind, Option<usize>, &str)> {
+        report.findings.iter().map(|f| (f.kind, f.event_index, f.text.as_str())).collect()
+    }
+
+    #[test]
+    fn words_sentences_and_syllables_are_split_like_prose() {
+        let found = words("She didn't go\u{2019} home.");
+        assert_eq!(found.iter().map(|w| (w.lower.as_str(), w.start, w.end)).collect::<Vec<_>>(), vec![("she", 0, 3), ("didn't", 4, 10), ("go", 11, 13), ("home", 15, 19)]);
+        assert_eq!(sentences("Wait... \"Go!\" Mr.Hale said. No"), vec![(0, 7), (7, 13), (13, 27), (27, 30)]);
+        assert_eq!(["table", "make", "the", "rhythm", "lantern"].map(syllables), [2, 1, 1, 1, 2]);
+    }
+
+    #[test]
+    fn tab_findings_point_at_their_event() {
+        let report = analyse_tab(&tab(&["Mira walks in slowly and notices the lantern.", "She waited. The lantern is tired."]), &StyleOptions::default());
+        assert_eq!(
+            kinds(&report),
+            vec![
+                (FindingKind::Adverb, Some(0), "slowly"),
+                (FindingKind::FilterWord, Some(0), "notices"),
+                (FindingKind::Pronoun, Some(1), "She"),
+                (FindingKind::TenseShift, Some(1), "waited"),
+                (FindingKind::RepeatedWord, Some(1), "lantern"),
+            ]
+        );
+        assert_eq!((report.findings[3].start, report.findings[3].end), (4, 10));
+        assert_eq!(report.findings[4].message, "\"lantern\" repeated 4 words after its last use");
+        assert_eq!((report.tense.present_markers, report.tense.past_markers, report.tense.consistent), (1, 1, false));
+        assert_eq!((report.sentences.count, report.sentences.median_words, report.sentences.max_words), (3, 4.0, 8));
+        assert_eq!(report.pronoun_count, 1);
+    }
+
+    #[test]
+    fn without_an_expected_tense_the_dominant_one_wins() {
+        let options = StyleOptions { expected_tense: None, ..StyleOptions::default() };
+        let report = analyse_tab(&tab(&["Hale waited. Mira turned. Oren is here."]), &options);
+        assert_eq!(report.tense.dominant, Some(Tense::Past));
+        let shifts: Vec<_> = report.findings.iter().filter(|f| f.kind == FindingKind::TenseShift).map(|f| (f.text.as_str(), f.message.as_str())).collect();
+        assert_eq!(shifts, vec![("is", "Present tense in past-tense prose")]);
+    }
+
+    #[test]
+    fn descriptions_are_checked_against_their_word_limit() {
+        let tab = tab(&[]);
+        let description = Description {
+            id: "d1".to_string(),
+            text: "  Rain falls. Rain drums on the harbour roofs tonight.".to_string(),
+            is_important: false,
+            origin_star_id: None,
+            target_event_id: None,
+            scope: "tab".to_string(),
+            provenance: None,
+        };
+        let options = StyleOptions { description_word_limit: Some(5), long_sentence_words: 7, ..StyleOptions::default() };
+        let report = analyse_description(&tab, &description, &options);
+        assert_eq!((report.kind.as_str(), report.description_id.as_deref(), report.word_count, report.over_limit), ("description", Some("d1"), 9, true));
+        let long = report.findings.iter().find(|f| f.kind == FindingKind::LongSentence).unwrap();
+        assert_eq!((long.event_index, long.start, long.text.as_str()), (None, 14, "Rain drums on the harbour roofs tonight."));
+        assert!(report.flesch_reading_ease > 0.0);
+    }
+}
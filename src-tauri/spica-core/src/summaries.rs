@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::context_engine::estimate_tokens;
+use crate::outline::ordered_scenes;
+use crate::prompts;
+use crate::{DraftTab, ProjectData, Scene};
+
+// Offline summaries keep at most this many tokens of their source
+const EXTRACTIVE_TOKENS: usize = 120;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CachedSummary {
+    pub hash: String, // content hash of the material the summary was written from
+    pub text: String,
+    pub updated_at: u64,
+}
+
+// Persisted with the project so unchanged drafts are never summarised twice.
+// Tab summaries themselves live in DraftTab.summary; the cache only remembers what they were written from.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SummaryCache {
+    #[serde(default)]
+    pub tabs: HashMap<String, CachedSummary>,
+    #[serde(default)]
+    pub scenes: HashMap<String, CachedSummary>,
+    #[serde(default)]
+    pub story: Option<CachedSummary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryLevel {
+    Tab,
+    Scene,
+    Story,
+}
+
+// One summary that needs (re)writing, with the source text it should be written from
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SummaryJob {
+    pub level: SummaryLevel,
+    pub id: String, // tab or scene ID; "story" for the whole story
+    pub hash: String,
+    pub source: String,
+}
+
+// FNV-1a: stable across builds, unlike DefaultHasher, so cached hashes survive a restart
+pub fn content_hash(text: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in text.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+fn scene_tabs<'a>(project: &'a ProjectData, scene: &Scene) -> Vec<&'a DraftTab> {
+    let mut tabs: Vec<&DraftTab> = scene.draft_tab_ids.iter().filter_map(|id| project.draft_tabs.get(id)).collect();
+    tabs.sort_by_key(|tab| tab.index);
+    tabs
+}
+
+fn tab_source(tab: &DraftTab) -> String {
+    tab.timeline
+        .iter()
+        .filter(|event| event.checked)
+        .map(|event| match &event.dialogue {
+            Some(dialogue) => format!("- {} -> \"{}\"\n", event.text, dialogue),
+            None => format!("- {}\n", event.text),
+        })
+        .collect()
+}
+
+fn tab_summary(tab: &DraftTab) -> Option<&str> {
+    tab.summary.as_deref().map(str::trim).filter(|s| !s.is_empty())
+}
+
+fn scene_source(project: &ProjectData, scene: &Scene) -> String {
+    let mut source = format!("Scene: {}\n", scene.name);
+    for tab in scene_tabs(project, scene) {
+        if let Some(summary) = tab_summary(tab) {
+            source.push_str(&format!("- {}\n", summary));
+        }
+    }
+    source
+}
+
+fn story_source(project: &ProjectData) -> String {
+    scenes_source(project, &ordered_scenes(project))
+}
+
+fn scenes_source(project: &ProjectData, scenes: &[&Scene]) -> String {
+    scenes
+        .iter()
+        .filter_map(|scene| {
+            let summary = project.summaries.scenes.get(&scene.id)?;
+            Some(format!("{}: {}\n", scene.name, summary.text))
+        })
+        .collect()
+}
+
+// Hash of what a scene summary would be written from right now
+pub fn scene_hash(project: &ProjectData, scene: &Scene) -> String {
+    content_hash(&scene_source(project, scene))
+}
+
+// Tabs without a summary, or whose generated summary predates edits to the timeline.
+// A summary we did not write (e.g. from the timeline generator) is adopted as-is.
+pub fn stale_tabs(project: &mut ProjectData, now_ms: u64) -> Vec<SummaryJob> {
+    let mut jobs = Vec::new();
+    let mut adopted = Vec::new();
+
+    for tab in project.draft_tabs.values() {
+        let source = tab_source(tab);
+        if source.is_empty() {
+            continue;
+        }
+        let hash = content_hash(&source);
+
+        let regenerate = match (tab_summary(tab), project.summaries.tabs.get(&tab.id)) {
+            (None, _) => true,
+            (Some(summary), Some(cached)) if cached.text == summary => cached.hash != hash,
+            (Some(summary), _) => {
+                adopted.push((tab.id.clone(), CachedSummary { hash, text: summary.to_string(), updated_at: now_ms }));
+                continue;
+            }
+        };
+        if regenerate {
+            jobs.push(SummaryJob { level: SummaryLevel::Tab, id: tab.id.clone(), hash, source });
+        }
+    }
+
+    project.summaries.tabs.extend(adopted);
+    jobs.sort_by(|a, b| a.id.cmp(&b.id));
+    jobs
+}
+
+// Run after tab summaries are current: scene summaries are written from them
+pub fn stale_scenes(project: &ProjectData) -> Vec<SummaryJob> {
+    ordered_scenes(project)
+        .into_iter()
+        .filter(|scene| scene_tabs(project, scene).iter().any(|tab| tab_summary(tab).is_some()))
+        .filter_map(|scene| {
+            let source = scene_source(project, scene);
+            let hash = content_hash(&source);
+            match project.summaries.scenes.get(&scene.id) {
+                Some(cached) if cached.hash == hash => None,
+                _ => Some(SummaryJob { level: SummaryLevel::Scene, id: scene.id.clone(), hash, source }),
+            }
+        })
+        .collect()
+}
+
+// Run after scene summaries are current
+pub fn stale_story(project: &ProjectData) -> Option<SummaryJob> {
+    let source = story_source(project);
+    if source.is_empty() {
+        return None;
+    }
+    let hash = content_hash(&source);
+    match &project.summaries.story {
+        Some(cached) if cached.hash == hash => None,
+        _ => Some(SummaryJob { level: SummaryLevel::Story, id: "story".to_string(), hash, source }),
+    }
+}
+
+pub fn build_prompt(job: &SummaryJob) -> (String, String) {
+    let (heading, instructions) = match job.level {
+        SummaryLevel::Tab => ("### TIMELINE", prompts::SUMMARY_TAB_INSTRUCTIONS),
+        SummaryLevel::Scene => ("### SCENE PARTS", prompts::SUMMARY_SCENE_INSTRUCTIONS),
+        SummaryLevel::Story => ("### SCENES", prompts::SUMMARY_STORY_INSTRUCTIONS),
+    };
+    let user_prompt = format!("{}\n{}\n{}", heading, job.source, instructions);
+    (prompts::SUMMARY_SYSTEM.to_string(), user_prompt)
+}
+
+// Offline fallback: the source's leading lines, joined into prose, up to a small budget
+pub fn extractive_summary(job: &SummaryJob) -> String {
+    let mut summary = String::new();
+    for line in job.source.lines() {
+        let line = line.trim_start_matches("- ").trim();
+        if line.is_empty() || (job.level == SummaryLevel::Scene && line.starts_with("Scene: ")) {
+            continue;
+        }
+        if !summary.is_empty() && estimate_tokens(&summary) + estimate_tokens(line) > EXTRACTIVE_TOKENS {
+            break;
+        }
+        if !summary.is_empty() {
+            summary.push(' ');
+        }
+        summary.push_str(line);
+    }
+    summary
+}
+
+// Extractive rollup of just these scenes' summaries. The cached story summary covers every scene,
+// so a prompt for a scene in the middle of the book must not use it.
+pub fn rollup(project: &ProjectData, scenes: &[&Scene]) -> Option<String> {
+    let source = scenes_source(project, scenes);
+    if source.is_empty() {
+        return None;
+    }
+    let job = SummaryJob { level: SummaryLevel::Story, id: "story".to_string(), hash: content_hash(&source), source };
+    Some(extractive_summary(&job))
+}
+
+pub fn apply(project: &mut ProjectData, job: &SummaryJob, text: String, now_ms: u64) {
+    let cached = CachedSummary { hash: job.hash.clone(), text: text.clone(), updated_at: now_ms };
+    match job.level {
+        SummaryLevel::Tab => {
+            if let Some(tab) = project.draft_tabs.get_mut(&job.id) {
+                tab.summary = Some(text);
+            }
+            project.summaries.tabs.insert(job.id.clone(), cached);
+        }
+        SummaryLevel::Scene => {
+            project.summaries.scenes.insert(job.id.clone(), cached);
+        }
+        SummaryLevel::Story => project.summaries.story = Some(cached),
+    }
+}
+
+// Drop cache entries for tabs and scenes that no longer exist
+pub fn prune(project: &mut ProjectData) {
+    let ProjectData { summaries, draft_tabs, scenes, .. } = project;
+    summaries.tabs.retain(|id, _| draft_tabs.contains_key(id));
+    summaries.scenes.retain(|id, _| scenes.contains_key(id));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::add_scene;
+
+    fn project() -> ProjectData {
+        let mut project = ProjectData::new("Test", 0);
+        add_scene(&mut project, "s1", "Harbour", &[&["Mira finds the letter", "Hale notices"]]);
+        add_scene(&mut project, "s2", "Ferry", &[&["The ferry leaves"]]);
+        project
+    }
+
+    fn run(project: &mut ProjectData, jobs: Vec<SummaryJob>) -> Vec<String> {
+        for job in &jobs {
+            apply(project, job, extractive_summary(job), 1);
+        }
+        jobs.into_iter().map(|job| job.id).collect()
+    }
+
+    #[test]
+    fn summaries_cascade_from_tabs_to_scenes_to_the_story() {
+        let mut project = project();
+        let jobs = stale_tabs(&mut project, 1);
+        assert_eq!(run(&mut project, jobs), vec!["s1-t0", "s2-t0"]);
+        assert_eq!(project.draft_tabs["s1-t0"].summary.as_deref(), Some("Mira finds the letter Hale notices"));
+        let jobs = stale_scenes(&project);
+        assert_eq!(run(&mut project, jobs), vec!["s1", "s2"]);
+        let story = stale_story(&project).unwrap();
+        assert_eq!(story.source, "Harbour: Mira finds the letter Hale notices\nFerry: The ferry leaves\n");
+        run(&mut project, vec![story]);
+        assert!(stale_tabs(&mut project, 2).is_empty() && stale_scenes(&project).is_empty() && stale_story(&project).is_none());
+
+        // An edit only reaches the summaries built on it
+        project.draft_tabs.get_mut("s1-t0").unwrap().timeline[1].text = "Hale looks away".to_string();
+        let jobs = stale_tabs(&mut project, 2);
+        assert_eq!(run(&mut project, jobs), vec!["s1-t0"]);
+        assert_eq!(stale_scenes(&project).iter().map(|job| job.id.as_str()).collect::<Vec<_>>(), vec!["s1"]);
+    }
+
+    #[test]
+    fn summaries_written_elsewhere_are_adopted_and_unchecked_events_ignored() {
+        let mut project = project();
+        project.draft_tabs.get_mut("s2-t0").unwrap().summary = Some("The ferry goes".to_string());
+        project.draft_tabs.get_mut("s1-t0").unwrap().timeline[1].checked = false;
+        let jobs = stale_tabs(&mut project, 1);
+        assert_eq!(jobs.iter().map(|job| job.id.as_str()).collect::<Vec<_>>(), vec!["s1-t0"]);
+        assert_eq!(jobs[0].source, "- Mira finds the letter\n");
+        assert_eq!(project.summaries.tabs["s2-t0"].text, "The ferry goes");
+    }
+
+    #[test]
+    fn rollups_cover_only_the_given_scenes_and_prune_drops_deleted_ones() {
+        let mut project = project();
+        let jobs = stale_tabs(&mut project, 1);
+        run(&mut project, jobs);
+        let jobs = stale_scenes(&project);
+        run(&mut project, jobs);
+
+        let first = vec![&project.scenes["s1"]];
+        assert_eq!(rollup(&project, &first).as_deref(), Some("Harbour: Mira finds the letter Hale notices"));
+        assert_eq!(rollup(&project, &[]), None);
+
+        let long = SummaryJob { level: SummaryLevel::Tab, id: "t".to_string(), hash: String::new(), source: "- word word word\n".repeat(200) };
+        assert!(estimate_tokens(&extractive_summary(&long)) <= EXTRACTIVE_TOKENS);
+
+        project.scenes.remove("s2");
+        project.draft_tabs.remove("s2-t0");
+        prune(&mut project);
+        assert_eq!(project.summaries.scenes.keys().collect::<Vec<_>>(), vec!["s1"]);
+        assert_eq!(project.summaries.tabs.keys().collect::<Vec<_>>(), vec!["s1-t0"]);
+        assert_eq!(content_hash(""), "cbf29ce484222325");
+    }
+}
@@ -0,0 +1,518 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::context_engine::PromptType;
+use crate::prompts;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateSource {
+    Builtin,
+    App,     // <app dir>/templates/*.json
+    Project, // saved with the project
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PromptTemplate {
+    pub name: String, // lowercase letters, digits, '-' and '_'; also the file name
+    #[serde(default)]
+    pub version: u32, // assigned on save
+    pub prompt_type: PromptType,
+    pub system: String,
+    pub user: String, // {context}, {userInput}, {targetEvent}, {responseInstructions}
+    #[serde(default)]
+    pub instructions: String, // substituted for {responseInstructions}
+    #[serde(default)]
+    pub note: String, // what changed in this version
+    #[serde(default)]
+    pub created_at: u64,
+}
+
+// Per-book templates and the versions pinned for this project
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ProjectTemplates {
+    #[serde(default)]
+    pub templates: Vec<PromptTemplate>,
+    #[serde(default)]
+    pub pinned: HashMap<String, u32>, // template name -> version
+    #[serde(default)]
+    pub selected: HashMap<PromptType, String>, // template name generation uses; the built-in one when unset
+}
+
+impl ProjectTemplates {
+    pub fn template_name(&self, prompt_type: PromptType) -> &str {
+        self.selected.get(&prompt_type).map_or(default_name(prompt_type), String::as_str)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RegisteredTemplate {
+    pub template: PromptTemplate,
+    pub source: TemplateSource,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TemplateIssue {
+    pub field: String, // "name" | "version" | "system" | "user" | "instructions"
+    pub placeholder: Option<String>,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TemplateValues {
+    pub context: String,
+    pub user_input: String,
+    pub target_event: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RenderedPrompt {
+    pub name: String,
+    pub version: u32,
+    pub system_prompt: String,
+    pub user_prompt: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffOp {
+    Same,
+    Added,
+    Removed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DiffChunk {
+    pub op: DiffOp,
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TemplateDiff {
+    pub name: String,
+    pub from_version: u32,
+    pub to_version: u32,
+    pub prompt_type_changed: bool,
+    pub system: Vec<DiffChunk>, // word-level; concatenating Same + Added chunks gives the new text
+    pub user: Vec<DiffChunk>,
+    pub instructions: Vec<DiffChunk>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Placeholder {
+    Context,
+    UserInput,
+    TargetEvent,
+    ResponseInstructions,
+}
+
+impl Placeholder {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "context" => Some(Placeholder::Context),
+            "userInput" => Some(Placeholder::UserInput),
+            "targetEvent" => Some(Placeholder::TargetEvent),
+            "responseInstructions" => Some(Placeholder::ResponseInstructions),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Placeholder::Context => "context",
+            Placeholder::UserInput => "userInput",
+            Placeholder::TargetEvent => "targetEvent",
+            Placeholder::ResponseInstructions => "responseInstructions",
+        }
+    }
+}
+
+fn required(prompt_type: PromptType) -> &'static [Placeholder] {
+    match prompt_type {
+        PromptType::SceneTimeline => &[Placeholder::Context, Placeholder::UserInput],
+        PromptType::EventDescription => &[Placeholder::Context, Placeholder::UserInput, Placeholder::TargetEvent],
+    }
+}
+
+// Only description prompts have a target event to fill in
+fn allowed(prompt_type: PromptType, placeholder: Placeholder) -> bool {
+    placeholder != Placeholder::TargetEvent || prompt_type == PromptType::EventDescription
+}
+
+pub fn default_name(prompt_type: PromptType) -> &'static str {
+    match prompt_type {
+        PromptType::SceneTimeline => "scene_timeline",
+        PromptType::EventDescription => "event_description",
+    }
+}
+
+pub fn builtin() -> Vec<PromptTemplate> {
+    let template = |prompt_type, system: &str, user: &str, instructions: &str| PromptTemplate {
+        name: default_name(prompt_type).to_string(),
+        version: 1,
+        prompt_type,
+        system: system.to_string(),
+        user: user.to_string(),
+        instructions: instructions.to_string(),
+        note: "Built-in".to_string(),
+        created_at: 0,
+    };
+    vec![
+        template(
+            PromptType::SceneTimeline,
+            prompts::SCENE_TIMELINE_GENERATOR,
+            prompts::SCENE_TIMELINE_TEMPLATE,
+            prompts::SCENE_TIMELINE_INSTRUCTIONS,
+        ),
+        template(
+            PromptType::EventDescription,
+            prompts::EVENT_DESCRIPTION_GENERATOR,
+            prompts::EVENT_DESCRIPTION_TEMPLATE,
+            prompts::EVENT_DESCRIPTION_INSTRUCTIONS,
+        ),
+    ]
+}
+
+// `{name}` placeholders with their byte ranges; other braces (e.g. JSON examples) are left alone
+fn placeholders(text: &str) -> Vec<(usize, usize, &str)> {
+    let mut found = Vec::new();
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'{' {
+            let start = i + 1;
+            let mut end = start;
+            while end < bytes.len() && (bytes[end].is_ascii_alphanumeric() || bytes[end] == b'_') {
+                end += 1;
+            }
+            if end > start && end < bytes.len() && bytes[end] == b'}' && !bytes[start].is_ascii_digit() {
+                found.push((i, end + 1, &text[start..end]));
+                i = end + 1;
+                continue;
+            }
+        }
+        i += 1;
+    }
+    found
+}
+
+fn issue(field: &str, placeholder: Option<&str>, message: String) -> TemplateIssue {
+    TemplateIssue { field: field.to_string(), placeholder: placeholder.map(str::to_string), message }
+}
+
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+pub fn validate(template: &PromptTemplate) -> Vec<TemplateIssue> {
+    let mut issues = Vec::new();
+    if !valid_name(&template.name) {
+        issues.push(issue("name", None, format!("Invalid template name \"{}\": use a-z, 0-9, '-' and '_'", template.name)));
+    }
+    if template.version == 0 {
+        issues.push(issue("version", None, "Template version must be 1 or higher".to_string()));
+    }
+
+    let mut seen = Vec::new();
+    for (_, _, name) in placeholders(&template.user) {
+        match Placeholder::parse(name) {
+            None => issues.push(issue("user", Some(name), format!("Unknown placeholder {{{}}}", name))),
+            Some(placeholder) if !allowed(template.prompt_type, placeholder) => issues.push(issue(
+                "user",
+                Some(name),
+                format!("{{{}}} is not available in {:?} templates", name, template.prompt_type),
+            )),
+            Some(placeholder) => seen.push(placeholder),
+        }
+    }
+    for placeholder in required(template.prompt_type) {
+        if !seen.contains(placeholder) {
+            let name = placeholder.name();
+            issues.push(issue("user", Some(name), format!("Missing required placeholder {{{}}}", name)));
+        }
+    }
+
+    // Only the user prompt is filled in
+    for (field, text) in [("system", &template.system), ("instructions", &template.instructions)] {
+        for (_, _, name) in placeholders(text) {
+            issues.push(issue(field, Some(name), format!("Placeholders are not filled in the {} text: {{{}}}", field, name)));
+        }
+    }
+    issues
+}
+
+// One pass over the template, so placeholder-like text inside the values is never substituted again
+pub fn render(template: &PromptTemplate, values: &TemplateValues) -> Result<RenderedPrompt, Vec<TemplateIssue>> {
+    let mut issues = validate(template);
+    if template.prompt_type == PromptType::EventDescription && values.target_event.is_none() {
+        issues.push(issue("user", Some("targetEvent"), "No target event given".to_string()));
+    }
+    if !issues.is_empty() {
+        return Err(issues);
+    }
+
+    let mut user_prompt = String::with_capacity(template.user.len() + values.context.len());
+    let mut last = 0;
+    for (start, end, name) in placeholders(&template.user) {
+        user_prompt.push_str(&template.user[last..start]);
+        match Placeholder::parse(name) {
+            Some(Placeholder::Context) => user_prompt.push_str(&values.context),
+            Some(Placeholder::UserInput) => user_prompt.push_str(&values.user_input),
+            Some(Placeholder::TargetEvent) => user_prompt.push_str(values.target_event.as_deref().unwrap_or_default()),
+            Some(Placeholder::ResponseInstructions) => user_prompt.push_str(&template.instructions),
+            None => user_prompt.push_str(&template.user[start..end]),
+        }
+        last = end;
+    }
+    user_prompt.push_str(&template.user[last..]);
+
+    Ok(RenderedPrompt {
+        name: template.name.clone(),
+        version: template.version,
+        system_prompt: template.system.clone(),
+        user_prompt,
+    })
+}
+
+pub fn sample_values(prompt_type: PromptType) -> TemplateValues {
+    TemplateValues {
+        context: "### SCENE\nA rain-soaked harbour at dusk.\n### CHARACTERS\nMira: a smuggler who owes everyone money.\n".to_string(),
+        user_input: "Mira tries to talk her way onto the last boat out.".to_string(),
+        target_event: match prompt_type {
+            PromptType::EventDescription => Some("Mira slips the ferryman a forged ticket.".to_string()),
+            PromptType::SceneTimeline => None,
+        },
+    }
+}
+
+// Every *.json template in `dir`, plus a message for each file that could not be read
+pub fn load_dir(dir: &Path) -> (Vec<PromptTemplate>, Vec<String>) {
+    let mut templates = Vec::new();
+    let mut errors = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return (templates, errors), // no templates directory yet
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let parsed = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| serde_json::from_str::<PromptTemplate>(&content).map_err(|e| e.to_string()));
+        match parsed {
+            Ok(template) => templates.push(template),
+            Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+    (templates, errors)
+}
+
+pub fn save_to_dir(dir: &Path, template: &PromptTemplate) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}.v{}.json", template.name, template.version));
+    fs::write(path, serde_json::to_string_pretty(template)?)
+}
+
+pub struct Registry {
+    entries: Vec<RegisteredTemplate>,
+}
+
+impl Registry {
+    // The same name and version from several sources resolves to the most specific one
+    pub fn new(app: Vec<PromptTemplate>, project: &ProjectTemplates) -> Self {
+        let mut entries: Vec<RegisteredTemplate> = Vec::new();
+        let sources = builtin()
+            .into_iter()
+            .map(|t| (t, TemplateSource::Builtin))
+            .chain(app.into_iter().map(|t| (t, TemplateSource::App)))
+            .chain(project.templates.iter().cloned().map(|t| (t, TemplateSource::Project)));
+        for (template, source) in sources {
+            entries.retain(|e| e.template.name != template.name || e.template.version != template.version);
+            entries.push(RegisteredTemplate { template, source });
+        }
+        entries.sort_by(|a, b| a.template.name.cmp(&b.template.name).then(a.template.version.cmp(&b.template.version)));
+        Self { entries }
+    }
+
+    pub fn all(&self) -> &[RegisteredTemplate] {
+        &self.entries
+    }
+
+    pub fn get(&self, name: &str, version: u32) -> Option<&RegisteredTemplate> {
+        self.entries.iter().find(|e| e.template.name == name && e.template.version == version)
+    }
+
+    // A new version must keep the prompt type of the versions before it
+    pub fn validate(&self, template: &PromptTemplate) -> Vec<TemplateIssue> {
+        let mut issues = validate(template);
+        if let Some(other) = self.entries.iter().find(|e| e.template.name == template.name && e.template.prompt_type != template.prompt_type) {
+            let message = format!("\"{}\" is a {:?} template; save {:?} templates under another name", template.name, other.template.prompt_type, template.prompt_type);
+            issues.push(issue("name", None, message));
+        }
+        issues
+    }
+
+    pub fn latest_version(&self, name: &str) -> u32 {
+        self.entries.iter().filter(|e| e.template.name == name).map(|e| e.template.version).max().unwrap_or(0)
+    }
+
+    // Pinned version first, then the project's newest, then the newest app or built-in one
+    pub fn active(&self, name: &str, pinned: &HashMap<String, u32>) -> Option<&RegisteredTemplate> {
+        if let Some(entry) = pinned.get(name).and_then(|version| self.get(name, *version)) {
+            return Some(entry);
+        }
+        let versions = || self.entries.iter().filter(|e| e.template.name == name);
+        versions()
+            .filter(|e| e.source == TemplateSource::Project)
+            .max_by_key(|e| e.template.version)
+            .or_else(|| versions().max_by_key(|e| e.template.version))
+    }
+}
+
+// Words and the whitespace between them, so chunks join back into the original text
+fn tokens(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut in_space = None;
+    for (i, c) in text.char_indices() {
+        let space = c.is_whitespace();
+        if in_space.map_or(false, |s| s != space) {
+            tokens.push(&text[start..i]);
+            start = i;
+        }
+        in_space = Some(space);
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+fn diff_text(old: &str, new: &str) -> Vec<DiffChunk> {
+    let (a, b) = (tokens(old), tokens(new));
+    // Longest common subsequence table, filled from the end
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut chunks: Vec<DiffChunk> = Vec::new();
+    let mut push = |op: DiffOp, text: &str| match chunks.last_mut() {
+        Some(last) if last.op == op => last.text.push_str(text),
+        _ => chunks.push(DiffChunk { op, text: text.to_string() }),
+    };
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            push(DiffOp::Same, a[i]);
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            push(DiffOp::Removed, a[i]);
+            i += 1;
+        } else {
+            push(DiffOp::Added, b[j]);
+            j += 1;
+        }
+    }
+    chunks
+}
+
+pub fn diff(from: &PromptTemplate, to: &PromptTemplate) -> TemplateDiff {
+    TemplateDiff {
+        name: to.name.clone(),
+        from_version: from.version,
+        to_version: to.version,
+        prompt_type_changed: from.prompt_type != to.prompt_type,
+        system: diff_text(&from.system, &to.system),
+        user: diff_text(&from.user, &to.user),
+        instructions: diff_text(&from.instructions, &to.instructions),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timeline_template(user: &str) -> PromptTemplate {
+        PromptTemplate { user: user.to_string(), ..builtin().remove(0) }
+    }
+
+    #[test]
+    fn builtin_templates_are_valid() {
+        for template in builtin() {
+            assert!(validate(&template).is_empty(), "{}: {:?}", template.name, validate(&template));
+        }
+    }
+
+    #[test]
+    fn any_plain_name_is_valid_but_a_name_keeps_its_prompt_type() {
+        let mut template = timeline_template("{context}\n{userInput}");
+        template.name = "noir".to_string();
+        assert!(validate(&template).is_empty());
+        template.name = "Noir Draft".to_string();
+        assert_eq!(validate(&template)[0].field, "name");
+
+        let registry = Registry::new(Vec::new(), &ProjectTemplates::default());
+        template.name = default_name(PromptType::EventDescription).to_string();
+        assert!(validate(&template).is_empty());
+        let issues = registry.validate(&template);
+        assert_eq!((issues.len(), issues[0].field.as_str()), (1, "name"));
+
+        let mut project = ProjectTemplates::default();
+        assert_eq!(project.template_name(PromptType::SceneTimeline), "scene_timeline");
+        project.selected.insert(PromptType::SceneTimeline, "noir".to_string());
+        assert_eq!(project.template_name(PromptType::SceneTimeline), "noir");
+    }
+
+    #[test]
+    fn unknown_missing_and_misplaced_placeholders_are_reported() {
+        let issues = validate(&timeline_template("{context} {targetEvent} {mood}"));
+        let placeholders: Vec<_> = issues.iter().filter_map(|i| i.placeholder.as_deref()).collect();
+        assert!(placeholders.contains(&"targetEvent"));
+        assert!(placeholders.contains(&"mood"));
+        assert!(placeholders.contains(&"userInput"));
+    }
+
+    #[test]
+    fn render_substitutes_placeholders_once() {
+        let template = timeline_template("{context}|{userInput}|{responseInstructions}");
+        let values = TemplateValues { context: "ctx {userInput}".to_string(), user_input: "go".to_string(), target_event: None };
+        let rendered = render(&template, &values).unwrap();
+        assert_eq!(rendered.user_prompt, format!("ctx {{userInput}}|go|{}", template.instructions));
+    }
+
+    #[test]
+    fn registry_prefers_pinned_then_project_versions() {
+        let mut v2 = builtin().remove(0);
+        v2.version = 2;
+        let mut project = ProjectTemplates { templates: vec![v2], ..ProjectTemplates::default() };
+        let mut app_v3 = builtin().remove(0);
+        app_v3.version = 3;
+
+        let registry = Registry::new(vec![app_v3.clone()], &project);
+        assert_eq!(registry.latest_version("scene_timeline"), 3);
+        assert_eq!(registry.active("scene_timeline", &project.pinned).unwrap().template.version, 2);
+
+        project.pinned.insert("scene_timeline".to_string(), 1);
+        assert_eq!(registry.active("scene_timeline", &project.pinned).unwrap().source, TemplateSource::Builtin);
+    }
+
+    #[test]
+    fn diff_chunks_rebuild_the_new_text() {
+        let chunks = diff_text("the quick fox", "the slow brown fox");
+        let rebuilt: String = chunks.iter().filter(|c| c.op != DiffOp::Removed).map(|c| c.text.as_str()).collect();
+        assert_eq!(rebuilt, "the slow brown fox");
+        assert!(chunks.iter().any(|c| c.op == DiffOp::Removed && c.text.contains("quick")));
+    }
+}
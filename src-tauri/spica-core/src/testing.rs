@@ -0,0 +1,164 @@
+// Test support: a local HTTP server standing in for the OpenAI API, and small project fixtures
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use crate::openai_client::{ClientConfig, OpenAIClient, TimelineEvent, Transport};
+use crate::{Character, DraftTab, ProjectData, Scene, ScenePlan};
+
+#[derive(Clone, Debug)]
+pub struct ReceivedRequest {
+    pub path: String,
+    pub authorization: Option<String>,
+    pub body: Value,
+}
+
+// Serves the queued responses in order, then 500s; every request is kept for assertions
+pub struct MockServer {
+    pub base_url: String,
+    received: Arc<Mutex<Vec<ReceivedRequest>>>,
+}
+
+fn header_end(buffer: &[u8]) -> Option<usize> {
+    buffer.windows(4).position(|w| w == b"\r\n\r\n").map(|i| i + 4)
+}
+
+impl MockServer {
+    pub async fn start(responses: Vec<(u16, String)>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind mock server");
+        let base_url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        let queue = Arc::new(Mutex::new(responses.into_iter()));
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buffer = Vec::new();
+                let mut chunk = [0u8; 4096];
+                // Read the headers, then as much body as Content-Length announces
+                let (head_len, content_length) = loop {
+                    let n = socket.read(&mut chunk).await.unwrap_or(0);
+                    if n == 0 {
+                        break (buffer.len(), 0);
+                    }
+                    buffer.extend_from_slice(&chunk[..n]);
+                    if let Some(end) = header_end(&buffer) {
+                        let head = String::from_utf8_lossy(&buffer[..end]).to_lowercase();
+                        let length = head
+                            .lines()
+                            .find_map(|l| l.strip_prefix("content-length:"))
+                            .and_then(|v| v.trim().parse::<usize>().ok())
+                            .unwrap_or(0);
+                        break (end, length);
+                    }
+                };
+                while buffer.len() < head_len + content_length {
+                    let n = socket.read(&mut chunk).await.unwrap_or(0);
+                    if n == 0 {
+                        break;
+                    }
+                    buffer.extend_from_slice(&chunk[..n]);
+                }
+
+                let head = String::from_utf8_lossy(&buffer[..head_len]).to_string();
+                let path = head.split_whitespace().nth(1).unwrap_or_default().to_string();
+                let authorization = head
+                    .lines()
+                    .find(|l| l.to_lowercase().starts_with("authorization:"))
+                    .map(|l| l["authorization:".len()..].trim().to_string());
+                let body = serde_json::from_slice(&buffer[head_len..]).unwrap_or(Value::Null);
+                log.lock().unwrap().push(ReceivedRequest { path, authorization, body });
+
+                let (status, response) = queue.lock().unwrap().next().unwrap_or((500, "no response queued".to_string()));
+                let reply = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    response.len(),
+                    response
+                );
+                let _ = socket.write_all(reply.as_bytes()).await;
+                let _ = socket.shutdown().await;
+            }
+        });
+
+        Self { base_url, received }
+    }
+
+    pub fn received(&self) -> Vec<ReceivedRequest> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+// A chat completions body with the given reply text
+pub fn completion_body(content: &str) -> String {
+    serde_json::json!({
+        "model": "gpt-4.1-2025-04-14",
+        "choices": [{ "message": { "role": "assistant", "content": content } }],
+        "usage": { "prompt_tokens": 12, "completion_tokens": 34, "total_tokens": 46 },
+    })
+    .to_string()
+}
+
+pub fn client(base_url: &str, transport: Transport) -> OpenAIClient {
+    OpenAIClient::with_config(ClientConfig {
+        api_key: "sk-test-key".to_string(),
+        base_url: base_url.to_string(),
+        model: "gpt-4.1".to_string(),
+        transport,
+    })
+}
+
+// Fresh directory for fixtures recorded during a test
+pub fn temp_dir(label: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("spica-{}-{}", label, uuid::Uuid::new_v4().simple()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+pub fn character(id: &str, name: &str) -> Character {
+    Character { id: id.to_string(), name: name.to_string(), fields: Default::default(), is_checked: true, last_used_in_prompt: None, usage_count: 0 }
+}
+
+pub fn event(text: &str) -> TimelineEvent {
+    TimelineEvent { id: None, text: text.to_string(), dialogue: None, checked: true, associated_stars: Vec::new(), speaker: None }
+}
+
+// A scene with its tabs, one per entry of `tabs`, each holding those events in order
+pub fn add_scene(project: &mut ProjectData, id: &str, name: &str, tabs: &[&[&str]]) {
+    let mut scene = Scene {
+        id: id.to_string(),
+        name: name.to_string(),
+        setting: None,
+        backstory: None,
+        plan: ScenePlan { raw_text: String::new(), parsed_steps: Vec::new() },
+        draft_tab_ids: Vec::new(),
+        created_at: 0,
+        updated_at: 0,
+    };
+    for (index, events) in tabs.iter().enumerate() {
+        let tab_id = format!("{}-t{}", id, index);
+        project.draft_tabs.insert(
+            tab_id.clone(),
+            DraftTab {
+                id: tab_id.clone(),
+                scene_id: Some(id.to_string()),
+                index: index as u32,
+                timeline: events.iter().map(|text| event(text)).collect(),
+                descriptions: Vec::new(),
+                summary: None,
+                atmosphere: None,
+                fulfilled_plan_steps: Vec::new(),
+                suggested_plan_steps: Vec::new(),
+                created_at: 0,
+                updated_at: 0,
+                provenance: None,
+            },
+        );
+        scene.draft_tab_ids.push(tab_id);
+    }
+    project.scenes.insert(id.to_string(), scene);
+}
@@ -0,0 +1,233 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::retrieval::tokenize;
+use crate::{DraftTab, ProjectData, Star};
+
+const MS_PER_DAY: f64 = 86_400_000.0;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct PromptUsage {
+    pub star_ids: Vec<String>,
+    pub character_ids: Vec<String>,
+    pub draft_tab_ids: Vec<String>, // drafts created from the generation
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StaleStar {
+    pub star_id: String,
+    pub title: String,
+    pub last_used_in_prompt: Option<u64>,
+    pub days_since_use: Option<f64>, // None if never used
+    pub usage_count: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UnreflectedStar {
+    pub star_id: String,
+    pub title: String,
+    pub usage_count: u32,
+    pub influenced_draft_tab_ids: Vec<String>,
+    pub reflection: f64, // best share of the star's key terms found in any influenced draft
+}
+
+// Record one generation: stamp last_used_in_prompt, bump counters and link the resulting drafts
+pub fn record_usage(project: &mut ProjectData, usage: &PromptUsage, now_ms: u64) {
+    for star_id in &usage.star_ids {
+        if let Some(star) = project.stars.get_mut(star_id) {
+            star.last_used_in_prompt = Some(now_ms);
+            star.usage_count += 1;
+            for tab_id in &usage.draft_tab_ids {
+                if !star.influenced_draft_tab_ids.contains(tab_id) {
+                    star.influenced_draft_tab_ids.push(tab_id.clone());
+                }
+            }
+        }
+    }
+
+    for character_id in &usage.character_ids {
+        if let Some(character) = project.characters.get_mut(character_id) {
+            character.last_used_in_prompt = Some(now_ms);
+            character.usage_count += 1;
+        }
+    }
+}
+
+// Stars not used in a prompt for at least `days` days, never-used ones first
+pub fn stale_stars(project: &ProjectData, days: u32, now_ms: u64) -> Vec<StaleStar> {
+    let mut stale: Vec<StaleStar> = project
+        .stars
+        .values()
+        .filter_map(|star| {
+            let days_since_use = star
+                .last_used_in_prompt
+                .map(|used| now_ms.saturating_sub(used) as f64 / MS_PER_DAY);
+            if matches!(days_since_use, Some(d) if d < days as f64) {
+                return None;
+            }
+            Some(StaleStar {
+                star_id: star.id.clone(),
+                title: star.title.clone(),
+                last_used_in_prompt: star.last_used_in_prompt,
+                days_since_use,
+                usage_count: star.usage_count,
+            })
+        })
+        .collect();
+
+    stale.sort_by(|a, b| {
+        let age = |s: &StaleStar| s.days_since_use.unwrap_or(f64::INFINITY);
+        age(b)
+            .partial_cmp(&age(a))
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.title.cmp(&b.title))
+    });
+    stale
+}
+
+fn draft_text(tab: &DraftTab) -> String {
+    let mut text = String::new();
+    for event in &tab.timeline {
+        text.push_str(&event.text);
+        text.push(' ');
+        if let Some(dialogue) = &event.dialogue {
+            text.push_str(dialogue);
+            text.push(' ');
+        }
+    }
+    for description in &tab.descriptions {
+        text.push_str(&description.text);
+        text.push(' ');
+    }
+    if let Some(summary) = &tab.summary {
+        text.push_str(summary);
+    }
+    text
+}
+
+// Title terms identify a star best; fall back to the body for untitled stars
+fn key_terms(star: &Star) -> HashSet<String> {
+    let terms: HashSet<String> = tokenize(&star.title).into_iter().collect();
+    if terms.is_empty() {
+        tokenize(&star.body).into_iter().collect()
+    } else {
+        terms
+    }
+}
+
+// Share of the star's key terms that appear in the draft, 0.0 to 1.0
+pub fn reflection_score(star: &Star, tab: &DraftTab) -> f64 {
+    let terms = key_terms(star);
+    if terms.is_empty() {
+        return 0.0;
+    }
+    let draft_terms: HashSet<String> = tokenize(&draft_text(tab)).into_iter().collect();
+    terms.iter().filter(|t| draft_terms.contains(*t)).count() as f64 / terms.len() as f64
+}
+
+// Stars that went into prompts but left no trace in any draft they influenced
+pub fn unreflected_stars(project: &ProjectData, threshold: f64) -> Vec<UnreflectedStar> {
+    let mut result: Vec<UnreflectedStar> = project
+        .stars
+        .values()
+        .filter(|star| star.usage_count > 0)
+        .filter_map(|star| {
+            let reflection = star
+                .influenced_draft_tab_ids
+                .iter()
+                .filter_map(|id| project.draft_tabs.get(id))
+                .map(|tab| reflection_score(star, tab))
+                .fold(0.0, f64::max);
+            if reflection >= threshold {
+                return None;
+            }
+            Some(UnreflectedStar {
+                star_id: star.id.clone(),
+                title: star.title.clone(),
+                usage_count: star.usage_count,
+                influenced_draft_tab_ids: star.influenced_draft_tab_ids.clone(),
+                reflection,
+            })
+        })
+        .collect();
+
+    result.sort_by(|a, b| b.usage_count.cmp(&a.usage_count).then_with(|| a.title.cmp(&b.title)));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{add_scene, character};
+
+    const DAY: u64 = 86_400_000;
+
+    fn star(project: &mut ProjectData, id: &str, title: &str) {
+        let mut star = Star::new(title.to_string(), String::new(), 0);
+        star.id = id.to_string();
+        project.stars.insert(id.to_string(), star);
+    }
+
+    fn ids(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn usage_stamps_counts_and_links_drafts_once() {
+        let mut project = ProjectData::new("Test", 0);
+        star(&mut project, "st1", "Lighthouse key");
+        star(&mut project, "st2", "Storm");
+        project.characters.insert("c1".to_string(), character("c1", "Mira"));
+
+        let usage = |tabs: &[&str]| PromptUsage { star_ids: ids(&["st1", "gone"]), character_ids: ids(&["c1", "gone"]), draft_tab_ids: ids(tabs) };
+        record_usage(&mut project, &usage(&["t1"]), 10);
+        record_usage(&mut project, &usage(&["t1", "t2"]), 20);
+
+        let st1 = &project.stars["st1"];
+        assert_eq!((st1.usage_count, st1.last_used_in_prompt, st1.influenced_draft_tab_ids.clone()), (2, Some(20), ids(&["t1", "t2"])));
+        assert_eq!((project.stars["st2"].usage_count, project.stars["st2"].last_used_in_prompt), (0, None));
+        assert_eq!((project.characters["c1"].usage_count, project.characters["c1"].last_used_in_prompt), (2, Some(20)));
+        // Ids that no longer exist are skipped, not created
+        assert_eq!((project.stars.len(), project.characters.len()), (2, 1));
+    }
+
+    #[test]
+    fn stale_stars_list_never_used_ones_first() {
+        let mut project = ProjectData::new("Test", 0);
+        assert!(stale_stars(&project, 5, 10 * DAY).is_empty());
+
+        star(&mut project, "st1", "Lighthouse key");
+        star(&mut project, "st2", "Storm");
+        star(&mut project, "st3", "Ferry");
+        project.stars.get_mut("st1").unwrap().last_used_in_prompt = Some(0);
+        project.stars.get_mut("st3").unwrap().last_used_in_prompt = Some(9 * DAY);
+
+        let stale = stale_stars(&project, 5, 10 * DAY);
+        let found: Vec<_> = stale.iter().map(|s| (s.star_id.as_str(), s.days_since_use)).collect();
+        assert_eq!(found, vec![("st2", None), ("st1", Some(10.0))]);
+    }
+
+    #[test]
+    fn unreflected_stars_left_no_trace_in_their_drafts() {
+        let mut project = ProjectData::new("Test", 0);
+        assert!(unreflected_stars(&project, 0.5).is_empty());
+
+        add_scene(&mut project, "s1", "Harbour", &[&["Mira climbs the lighthouse"]]);
+        star(&mut project, "st1", "Lighthouse key");
+        star(&mut project, "st2", "Storm");
+        star(&mut project, "st3", "Ferry");
+        let used = |project: &mut ProjectData, id: &str, count: u32, tabs: &[&str]| {
+            let star = project.stars.get_mut(id).unwrap();
+            star.usage_count = count;
+            star.influenced_draft_tab_ids = ids(tabs);
+        };
+        used(&mut project, "st1", 1, &["s1-t0", "gone"]);
+        used(&mut project, "st3", 2, &["gone"]);
+
+        let found: Vec<_> = unreflected_stars(&project, 0.6).into_iter().map(|s| (s.star_id, s.reflection)).collect();
+        assert_eq!(found, vec![("st3".to_string(), 0.0), ("st1".to_string(), 0.5)]);
+        let found: Vec<_> = unreflected_stars(&project, 0.5).into_iter().map(|s| s.star_id).collect();
+        assert_eq!(found, vec!["st3"]);
+    }
+}
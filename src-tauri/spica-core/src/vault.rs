@@ -0,0 +1,818 @@
+// Two-way sync of characters and stars with a folder of Markdown notes, such as an Obsidian vault
+//
+// Characters become <folder>/Characters/<name>.md with a `## Field` section per field, and stars
+// <folder>/Stars/<title>.md with their tags in the YAML front-matter and the body below it. Notes
+// are matched to the project by their `spica_id` property, so they can be renamed or moved into
+// subfolders. The hash of each note as last synced, kept in <folder>/.spica-sync.json, tells which
+// side changed; when both did, the note is reported as a conflict and neither side is touched
+// until it is resolved.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+
+use crate::engine;
+use crate::summaries::content_hash;
+use crate::{Character, ProjectData, Star, STAR_SCOPES};
+
+pub const STATE_FILE: &str = ".spica-sync.json";
+const STATUSES: [&str; 3] = ["Active", "Resolved", "Deferred"];
+// Editors save in bursts; sync once the folder has been quiet this long
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct VaultSettings {
+    pub enabled: bool,
+    pub folder: Option<PathBuf>,
+}
+
+impl VaultSettings {
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path).ok().and_then(|s| serde_json::from_str(&s).ok()).unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self).unwrap())
+    }
+
+    // The folder to sync with, when syncing is on
+    pub fn active_folder(&self) -> Option<&Path> {
+        self.folder.as_deref().filter(|_| self.enabled)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteKind {
+    Character,
+    Star,
+}
+
+impl NoteKind {
+    fn dir(self) -> &'static str {
+        match self {
+            NoteKind::Character => "Characters",
+            NoteKind::Star => "Stars",
+        }
+    }
+}
+
+// How to resolve a conflict
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Keep {
+    Project,
+    Vault,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SyncConflict {
+    pub id: String,
+    pub kind: NoteKind,
+    pub title: String,
+    pub path: String, // relative to the vault folder
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SyncReport {
+    pub written: Vec<String>,  // notes created or rewritten from the project
+    pub removed: Vec<String>,  // notes deleted because their character or star was
+    pub imported: Vec<String>, // ids of characters and stars created or changed from notes
+    pub deleted: Vec<String>,  // ids removed from the project because their note was deleted
+    pub conflicts: Vec<SyncConflict>,
+    pub warnings: Vec<String>, // unreadable notes and links that match no note
+}
+
+impl SyncReport {
+    pub fn project_changed(&self) -> bool {
+        !self.imported.is_empty() || !self.deleted.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct SyncState {
+    notes: HashMap<String, SyncedNote>, // by character or star id
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct SyncedNote {
+    kind: NoteKind,
+    path: String,
+    hash: String,         // of the note as written
+    project_hash: String, // of the note without the user's own properties, which the project does not keep
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct FrontMatter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    spica_id: Option<String>,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    kind: Option<NoteKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>, // only when the file name cannot hold the name or title
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checked: Option<bool>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    characters: Vec<String>, // wiki-links
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    applies_to: Option<String>, // wiki-link to the character a constraint is about
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>, // the star's custom tags
+    #[serde(flatten)]
+    extra: BTreeMap<String, serde_yaml::Value>, // the user's own properties, kept as they are
+}
+
+struct Note {
+    kind: NoteKind,
+    path: String, // relative, with '/' separators
+    stem: String,
+    content: String,
+    front: FrontMatter,
+    body: String,
+}
+
+// Link targets by lowercased note name, title or id
+type LinkIndex = HashMap<String, (NoteKind, String)>;
+
+pub fn sync(project: &mut ProjectData, folder: &Path, resolutions: &HashMap<String, Keep>) -> io::Result<SyncReport> {
+    let mut report = SyncReport::default();
+    let mut state = load_state(folder);
+    let mut unreadable = HashSet::new();
+    let mut notes = Vec::new();
+    // A missing Characters/ or Stars/ folder is more likely moved or unmounted than emptied on purpose,
+    // so its notes are left alone instead of all being taken as deleted
+    let mut missing = Vec::new();
+    for kind in [NoteKind::Character, NoteKind::Star] {
+        let found = scan(folder, kind, &mut notes, &mut unreadable, &mut report)?;
+        let synced = state.notes.values().filter(|synced| synced.kind == kind).count();
+        if !found && synced > 0 {
+            report.warnings.push(format!(
+                "{}/ is missing; its {} notes were not synced. Restore the folder, or delete them in the app",
+                kind.dir(),
+                synced
+            ));
+            missing.push(kind);
+        }
+    }
+    let mut used: HashSet<String> = notes.iter().map(|note| note.path.to_lowercase()).chain(unreadable.iter().map(|p| p.to_lowercase())).collect();
+
+    // Notes without an id, or copies of another note, are new characters and stars
+    let mut by_id: HashMap<String, Note> = HashMap::new();
+    let mut fresh = Vec::new();
+    for note in notes {
+        match note.front.spica_id.clone() {
+            Some(id) if !by_id.contains_key(&id) => {
+                by_id.insert(id, note);
+            }
+            _ => fresh.push(note),
+        }
+    }
+
+    let mut sync = Sync { folder, project, state: &mut state, report: &mut report, used: &mut used, links: LinkIndex::new() };
+    for kind in [NoteKind::Character, NoteKind::Star] {
+        if missing.contains(&kind) {
+            continue;
+        }
+        // Stars link to characters as they stand after their own sync
+        sync.links = link_index(sync.project, sync.state);
+        let mut ids: Vec<String> = match kind {
+            NoteKind::Character => sync.project.characters.keys().cloned().collect(),
+            NoteKind::Star => sync.project.stars.keys().cloned().collect(),
+        };
+        ids.extend(sync.state.notes.iter().filter(|(_, synced)| synced.kind == kind).map(|(id, _)| id.clone()));
+        ids.extend(by_id.iter().filter(|(_, note)| note.kind == kind).map(|(id, _)| id.clone()));
+        ids.sort();
+        ids.dedup();
+        for id in ids {
+            let note = by_id.remove(&id);
+            let base = sync.state.notes.get(&id).cloned();
+            if note.is_none() && base.as_ref().map_or(false, |base| unreadable.contains(&base.path)) {
+                continue; // its note could not be read, which is not a deletion
+            }
+            sync.entity(kind, &id, note, base, resolutions.get(&id).copied())?;
+        }
+        for note in fresh.iter().filter(|note| note.kind == kind) {
+            let id = uuid::Uuid::new_v4().to_string();
+            sync.import(kind, &id, note);
+            sync.write(kind, &id, Some(note))?;
+        }
+    }
+
+    fs::write(folder.join(STATE_FILE), serde_json::to_string_pretty(&state).unwrap())?;
+    Ok(report)
+}
+
+struct Sync<'a> {
+    folder: &'a Path,
+    project: &'a mut ProjectData,
+    state: &'a mut SyncState,
+    report: &'a mut SyncReport,
+    used: &'a mut HashSet<String>, // lowercased note paths, so new notes get unique names
+    links: LinkIndex,
+}
+
+impl Sync<'_> {
+    fn entity(&mut self, kind: NoteKind, id: &str, note: Option<Note>, base: Option<SyncedNote>, keep: Option<Keep>) -> io::Result<()> {
+        let extra = note.as_ref().map(|note| note.front.extra.clone()).unwrap_or_default();
+        let rendered = self.render(kind, id, &extra);
+        let project_changed = self.project_hash(kind, id) != base.as_ref().map(|base| base.project_hash.clone());
+        let file_changed = note.as_ref().map(|note| note_hash(&note.stem, &note.content)) != base.as_ref().map(|base| base.hash.clone());
+
+        let to_vault = match (project_changed, file_changed) {
+            (false, false) => return Ok(()),
+            (true, false) => true,
+            (false, true) => false,
+            // Both sides changed: fine if they agree, otherwise the caller decides
+            (true, true) => match (&note, &rendered) {
+                (None, None) => {
+                    self.state.notes.remove(id);
+                    return Ok(());
+                }
+                (Some(note), Some((_, content))) if self.imported_content(kind, id, note) == *content => true,
+                _ => match keep {
+                    Some(keep) => keep == Keep::Project,
+                    None => {
+                        let reason = match (&note, &rendered) {
+                            (Some(_), None) => "deleted in the project and edited in the vault",
+                            (None, Some(_)) => "edited in the project and deleted in the vault",
+                            _ if base.is_none() => "exists on both sides with different contents",
+                            _ => "edited in both the project and the vault",
+                        };
+                        let title = match (&note, &rendered) {
+                            (_, Some((stem, _))) => stem.clone(),
+                            (Some(note), None) => note.stem.clone(),
+                            (None, None) => id.to_string(),
+                        };
+                        let path = note.as_ref().map(|note| note.path.clone()).or(base.map(|base| base.path)).unwrap_or_default();
+                        self.report.conflicts.push(SyncConflict { id: id.to_string(), kind, title, path, reason: reason.to_string() });
+                        return Ok(());
+                    }
+                },
+            },
+        };
+
+        match (to_vault, note) {
+            (true, note) if rendered.is_some() => self.write(kind, id, note.as_ref()),
+            (true, Some(note)) => {
+                self.remove_file(&note.path)?;
+                self.report.removed.push(note.path);
+                self.state.notes.remove(id);
+                Ok(())
+            }
+            (false, Some(note)) => {
+                self.import(kind, id, &note);
+                self.write(kind, id, Some(&note))
+            }
+            (_, None) => {
+                let removed = match kind {
+                    NoteKind::Character => engine::remove_character(self.project, id).is_some(),
+                    NoteKind::Star => engine::remove_star(self.project, id).is_some(),
+                };
+                if removed {
+                    self.report.deleted.push(id.to_string());
+                }
+                self.state.notes.remove(id);
+                Ok(())
+            }
+        }
+    }
+
+    // The note the project's character or star would be written as, and its file stem
+    fn render(&self, kind: NoteKind, id: &str, extra: &BTreeMap<String, serde_yaml::Value>) -> Option<(String, String)> {
+        let mut front = FrontMatter { spica_id: Some(id.to_string()), kind: Some(kind), extra: extra.clone(), ..FrontMatter::default() };
+        let (title, body) = match kind {
+            NoteKind::Character => {
+                let character = self.project.characters.get(id)?;
+                front.checked = Some(character.is_checked);
+                let mut fields: Vec<_> = character.fields.iter().collect();
+                fields.sort();
+                let sections: Vec<String> = fields.iter().map(|(name, value)| format!("## {}\n{}\n", name, value.trim())).collect();
+                (character.name.clone(), sections.join("\n"))
+            }
+            NoteKind::Star => {
+                let star = self.project.stars.get(id)?;
+                front.scope = Some(star.tags.scope.clone());
+                front.status = Some(star.tags.status.clone());
+                front.priority = Some(star.priority);
+                front.checked = Some(star.is_checked);
+                front.characters = star.tags.characters.iter().map(|id| format!("[[{}]]", self.link_name(id))).collect();
+                front.applies_to = star.applies_to_character.as_ref().map(|id| format!("[[{}]]", self.link_name(id)));
+                front.tags = star.tags.custom.clone();
+                let mut body = star.body.clone();
+                if !body.is_empty() && !body.ends_with('\n') {
+                    body.push('\n');
+                }
+                (star.title.clone(), body)
+            }
+        };
+        let stem = file_stem(&title, id);
+        if stem != title {
+            front.title = Some(title);
+        }
+        let yaml = serde_yaml::to_string(&front).unwrap();
+        Some((stem, format!("---\n{}---\n{}", yaml, body)))
+    }
+
+    // Writes the project's version of the note where it is, renaming it if the name changed
+    fn write(&mut self, kind: NoteKind, id: &str, note: Option<&Note>) -> io::Result<()> {
+        let extra = note.map(|note| note.front.extra.clone()).unwrap_or_default();
+        let (stem, content) = match self.render(kind, id, &extra) {
+            Some(rendered) => rendered,
+            None => return Ok(()),
+        };
+        let path = match note {
+            Some(note) if note.stem == stem => note.path.clone(),
+            Some(note) => {
+                let dir = note.path.rsplit_once('/').map_or(kind.dir(), |(dir, _)| dir).to_string();
+                self.allocate(&dir, &stem)
+            }
+            None => match self.state.notes.get(id) {
+                Some(synced) if synced.path.ends_with(&format!("/{}.md", stem)) => synced.path.clone(),
+                _ => self.allocate(kind.dir(), &stem),
+            },
+        };
+
+        let full = self.folder.join(&path);
+        if fs::read_to_string(&full).ok().as_deref() != Some(content.as_str()) {
+            if let Some(dir) = full.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(&full, &content)?;
+            self.report.written.push(path.clone());
+        }
+        if let Some(note) = note.filter(|note| note.path != path) {
+            self.remove_file(&note.path)?;
+        }
+        let project_hash = self.project_hash(kind, id).unwrap_or_default();
+        self.state.notes.insert(id.to_string(), SyncedNote { kind, path, hash: note_hash(&stem, &content), project_hash });
+        Ok(())
+    }
+
+    fn project_hash(&self, kind: NoteKind, id: &str) -> Option<String> {
+        self.render(kind, id, &BTreeMap::new()).map(|(stem, content)| note_hash(&stem, &content))
+    }
+
+    fn import(&mut self, kind: NoteKind, id: &str, note: &Note) {
+        let before = self.render(kind, id, &note.front.extra).map(|(_, content)| content);
+        match kind {
+            NoteKind::Character => {
+                let character = imported_character(self.project.characters.get(id), id, note);
+                self.project.characters.insert(id.to_string(), character);
+            }
+            NoteKind::Star => {
+                let star = imported_star(self.project.stars.get(id), id, note, &self.links, &mut self.report.warnings);
+                self.project.stars.insert(id.to_string(), star);
+            }
+        }
+        // Notes that differ only in layout do not count as changes
+        if self.render(kind, id, &note.front.extra).map(|(_, content)| content) != before {
+            self.report.imported.push(id.to_string());
+        }
+    }
+
+    // The note as the project would write it after importing it, without changing the project
+    fn imported_content(&mut self, kind: NoteKind, id: &str, note: &Note) -> String {
+        let project = self.project.clone();
+        let warnings = self.report.warnings.len();
+        self.import(kind, id, note);
+        let content = self.render(kind, id, &note.front.extra).map(|(_, content)| content).unwrap_or_default();
+        *self.project = project;
+        self.report.imported.retain(|imported| imported != id);
+        self.report.warnings.truncate(warnings);
+        content
+    }
+
+    fn link_name(&self, id: &str) -> String {
+        match self.state.notes.get(id) {
+            Some(synced) => stem_of(&synced.path),
+            None => match self.project.characters.get(id) {
+                Some(character) => file_stem(&character.name, id),
+                None => id.to_string(),
+            },
+        }
+    }
+
+    fn allocate(&mut self, dir: &str, stem: &str) -> String {
+        let mut path = format!("{}/{}.md", dir, stem);
+        let mut n = 2;
+        while self.used.contains(&path.to_lowercase()) {
+            path = format!("{}/{} {}.md", dir, stem, n);
+            n += 1;
+        }
+        self.used.insert(path.to_lowercase());
+        path
+    }
+
+    fn remove_file(&self, path: &str) -> io::Result<()> {
+        match fs::remove_file(self.folder.join(path)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn imported_character(existing: Option<&Character>, id: &str, note: &Note) -> Character {
+    let mut fields = HashMap::new();
+    let mut current: Option<(String, Vec<&str>)> = None;
+    let mut preamble = Vec::new();
+    for line in note.body.lines() {
+        if let Some(heading) = line.strip_prefix("## ") {
+            if let Some((name, lines)) = current.take() {
+                fields.insert(name, lines.join("\n").trim().to_string());
+            }
+            current = Some((heading.trim().to_string(), Vec::new()));
+        } else {
+            match current.as_mut() {
+                Some((_, lines)) => lines.push(line),
+                None => preamble.push(line),
+            }
+        }
+    }
+    if let Some((name, lines)) = current {
+        fields.insert(name, lines.join("\n").trim().to_string());
+    }
+    // Text above the first heading is kept as a field of its own
+    let preamble = preamble.join("\n").trim().to_string();
+    if !preamble.is_empty() {
+        fields.insert("Notes".to_string(), preamble);
+    }
+
+    Character {
+        id: id.to_string(),
+        name: note.front.title.clone().unwrap_or_else(|| note.stem.clone()),
+        fields,
+        is_checked: note.front.checked.unwrap_or_else(|| existing.map_or(true, |c| c.is_checked)),
+        last_used_in_prompt: existing.and_then(|c| c.last_used_in_prompt),
+        usage_count: existing.map_or(0, |c| c.usage_count),
+    }
+}
+
+// Characters linked anywhere in the note, in the properties or the body, tag the star
+fn imported_star(existing: Option<&Star>, id: &str, note: &Note, links: &LinkIndex, warnings: &mut Vec<String>) -> Star {
+    let title = note.front.title.clone().unwrap_or_else(|| note.stem.clone());
+    let body = note.body.strip_suffix('\n').unwrap_or(&note.body).to_string();
+    let mut star = match existing {
+        Some(existing) => Star { title, body, ..existing.clone() },
+        None => Star::new(title, body, now_ms()),
+    };
+    star.id = id.to_string();
+    let front = &note.front;
+
+    if let Some(scope) = &front.scope {
+        match STAR_SCOPES.contains(&scope.as_str()) {
+            true => star.tags.scope = scope.clone(),
+            false => warnings.push(format!("{}: unknown scope {}, expected one of {}", note.path, scope, STAR_SCOPES.join(", "))),
+        }
+    }
+    if let Some(status) = &front.status {
+        match STATUSES.contains(&status.as_str()) {
+            true => star.tags.status = status.clone(),
+            false => warnings.push(format!("{}: unknown status {}, expected one of {}", note.path, status, STATUSES.join(", "))),
+        }
+    }
+    if let Some(priority) = front.priority {
+        star.priority = priority.clamp(0.0, 1.0);
+    }
+    if let Some(checked) = front.checked {
+        star.is_checked = checked;
+    }
+    star.tags.custom = front.tags.clone();
+
+    let mut resolve = |link: &str| match links.get(&link_target(link).to_lowercase()) {
+        Some(target) => Some(target.clone()),
+        None => {
+            warnings.push(format!("{}: [[{}]] matches no character or star note", note.path, link_target(link)));
+            None
+        }
+    };
+    let mut characters = Vec::new();
+    let property_links = front.characters.iter().map(|link| link.trim_start_matches("[[").trim_end_matches("]]").to_string());
+    for link in property_links.chain(wiki_links(&note.body)) {
+        if let Some((NoteKind::Character, character_id)) = resolve(&link) {
+            if !characters.contains(&character_id) {
+                characters.push(character_id);
+            }
+        }
+    }
+    star.tags.characters = characters;
+    star.applies_to_character = front
+        .applies_to
+        .as_deref()
+        .map(|link| link.trim_start_matches("[[").trim_end_matches("]]"))
+        .and_then(|link| match resolve(link) {
+            Some((NoteKind::Character, character_id)) => Some(character_id),
+            _ => None,
+        });
+    star
+}
+
+fn link_index(project: &ProjectData, state: &SyncState) -> LinkIndex {
+    let mut index = LinkIndex::new();
+    let characters = project.characters.values().map(|c| (NoteKind::Character, &c.id, &c.name));
+    for (kind, id, title) in characters.chain(project.stars.values().map(|s| (NoteKind::Star, &s.id, &s.title))) {
+        for key in [id.clone(), title.clone(), file_stem(title, id)] {
+            index.insert(key.to_lowercase(), (kind, id.clone()));
+        }
+        if let Some(synced) = state.notes.get(id) {
+            index.insert(stem_of(&synced.path).to_lowercase(), (kind, id.clone()));
+        }
+    }
+    index
+}
+
+// [[Target]], [[Target|shown text]] and [[Target#Heading]]
+fn wiki_links(text: &str) -> Vec<String> {
+    let mut links = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        let inner = &rest[start + 2..];
+        match inner.find("]]") {
+            Some(end) if end > 0 && !inner[..end].contains(['[', ']']) => {
+                links.push(inner[..end].to_string());
+                rest = &inner[end + 2..];
+            }
+            // "[[[Target]]" still links to Target
+            Some(_) => rest = &rest[start + 1..],
+            None => break,
+        }
+    }
+    links
+}
+
+fn link_target(link: &str) -> &str {
+    let target = link.split(['|', '#']).next().unwrap_or(link).trim();
+    let target = target.rsplit('/').next().unwrap_or(target);
+    target.strip_suffix(".md").unwrap_or(target)
+}
+
+// File names cannot hold some characters, and Obsidian treats a few more specially
+fn file_stem(title: &str, id: &str) -> String {
+    let stem: String = title
+        .chars()
+        .map(|c| if "/\\:*?\"<>|#^[]".contains(c) || c.is_control() { '-' } else { c })
+        .take(120)
+        .collect();
+    let stem = stem.trim().trim_start_matches('.').trim();
+    if stem.is_empty() {
+        id.to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+fn stem_of(path: &str) -> String {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    name.strip_suffix(".md").unwrap_or(name).to_string()
+}
+
+// Renaming a note counts as a change, moving it to another folder does not
+fn note_hash(stem: &str, content: &str) -> String {
+    content_hash(&format!("{}\n{}", stem, content))
+}
+
+fn load_state(folder: &Path) -> SyncState {
+    fs::read_to_string(folder.join(STATE_FILE)).ok().and_then(|s| serde_json::from_str(&s).ok()).unwrap_or_default()
+}
+
+// False when the kind's folder does not exist
+fn scan(folder: &Path, kind: NoteKind, notes: &mut Vec<Note>, unreadable: &mut HashSet<String>, report: &mut SyncReport) -> io::Result<bool> {
+    if !folder.join(kind.dir()).is_dir() {
+        return Ok(false);
+    }
+    let mut dirs = vec![kind.dir().to_string()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(folder.join(&dir)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = format!("{}/{}", dir, name);
+            if name.starts_with('.') {
+                continue;
+            }
+            if entry.path().is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let stem = match name.strip_suffix(".md") {
+                Some(stem) => stem.to_string(),
+                None => continue,
+            };
+            let parsed = fs::read_to_string(entry.path()).map_err(|e| e.to_string()).and_then(|content| {
+                let (front, body) = parse_note(&content)?;
+                Ok((content, front, body))
+            });
+            match parsed {
+                Ok((content, front, body)) => notes.push(Note { kind, path, stem, content, front, body }),
+                Err(e) => {
+                    report.warnings.push(format!("{}: {}; the note was skipped", path, e));
+                    unreadable.insert(path);
+                }
+            }
+        }
+    }
+    notes.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(true)
+}
+
+fn parse_note(content: &str) -> Result<(FrontMatter, String), String> {
+    let rest = match content.strip_prefix("---\n").or_else(|| content.strip_prefix("---\r\n")) {
+        Some(rest) => rest,
+        None => return Ok((FrontMatter::default(), content.to_string())),
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            let yaml = &rest[..offset];
+            let front = match yaml.trim().is_empty() {
+                true => FrontMatter::default(),
+                false => serde_yaml::from_str(yaml).map_err(|e| format!("invalid front-matter: {}", e))?,
+            };
+            return Ok((front, rest[offset + line.len()..].to_string()));
+        }
+        offset += line.len();
+    }
+    Err("the front-matter is not closed with ---".to_string())
+}
+
+fn now_ms() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+// Calls on_change after files in a folder change; stops when dropped
+pub struct VaultWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+// Only notes count; the state file changes on every sync
+pub fn watch(folder: &Path, on_change: impl Fn() + Send + 'static) -> notify::Result<VaultWatcher> {
+    watch_files(folder, true, |path| path.extension().map_or(false, |ext| ext == "md"), on_change)
+}
+
+// Bursts of changes to matching files are reported once, after they settle
+pub fn watch_files(
+    folder: &Path,
+    recursive: bool,
+    matches: impl Fn(&Path) -> bool + Send + 'static,
+    on_change: impl Fn() + Send + 'static,
+) -> notify::Result<VaultWatcher> {
+    let (changes, received) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            if event.paths.iter().any(|path| matches(path)) {
+                let _ = changes.send(());
+            }
+        }
+    })?;
+    let mode = if recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+    watcher.watch(folder, mode)?;
+    thread::spawn(move || loop {
+        if received.recv().is_err() {
+            return;
+        }
+        loop {
+            match received.recv_timeout(DEBOUNCE) {
+                Ok(()) => continue,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        on_change();
+    });
+    Ok(VaultWatcher { _watcher: watcher })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::temp_dir;
+
+    fn project() -> ProjectData {
+        let mut project = ProjectData::new("Test", 0);
+        let mut fields = HashMap::new();
+        fields.insert("Role".to_string(), "Smuggler".to_string());
+        let mira = Character { id: "c1".to_string(), name: "Mira".to_string(), fields, is_checked: true, last_used_in_prompt: None, usage_count: 3 };
+        project.characters.insert("c1".to_string(), mira);
+        let mut star = Star::new("Mira fears water".to_string(), "She never learned to swim.".to_string(), 0);
+        star.id = "s1".to_string();
+        star.tags.characters.push("c1".to_string());
+        project.stars.insert("s1".to_string(), star);
+        project
+    }
+
+    fn read(folder: &Path, path: &str) -> String {
+        fs::read_to_string(folder.join(path)).unwrap()
+    }
+
+    #[test]
+    fn edits_flow_both_ways_and_links_become_references() {
+        let folder = temp_dir("vault-sync");
+        let mut project = project();
+        let report = sync(&mut project, &folder, &HashMap::new()).unwrap();
+        assert_eq!(report.written, vec!["Characters/Mira.md", "Stars/Mira fears water.md"]);
+        let star_note = read(&folder, "Stars/Mira fears water.md");
+        assert!(star_note.contains("spica_id: s1") && star_note.contains("[[Mira]]") && star_note.contains("priority: 0.5"));
+        assert!(read(&folder, "Characters/Mira.md").ends_with("## Role\nSmuggler\n"));
+
+        // Nothing changed, nothing to do
+        let report = sync(&mut project, &folder, &HashMap::new()).unwrap();
+        assert!(report.written.is_empty() && !report.project_changed());
+
+        // A vault edit: new character note, and a star linking to it with a property of the user's own
+        fs::write(folder.join("Characters/Oren.md"), "## Role\nFerryman\n").unwrap();
+        let edited = star_note.replace("priority: 0.5", "priority: 0.9\ncssclasses: wide").replace("She never", "[[Oren]] saw she never");
+        fs::write(folder.join("Stars/Mira fears water.md"), edited).unwrap();
+        let report = sync(&mut project, &folder, &HashMap::new()).unwrap();
+        assert!(report.conflicts.is_empty() && report.warnings.is_empty(), "{:?}", report);
+        let oren = project.characters.values().find(|c| c.name == "Oren").unwrap().id.clone();
+        assert!(read(&folder, "Characters/Oren.md").contains(&format!("spica_id: {}", oren)));
+        let star = &project.stars["s1"];
+        assert_eq!(star.priority, 0.9);
+        assert_eq!(star.tags.characters, vec!["c1".to_string(), oren]);
+        assert_eq!(project.characters["c1"].usage_count, 3);
+        assert!(read(&folder, "Stars/Mira fears water.md").contains("cssclasses: wide"));
+
+        // A project edit: the rename moves the note and updates the star's link
+        project.characters.get_mut("c1").unwrap().name = "Mira Vell".to_string();
+        let report = sync(&mut project, &folder, &HashMap::new()).unwrap();
+        assert!(!folder.join("Characters/Mira.md").exists());
+        assert!(read(&folder, "Stars/Mira fears water.md").contains("[[Mira Vell]]"));
+        assert!(report.written.contains(&"Characters/Mira Vell.md".to_string()));
+        let _ = fs::remove_dir_all(folder);
+    }
+
+    #[test]
+    fn conflicts_wait_for_a_decision_and_broken_notes_delete_nothing() {
+        let folder = temp_dir("vault-conflict");
+        let mut project = project();
+        sync(&mut project, &folder, &HashMap::new()).unwrap();
+
+        let path = folder.join("Stars/Mira fears water.md");
+        fs::write(&path, read(&folder, "Stars/Mira fears water.md").replace("swim", "row")).unwrap();
+        project.stars.get_mut("s1").unwrap().body = "She nearly drowned.".to_string();
+        let report = sync(&mut project, &folder, &HashMap::new()).unwrap();
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].reason, "edited in both the project and the vault");
+        assert_eq!(project.stars["s1"].body, "She nearly drowned.");
+        assert!(read(&folder, "Stars/Mira fears water.md").contains("row"));
+
+        let keep_vault: HashMap<String, Keep> = [("s1".to_string(), Keep::Vault)].into_iter().collect();
+        let report = sync(&mut project, &folder, &keep_vault).unwrap();
+        assert!(report.conflicts.is_empty());
+        assert_eq!(project.stars["s1"].body, "She never learned to row.");
+
+        // Unreadable front-matter is skipped, not taken for a deleted note
+        fs::write(folder.join("Characters/Mira.md"), "---\nspica_id: [c1\n---\n").unwrap();
+        let report = sync(&mut project, &folder, &HashMap::new()).unwrap();
+        assert_eq!(report.warnings.len(), 1);
+        assert!(project.characters.contains_key("c1"));
+
+        // A vanished Stars/ folder deletes nothing, and is not recreated under the remaining notes
+        let stars = folder.join("Stars");
+        let moved = folder.join("Stars moved");
+        fs::rename(&stars, &moved).unwrap();
+        let mut oren = Star::new("Oren rows".to_string(), String::new(), 0);
+        oren.id = "s2".to_string();
+        project.stars.insert("s2".to_string(), oren);
+        let report = sync(&mut project, &folder, &HashMap::new()).unwrap();
+        assert!(report.deleted.is_empty() && !stars.exists());
+        assert!(report.warnings.iter().any(|w| w.starts_with("Stars/ is missing")), "{:?}", report.warnings);
+        assert!(project.stars.contains_key("s1"));
+        fs::rename(&moved, &stars).unwrap();
+        project.stars.remove("s2");
+
+        // Deleting a note deletes the star
+        fs::remove_file(&path).unwrap();
+        let report = sync(&mut project, &folder, &HashMap::new()).unwrap();
+        assert_eq!(report.deleted, vec!["s1".to_string()]);
+        assert!(project.stars.is_empty());
+        let _ = fs::remove_dir_all(folder);
+    }
+
+    #[test]
+    fn wiki_links_keep_aliases_and_headings_for_link_target() {
+        let text = "Mira meets [[Oren|the ferryman]] at [[Harbour#Gate]]. [[]] and [[a]b]] are not links; [[[Mira]] is";
+        assert_eq!(wiki_links(text), vec!["Oren|the ferryman", "Harbour#Gate", "Mira"]);
+        assert_eq!(link_target("Characters/Oren.md|the ferryman"), "Oren");
+    }
+}
@@ -2,348 +2,1207 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
-use tauri::State;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use tauri::{Manager, State};
 
-mod openai_client;
-use openai_client::{OpenAIClient, LLMResponse, TimelineEvent};
+use spica_core::engine::{
+    self, assemble_for, parse_project, query_index, run_generation, script_host, template_not_found, template_registry, AppConfig, GeneratedDraft,
+};
+use spica_core::{
+    api_server, audit, character_profiles, context_engine, continuity, fulfilment, openai_client, outline, plan_parser, provenance, replace, retrieval,
+    scripting, search, snapshots, speaker, spellcheck, star_extraction, stats, style, summaries, templates, usage, vault,
+};
+use spica_core::{ApiError, Character, DraftTab, ProjectData, Scene, Star};
+use api_server::{ApiServer, ApiSettings, ProjectEvent};
+use audit::{LogConfig, LogLevel};
+use character_profiles::ProfileReport;
+use continuity::ContinuityIssue;
+use context_engine::{AssembledPrompt, PromptType, SectionBudgets};
+use fulfilment::{PlanProgress, StepCandidate, StepVerdict};
+use openai_client::{GenerationParams, TimelineEvent};
+use outline::{NodeKind, Outline};
+use plan_parser::PlanDiff;
+use provenance::GenerationRequest;
+use replace::{CharacterRename, ReplaceOptions, ReplacementPreview};
+use retrieval::{RetrievalHit, RetrievalOptions};
+use scripting::ScriptInfo;
+use search::{SearchFilters, SearchHit};
+use snapshots::SnapshotInfo;
+use speaker::UnresolvedSpeaker;
+use spellcheck::Misspelling;
+use star_extraction::StarSuggestion;
+use stats::{ManuscriptStats, ProgressReport, StatsOptions};
+use style::{StyleOptions, StyleReport};
+use summaries::{SummaryJob, SummaryLevel};
+use templates::{PromptTemplate, RegisteredTemplate, RenderedPrompt, TemplateDiff, TemplateIssue, TemplateSource, TemplateValues};
+use usage::{StaleStar, UnreflectedStar};
+use vault::{Keep, SyncReport, VaultSettings, VaultWatcher};
 
-// Data structures matching the frontend types
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct Description {
-    id: String,
-    text: String,
-    is_important: bool,
-    origin_star_id: Option<String>,
+// Tauri commands
+#[derive(Serialize, Deserialize)]
+struct SpeakerResolution {
+    timeline: Vec<TimelineEvent>,
+    unresolved: Vec<UnresolvedSpeaker>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct DraftTab {
-    id: String,
+#[tauri::command]
+async fn resolve_speakers(timeline: Vec<TimelineEvent>, characters: Vec<Character>) -> Result<SpeakerResolution, ApiError> {
+    // Re-run attribution on an existing draft, e.g. after characters were added or renamed
+    let mut timeline = timeline;
+    let unresolved = speaker::resolve_speakers(&mut timeline, &characters);
+    Ok(SpeakerResolution { timeline, unresolved })
+}
+
+#[tauri::command]
+async fn retrieve_relevant(
+    state_json: String,
+    scene_id: Option<String>,
+    user_input: String,
+    options: Option<RetrievalOptions>,
+    config: State<'_, Arc<AppConfig>>,
+) -> Result<Vec<RetrievalHit>, ApiError> {
+    let project = parse_project(&state_json)?;
+    Ok(query_index(&config, &project, scene_id.as_deref(), &user_input, &options.unwrap_or_default()))
+}
+
+#[tauri::command]
+async fn assemble_prompt(
+    state_json: String,
     scene_id: String,
-    index: u32,
-    timeline: Vec<TimelineEvent>,
-    descriptions: Vec<Description>,
-    summary: Option<String>,
-    atmosphere: Option<String>,
-    fulfilled_plan_steps: Vec<String>,
-    suggested_plan_steps: Vec<String>,
-    created_at: u64,
-    updated_at: u64,
+    prompt_type: PromptType,
+    user_input: String,
+    target_event: Option<String>,
+    budgets: Option<SectionBudgets>,
+    config: State<'_, Arc<AppConfig>>,
+) -> Result<AssembledPrompt, ApiError> {
+    let project = parse_project(&state_json)?;
+    let request = GenerationRequest {
+        scene_id,
+        prompt_type,
+        user_input,
+        target_event,
+        budgets: budgets.unwrap_or_default(),
+        params: GenerationParams::default(),
+        template_name: None,
+        template_version: None,
+    };
+    assemble_for(&config, &project, &request)
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct Character {
-    id: String,
-    name: String,
-    fields: std::collections::HashMap<String, String>,
+// Assemble, send and parse in the backend so the result carries a full provenance record
+#[tauri::command]
+async fn generate_draft(state_json: String, request: GenerationRequest, config: State<'_, Arc<AppConfig>>) -> Result<GeneratedDraft, ApiError> {
+    let project = parse_project(&state_json)?;
+    run_generation(&config, &script_host(&config), &project, request).await
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct StarTags {
-    characters: Vec<String>,
-    scope: String, // "CurrentScene" | "FuturePlot" | "Backstory" | "Worldbuilding"
-    status: String, // "Active" | "Resolved" | "Deferred"
-    custom: Vec<String>,
+// Replay a tab's (or description's) generation with the same template version, model, temperature,
+// request and budgets; the context is rebuilt from the current project
+#[tauri::command]
+async fn regenerate_draft(
+    state_json: String,
+    tab_id: String,
+    description_id: Option<String>,
+    config: State<'_, Arc<AppConfig>>,
+) -> Result<GeneratedDraft, ApiError> {
+    let project = parse_project(&state_json)?;
+    let original = provenance::find(&project, &tab_id, description_id.as_deref()).map_err(|message| ApiError {
+        error: true,
+        message,
+        code: Some("NOT_FOUND".to_string()),
+    })?;
+    let request = original.replay_request().ok_or(ApiError {
+        error: true,
+        message: "This draft was generated from a frontend prompt and cannot be replayed".to_string(),
+        code: Some("NOT_REPLAYABLE".to_string()),
+    })?;
+
+    let mut generated = run_generation(&config, &script_host(&config), &project, request).await?;
+    generated.prompt_changed = Some(generated.provenance.prompt_hash != original.prompt_hash);
+    Ok(generated)
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct Star {
-    id: String,
-    title: String,
-    body: String,
-    tags: StarTags,
-    priority: f64,
-    is_checked: bool,
-    origin_draft_tab_id: Option<String>,
-    created_at: u64,
-    last_used_in_prompt: Option<u64>,
+#[derive(Serialize, Deserialize)]
+struct DraftWriteOutcome {
+    state_json: String,
+    ids: Vec<String>, // new draft tabs, or the new description
+}
+
+// Add a generate_draft result to the project as it is now, which may have changed during the call.
+// Records the stars and characters the prompt used on them.
+#[tauri::command]
+async fn add_generated_draft(
+    state_json: String,
+    generated: GeneratedDraft,
+    tab_id: Option<String>,
+    event_id: Option<String>,
+) -> Result<DraftWriteOutcome, ApiError> {
+    let mut project = parse_project(&state_json)?;
+    let now = chrono::Utc::now().timestamp_millis() as u64;
+    let ids = engine::write_generation(&mut project, generated, None, tab_id.as_deref(), event_id.as_deref(), now)?;
+    Ok(DraftWriteOutcome { state_json: serde_json::to_string(&project).unwrap(), ids })
+}
+
+#[tauri::command]
+async fn get_stale_stars(state_json: String, days: u32) -> Result<Vec<StaleStar>, ApiError> {
+    let project = parse_project(&state_json)?;
+    Ok(usage::stale_stars(&project, days, chrono::Utc::now().timestamp_millis() as u64))
+}
+
+#[tauri::command]
+async fn get_unreflected_stars(state_json: String, threshold: Option<f64>) -> Result<Vec<UnreflectedStar>, ApiError> {
+    let project = parse_project(&state_json)?;
+    Ok(usage::unreflected_stars(&project, threshold.unwrap_or(0.5)))
+}
+
+#[derive(Serialize, Deserialize)]
+struct PlanParseOutcome {
+    state_json: String,
+    diff: PlanDiff,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct PlanStep {
+#[tauri::command]
+async fn parse_scene_plan(state_json: String, scene_id: String) -> Result<PlanParseOutcome, ApiError> {
+    let mut project = parse_project(&state_json)?;
+    let scene = project.scenes.get(&scene_id).ok_or(ApiError {
+        error: true,
+        message: format!("Scene not found: {}", scene_id),
+        code: Some("NOT_FOUND".to_string()),
+    })?;
+
+    let stars: Vec<&Star> = project.stars.values().collect();
+    let parsed = plan_parser::parse_plan(&scene.plan.raw_text, &scene.plan.parsed_steps, &stars);
+
+    // Keep the project-wide step map in sync with the scene's plan
+    for id in &parsed.diff.removed {
+        project.plan_steps.remove(id);
+    }
+    for step in &parsed.steps {
+        project.plan_steps.insert(step.id.clone(), step.clone());
+    }
+    if let Some(scene) = project.scenes.get_mut(&scene_id) {
+        scene.plan.parsed_steps = parsed.steps;
+    }
+
+    Ok(PlanParseOutcome {
+        state_json: serde_json::to_string(&project).unwrap(),
+        diff: parsed.diff,
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+struct FulfilmentOutcome {
+    state_json: String,
+    candidates: Vec<StepCandidate>,
+    verdicts: Vec<StepVerdict>,
+    progress: PlanProgress,
+}
+
+// Re-judge an existing draft, e.g. after it or the plan was edited; new generations are judged as they are written
+#[tauri::command]
+async fn detect_plan_fulfilment(
+    state_json: String,
+    tab_id: String,
+    use_llm: Option<bool>,
+    state: State<'_, Arc<AppConfig>>,
+) -> Result<FulfilmentOutcome, ApiError> {
+    let mut project = parse_project(&state_json)?;
+    let not_found = |message: String| ApiError {
+        error: true,
+        message,
+        code: Some("NOT_FOUND".to_string()),
+    };
+
+    let tab = project
+        .draft_tabs
+        .get(&tab_id)
+        .ok_or_else(|| not_found(format!("Draft tab not found: {}", tab_id)))?;
+    let scene_id = tab
+        .scene_id
+        .clone()
+        .or_else(|| project.active_scene_id.clone())
+        .ok_or_else(|| not_found("Draft tab has no scene and no scene is active".to_string()))?;
+    let scene = project
+        .scenes
+        .get(&scene_id)
+        .ok_or_else(|| not_found(format!("Scene not found: {}", scene_id)))?;
+
+    let (candidates, verdicts) = engine::judge_plan_steps(&state.openai_client, &scene.plan.parsed_steps, &tab.timeline, use_llm.unwrap_or(true)).await?;
+
+    fulfilment::apply_verdicts(&mut project, &scene_id, &tab_id, &verdicts);
+    let progress = fulfilment::plan_progress(&project, &scene_id)
+        .ok_or_else(|| not_found(format!("Scene not found: {}", scene_id)))?;
+
+    Ok(FulfilmentOutcome {
+        state_json: serde_json::to_string(&project).unwrap(),
+        candidates,
+        verdicts,
+        progress,
+    })
+}
+
+#[tauri::command]
+async fn get_plan_progress(state_json: String, scene_id: String) -> Result<PlanProgress, ApiError> {
+    let project = parse_project(&state_json)?;
+    fulfilment::plan_progress(&project, &scene_id).ok_or(ApiError {
+        error: true,
+        message: format!("Scene not found: {}", scene_id),
+        code: Some("NOT_FOUND".to_string()),
+    })
+}
+
+#[tauri::command]
+async fn check_continuity(
+    state_json: String,
+    tab_id: Option<String>,
+    scene_id: Option<String>,
+    use_llm: Option<bool>,
+    state: State<'_, Arc<AppConfig>>,
+) -> Result<Vec<ContinuityIssue>, ApiError> {
+    let project = parse_project(&state_json)?;
+    let not_found = |message: String| ApiError {
+        error: true,
+        message,
+        code: Some("NOT_FOUND".to_string()),
+    };
+
+    // Check a single draft, or every draft of a scene
+    let (tabs, scene): (Vec<&DraftTab>, Option<&Scene>) = match (&tab_id, &scene_id) {
+        (Some(tab_id), _) => {
+            let tab = project
+                .draft_tabs
+                .get(tab_id)
+                .ok_or_else(|| not_found(format!("Draft tab not found: {}", tab_id)))?;
+            let scene = tab.scene_id.as_ref().and_then(|id| project.scenes.get(id));
+            (vec![tab], scene)
+        }
+        (None, Some(scene_id)) => {
+            let scene = project
+                .scenes
+                .get(scene_id)
+                .ok_or_else(|| not_found(format!("Scene not found: {}", scene_id)))?;
+            let tabs = scene.draft_tab_ids.iter().filter_map(|id| project.draft_tabs.get(id)).collect();
+            (tabs, Some(scene))
+        }
+        (None, None) => return Err(not_found("Either tab_id or scene_id is required".to_string())),
+    };
+
+    let mut issues = continuity::check_rules(&project, &tabs, scene);
+
+    if use_llm.unwrap_or(false) {
+        for tab in &tabs {
+            let (system_prompt, user_prompt) = continuity::build_llm_prompt(&project, tab);
+            let response = state.openai_client.send_prompt(&system_prompt, &user_prompt).await.map_err(|e| ApiError {
+                error: true,
+                message: format!("Failed to run continuity check: {}", e),
+                code: Some("LLM_ERROR".to_string()),
+            })?;
+            let llm_issues = continuity::parse_llm_issues(&response, tab).map_err(|message| ApiError {
+                error: true,
+                message,
+                code: Some("CONTINUITY_PARSE_ERROR".to_string()),
+            })?;
+            issues.extend(llm_issues);
+        }
+    }
+
+    Ok(issues)
+}
+
+#[tauri::command]
+async fn suggest_stars(
+    state_json: String,
+    tab_id: String,
+    use_llm: Option<bool>,
+    state: State<'_, Arc<AppConfig>>,
+) -> Result<Vec<StarSuggestion>, ApiError> {
+    let project = parse_project(&state_json)?;
+    let tab = project.draft_tabs.get(&tab_id).ok_or(ApiError {
+        error: true,
+        message: format!("Draft tab not found: {}", tab_id),
+        code: Some("NOT_FOUND".to_string()),
+    })?;
+    let now = chrono::Utc::now().timestamp_millis() as u64;
+
+    let suggestions = if use_llm.unwrap_or(true) {
+        let (system_prompt, user_prompt) = star_extraction::build_prompt(&project, tab);
+        let response = state.openai_client.send_prompt(&system_prompt, &user_prompt).await.map_err(|e| ApiError {
+            error: true,
+            message: format!("Failed to extract stars: {}", e),
+            code: Some("LLM_ERROR".to_string()),
+        })?;
+        star_extraction::parse_llm_suggestions(&project, tab, &response, now).map_err(|message| ApiError {
+            error: true,
+            message,
+            code: Some("EXTRACTION_PARSE_ERROR".to_string()),
+        })?
+    } else {
+        star_extraction::heuristic_suggestions(&project, tab, now)
+    };
+
+    Ok(star_extraction::deduplicate(&project, suggestions))
+}
+
+#[tauri::command]
+async fn suggest_character_updates(
+    state_json: String,
+    scene_id: Option<String>,
+    use_llm: Option<bool>,
+    state: State<'_, Arc<AppConfig>>,
+) -> Result<ProfileReport, ApiError> {
+    let project = parse_project(&state_json)?;
+    if let Some(scene_id) = &scene_id {
+        if !project.scenes.contains_key(scene_id) {
+            return Err(ApiError {
+                error: true,
+                message: format!("Scene not found: {}", scene_id),
+                code: Some("NOT_FOUND".to_string()),
+            });
+        }
+    }
+
+    let tabs: Vec<&DraftTab> = project
+        .draft_tabs
+        .values()
+        .filter(|tab| match &scene_id {
+            Some(scene_id) => tab.scene_id.as_ref() == Some(scene_id),
+            None => tab.scene_id.is_some(),
+        })
+        .collect();
+
+    let mut replies = Vec::new();
+    if use_llm.unwrap_or(false) {
+        for tab in &tabs {
+            let (system_prompt, user_prompt) = character_profiles::build_llm_prompt(&project, tab);
+            let response = state.openai_client.send_prompt(&system_prompt, &user_prompt).await.map_err(|e| ApiError {
+                error: true,
+                message: format!("Failed to extract character details: {}", e),
+                code: Some("LLM_ERROR".to_string()),
+            })?;
+            replies.push((tab.id.clone(), response));
+        }
+    }
+
+    character_profiles::build_report(&project, &tabs, &replies).map_err(|message| ApiError {
+        error: true,
+        message,
+        code: Some("PROFILE_PARSE_ERROR".to_string()),
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+struct RefreshedSummary {
+    level: SummaryLevel,
     id: String,
     text: String,
-    fulfilled_by: Vec<String>,
-    linked_stars: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct ScenePlan {
-    raw_text: String,
-    parsed_steps: Vec<PlanStep>,
+#[derive(Serialize, Deserialize)]
+struct SummaryRefreshOutcome {
+    state_json: String,
+    regenerated: Vec<RefreshedSummary>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct Scene {
-    id: String,
-    name: String,
-    setting: Option<String>,
-    backstory: Option<String>,
-    plan: ScenePlan,
-    draft_tab_ids: Vec<String>,
-    created_at: u64,
-    updated_at: u64,
+async fn write_summary(job: &SummaryJob, use_llm: bool, state: &AppConfig) -> Result<String, ApiError> {
+    if !use_llm {
+        return Ok(summaries::extractive_summary(job));
+    }
+    let (system_prompt, user_prompt) = summaries::build_prompt(job);
+    let response = state.openai_client.send_prompt(&system_prompt, &user_prompt).await.map_err(|e| ApiError {
+        error: true,
+        message: format!("Failed to summarise: {}", e),
+        code: Some("LLM_ERROR".to_string()),
+    })?;
+    Ok(response.trim().to_string())
+}
+
+// Bring tab, scene and story summaries up to date, rewriting only those whose sources changed
+#[tauri::command]
+async fn refresh_summaries(
+    state_json: String,
+    use_llm: Option<bool>,
+    state: State<'_, Arc<AppConfig>>,
+) -> Result<SummaryRefreshOutcome, ApiError> {
+    let mut project = parse_project(&state_json)?;
+    let use_llm = use_llm.unwrap_or(true);
+    let now = chrono::Utc::now().timestamp_millis() as u64;
+    let mut regenerated = Vec::new();
+
+    summaries::prune(&mut project);
+
+    // Each level is written from the one below, so finish a level before planning the next
+    let tab_jobs = summaries::stale_tabs(&mut project, now);
+    for job in tab_jobs {
+        let text = write_summary(&job, use_llm, &state).await?;
+        summaries::apply(&mut project, &job, text.clone(), now);
+        regenerated.push(RefreshedSummary { level: job.level, id: job.id, text });
+    }
+    for job in summaries::stale_scenes(&project) {
+        let text = write_summary(&job, use_llm, &state).await?;
+        summaries::apply(&mut project, &job, text.clone(), now);
+        regenerated.push(RefreshedSummary { level: job.level, id: job.id, text });
+    }
+    if let Some(job) = summaries::stale_story(&project) {
+        let text = write_summary(&job, use_llm, &state).await?;
+        summaries::apply(&mut project, &job, text.clone(), now);
+        regenerated.push(RefreshedSummary { level: job.level, id: job.id, text });
+    }
+
+    Ok(SummaryRefreshOutcome {
+        state_json: serde_json::to_string(&project).unwrap(),
+        regenerated,
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+struct OutlineOutcome {
+    state_json: String,
+    outline: Outline,
+    node_id: Option<String>, // set by create_outline_node
+}
+
+fn outline_outcome(project: &ProjectData, node_id: Option<String>) -> OutlineOutcome {
+    OutlineOutcome {
+        state_json: serde_json::to_string(project).unwrap(),
+        outline: project.outline.clone(),
+        node_id,
+    }
+}
+
+fn outline_error(message: String) -> ApiError {
+    let code = if message.contains("not found") { "NOT_FOUND" } else { "OUTLINE_ERROR" };
+    ApiError {
+        error: true,
+        message,
+        code: Some(code.to_string()),
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct IdeaBank {
-    stored_draft_tab_ids: Vec<String>,
+#[tauri::command]
+async fn get_outline(state_json: String) -> Result<OutlineOutcome, ApiError> {
+    let project = parse_project(&state_json)?;
+    Ok(outline_outcome(&project, None))
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct ProjectMetadata {
+#[tauri::command]
+async fn create_outline_node(
+    state_json: String,
+    kind: NodeKind,
     title: String,
-    author: Option<String>,
-    created_at: u64,
-    updated_at: u64,
+    parent_id: Option<String>,
+    index: Option<usize>,
+) -> Result<OutlineOutcome, ApiError> {
+    let mut project = parse_project(&state_json)?;
+    let id = outline::create_node(&mut project, kind, &title, parent_id.as_deref(), index).map_err(outline_error)?;
+    Ok(outline_outcome(&project, Some(id)))
+}
+
+#[tauri::command]
+async fn move_outline_node(
+    state_json: String,
+    node_id: String,
+    new_parent_id: String,
+    index: Option<usize>,
+) -> Result<OutlineOutcome, ApiError> {
+    let mut project = parse_project(&state_json)?;
+    outline::move_node(&mut project, &node_id, &new_parent_id, index).map_err(outline_error)?;
+    Ok(outline_outcome(&project, None))
+}
+
+#[tauri::command]
+async fn reorder_outline_children(
+    state_json: String,
+    parent_id: Option<String>,
+    ordered_ids: Vec<String>,
+) -> Result<OutlineOutcome, ApiError> {
+    let mut project = parse_project(&state_json)?;
+    outline::reorder_children(&mut project, parent_id.as_deref(), ordered_ids).map_err(outline_error)?;
+    Ok(outline_outcome(&project, None))
+}
+
+#[tauri::command]
+async fn export_manuscript(state_json: String, config: State<'_, Arc<AppConfig>>) -> Result<String, ApiError> {
+    let project = parse_project(&state_json)?;
+    let (text, _) = script_host(&config).on_export("markdown", outline::manuscript_markdown(&project));
+    Ok(text)
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct ProjectData {
-    version: String,
-    metadata: ProjectMetadata,
-    scenes: std::collections::HashMap<String, Scene>,
-    draft_tabs: std::collections::HashMap<String, DraftTab>,
-    stars: std::collections::HashMap<String, Star>,
-    characters: std::collections::HashMap<String, Character>,
-    plan_steps: std::collections::HashMap<String, PlanStep>,
-    idea_bank: IdeaBank,
-    active_scene_id: Option<String>,
+#[tauri::command]
+async fn search_project(
+    state_json: String,
+    query: String,
+    filters: Option<SearchFilters>,
+    config: State<'_, Arc<AppConfig>>,
+) -> Result<Vec<SearchHit>, ApiError> {
+    let project = parse_project(&state_json)?;
+    let mut index = config.search_index.lock().unwrap_or_else(|e| e.into_inner());
+    index.sync(&project);
+    Ok(index.search(&query, &filters.unwrap_or_default()))
 }
 
 #[derive(Serialize, Deserialize)]
-struct ApiError {
-    error: bool,
-    message: String,
-    code: Option<String>,
+struct ReplacePreviewOutcome {
+    previews: Vec<ReplacementPreview>,
+    renames: Vec<CharacterRename>,
 }
 
-// Application state
-struct AppConfig {
-    project_dir: PathBuf,
-    openai_client: OpenAIClient,
+#[derive(Serialize, Deserialize)]
+struct ReplaceApplyOutcome {
+    state_json: String,
+    applied: usize,
+    renames: Vec<CharacterRename>,
+    snapshot: SnapshotInfo, // restore it to undo the replacement
 }
 
-impl AppConfig {
-    fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        // Create project storage directory
-        let home_dir = dirs::home_dir().ok_or("Could not find home directory")?;
-        let project_dir = home_dir.join("Documents").join("SpicaWriter");
-        
-        if !project_dir.exists() {
-            fs::create_dir_all(&project_dir)?;
+fn replace_error(message: String) -> ApiError {
+    ApiError {
+        error: true,
+        message,
+        code: Some("REPLACE_ERROR".to_string()),
+    }
+}
+
+fn snapshot_error(e: std::io::Error) -> ApiError {
+    ApiError {
+        error: true,
+        message: format!("Snapshot failed: {}", e),
+        code: Some("SNAPSHOT_ERROR".to_string()),
+    }
+}
+
+#[tauri::command]
+async fn preview_replace(
+    state_json: String,
+    find: String,
+    replace: String,
+    options: Option<ReplaceOptions>,
+) -> Result<ReplacePreviewOutcome, ApiError> {
+    let project = parse_project(&state_json)?;
+    let result = replace::find_and_replace(&project, &find, &replace, &options.unwrap_or_default(), None)
+        .map_err(replace_error)?;
+    Ok(ReplacePreviewOutcome {
+        previews: result.previews,
+        renames: result.renames,
+    })
+}
+
+// Apply every match, or only `match_ids` from a preview, after snapshotting the current state
+#[tauri::command]
+async fn apply_replace(
+    state_json: String,
+    find: String,
+    replace: String,
+    options: Option<ReplaceOptions>,
+    match_ids: Option<Vec<String>>,
+    config: State<'_, Arc<AppConfig>>,
+) -> Result<ReplaceApplyOutcome, ApiError> {
+    let project = parse_project(&state_json)?;
+    let selected: Option<std::collections::HashSet<String>> = match_ids.map(|ids| ids.into_iter().collect());
+    let result = replace::find_and_replace(&project, &find, &replace, &options.unwrap_or_default(), selected.as_ref())
+        .map_err(replace_error)?;
+
+    let applied = result
+        .previews
+        .iter()
+        .filter(|p| p.original != p.replacement)
+        .filter(|p| selected.as_ref().map_or(true, |ids| ids.contains(&p.match_id)))
+        .count();
+    let label = format!("Replace \"{}\" with \"{}\" ({} changes)", find, replace, applied);
+    let now = chrono::Utc::now().timestamp_millis() as u64;
+    let dir = snapshots::project_dir(&config.get_snapshot_dir(), &project);
+    let snapshot = snapshots::save(&dir, &label, &state_json, now).map_err(snapshot_error)?;
+
+    Ok(ReplaceApplyOutcome {
+        state_json: serde_json::to_string(&result.project).unwrap(),
+        applied,
+        renames: result.renames,
+        snapshot,
+    })
+}
+
+#[tauri::command]
+async fn list_snapshots(state_json: String, config: State<'_, Arc<AppConfig>>) -> Result<Vec<SnapshotInfo>, ApiError> {
+    let project = parse_project(&state_json)?;
+    snapshots::list(&snapshots::project_dir(&config.get_snapshot_dir(), &project)).map_err(snapshot_error)
+}
+
+// Returns the project JSON as it was when the snapshot was taken
+#[tauri::command]
+async fn restore_snapshot(
+    state_json: String,
+    snapshot_id: String,
+    config: State<'_, Arc<AppConfig>>,
+) -> Result<String, ApiError> {
+    let project = parse_project(&state_json)?;
+    let dir = snapshots::project_dir(&config.get_snapshot_dir(), &project);
+    let snapshot = snapshots::load(&dir, &snapshot_id).map_err(|e| ApiError {
+        error: true,
+        message: format!("Snapshot not found: {} ({})", snapshot_id, e),
+        code: Some("NOT_FOUND".to_string()),
+    })?;
+    Ok(snapshot.state_json)
+}
+
+#[tauri::command]
+async fn get_manuscript_stats(state_json: String, options: Option<StatsOptions>) -> Result<ManuscriptStats, ApiError> {
+    let project = parse_project(&state_json)?;
+    Ok(stats::compute(&project, &options.unwrap_or_default()))
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProgressOutcome {
+    state_json: String,
+    report: ProgressReport,
+}
+
+// Update today's entry in the word-count history; call on save
+#[tauri::command]
+async fn record_writing_progress(state_json: String, options: Option<StatsOptions>) -> Result<ProgressOutcome, ApiError> {
+    let mut project = parse_project(&state_json)?;
+    let today = chrono::Local::now().date_naive();
+    let total = stats::compute(&project, &options.unwrap_or_default()).words.total;
+    stats::record_day(&mut project.progress, total, today);
+
+    Ok(ProgressOutcome {
+        report: stats::report(&project.progress, today),
+        state_json: serde_json::to_string(&project).unwrap(),
+    })
+}
+
+// None clears the goal; without one, any day with new words keeps the streak
+#[tauri::command]
+async fn set_writing_goal(state_json: String, daily_goal: Option<usize>) -> Result<ProgressOutcome, ApiError> {
+    let mut project = parse_project(&state_json)?;
+    project.progress.daily_goal = daily_goal.filter(|goal| *goal > 0);
+
+    Ok(ProgressOutcome {
+        report: stats::report(&project.progress, chrono::Local::now().date_naive()),
+        state_json: serde_json::to_string(&project).unwrap(),
+    })
+}
+
+// table: "scenes" | "characters" | "history"
+#[tauri::command]
+async fn export_stats_csv(
+    state_json: String,
+    table: String,
+    options: Option<StatsOptions>,
+    config: State<'_, Arc<AppConfig>>,
+) -> Result<String, ApiError> {
+    let project = parse_project(&state_json)?;
+    let text = match table.as_str() {
+        "scenes" => stats::scenes_csv(&stats::compute(&project, &options.unwrap_or_default())),
+        "characters" => stats::characters_csv(&stats::compute(&project, &options.unwrap_or_default())),
+        "history" => stats::history_csv(&project.progress),
+        _ => {
+            return Err(ApiError {
+                error: true,
+                message: format!("Unknown stats table: {}", table),
+                code: Some("INVALID_TABLE".to_string()),
+            })
+        }
+    };
+    let (text, _) = script_host(&config).on_export(&format!("{}_csv", table), text);
+    Ok(text)
+}
+
+// Style reports for one tab, every tab in a scene, or the whole project: one for each tab's
+// timeline and one for each of its descriptions
+#[tauri::command]
+async fn analyze_style(
+    state_json: String,
+    tab_id: Option<String>,
+    scene_id: Option<String>,
+    options: Option<StyleOptions>,
+) -> Result<Vec<StyleReport>, ApiError> {
+    let project = parse_project(&state_json)?;
+    let options = options.unwrap_or_default();
+
+    let mut tabs: Vec<&DraftTab> = match (&tab_id, &scene_id) {
+        (Some(tab_id), _) => vec![project.draft_tabs.get(tab_id).ok_or(ApiError {
+            error: true,
+            message: format!("Draft tab not found: {}", tab_id),
+            code: Some("NOT_FOUND".to_string()),
+        })?],
+        (None, Some(scene_id)) => {
+            let scene = project.scenes.get(scene_id).ok_or(ApiError {
+                error: true,
+                message: format!("Scene not found: {}", scene_id),
+                code: Some("NOT_FOUND".to_string()),
+            })?;
+            scene.draft_tab_ids.iter().filter_map(|id| project.draft_tabs.get(id)).collect()
         }
+        (None, None) => project.draft_tabs.values().collect(),
+    };
+    tabs.sort_by(|a, b| a.index.cmp(&b.index).then_with(|| a.id.cmp(&b.id)));
 
-        // Initialize OpenAI client
-        let openai_client = OpenAIClient::new()?;
+    let mut reports = Vec::new();
+    for tab in tabs {
+        reports.push(style::analyse_tab(tab, &options));
+        for description in &tab.descriptions {
+            reports.push(style::analyse_description(tab, description, &options));
+        }
+    }
+    Ok(reports)
+}
+
+fn dictionary_dirs(app: &tauri::AppHandle, config: &AppConfig) -> Vec<PathBuf> {
+    config.get_dictionary_dirs(app.path_resolver().resolve_resource("dictionaries"))
+}
+
+// Misspellings in any text field; names from the project and its custom dictionary are accepted
+#[tauri::command]
+async fn check_spelling(
+    state_json: String,
+    text: String,
+    language: Option<String>,
+    app: tauri::AppHandle,
+    config: State<'_, Arc<AppConfig>>,
+) -> Result<Vec<Misspelling>, ApiError> {
+    let project = parse_project(&state_json)?;
+    let dictionary = engine::load_dictionary(language.as_deref().unwrap_or("en_US"), &dictionary_dirs(&app, &config), &config)?;
+    Ok(spellcheck::check(&dictionary, &spellcheck::project_words(&project), &text))
+}
+
+#[tauri::command]
+async fn add_dictionary_word(state_json: String, word: String) -> Result<String, ApiError> {
+    let mut project = parse_project(&state_json)?;
+    let word = word.trim().to_string();
+    if word.is_empty() || word.chars().any(char::is_whitespace) {
+        return Err(ApiError {
+            error: true,
+            message: "Dictionary entries must be a single word".to_string(),
+            code: Some("INVALID_WORD".to_string()),
+        });
+    }
+    let words = &mut project.custom_dictionary.words;
+    if !words.iter().any(|w| w.eq_ignore_ascii_case(&word)) {
+        words.push(word);
+        words.sort_by_key(|w| w.to_lowercase());
+    }
+    Ok(serde_json::to_string(&project).unwrap())
+}
+
+#[tauri::command]
+async fn remove_dictionary_word(state_json: String, word: String) -> Result<String, ApiError> {
+    let mut project = parse_project(&state_json)?;
+    project.custom_dictionary.words.retain(|w| !w.eq_ignore_ascii_case(word.trim()));
+    Ok(serde_json::to_string(&project).unwrap())
+}
 
-        Ok(Self {
-            project_dir,
-            openai_client,
+#[tauri::command]
+async fn list_spelling_languages(app: tauri::AppHandle, config: State<'_, Arc<AppConfig>>) -> Result<Vec<String>, ApiError> {
+    Ok(spellcheck::available_languages(&dictionary_dirs(&app, &config)))
+}
+
+#[derive(Serialize, Deserialize)]
+struct TemplateListing {
+    templates: Vec<TemplateListEntry>,
+    load_errors: Vec<String>, // template files that could not be read
+}
+
+#[derive(Serialize, Deserialize)]
+struct TemplateListEntry {
+    #[serde(flatten)]
+    registered: RegisteredTemplate,
+    active: bool,
+    issues: Vec<TemplateIssue>,
+}
+
+// Every version of every template; without a project only built-in and app templates are listed
+#[tauri::command]
+async fn list_prompt_templates(state_json: Option<String>, config: State<'_, Arc<AppConfig>>) -> Result<TemplateListing, ApiError> {
+    let project = state_json.as_deref().map(parse_project).transpose()?;
+    let (registry, load_errors) = template_registry(&config, project.as_ref());
+    let pinned = project.as_ref().map(|p| p.prompt_templates.pinned.clone()).unwrap_or_default();
+
+    let templates = registry
+        .all()
+        .iter()
+        .map(|entry| {
+            let active = registry
+                .active(&entry.template.name, &pinned)
+                .map_or(false, |a| a.template.version == entry.template.version);
+            TemplateListEntry { issues: templates::validate(&entry.template), active, registered: entry.clone() }
         })
+        .collect();
+    Ok(TemplateListing { templates, load_errors })
+}
+
+#[derive(Serialize, Deserialize)]
+struct TemplateSaveOutcome {
+    state_json: String, // unchanged when saved to the app directory
+    template: PromptTemplate,
+}
+
+// Save as the next version of its name, either with the project or as an app-wide file
+#[tauri::command]
+async fn save_prompt_template(
+    state_json: String,
+    template: PromptTemplate,
+    scope: TemplateSource,
+    config: State<'_, Arc<AppConfig>>,
+) -> Result<TemplateSaveOutcome, ApiError> {
+    let mut project = parse_project(&state_json)?;
+    let (registry, _) = template_registry(&config, Some(&project));
+    let mut template = template;
+    template.version = registry.latest_version(&template.name) + 1;
+    template.created_at = chrono::Utc::now().timestamp_millis() as u64;
+
+    let issues = registry.validate(&template);
+    if !issues.is_empty() {
+        let messages: Vec<String> = issues.into_iter().map(|issue| issue.message).collect();
+        return Err(ApiError {
+            error: true,
+            message: format!("Invalid template: {}", messages.join("; ")),
+            code: Some("TEMPLATE_INVALID".to_string()),
+        });
     }
 
-    fn get_project_path(&self) -> PathBuf {
-        self.project_dir.join("last_project.json")
+    match scope {
+        TemplateSource::Project => project.prompt_templates.templates.push(template.clone()),
+        TemplateSource::App => templates::save_to_dir(&config.get_template_dir(), &template).map_err(|e| ApiError {
+            error: true,
+            message: format!("Failed to save template: {}", e),
+            code: Some("SAVE_ERROR".to_string()),
+        })?,
+        TemplateSource::Builtin => {
+            return Err(ApiError {
+                error: true,
+                message: "Built-in templates cannot be changed; save to the project or app instead".to_string(),
+                code: Some("INVALID_SCOPE".to_string()),
+            })
+        }
     }
+
+    Ok(TemplateSaveOutcome { state_json: serde_json::to_string(&project).unwrap(), template })
 }
 
-// Parse LLM response into timeline events and extract summary and atmosphere
-fn parse_response(response: &str) -> (Vec<TimelineEvent>, Option<String>, Option<String>) {
-    let mut timeline = Vec::new();
-    let mut summary: Option<String> = None;
-    let mut atmosphere: Option<String> = None;
-    
-    // Extract summary and atmosphere from between pipes (|Summary|Atmosphere|)
-    if let Some(first_pipe) = response.rfind('|') {
-        if let Some(third_pipe) = response[..first_pipe].rfind('|') {
-            if let Some(second_pipe) = response[..third_pipe].rfind('|') {
-                let summary_text = response[second_pipe + 1..third_pipe].trim();
-                let atmosphere_text = response[third_pipe + 1..first_pipe].trim();
-                
-                if !summary_text.is_empty() {
-                    summary = Some(summary_text.to_string());
-                }
-                if !atmosphere_text.is_empty() {
-                    atmosphere = Some(atmosphere_text.to_string());
-                }
-                
-                // Process only the content before the summary
-                let content_without_summary = &response[..second_pipe];
-            
-                for line in content_without_summary.lines() {
-                    let line = line.trim();
-                    if line.is_empty() {
-                        continue;
-                    }
-                    
-                    // Check if line contains dialogue (indicated by quotes)
-                    if let Some(dialogue_start) = line.find('"') {
-                        if let Some(dialogue_end) = line.rfind('"') {
-                            if dialogue_start < dialogue_end {
-                                let text_part = line[..dialogue_start].trim();
-                                let dialogue_part = line[dialogue_start + 1..dialogue_end].trim();
-                                
-                                timeline.push(TimelineEvent {
-                                    text: text_part.to_string(),
-                                    dialogue: if dialogue_part.is_empty() { None } else { Some(dialogue_part.to_string()) },
-                                    checked: true,
-                                });
-                                continue;
-                            }
-                        }
-                    }
-                    
-                    // Regular text event
-                    timeline.push(TimelineEvent {
-                        text: line.to_string(),
-                        dialogue: None,
-                        checked: true,
-                    });
-                }
-            } else {
-                // Fallback: only one pipe found, try to extract as summary only
-                let summary_text = response[..first_pipe].trim();
-                if !summary_text.is_empty() {
-                    summary = Some(summary_text.to_string());
-                }
-                
-                // Process only the content before the summary
-                let content_without_summary = &response[..first_pipe];
-                
-                for line in content_without_summary.lines() {
-                    let line = line.trim();
-                    if line.is_empty() {
-                        continue;
-                    }
-                    
-                    // Check if line contains dialogue (indicated by quotes)
-                    if let Some(dialogue_start) = line.find('"') {
-                        if let Some(dialogue_end) = line.rfind('"') {
-                            if dialogue_start < dialogue_end {
-                                let text_part = line[..dialogue_start].trim();
-                                let dialogue_part = line[dialogue_start + 1..dialogue_end].trim();
-                                
-                                timeline.push(TimelineEvent {
-                                    text: text_part.to_string(),
-                                    dialogue: if dialogue_part.is_empty() { None } else { Some(dialogue_part.to_string()) },
-                                    checked: true,
-                                });
-                                continue;
-                            }
-                        }
-                    }
-                    
-                    // Regular text event
-                    timeline.push(TimelineEvent {
-                        text: line.to_string(),
-                        dialogue: None,
-                        checked: true,
-                    });
-                }
-            }
+// Pin the version this project uses; None goes back to the newest
+#[tauri::command]
+async fn pin_prompt_template(
+    state_json: String,
+    name: String,
+    version: Option<u32>,
+    config: State<'_, Arc<AppConfig>>,
+) -> Result<String, ApiError> {
+    let mut project = parse_project(&state_json)?;
+    match version {
+        Some(version) => {
+            let (registry, _) = template_registry(&config, Some(&project));
+            registry.get(&name, version).ok_or_else(|| template_not_found(&name, Some(version)))?;
+            project.prompt_templates.pinned.insert(name, version);
         }
-    } else {
-        // Fallback: no pipes found, process entire response as timeline
-        for line in response.lines() {
-            let line = line.trim();
-            if line.is_empty() {
-                continue;
-            }
-            
-            // Check if line contains dialogue (indicated by quotes)
-            if let Some(dialogue_start) = line.find('"') {
-                if let Some(dialogue_end) = line.rfind('"') {
-                    if dialogue_start < dialogue_end {
-                        let text_part = line[..dialogue_start].trim();
-                        let dialogue_part = line[dialogue_start + 1..dialogue_end].trim();
-                        
-                        timeline.push(TimelineEvent {
-                            text: text_part.to_string(),
-                            dialogue: if dialogue_part.is_empty() { None } else { Some(dialogue_part.to_string()) },
-                            checked: true,
-                        });
-                        continue;
-                    }
-                }
+        None => {
+            project.prompt_templates.pinned.remove(&name);
+        }
+    }
+    Ok(serde_json::to_string(&project).unwrap())
+}
+
+// Choose the template generation uses for a prompt type; None goes back to the built-in one
+#[tauri::command]
+async fn select_prompt_template(
+    state_json: String,
+    prompt_type: PromptType,
+    name: Option<String>,
+    config: State<'_, Arc<AppConfig>>,
+) -> Result<String, ApiError> {
+    let mut project = parse_project(&state_json)?;
+    match name {
+        Some(name) => {
+            let (registry, _) = template_registry(&config, Some(&project));
+            let template = &registry.active(&name, &project.prompt_templates.pinned).ok_or_else(|| template_not_found(&name, None))?.template;
+            if template.prompt_type != prompt_type {
+                return Err(ApiError {
+                    error: true,
+                    message: format!("Template {} is for {:?} prompts, not {:?}", name, template.prompt_type, prompt_type),
+                    code: Some("TEMPLATE_MISMATCH".to_string()),
+                });
             }
-            
-            // Regular text event
-            timeline.push(TimelineEvent {
-                text: line.to_string(),
-                dialogue: None,
-                checked: true,
-            });
+            project.prompt_templates.selected.insert(prompt_type, name);
+        }
+        None => {
+            project.prompt_templates.selected.remove(&prompt_type);
         }
     }
-    
-    if timeline.is_empty() {
-        // Fallback: create a single event with the entire response
-        timeline.push(TimelineEvent {
-            text: response.trim().to_string(),
-            dialogue: None,
-            checked: true,
-        });
+    Ok(serde_json::to_string(&project).unwrap())
+}
+
+// Preview a template; sample data is used for anything not given
+#[tauri::command]
+async fn render_prompt_template(
+    state_json: Option<String>,
+    name: String,
+    version: Option<u32>,
+    values: Option<TemplateValues>,
+    config: State<'_, Arc<AppConfig>>,
+) -> Result<RenderedPrompt, ApiError> {
+    let project = state_json.as_deref().map(parse_project).transpose()?;
+    let (registry, _) = template_registry(&config, project.as_ref());
+    let pinned = project.as_ref().map(|p| p.prompt_templates.pinned.clone()).unwrap_or_default();
+    let entry = match version {
+        Some(version) => registry.get(&name, version),
+        None => registry.active(&name, &pinned),
     }
-    
-    (timeline, summary, atmosphere)
+    .ok_or_else(|| template_not_found(&name, version))?;
+
+    let values = values.unwrap_or_else(|| templates::sample_values(entry.template.prompt_type));
+    templates::render(&entry.template, &values).map_err(|issues| {
+        let messages: Vec<String> = issues.into_iter().map(|issue| issue.message).collect();
+        ApiError { error: true, message: messages.join("; "), code: Some("TEMPLATE_INVALID".to_string()) }
+    })
 }
 
-// Tauri commands
 #[tauri::command]
-async fn send_prompt(system_prompt: String, user_prompt: String, state: State<'_, AppConfig>) -> Result<LLMResponse, ApiError> {
-    match state.openai_client.send_prompt(&system_prompt, &user_prompt).await {
-        Ok(response) => {
-            // Parse the response into timeline events and extract summary and atmosphere
-            let (timeline, summary, atmosphere) = parse_response(&response);
-            
-            let tabs = vec![
-                openai_client::LLMTab {
-                    title: "Generated Scene Segment".to_string(),
-                    timeline,
-                    summary,
-                    atmosphere,
-                }
-            ];
-            
-            Ok(LLMResponse { tabs })
+async fn diff_prompt_templates(
+    state_json: Option<String>,
+    name: String,
+    from_version: u32,
+    to_version: u32,
+    config: State<'_, Arc<AppConfig>>,
+) -> Result<TemplateDiff, ApiError> {
+    let project = state_json.as_deref().map(parse_project).transpose()?;
+    let (registry, _) = template_registry(&config, project.as_ref());
+    let from = registry.get(&name, from_version).ok_or_else(|| template_not_found(&name, Some(from_version)))?;
+    let to = registry.get(&name, to_version).ok_or_else(|| template_not_found(&name, Some(to_version)))?;
+    Ok(templates::diff(&from.template, &to.template))
+}
+
+#[tauri::command]
+async fn get_log_config(config: State<'_, Arc<AppConfig>>) -> Result<LogConfig, ApiError> {
+    Ok(config.audit.config())
+}
+
+#[tauri::command]
+async fn set_log_config(log_config: LogConfig, config: State<'_, Arc<AppConfig>>) -> Result<LogConfig, ApiError> {
+    config.audit.set_config(log_config).map_err(|e| ApiError {
+        error: true,
+        message: format!("Failed to save log settings: {}", e),
+        code: Some("SAVE_ERROR".to_string()),
+    })?;
+    Ok(config.audit.config())
+}
+
+// The newest entries as JSON lines, for attaching to bug reports
+#[tauri::command]
+async fn export_log_entries(limit: Option<usize>, config: State<'_, Arc<AppConfig>>) -> Result<String, ApiError> {
+    config.audit.export(limit.unwrap_or(200)).map_err(|e| ApiError {
+        error: true,
+        message: format!("Failed to read logs: {}", e),
+        code: Some("LOG_ERROR".to_string()),
+    })
+}
+
+// Scripts in <project dir>/scripts and the hooks each defines; scripts that failed to compile carry the error
+#[tauri::command]
+async fn list_scripts(config: State<'_, Arc<AppConfig>>) -> Result<Vec<ScriptInfo>, ApiError> {
+    Ok(script_host(&config).info())
+}
+
+// The localhost API server, when running
+struct LocalApi(Mutex<Option<ApiServer>>);
+
+#[derive(Serialize, Deserialize)]
+struct ApiStatus {
+    settings: ApiSettings,
+    address: Option<String>, // where the server is listening, e.g. 127.0.0.1:7357
+    error: Option<String>,   // why it failed to start
+}
+
+fn api_status(settings: ApiSettings, api: &LocalApi, error: Option<String>) -> ApiStatus {
+    let address = api.0.lock().unwrap_or_else(|e| e.into_inner()).as_ref().map(|server| server.addr.to_string());
+    ApiStatus { settings, address, error }
+}
+
+// Stop any running server and start a new one if the settings enable it
+async fn restart_api(config: &Arc<AppConfig>, api: &LocalApi, settings: &ApiSettings) -> Option<String> {
+    if let Some(server) = api.0.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        server.stop();
+    }
+    if !settings.enabled {
+        return None;
+    }
+    match ApiServer::start(config.clone(), settings).await {
+        Ok(server) => {
+            config.audit.log(LogLevel::Info, "api_server", "started", serde_json::json!({ "address": server.addr.to_string() }), serde_json::Value::Null);
+            *api.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(server);
+            None
         }
-        Err(e) => Err(ApiError {
-            error: true,
-            message: format!("Failed to process prompt: {}", e),
-            code: Some("LLM_ERROR".to_string()),
-        })
+        Err(e) => {
+            let message = format!("Failed to start the API server on port {}: {}", settings.port, e);
+            config.audit.log(LogLevel::Error, "api_server", "start_failed", serde_json::json!({ "message": message }), serde_json::Value::Null);
+            Some(message)
+        }
+    }
+}
+
+fn api_settings_error(e: std::io::Error) -> ApiError {
+    ApiError {
+        error: true,
+        message: format!("Failed to save API settings: {}", e),
+        code: Some("SAVE_ERROR".to_string()),
     }
 }
 
 #[tauri::command]
-async fn generate_description(system_prompt: String, user_prompt: String, state: State<'_, AppConfig>) -> Result<String, ApiError> {
-    match state.openai_client.send_prompt(&system_prompt, &user_prompt).await {
-        Ok(response) => {
-            // For description generation, return the raw response as a string
-            Ok(response)
+async fn get_api_settings(config: State<'_, Arc<AppConfig>>, api: State<'_, LocalApi>) -> Result<ApiStatus, ApiError> {
+    Ok(api_status(ApiSettings::load(&config.get_api_settings_path()), &api, None))
+}
+
+#[tauri::command]
+async fn set_api_settings(
+    enabled: bool,
+    port: Option<u16>,
+    config: State<'_, Arc<AppConfig>>,
+    api: State<'_, LocalApi>,
+) -> Result<ApiStatus, ApiError> {
+    let mut settings = ApiSettings::load(&config.get_api_settings_path());
+    settings.enabled = enabled;
+    settings.port = port.unwrap_or(settings.port);
+    settings.save(&config.get_api_settings_path()).map_err(api_settings_error)?;
+    let error = restart_api(&config, &api, &settings).await;
+    Ok(api_status(settings, &api, error))
+}
+
+// Invalidates the old token; clients need the new one
+#[tauri::command]
+async fn rotate_api_token(config: State<'_, Arc<AppConfig>>, api: State<'_, LocalApi>) -> Result<ApiStatus, ApiError> {
+    let mut settings = ApiSettings::load(&config.get_api_settings_path());
+    settings.token = api_server::new_token();
+    settings.save(&config.get_api_settings_path()).map_err(api_settings_error)?;
+    let error = restart_api(&config, &api, &settings).await;
+    Ok(api_status(settings, &api, error))
+}
+
+// Watches the notes folder while vault sync is on
+struct VaultSync(Mutex<Option<VaultWatcher>>);
+
+// Watches the saved project for writes by other programs, such as `spica mcp`
+struct ProjectWatch(Mutex<Option<VaultWatcher>>);
+
+#[derive(Serialize, Deserialize)]
+struct VaultStatus {
+    settings: VaultSettings,
+    watching: bool,
+    report: Option<SyncReport>, // from the first sync when syncing is turned on
+    error: Option<String>,
+}
+
+fn log_vault_error(config: &AppConfig, result: Result<SyncReport, ApiError>) {
+    if let Err(e) = result {
+        config.audit.log(LogLevel::Error, "vault", "sync_failed", serde_json::json!({ "message": e.message }), serde_json::Value::Null);
+    }
+}
+
+// Notes edited in the folder are synced into the saved project as soon as they are written
+fn restart_vault(config: &Arc<AppConfig>, vault: &VaultSync, settings: &VaultSettings) -> Option<String> {
+    let mut watcher = vault.0.lock().unwrap_or_else(|e| e.into_inner());
+    *watcher = None;
+    let folder = settings.active_folder()?.to_path_buf();
+    let sync_config = config.clone();
+    let watched = folder.clone();
+    match vault::watch(&folder, move || log_vault_error(&sync_config, engine::sync_vault(&sync_config, &watched, &HashMap::new()))) {
+        Ok(started) => {
+            *watcher = Some(started);
+            None
         }
-        Err(e) => Err(ApiError {
-            error: true,
-            message: format!("Failed to generate description: {}", e),
-            code: Some("LLM_ERROR".to_string()),
-        })
+        Err(e) => Some(format!("Failed to watch {}: {}", folder.display(), e)),
+    }
+}
+
+fn vault_settings_error(e: std::io::Error) -> ApiError {
+    ApiError {
+        error: true,
+        message: format!("Failed to save vault settings: {}", e),
+        code: Some("SAVE_ERROR".to_string()),
     }
 }
 
 #[tauri::command]
-async fn save_project(state_json: String, config: State<'_, AppConfig>) -> Result<(), ApiError> {
-    let project_path = config.get_project_path();
+async fn get_vault_settings(config: State<'_, Arc<AppConfig>>, vault: State<'_, VaultSync>) -> Result<VaultStatus, ApiError> {
+    let settings = VaultSettings::load(&config.get_vault_settings_path());
+    let watching = vault.0.lock().unwrap_or_else(|e| e.into_inner()).is_some();
+    Ok(VaultStatus { settings, watching, report: None, error: None })
+}
+
+// Turning syncing on runs a first sync, which writes every character and star to the folder
+#[tauri::command]
+async fn set_vault_settings(
+    enabled: bool,
+    folder: Option<PathBuf>,
+    config: State<'_, Arc<AppConfig>>,
+    vault: State<'_, VaultSync>,
+) -> Result<VaultStatus, ApiError> {
+    let settings = VaultSettings { enabled, folder };
+    if let Some(folder) = settings.active_folder() {
+        fs::create_dir_all(folder).map_err(vault_settings_error)?;
+    }
+    settings.save(&config.get_vault_settings_path()).map_err(vault_settings_error)?;
+
+    let mut error = restart_vault(&config, &vault, &settings);
+    let mut report = None;
+    if let Some(folder) = settings.active_folder() {
+        match engine::sync_vault(&config, folder, &HashMap::new()) {
+            Ok(first) => report = Some(first),
+            Err(e) => error = error.or(Some(e.message)),
+        }
+    }
+    let watching = vault.0.lock().unwrap_or_else(|e| e.into_inner()).is_some();
+    Ok(VaultStatus { settings, watching, report, error })
+}
+
+// resolutions: which side wins for conflicts from an earlier report, by character or star id
+#[tauri::command]
+async fn sync_vault(resolutions: Option<HashMap<String, Keep>>, config: State<'_, Arc<AppConfig>>) -> Result<SyncReport, ApiError> {
+    let settings = VaultSettings::load(&config.get_vault_settings_path());
+    let folder = settings.active_folder().ok_or(ApiError {
+        error: true,
+        message: "Vault sync is turned off".to_string(),
+        code: Some("VAULT_DISABLED".to_string()),
+    })?;
+    engine::sync_vault(&config, folder, &resolutions.unwrap_or_default())
+}
+
+#[tauri::command]
+async fn save_project(state_json: String, config: State<'_, Arc<AppConfig>>) -> Result<(), ApiError> {
+    let _guard = config.project_lock.lock().unwrap_or_else(|e| e.into_inner());
+
+    // Save scripts see the project first; if they changed it, the window reloads it
+    let scripts = script_host(&config);
+    let mut rewritten = false;
+    let state_json = match parse_project(&state_json) {
+        Ok(project) if !scripts.is_empty() => {
+            let original = serde_json::to_value(&project).unwrap();
+            let (project, _) = scripts.before_save(project);
+            rewritten = serde_json::to_value(&project).unwrap() != original;
+            if rewritten { serde_json::to_string(&project).unwrap() } else { state_json }
+        }
+        _ => state_json,
+    };
     
-    match fs::write(&project_path, state_json) {
-        Ok(_) => Ok(()),
-        Err(e) => Err(ApiError {
-            error: true,
-            message: format!("Failed to save project: {}", e),
-            code: Some("SAVE_ERROR".to_string()),
-        })
+    match config.write_saved_project(&state_json) {
+        Ok(_) => {
+            config.saved_project_open.store(true, Ordering::SeqCst);
+            // Keep search results current; only edited items are re-indexed
+            if let Ok(project) = parse_project(&state_json) {
+                config.search_index.lock().unwrap_or_else(|e| e.into_inner()).sync(&project);
+            }
+            let source = if rewritten { "scripts" } else { "app" };
+            let _ = config.events.send(ProjectEvent::new("project_saved", source, None));
+
+            // Mirror the save to the notes folder; conflicts are left for sync_vault to report
+            if let Some(folder) = VaultSettings::load(&config.get_vault_settings_path()).active_folder() {
+                drop(_guard);
+                log_vault_error(&config, engine::sync_vault(&config, folder, &HashMap::new()));
+            }
+            Ok(())
+        }
+        Err(e) => Err(e),
     }
 }
 
@@ -385,35 +1244,22 @@ async fn save_project_as(state_json: String) -> Result<(), ApiError> {
 }
 
 #[tauri::command]
-async fn load_project(config: State<'_, AppConfig>) -> Result<String, ApiError> {
+async fn load_project(config: State<'_, Arc<AppConfig>>) -> Result<String, ApiError> {
     let project_path = config.get_project_path();
+    config.saved_project_open.store(true, Ordering::SeqCst);
     
     if !project_path.exists() {
         // Return empty state if no project exists
-        let empty_project = ProjectData {
-            version: "1.0".to_string(),
-            metadata: ProjectMetadata {
-                title: "New Project".to_string(),
-                author: None,
-                created_at: chrono::Utc::now().timestamp_millis() as u64,
-                updated_at: chrono::Utc::now().timestamp_millis() as u64,
-            },
-            scenes: std::collections::HashMap::new(),
-            draft_tabs: std::collections::HashMap::new(),
-            stars: std::collections::HashMap::new(),
-            characters: std::collections::HashMap::new(),
-            plan_steps: std::collections::HashMap::new(),
-            idea_bank: IdeaBank {
-                stored_draft_tab_ids: Vec::new(),
-            },
-            active_scene_id: None,
-        };
+        let empty_project = ProjectData::new("New Project", chrono::Utc::now().timestamp_millis() as u64);
         
         return Ok(serde_json::to_string(&empty_project).unwrap());
     }
     
     match fs::read_to_string(&project_path) {
-        Ok(content) => Ok(content),
+        Ok(content) => {
+            config.note_saved_project(&content);
+            Ok(content)
+        }
         Err(e) => Err(ApiError {
             error: true,
             message: format!("Failed to load project: {}", e),
@@ -423,7 +1269,7 @@ async fn load_project(config: State<'_, AppConfig>) -> Result<String, ApiError>
 }
 
 #[tauri::command]
-async fn load_project_from_file() -> Result<String, ApiError> {
+async fn load_project_from_file(config: State<'_, Arc<AppConfig>>) -> Result<String, ApiError> {
     use tauri::api::dialog::blocking::FileDialogBuilder;
     
     // Get the default project directory
@@ -442,7 +1288,11 @@ async fn load_project_from_file() -> Result<String, ApiError> {
     
     if let Some(path) = file_path {
         match fs::read_to_string(&path) {
-            Ok(content) => Ok(content),
+            Ok(content) => {
+                // Until it is saved, the window shows something other than the saved project
+                config.saved_project_open.store(false, Ordering::SeqCst);
+                Ok(content)
+            }
             Err(e) => Err(ApiError {
                 error: true,
                 message: format!("Failed to load project: {}", e),
@@ -458,8 +1308,15 @@ async fn load_project_from_file() -> Result<String, ApiError> {
     }
 }
 
+// The window started a new project, which is not the saved file until it is saved
+#[tauri::command]
+async fn close_saved_project(config: State<'_, Arc<AppConfig>>) -> Result<(), ApiError> {
+    config.saved_project_open.store(false, Ordering::SeqCst);
+    Ok(())
+}
+
 #[tauri::command]
-async fn create_new_project_path(config: State<'_, AppConfig>) -> Result<String, ApiError> {
+async fn create_new_project_path(config: State<'_, Arc<AppConfig>>) -> Result<String, ApiError> {
     // For now, return the default path
     // In the future, this could open a file dialog
     Ok(config.get_project_path().to_string_lossy().to_string())
@@ -471,24 +1328,119 @@ fn main() {
 
     // Initialize application config
     let app_config = match AppConfig::new() {
-        Ok(config) => config,
+        Ok(config) => Arc::new(config),
         Err(e) => {
             eprintln!("Failed to initialize application: {}", e);
             std::process::exit(1);
         }
     };
 
+    // The window starts with an empty project; loading or saving opens the saved file
+    app_config.saved_project_open.store(false, Ordering::SeqCst);
+
+    let audit = app_config.audit.clone();
+    audit.log(LogLevel::Info, "startup", env!("CARGO_PKG_VERSION"), serde_json::Value::Null, serde_json::Value::Null);
+
+    let handler = tauri::generate_handler![
+        resolve_speakers,
+        assemble_prompt,
+        retrieve_relevant,
+        get_stale_stars,
+        get_unreflected_stars,
+        parse_scene_plan,
+        detect_plan_fulfilment,
+        get_plan_progress,
+        check_continuity,
+        suggest_stars,
+        suggest_character_updates,
+        refresh_summaries,
+        get_outline,
+        create_outline_node,
+        move_outline_node,
+        reorder_outline_children,
+        export_manuscript,
+        search_project,
+        preview_replace,
+        apply_replace,
+        list_snapshots,
+        restore_snapshot,
+        get_manuscript_stats,
+        record_writing_progress,
+        set_writing_goal,
+        export_stats_csv,
+        analyze_style,
+        check_spelling,
+        add_dictionary_word,
+        remove_dictionary_word,
+        list_spelling_languages,
+        list_prompt_templates,
+        save_prompt_template,
+        pin_prompt_template,
+        select_prompt_template,
+        render_prompt_template,
+        diff_prompt_templates,
+        generate_draft,
+        regenerate_draft,
+        add_generated_draft,
+        get_log_config,
+        set_log_config,
+        export_log_entries,
+        list_scripts,
+        get_api_settings,
+        set_api_settings,
+        rotate_api_token,
+        get_vault_settings,
+        set_vault_settings,
+        sync_vault,
+        save_project,
+        load_project,
+        save_project_as,
+        load_project_from_file,
+        close_saved_project,
+        create_new_project_path
+    ];
+
     tauri::Builder::default()
-        .manage(app_config)
-        .invoke_handler(tauri::generate_handler![
-            send_prompt,
-            generate_description,
-            save_project,
-            load_project,
-            save_project_as,
-            load_project_from_file,
-            create_new_project_path
-        ])
+        .manage(app_config.clone())
+        .manage(LocalApi(Mutex::new(None)))
+        .manage(VaultSync(Mutex::new(None)))
+        .manage(ProjectWatch(Mutex::new(None)))
+        .setup(move |app| {
+            let handle = app.handle();
+            let mut events = app_config.events.subscribe();
+            tauri::async_runtime::spawn(async move {
+                // The window reloads, or merges into unsaved edits, when the API, a save script, the vault or another program changed the project
+                while let Some(event) = api_server::next_event(&mut events).await {
+                    if event.source != "app" {
+                        let _ = handle.emit_all("project-changed", event);
+                    }
+                }
+            });
+
+            match engine::watch_saved_project(app_config.clone()) {
+                Ok(watcher) => *app.handle().state::<ProjectWatch>().0.lock().unwrap_or_else(|e| e.into_inner()) = Some(watcher),
+                Err(e) => eprintln!("Failed to watch the saved project: {}", e),
+            }
+
+            let vault_settings = VaultSettings::load(&app_config.get_vault_settings_path());
+            if let Some(error) = restart_vault(&app_config, &app.handle().state::<VaultSync>(), &vault_settings) {
+                eprintln!("{}", error);
+            }
+
+            let settings = ApiSettings::load(&app_config.get_api_settings_path());
+            let handle = app.handle();
+            tauri::async_runtime::spawn(async move {
+                if let Some(error) = restart_api(&app_config, &handle.state::<LocalApi>(), &settings).await {
+                    eprintln!("{}", error);
+                }
+            });
+            Ok(())
+        })
+        // Every invocation goes to the audit log before it runs; its result is not visible here
+        .invoke_handler(move |invoke| {
+            audit.command(invoke.message.command(), invoke.message.payload());
+            handler(invoke)
+        })
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
-}
\ No newline at end of file
+}
@@ -1,4 +1,5 @@
-import React from 'react';
+import React, { useEffect } from 'react';
+import { listen } from '@tauri-apps/api/event';
 import { useAppStore } from './stores';
 import { useHotkeys } from 'react-hotkeys-hook';
 import { BlueprintPanel } from './components/BlueprintPanel';
@@ -6,6 +7,7 @@ import { WorkingArea } from './components/WorkingArea';
 import { StarsPanel } from './components/StarsPanel';
 import { ModalEditor } from './components/ModalEditor';
 import { FileText, FolderOpen, Save, Plus } from 'lucide-react';
+import type { ProjectEvent } from './types';
 import './App.css';
 
 function App() {
@@ -14,12 +16,34 @@ function App() {
     saveProject, 
     saveProjectAs,
     loadProjectFromFile,
+    loadProject,
     createNewProject,
     closeModal 
   } = useAppStore();
 
   // Removed automatic loading on startup
 
+  // Pick up changes made through the local API, a save script or the notes folder, which edit the
+  // saved project. The backend only makes them while the window shows that file.
+  useEffect(() => {
+    const unlisten = listen<ProjectEvent>('project-changed', async ({ payload }) => {
+      const { hasUnsavedChanges, mergeSavedStar } = useAppStore.getState();
+      if (!hasUnsavedChanges()) {
+        await loadProject();
+      } else if (payload.id && payload.kind.startsWith('star_')) {
+        await mergeSavedStar(payload.id);
+      } else if (window.confirm(
+        `The project was changed outside the app (${payload.source}). Reload it and discard your unsaved changes?\n\n` +
+        'Cancel keeps your changes; saving them will overwrite the outside change.'
+      )) {
+        await loadProject();
+      }
+    });
+    return () => {
+      unlisten.then(stop => stop());
+    };
+  }, [loadProject]);
+
   // Global keyboard shortcuts
   useHotkeys('cmd+s, ctrl+s', (e) => {
     e.preventDefault();
@@ -46,9 +70,7 @@ function App() {
   });
 
   const handleNewProject = async () => {
-    const hasUnsavedWork = true; // We could add logic to detect actual changes later
-    
-    if (hasUnsavedWork) {
+    if (useAppStore.getState().hasUnsavedChanges()) {
       const shouldSave = window.confirm(
         'Save current project before creating a new one? This will save your current work.'
       );
@@ -1,46 +1,45 @@
 import React, { useState } from 'react';
 import { X, Send, Loader } from 'lucide-react';
-import { LLMService } from '../services/llmService';
-import type { Scene, Character, Star, DraftTab } from '../types';
+import { useAppStore } from '../stores';
 
 interface DescriptionGenerationModalProps {
   isOpen: boolean;
   onClose: () => void;
-  onGenerate: (description: string) => void;
+  onGenerated: () => void; // the description has been added to the tab in the store
+  tabId: string;
+  sceneId?: string; // context scene; the tab's scene, or the active one for workbench tabs
   targetEvent: {
+    id?: string;
     text: string;
     dialogue?: string;
   };
-  contextParams: {
-    scene: Scene;
-    characters: Character[];
-    checkedStars: Star[];
-    recentTabs: DraftTab[];
-  };
 }
 
 export const DescriptionGenerationModal: React.FC<DescriptionGenerationModalProps> = ({
   isOpen,
   onClose,
-  onGenerate,
-  targetEvent,
-  contextParams
+  onGenerated,
+  tabId,
+  sceneId,
+  targetEvent
 }) => {
+  const generateDescription = useAppStore(state => state.generateDescription);
   const [promptText, setPromptText] = useState('');
   const [isGenerating, setIsGenerating] = useState(false);
 
   const handleGenerate = async () => {
     if (!promptText.trim()) return;
+    if (!sceneId) {
+      alert('Select a scene first: descriptions are generated with its context.');
+      return;
+    }
 
     setIsGenerating(true);
     try {
-      const response = await LLMService.generateEventDescription(
-        targetEvent,
-        promptText,
-        contextParams
-      );
+      const targetEventText = `${targetEvent.text}${targetEvent.dialogue ? ` -> "${targetEvent.dialogue}"` : ''}`;
+      await generateDescription(tabId, sceneId, promptText, targetEventText, targetEvent.id);
       
-      onGenerate(response.description);
+      onGenerated();
       onClose();
     } catch (error) {
       console.error('Failed to generate description:', error);
@@ -8,7 +8,7 @@ import { DescriptionGenerationModal } from './DescriptionGenerationModal';
 export const ModalEditor: React.FC = () => {
   const { 
     draft_tabs,
-    scenes,
+    active_scene_id,
     characters,
     stars,
     idea_bank,
@@ -23,7 +23,6 @@ export const ModalEditor: React.FC = () => {
     addTimelineEvent,
     updateTimelineEvent,
     deleteTimelineEvent,
-    addDescription,
     updateDescription,
     deleteDescription
   } = useAppStore();
@@ -168,27 +167,13 @@ export const ModalEditor: React.FC = () => {
     }
   };
 
-  const handleDescriptionGenerated = (description: string) => {
+  const handleDescriptionGenerated = () => {
     if (!modal || modal.type !== 'tab') return;
-    
-    const event = localState.timeline?.[descriptionModal.eventIndex];
-    if (!event) {
-      console.error('Event not found at index:', descriptionModal.eventIndex);
-      return;
-    }
-
-    // Add the generated description
-    addDescription(modal.id, {
-      text: description,
-      is_important: false,
-      scope: 'event',
-      target_event_id: event.id
-    });
 
-    // Refresh local state to show the new description
-    const updatedTab = draft_tabs[modal.id];
+    // The store now holds the new description; keep the other unsaved edits
+    const updatedTab = useAppStore.getState().draft_tabs[modal.id];
     if (updatedTab) {
-      setLocalState({ ...updatedTab });
+      setLocalState((prev: any) => ({ ...prev, descriptions: updatedTab.descriptions }));
     }
   };
 
@@ -781,14 +766,10 @@ export const ModalEditor: React.FC = () => {
         <DescriptionGenerationModal
           isOpen={descriptionModal.isOpen}
           onClose={() => setDescriptionModal({ isOpen: false, eventIndex: -1 })}
-          onGenerate={handleDescriptionGenerated}
+          onGenerated={handleDescriptionGenerated}
+          tabId={modal.id}
+          sceneId={localState.scene_id || active_scene_id}
           targetEvent={localState.timeline[descriptionModal.eventIndex]}
-          contextParams={{
-            scene: scenes[localState.scene_id] || { id: '', name: '', draft_tab_ids: [], created_at: 0, updated_at: 0, plan: { raw_text: '', parsed_steps: [] } },
-            characters: Object.values(characters),
-            checkedStars: Object.values(stars).filter(star => star.is_checked),
-            recentTabs: Object.values(draft_tabs).slice(-5) // Last 5 tabs for context
-          }}
         />
       )}
     </div>
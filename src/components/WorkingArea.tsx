@@ -1,4 +1,4 @@
-import React, { useState } from 'react';
+import React, { useEffect, useState } from 'react';
 import { useAppStore } from '../stores';
 import { useHotkeys } from 'react-hotkeys-hook';
 import { useSortable, SortableContext, verticalListSortingStrategy } from '@dnd-kit/sortable';
@@ -429,6 +429,8 @@ export const WorkingArea: React.FC = () => {
   const { 
     draft_tabs,
     scenes,
+    stars,
+    characters,
     active_scene_id,
     idea_bank,
     isLoading, 
@@ -451,8 +453,22 @@ export const WorkingArea: React.FC = () => {
   
   const [promptText, setPromptText] = useState('');
   const [bankCollapsed, setBankCollapsed] = useState(false);
+  const [contextPreview, setContextPreview] = useState('');
   const activeScene = active_scene_id ? scenes[active_scene_id] : null;
 
+  // Re-assembled in the backend whenever something that feeds the context changes, including the
+  // prompt being typed; waits for a pause in typing
+  useEffect(() => {
+    if (!ui.showContextPreview || !active_scene_id) return;
+    let cancelled = false;
+    const timer = setTimeout(() => {
+      buildLLMContext(active_scene_id, promptText.trim())
+        .then(context => { if (!cancelled) setContextPreview(context); })
+        .catch(error => { if (!cancelled) setContextPreview(`Could not assemble the context: ${error instanceof Error ? error.message : error}`); });
+    }, 300);
+    return () => { cancelled = true; clearTimeout(timer); };
+  }, [ui.showContextPreview, active_scene_id, scenes, draft_tabs, stars, characters, promptText, buildLLMContext]);
+
   const workbenchTabs = getWorkbenchTabs();
   const workbenchTabIds = workbenchTabs.map(tab => tab.id);
 
@@ -563,7 +579,7 @@ export const WorkingArea: React.FC = () => {
               </button>
             </div>
             <pre className="context-preview-content">
-              {buildLLMContext(active_scene_id)}
+              {contextPreview}
             </pre>
           </div>
         )}
@@ -37,6 +37,7 @@ export class PersistenceService {
     return {
       version: '1.0',
       metadata: {
+        id: uuidv4(),
         title,
         author,
         created_at: Date.now(),
@@ -73,7 +74,12 @@ export class PersistenceService {
       characters: data.characters || {},
       plan_steps: data.plan_steps || {},
       idea_bank: data.idea_bank || { stored_draft_tab_ids: [] },
-      active_scene_id: data.active_scene_id
+      active_scene_id: data.active_scene_id,
+      outline: data.outline,
+      summaries: data.summaries,
+      progress: data.progress,
+      custom_dictionary: data.custom_dictionary,
+      prompt_templates: data.prompt_templates
     };
 
     // Clean up orphaned references
@@ -115,8 +115,9 @@ interface AppStore extends AppState {
   toggleContextPreview: () => void;
   
   // === LLM ACTIONS ===
-  buildLLMContext: (sceneId: string) => string;
+  buildLLMContext: (sceneId: string, userInput: string) => Promise<string>;
   sendPrompt: (text: string, sceneId: string) => Promise<void>;
+  generateDescription: (tabId: string, sceneId: string, text: string, targetEvent: string, eventId?: string) => Promise<void>;
   addPrompt: (text: string) => void;
   
   // === PERSISTENCE ACTIONS ===
@@ -125,6 +126,8 @@ interface AppStore extends AppState {
   saveProjectAs: () => Promise<void>;
   loadProjectFromFile: () => Promise<void>;
   createNewProject: (title: string, author?: string) => void;
+  hasUnsavedChanges: () => boolean;
+  mergeSavedStar: (starId: string) => Promise<void>;
   
   // === INTERNAL ===
   setLoading: (loading: boolean) => void;
@@ -153,6 +156,7 @@ const createEmptyProject = (title: string, author?: string): ProjectData => {
   return {
     version: '1.0',
     metadata: {
+      id: uuidv4(),
       title,
       author,
       created_at: Date.now(),
@@ -186,7 +190,12 @@ const repairProjectData = (data: Partial<ProjectData>): ProjectData => {
     characters: data.characters || {},
     plan_steps: data.plan_steps || {},
     idea_bank: data.idea_bank || { stored_draft_tab_ids: [] },
-    active_scene_id: data.active_scene_id
+    active_scene_id: data.active_scene_id,
+    outline: data.outline,
+    summaries: data.summaries,
+    progress: data.progress,
+    custom_dictionary: data.custom_dictionary,
+    prompt_templates: data.prompt_templates
   };
 
   // Clean up orphaned references
@@ -251,6 +260,29 @@ const repairProjectData = (data: Partial<ProjectData>): ProjectData => {
   return repaired;
 };
 
+// The persisted part of the store, as the backend commands expect it
+const toProjectData = (state: AppState): ProjectData => ({
+  version: state.version,
+  metadata: state.metadata,
+  scenes: state.scenes,
+  draft_tabs: state.draft_tabs,
+  workbench: state.workbench,
+  stars: state.stars,
+  characters: state.characters,
+  plan_steps: state.plan_steps,
+  idea_bank: state.idea_bank,
+  active_scene_id: state.active_scene_id,
+  outline: state.outline,
+  summaries: state.summaries,
+  progress: state.progress,
+  custom_dictionary: state.custom_dictionary,
+  prompt_templates: state.prompt_templates
+});
+
+// The project as last loaded from or saved to the saved file; null for a project never saved there.
+// Edits replace the top-level fields they touch, so comparing references finds unsaved changes.
+let savedProject: ProjectData | null = null;
+
 export const useAppStore = create<AppStore>((set, get) => ({
   // === INITIAL STATE ===
   ...createEmptyProject('New Project'),
@@ -1247,86 +1279,38 @@ export const useAppStore = create<AppStore>((set, get) => ({
 
   // === LLM ACTIONS ===
   
-  buildLLMContext: (sceneId: string) => {
-    const state = get();
-    const scene = state.scenes[sceneId];
-    if (!scene) return '';
+  buildLLMContext: async (sceneId: string, userInput: string) => {
+    if (!get().scenes[sceneId]) return '';
 
-    const checkedStars = get().getCheckedStars();
-    const checkedCharacters = get().getCheckedCharacters();
-    const sceneTabs = get().getDraftTabsForScene(sceneId);
-
-    // Use LLMService to build context with character constraints
-    return LLMService.buildContext({
-      scene,
-      characters: checkedCharacters,
-      checkedStars,
-      recentTabs: sceneTabs
-    });
+    // The same context generate_draft sends for this input (stars are ranked on it), within the same budgets
+    const assembled = await LLMService.assemble(toProjectData(get()), sceneId, 'SCENE_TIMELINE', userInput);
+    return assembled.context;
   },
 
   sendPrompt: async (text: string, sceneId: string) => {
-    const { addPrompt, setLoading, createDraftTab } = get();
-    const state = get();
+    const { addPrompt, setLoading } = get();
     
     try {
-      console.log('Starting sendPrompt...', { text, sceneId });
       setLoading(true);
       addPrompt(text);
 
-      const scene = state.scenes[sceneId];
-      if (!scene) {
+      if (!get().scenes[sceneId]) {
         console.error('Scene not found:', sceneId);
         return;
       }
 
-      const checkedStars = get().getCheckedStars();
-      const sceneTabs = get().getDraftTabsForScene(sceneId);
-      const characters = get().getCheckedCharacters();
-
-      console.log('Calling LLMService.generateSceneTimeline...', { scene: scene.name, charactersCount: characters.length });
-
-      // Use LLMService for scene timeline generation
-      const response = await LLMService.generateSceneTimeline(text, {
-        scene,
-        characters,
-        checkedStars,
-        recentTabs: sceneTabs
+      // The backend assembles the prompt and records a replayable provenance on each tab
+      const generated = await LLMService.generate(toProjectData(get()), {
+        scene_id: sceneId,
+        prompt_type: 'SCENE_TIMELINE',
+        user_input: text
       });
 
-      console.log('LLM Response received:', response);
-
-      // Create draft tabs from response (they go to workbench by default)
-      response.tabs.forEach((tab: any) => {
-        const tabId = createDraftTab(undefined, tab.title);
-        console.log('Created draft tab:', tabId, tab.title);
-        
-        // Update the summary and atmosphere if provided by LLM
-        const updates: Partial<DraftTab> = {};
-        if (tab.summary) {
-          updates.summary = tab.summary;
-          console.log('Set summary for draft tab:', tabId, tab.summary);
-        }
-        if (tab.atmosphere) {
-          updates.atmosphere = tab.atmosphere;
-          console.log('Set atmosphere for draft tab:', tabId, tab.atmosphere);
-        }
-        if (Object.keys(updates).length > 0) {
-          get().updateDraftTab(tabId, updates);
-        }
-        
-        // Add timeline events
-        tab.timeline.forEach((event: any) => {
-          get().addTimelineEvent(tabId, {
-            text: event.text,
-            dialogue: event.dialogue,
-            associated_stars: [],
-            checked: true // LLM-generated events are checked by default
-          });
-        });
-      });
-
-      console.log('Prompt processing completed successfully');
+      // New tabs go to the workbench; usage and plan step links are recorded as they are added
+      const outcome = await LLMService.addGenerated(toProjectData(get()), generated);
+      const projectData = repairProjectData(JSON.parse(outcome.state_json) as Partial<ProjectData>);
+      set(state => ({ ...projectData, ui: state.ui, prompts: state.prompts, isLoading: state.isLoading }));
+      console.log('Created draft tabs:', outcome.ids);
     } catch (error) {
       console.error('Failed to send prompt:', error);
       alert(`Error sending prompt: ${error instanceof Error ? error.message : 'Unknown error'}`);
@@ -1335,6 +1319,19 @@ export const useAppStore = create<AppStore>((set, get) => ({
     }
   },
 
+  generateDescription: async (tabId: string, sceneId: string, text: string, targetEvent: string, eventId?: string) => {
+    const generated = await LLMService.generate(toProjectData(get()), {
+      scene_id: sceneId,
+      prompt_type: 'EVENT_DESCRIPTION',
+      user_input: text,
+      target_event: targetEvent
+    });
+
+    const outcome = await LLMService.addGenerated(toProjectData(get()), generated, tabId, eventId);
+    const projectData = repairProjectData(JSON.parse(outcome.state_json) as Partial<ProjectData>);
+    set(state => ({ ...projectData, ui: state.ui, prompts: state.prompts, isLoading: state.isLoading }));
+  },
+
   addPrompt: (text: string) => {
     const prompt: Prompt = {
       id: uuidv4(),
@@ -1354,26 +1351,22 @@ export const useAppStore = create<AppStore>((set, get) => ({
     console.log('Got state:', { version: state.version, metadata: state.metadata });
     
     const projectData: ProjectData = {
-      version: state.version,
+      ...toProjectData(state),
       metadata: {
         ...state.metadata,
         updated_at: Date.now()
-      },
-      scenes: state.scenes,
-      draft_tabs: state.draft_tabs,
-      workbench: state.workbench,
-      stars: state.stars,
-      characters: state.characters,
-      plan_steps: state.plan_steps,
-      idea_bank: state.idea_bank,
-      active_scene_id: state.active_scene_id
+      }
     };
 
+    // Set before the call: save scripts and the notes folder announce their changes while it runs
+    const previous = savedProject;
+    savedProject = toProjectData(state);
     try {
       console.log('Calling save_project with data size:', JSON.stringify(projectData).length);
       await invoke('save_project', { stateJson: JSON.stringify(projectData) });
       console.log('Save successful');
     } catch (error) {
+      savedProject = previous;
       console.error('Failed to save project:', error);
       alert(`Save failed: ${error}`);
     }
@@ -1392,8 +1385,10 @@ export const useAppStore = create<AppStore>((set, get) => ({
         prompts: state.prompts,
         isLoading: state.isLoading
       }));
+      savedProject = toProjectData(get());
     } catch (error) {
       console.error('Failed to load project:', error);
+      savedProject = null;
       // Initialize with empty project on load failure
       const emptyProject = createEmptyProject('New Project');
       set(state => ({
@@ -1409,19 +1404,11 @@ export const useAppStore = create<AppStore>((set, get) => ({
     console.log('saveProjectAs called');
     const state = get();
     const projectData: ProjectData = {
-      version: state.version,
+      ...toProjectData(state),
       metadata: {
         ...state.metadata,
         updated_at: Date.now()
-      },
-      scenes: state.scenes,
-      draft_tabs: state.draft_tabs,
-      workbench: state.workbench,
-      stars: state.stars,
-      characters: state.characters,
-      plan_steps: state.plan_steps,
-      idea_bank: state.idea_bank,
-      active_scene_id: state.active_scene_id
+      }
     };
 
     try {
@@ -1447,6 +1434,8 @@ export const useAppStore = create<AppStore>((set, get) => ({
         prompts: [],
         isLoading: state.isLoading
       }));
+      // Not the saved file: the backend stops outside writes to it until this project is saved
+      savedProject = null;
     } catch (error) {
       console.error('Failed to load project from file:', error);
     }
@@ -1461,6 +1450,28 @@ export const useAppStore = create<AppStore>((set, get) => ({
       prompts: [],
       isLoading: false
     }));
+    savedProject = null;
+    invoke('close_saved_project').catch(error => console.error('Failed to close the saved project:', error));
+  },
+
+  hasUnsavedChanges: () => {
+    if (!savedProject) return true;
+    const current = toProjectData(get());
+    const saved = savedProject;
+    return (Object.keys(current) as (keyof ProjectData)[]).some(key => current[key] !== saved[key]);
+  },
+
+  // Take one star from the saved file, created, edited or deleted outside the app, and keep every other edit
+  mergeSavedStar: async (starId: string) => {
+    const loadedState = await invoke<string>('load_project');
+    const saved = repairProjectData(JSON.parse(loadedState) as Partial<ProjectData>);
+    const star = saved.stars[starId];
+    if (star) {
+      set(state => ({ stars: { ...state.stars, [starId]: star } }));
+    } else if (get().stars[starId]) {
+      // Deleted outside the app: also drop its links from events and plan steps
+      get().deleteStar(starId);
+    }
   },
 
   setLoading: (loading: boolean) => {
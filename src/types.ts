@@ -8,6 +8,20 @@ export interface TimelineEvent {
   dialogue?: string;
   associated_stars: string[]; // Star IDs
   checked: boolean; // Whether this event should be included in LLM context
+  speaker?: SpeakerAttribution; // Resolved by the backend for dialogue events
+}
+
+export interface SpeakerAttribution {
+  name: string;
+  character_id?: string; // Character ID, unset if the name matched no character
+  confidence: number; // 0.0 to 1.0
+  method: 'Explicit' | 'NameMatch' | 'AliasMatch' | 'Manual';
+}
+
+export interface UnresolvedSpeaker {
+  event_index: number;
+  reason: 'no_candidate' | 'ambiguous' | 'unknown_character';
+  candidates: string[]; // Character IDs
 }
 
 export interface Description {
@@ -17,6 +31,7 @@ export interface Description {
   origin_star_id?: string; // Star ID
   target_event_id?: string; // Optional - if null, describes whole tab
   scope: 'event' | 'tab'; // Whether this description targets a specific event or the whole tab
+  provenance?: Provenance; // set when the description came from a generation
 }
 
 export interface DraftTab {
@@ -31,6 +46,39 @@ export interface DraftTab {
   suggested_plan_steps: string[]; // e.g., ["Reveal letter's origin"]
   created_at: number;
   updated_at: number;
+  provenance?: Provenance; // set when the tab came from a generation
+}
+
+export interface TokenUsage {
+  prompt_tokens: number;
+  completion_tokens: number;
+  total_tokens: number;
+}
+
+export interface GenerationRequest {
+  scene_id: string;
+  prompt_type: PromptTypeName;
+  user_input: string;
+  target_event?: string;
+  budgets?: Record<string, number>; // SectionBudgets; omitted sections use the defaults
+  params?: { model?: string; temperature?: number };
+  template_name?: string; // unset uses the project's selected template for prompt_type
+  template_version?: number;
+}
+
+// How a draft or description was produced; regenerate_draft replays it
+export interface Provenance {
+  provider: string;
+  model: string;
+  temperature?: number;
+  template_name?: string; // unset when the prompt was assembled by the frontend
+  template_version?: number;
+  prompt_hash: string;
+  included_star_ids: string[];
+  included_character_ids: string[];
+  request?: GenerationRequest;
+  created_at: number;
+  usage?: TokenUsage;
 }
 
 export interface PlanStep {
@@ -38,6 +86,7 @@ export interface PlanStep {
   text: string;
   fulfilled_by: string[]; // DraftTab IDs
   linked_stars: string[]; // Star IDs
+  parent_id?: string; // PlanStep ID, set for indented sub-steps
 }
 
 export interface ScenePlan {
@@ -63,6 +112,8 @@ export interface Star {
   origin_draft_tab_id?: string; // DraftTab ID
   created_at: number;
   last_used_in_prompt?: number;
+  usage_count?: number; // Maintained by the backend when a generation is added to the project
+  influenced_draft_tab_ids?: string[]; // DraftTab IDs generated with this star in the prompt
   // Character constraint fields
   constraint_type?: 'character_behavior' | 'character_dialogue' | 'character_emotion' | 'character_social' | 'character_physical';
   applies_to_character?: string; // Character ID
@@ -79,6 +130,8 @@ export interface Character {
   name: string;
   fields: { [key: string]: string }; // user-defined fields
   is_checked: boolean;
+  last_used_in_prompt?: number;
+  usage_count?: number;
 }
 
 export interface Scene {
@@ -95,6 +148,7 @@ export interface Scene {
 // ===== GLOBAL STATE =====
 
 export interface ProjectMetadata {
+  id?: string; // stable across renames; keys per-project data such as snapshots
   title: string;
   author?: string;
   created_at: number;
@@ -112,6 +166,82 @@ export interface ProjectData {
   plan_steps: { [id: string]: PlanStep };
   idea_bank: { stored_draft_tab_ids: string[] };
   active_scene_id?: string;
+  outline?: Outline; // book structure; the backend migrates flat projects into one chapter
+  summaries?: SummaryCache; // maintained by the backend's refresh_summaries
+  progress?: WritingProgress; // daily word-count history from record_writing_progress
+  custom_dictionary?: CustomDictionary; // accepted spellings; character, star and scene names are accepted automatically
+  prompt_templates?: ProjectTemplates; // per-book prompt template versions, managed by the backend registry
+}
+
+export interface DailyCount {
+  date: string; // YYYY-MM-DD
+  total_words: number;
+  net_words: number;
+}
+
+export type PromptTypeName = 'SCENE_TIMELINE' | 'EVENT_DESCRIPTION';
+
+export interface PromptTemplate {
+  name: string;
+  version: number; // assigned by save_prompt_template
+  prompt_type: PromptTypeName;
+  system: string;
+  user: string; // {context}, {userInput}, {targetEvent}, {responseInstructions}
+  instructions: string;
+  note: string;
+  created_at: number;
+}
+
+export interface ProjectTemplates {
+  templates: PromptTemplate[];
+  pinned: Record<string, number>; // template name -> version
+  selected?: Partial<Record<PromptTypeName, string>>; // template name generation uses; the built-in one when unset
+}
+
+export interface CustomDictionary {
+  words: string[];
+}
+
+export interface Misspelling {
+  word: string;
+  start: number; // character offsets, end exclusive
+  end: number;
+  suggestions: string[];
+}
+
+export interface WritingProgress {
+  daily_goal?: number;
+  history: DailyCount[];
+}
+
+export interface OutlinePart {
+  id: string;
+  title: string;
+  chapter_ids: string[];
+}
+
+export interface OutlineChapter {
+  id: string;
+  title: string;
+  scene_ids: string[];
+}
+
+export interface Outline {
+  part_ids: string[];
+  parts: { [id: string]: OutlinePart };
+  chapters: { [id: string]: OutlineChapter };
+}
+
+export interface CachedSummary {
+  hash: string;
+  text: string;
+  updated_at: number;
+}
+
+export interface SummaryCache {
+  tabs: { [tabId: string]: CachedSummary };
+  scenes: { [sceneId: string]: CachedSummary };
+  story?: CachedSummary;
 }
 
 export interface AppState extends ProjectData {
@@ -144,12 +274,120 @@ export interface LLMResponse {
     timeline: Array<{
       text: string;
       dialogue?: string;
+      speaker?: SpeakerAttribution;
     }>;
     summary?: string;
     atmosphere?: string;
+    unresolved_speakers: UnresolvedSpeaker[];
+    provenance?: Provenance;
+    plan_verdicts: StepVerdict[]; // linked to the scene's plan steps when the tab is added
   }>;
 }
 
+// Why each candidate was or wasn't put in the context
+export interface TraceEntry {
+  section: string;
+  kind: string; // 'story_summary' | 'scene_summary' | 'setting' | 'backstory' | 'character' | 'plan' | 'plan_step' | 'summary' | 'event' | 'constraint' | 'star'
+  id?: string;
+  included: boolean;
+  reason: string;
+  tokens: number;
+}
+
+// assemble_prompt result: the exact prompt generate_draft would send
+export interface AssembledPrompt {
+  template_name: string;
+  template_version: number;
+  system_prompt: string;
+  user_prompt: string;
+  context: string;
+  trace: TraceEntry[];
+  included_star_ids: string[];
+  included_character_ids: string[];
+}
+
+// generate_draft result; add it to the project with add_generated_draft
+export interface GeneratedDraft {
+  tabs: LLMResponse['tabs']; // scene timeline generations; each carries the provenance
+  description?: string; // event description generations
+  provenance: Provenance;
+  prompt_changed?: boolean; // regenerations only
+}
+
+export interface DraftWriteOutcome {
+  state_json: string;
+  ids: string[]; // new draft tabs, or the new description
+}
+
+export interface StepVerdict {
+  step_id: string; // PlanStep ID
+  status: 'fulfilled' | 'partial' | 'not_fulfilled';
+  reason: string;
+}
+
+export interface LogConfig {
+  level: 'off' | 'error' | 'info' | 'debug';
+  redact_content: boolean; // log length and hash instead of prompts, responses and manuscript text
+  max_file_bytes: number;
+  max_files: number;
+}
+
+export interface ApiSettings {
+  enabled: boolean;
+  port: number;
+  token: string; // `Authorization: Bearer <token>`, or `?token=` for the WebSocket
+}
+
+export interface ApiStatus {
+  settings: ApiSettings;
+  address?: string; // e.g. 127.0.0.1:7357 while the server is running
+  error?: string; // why the server failed to start
+}
+
+export interface ProjectEvent {
+  kind: string; // 'project_saved' | 'scene_created' | 'scene_updated' | 'scene_deleted' | 'star_created' | 'star_updated' | 'star_deleted' | 'draft_added' | 'vault_synced'
+  source: 'app' | 'api' | 'scripts' | 'vault' | 'external'; // 'scripts': a save script changed what the app saved; 'external': another program, such as spica mcp, wrote the file
+  id?: string;
+  at: number;
+}
+
+export type ScriptHook = 'before_prompt_send' | 'after_response_parse' | 'before_save' | 'on_export';
+
+export interface ScriptInfo {
+  name: string; // file name in <project dir>/scripts
+  hooks: ScriptHook[];
+  error?: string; // compile error; the script is skipped
+}
+
+export interface VaultSettings {
+  enabled: boolean;
+  folder?: string;
+}
+
+export interface SyncConflict {
+  id: string; // character or star id
+  kind: 'character' | 'star';
+  title: string;
+  path: string; // relative to the vault folder
+  reason: string;
+}
+
+export interface SyncReport {
+  written: string[]; // notes created or rewritten from the project
+  removed: string[]; // notes deleted because their character or star was
+  imported: string[]; // ids created or changed from notes
+  deleted: string[]; // ids removed because their note was deleted
+  conflicts: SyncConflict[];
+  warnings: string[];
+}
+
+export interface VaultStatus {
+  settings: VaultSettings;
+  watching: boolean;
+  report?: SyncReport; // from the first sync when syncing is turned on
+  error?: string;
+}
+
 export interface ApiError {
   error: true;
   message: string;